@@ -0,0 +1,44 @@
+-- The schema as it existed before migrations were tracked.
+-- Every statement is idempotent so that existing databases can adopt migrations without changes.
+
+DO $$
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM pg_type WHERE typname = 'machine_type') THEN
+        CREATE TYPE machine_type AS ENUM ('washer', 'dryer');
+    END IF;
+
+    IF NOT EXISTS (SELECT 1 FROM pg_type WHERE typname = 'report_type') THEN
+        CREATE TYPE report_type AS ENUM ('operational', 'caution', 'broken');
+    END IF;
+END
+$$;
+
+CREATE TABLE IF NOT EXISTS room (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR NOT NULL,
+    description VARCHAR
+);
+
+CREATE TABLE IF NOT EXISTS public.user (
+    username VARCHAR PRIMARY KEY,
+    admin BOOLEAN NOT NULL DEFAULT false
+);
+
+CREATE TABLE IF NOT EXISTS machine (
+    room_id INTEGER NOT NULL REFERENCES room (id) ON DELETE CASCADE,
+    machine_id BPCHAR NOT NULL,
+    type machine_type NOT NULL,
+    PRIMARY KEY (room_id, machine_id)
+);
+
+CREATE TABLE IF NOT EXISTS report (
+    id SERIAL PRIMARY KEY,
+    room_id INTEGER NOT NULL,
+    machine_id BPCHAR NOT NULL,
+    reporter_username VARCHAR NOT NULL REFERENCES public.user (username) ON DELETE CASCADE,
+    type report_type NOT NULL,
+    time TIMESTAMP NOT NULL,
+    description VARCHAR,
+    archived BOOLEAN NOT NULL DEFAULT false,
+    FOREIGN KEY (room_id, machine_id) REFERENCES machine (room_id, machine_id) ON DELETE CASCADE
+);
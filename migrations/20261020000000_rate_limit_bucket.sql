@@ -0,0 +1,10 @@
+-- Token buckets shared between every instance when RATE_LIMIT_BACKEND=postgres.
+CREATE TABLE rate_limit_bucket (
+    key VARCHAR PRIMARY KEY,
+    tokens DOUBLE PRECISION NOT NULL,
+    updated_at TIMESTAMP NOT NULL,
+    limited_count INTEGER NOT NULL DEFAULT 0,
+    last_limited_at TIMESTAMP
+);
+
+CREATE INDEX rate_limit_bucket_last_limited_at_idx ON rate_limit_bucket (last_limited_at);
@@ -0,0 +1,11 @@
+-- Additional residents confirming an open report instead of filing a duplicate.
+CREATE TABLE report_confirmation (
+    report_id INTEGER NOT NULL REFERENCES report (id) ON DELETE CASCADE,
+    reporter_username VARCHAR NOT NULL REFERENCES public.user (username) ON DELETE CASCADE,
+    time TIMESTAMP NOT NULL,
+    description VARCHAR,
+    PRIMARY KEY (report_id, reporter_username)
+);
+
+CREATE INDEX report_open_machine_type_idx ON report (room_id, machine_id, type, time)
+    WHERE archived = false;
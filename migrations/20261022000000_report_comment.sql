@@ -0,0 +1,11 @@
+-- Discussion threads on reports, such as technicians asking for details or recording a repair.
+CREATE TABLE report_comment (
+    id SERIAL PRIMARY KEY,
+    report_id INTEGER NOT NULL REFERENCES report (id) ON DELETE CASCADE,
+    author_username VARCHAR NOT NULL REFERENCES public.user (username) ON DELETE CASCADE,
+    body VARCHAR NOT NULL,
+    time TIMESTAMP NOT NULL,
+    edited_time TIMESTAMP
+);
+
+CREATE INDEX report_comment_report_idx ON report_comment (report_id, time);
@@ -0,0 +1,38 @@
+-- Photos attached to reports. The files themselves live in the blob store under the stored keys.
+CREATE TABLE report_attachment (
+    id SERIAL PRIMARY KEY,
+    report_id INTEGER NOT NULL REFERENCES report (id) ON DELETE CASCADE,
+    uploader_username VARCHAR NOT NULL REFERENCES public.user (username) ON DELETE CASCADE,
+    file_name VARCHAR NOT NULL,
+    content_type VARCHAR NOT NULL,
+    size_bytes BIGINT NOT NULL,
+    width INTEGER NOT NULL,
+    height INTEGER NOT NULL,
+    storage_key VARCHAR NOT NULL,
+    thumbnail_key VARCHAR NOT NULL,
+    time TIMESTAMP NOT NULL
+);
+
+CREATE INDEX report_attachment_report_idx ON report_attachment (report_id);
+
+-- The attachment metadata included with every report.
+CREATE FUNCTION report_attachments(report_id INTEGER) RETURNS JSON
+LANGUAGE SQL STABLE
+AS $$
+    SELECT COALESCE(
+        json_agg(
+            json_build_object(
+                'attachment_id', attachment.id,
+                'file_name', attachment.file_name,
+                'content_type', attachment.content_type,
+                'size_bytes', attachment.size_bytes,
+                'width', attachment.width,
+                'height', attachment.height
+            )
+            ORDER BY attachment.id
+        ),
+        '[]'
+    )
+    FROM report_attachment AS attachment
+    WHERE attachment.report_id = report_attachments.report_id
+$$;
@@ -0,0 +1,14 @@
+-- Details of each machine needed by the vendor who services it.
+CREATE TYPE payment_type AS ENUM ('coin', 'card', 'coin_and_card', 'free');
+
+ALTER TABLE machine
+    ADD COLUMN manufacturer VARCHAR,
+    ADD COLUMN model VARCHAR,
+    ADD COLUMN serial_number VARCHAR,
+    ADD COLUMN install_date DATE,
+    ADD COLUMN warranty_expiry DATE,
+    ADD COLUMN capacity_kg REAL CHECK (capacity_kg > 0),
+    ADD COLUMN payment_type payment_type,
+    ADD COLUMN notes VARCHAR;
+
+CREATE INDEX machine_manufacturer_idx ON machine (lower(manufacturer));
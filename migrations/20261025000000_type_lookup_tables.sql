@@ -0,0 +1,34 @@
+-- Machine types and report types become admin-managed rows instead of enums.
+-- Existing values keep the names they are serialized with in JSON.
+ALTER TABLE machine ALTER COLUMN type TYPE VARCHAR USING initcap(type::TEXT);
+ALTER TABLE report ALTER COLUMN type TYPE VARCHAR USING initcap(type::TEXT);
+
+DROP TYPE machine_type;
+DROP TYPE report_type;
+
+CREATE TABLE machine_type (
+    name VARCHAR PRIMARY KEY,
+    display_name VARCHAR NOT NULL,
+    icon VARCHAR,
+    active BOOLEAN NOT NULL DEFAULT true
+);
+
+CREATE TABLE report_type (
+    name VARCHAR PRIMARY KEY,
+    display_name VARCHAR NOT NULL,
+    severity INTEGER NOT NULL,
+    icon VARCHAR,
+    active BOOLEAN NOT NULL DEFAULT true
+);
+
+INSERT INTO machine_type (name, display_name, icon) VALUES
+    ('Washer', 'Washer', 'washer'),
+    ('Dryer', 'Dryer', 'dryer');
+
+INSERT INTO report_type (name, display_name, severity, icon) VALUES
+    ('Operational', 'Operational', 0, 'check'),
+    ('Caution', 'Caution', 1, 'warning'),
+    ('Broken', 'Broken', 2, 'error');
+
+ALTER TABLE machine ADD FOREIGN KEY (type) REFERENCES machine_type (name);
+ALTER TABLE report ADD FOREIGN KEY (type) REFERENCES report_type (name);
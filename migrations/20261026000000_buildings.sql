@@ -0,0 +1,20 @@
+-- Rooms are grouped into buildings, which can in turn be grouped into sites such as campuses.
+CREATE TABLE site (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR NOT NULL,
+    description VARCHAR
+);
+
+CREATE TABLE building (
+    id SERIAL PRIMARY KEY,
+    site_id INTEGER REFERENCES site (id) ON DELETE SET NULL,
+    name VARCHAR NOT NULL,
+    address VARCHAR,
+    latitude DOUBLE PRECISION CHECK (latitude BETWEEN -90 AND 90),
+    longitude DOUBLE PRECISION CHECK (longitude BETWEEN -180 AND 180)
+);
+
+ALTER TABLE room ADD COLUMN building_id INTEGER REFERENCES building (id) ON DELETE SET NULL;
+
+CREATE INDEX building_site_idx ON building (site_id);
+CREATE INDEX room_building_idx ON room (building_id);
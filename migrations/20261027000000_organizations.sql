@@ -0,0 +1,63 @@
+-- Organizations share one deployment, each seeing only its own rooms, buildings, sites and users.
+-- Machines and reports belong to an organization through their room.
+CREATE TABLE organization (
+    id SERIAL PRIMARY KEY,
+    slug VARCHAR NOT NULL UNIQUE,
+    name VARCHAR NOT NULL
+);
+
+-- Existing data is kept in a default organization.
+INSERT INTO organization (id, slug, name) VALUES (1, 'default', 'Default');
+SELECT setval(pg_get_serial_sequence('organization', 'id'), 1);
+
+ALTER TABLE site ADD COLUMN organization_id INTEGER NOT NULL DEFAULT 1 REFERENCES organization (id) ON DELETE CASCADE;
+ALTER TABLE building ADD COLUMN organization_id INTEGER NOT NULL DEFAULT 1 REFERENCES organization (id) ON DELETE CASCADE;
+ALTER TABLE room ADD COLUMN organization_id INTEGER NOT NULL DEFAULT 1 REFERENCES organization (id) ON DELETE CASCADE;
+ALTER TABLE public.user ADD COLUMN organization_id INTEGER NOT NULL DEFAULT 1 REFERENCES organization (id) ON DELETE CASCADE;
+
+ALTER TABLE site ALTER COLUMN organization_id DROP DEFAULT;
+ALTER TABLE building ALTER COLUMN organization_id DROP DEFAULT;
+ALTER TABLE room ALTER COLUMN organization_id DROP DEFAULT;
+ALTER TABLE public.user ALTER COLUMN organization_id DROP DEFAULT;
+
+CREATE INDEX site_organization_idx ON site (organization_id);
+CREATE INDEX building_organization_idx ON building (organization_id);
+CREATE INDEX room_organization_idx ON room (organization_id);
+
+-- Usernames are only unique within an organization, so every record naming a user also names the
+-- organization, and refers to the user by both.
+ALTER TABLE report ADD COLUMN organization_id INTEGER;
+UPDATE report SET organization_id = room.organization_id FROM room WHERE room.id = report.room_id;
+ALTER TABLE report ALTER COLUMN organization_id SET NOT NULL;
+
+ALTER TABLE report_confirmation ADD COLUMN organization_id INTEGER;
+ALTER TABLE report_comment ADD COLUMN organization_id INTEGER;
+ALTER TABLE report_attachment ADD COLUMN organization_id INTEGER;
+UPDATE report_confirmation SET organization_id = report.organization_id
+    FROM report WHERE report.id = report_confirmation.report_id;
+UPDATE report_comment SET organization_id = report.organization_id
+    FROM report WHERE report.id = report_comment.report_id;
+UPDATE report_attachment SET organization_id = report.organization_id
+    FROM report WHERE report.id = report_attachment.report_id;
+ALTER TABLE report_confirmation ALTER COLUMN organization_id SET NOT NULL;
+ALTER TABLE report_comment ALTER COLUMN organization_id SET NOT NULL;
+ALTER TABLE report_attachment ALTER COLUMN organization_id SET NOT NULL;
+
+ALTER TABLE report DROP CONSTRAINT report_reporter_username_fkey;
+ALTER TABLE report_confirmation DROP CONSTRAINT report_confirmation_reporter_username_fkey;
+ALTER TABLE report_comment DROP CONSTRAINT report_comment_author_username_fkey;
+ALTER TABLE report_attachment DROP CONSTRAINT report_attachment_uploader_username_fkey;
+
+ALTER TABLE public.user DROP CONSTRAINT user_pkey;
+ALTER TABLE public.user ADD PRIMARY KEY (organization_id, username);
+
+ALTER TABLE report ADD FOREIGN KEY (organization_id, reporter_username)
+    REFERENCES public.user (organization_id, username) ON DELETE CASCADE;
+ALTER TABLE report_confirmation ADD FOREIGN KEY (organization_id, reporter_username)
+    REFERENCES public.user (organization_id, username) ON DELETE CASCADE;
+ALTER TABLE report_comment ADD FOREIGN KEY (organization_id, author_username)
+    REFERENCES public.user (organization_id, username) ON DELETE CASCADE;
+ALTER TABLE report_attachment ADD FOREIGN KEY (organization_id, uploader_username)
+    REFERENCES public.user (organization_id, username) ON DELETE CASCADE;
+
+CREATE INDEX report_organization_reporter_idx ON report (organization_id, reporter_username);
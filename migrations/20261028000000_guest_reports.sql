@@ -0,0 +1,22 @@
+-- Reports from visitors without an account wait in a moderation queue,
+-- and become reports without a reporter once approved.
+CREATE TYPE moderation_status AS ENUM ('pending', 'approved', 'rejected');
+
+ALTER TABLE report ALTER COLUMN reporter_username DROP NOT NULL;
+
+CREATE TABLE guest_report (
+    id SERIAL PRIMARY KEY,
+    room_id INTEGER NOT NULL,
+    machine_id BPCHAR NOT NULL,
+    type VARCHAR NOT NULL REFERENCES report_type (name),
+    description VARCHAR,
+    contact_email VARCHAR,
+    client_ip VARCHAR NOT NULL,
+    time TIMESTAMP NOT NULL,
+    status moderation_status NOT NULL DEFAULT 'pending',
+    moderated_time TIMESTAMP,
+    report_id INTEGER REFERENCES report (id) ON DELETE SET NULL,
+    FOREIGN KEY (room_id, machine_id) REFERENCES machine (room_id, machine_id) ON DELETE CASCADE
+);
+
+CREATE INDEX guest_report_status_idx ON guest_report (status, time);
@@ -0,0 +1,26 @@
+-- Cycles run on machines, started by a resident or a sensor integration,
+-- so that rooms can show which machines are free and when the others will be.
+CREATE TYPE session_end_reason AS ENUM ('finished', 'expired');
+
+CREATE TABLE machine_session (
+    id SERIAL PRIMARY KEY,
+    room_id INTEGER NOT NULL,
+    machine_id BPCHAR NOT NULL,
+    organization_id INTEGER NOT NULL,
+    username VARCHAR,
+    start_time TIMESTAMP NOT NULL,
+    expected_end_time TIMESTAMP NOT NULL CHECK (expected_end_time > start_time),
+    end_time TIMESTAMP,
+    end_reason session_end_reason,
+    CHECK ((end_time IS NULL) = (end_reason IS NULL)),
+    FOREIGN KEY (room_id, machine_id) REFERENCES machine (room_id, machine_id) ON DELETE CASCADE,
+    FOREIGN KEY (organization_id, username)
+        REFERENCES public.user (organization_id, username) ON DELETE SET NULL (username)
+);
+
+-- A machine runs at most one cycle at a time.
+CREATE UNIQUE INDEX machine_session_active_idx ON machine_session (room_id, machine_id)
+    WHERE end_time IS NULL;
+
+CREATE INDEX machine_session_expected_end_time_idx ON machine_session (expected_end_time)
+    WHERE end_time IS NULL;
@@ -0,0 +1,61 @@
+-- Waitlists hand the next free machine of a type to whoever has waited longest,
+-- and reservations book a machine for a time slot ahead.
+CREATE TYPE waitlist_status AS ENUM ('waiting', 'claimed', 'used', 'expired', 'cancelled');
+CREATE TYPE reservation_status AS ENUM ('booked', 'checked_in', 'cancelled', 'no_show');
+
+CREATE TABLE waitlist_entry (
+    id SERIAL PRIMARY KEY,
+    room_id INTEGER NOT NULL REFERENCES room (id) ON DELETE CASCADE,
+    machine_type VARCHAR NOT NULL REFERENCES machine_type (name),
+    organization_id INTEGER NOT NULL,
+    username VARCHAR NOT NULL,
+    join_time TIMESTAMP NOT NULL,
+    status waitlist_status NOT NULL DEFAULT 'waiting',
+    -- The machine offered to the entry, which must be used before the claim expires.
+    machine_id BPCHAR,
+    claim_expires_time TIMESTAMP,
+    FOREIGN KEY (room_id, machine_id) REFERENCES machine (room_id, machine_id) ON DELETE SET NULL (machine_id),
+    FOREIGN KEY (organization_id, username)
+        REFERENCES public.user (organization_id, username) ON DELETE CASCADE
+);
+
+-- A user waits at most once for each type of machine in a room.
+CREATE UNIQUE INDEX waitlist_entry_active_idx ON waitlist_entry (room_id, machine_type, username)
+    WHERE status IN ('waiting', 'claimed');
+
+CREATE INDEX waitlist_entry_queue_idx ON waitlist_entry (room_id, machine_type, join_time)
+    WHERE status = 'waiting';
+
+CREATE TABLE reservation (
+    id SERIAL PRIMARY KEY,
+    room_id INTEGER NOT NULL,
+    machine_id BPCHAR NOT NULL,
+    organization_id INTEGER NOT NULL,
+    username VARCHAR NOT NULL,
+    start_time TIMESTAMP NOT NULL,
+    end_time TIMESTAMP NOT NULL CHECK (end_time > start_time),
+    status reservation_status NOT NULL DEFAULT 'booked',
+    created_time TIMESTAMP NOT NULL,
+    FOREIGN KEY (room_id, machine_id) REFERENCES machine (room_id, machine_id) ON DELETE CASCADE,
+    FOREIGN KEY (organization_id, username)
+        REFERENCES public.user (organization_id, username) ON DELETE CASCADE
+);
+
+CREATE INDEX reservation_machine_time_idx ON reservation (room_id, machine_id, start_time)
+    WHERE status IN ('booked', 'checked_in');
+
+CREATE INDEX reservation_username_idx ON reservation (username)
+    WHERE status = 'booked';
+
+-- Organizations without a policy use the defaults built into the API.
+CREATE TABLE reservation_policy (
+    organization_id INTEGER PRIMARY KEY REFERENCES organization (id) ON DELETE CASCADE,
+    max_active_reservations INTEGER NOT NULL CHECK (max_active_reservations >= 0),
+    max_duration_minutes INTEGER NOT NULL CHECK (max_duration_minutes > 0),
+    claim_minutes INTEGER NOT NULL CHECK (claim_minutes > 0),
+    no_show_minutes INTEGER NOT NULL CHECK (no_show_minutes > 0),
+    -- Daily hours, in UTC, during which no reservation may take place.
+    blackout_start TIME,
+    blackout_end TIME,
+    CHECK ((blackout_start IS NULL) = (blackout_end IS NULL))
+);
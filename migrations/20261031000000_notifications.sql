@@ -0,0 +1,25 @@
+-- How each user wants to hear that their cycle is done,
+-- and when sessions were last notified about.
+CREATE TABLE notification_preference (
+    organization_id INTEGER NOT NULL,
+    username VARCHAR NOT NULL,
+    email VARCHAR,
+    webhook_url VARCHAR,
+    -- A Web Push subscription, as handed to the browser's PushManager.
+    push_endpoint VARCHAR,
+    push_p256dh VARCHAR,
+    push_auth VARCHAR,
+    -- How often to remind the user while a finished cycle's machine is not emptied, or never when null.
+    reminder_minutes INTEGER CHECK (reminder_minutes > 0),
+    CHECK ((push_endpoint IS NULL) = (push_p256dh IS NULL) AND (push_endpoint IS NULL) = (push_auth IS NULL)),
+    PRIMARY KEY (organization_id, username),
+    FOREIGN KEY (organization_id, username)
+        REFERENCES public.user (organization_id, username) ON DELETE CASCADE
+);
+
+ALTER TABLE machine_session
+    ADD COLUMN done_notified_time TIMESTAMP,
+    ADD COLUMN last_reminder_time TIMESTAMP;
+
+CREATE INDEX machine_session_notification_idx ON machine_session (expected_end_time)
+    WHERE end_time IS NULL AND username IS NOT NULL;
@@ -0,0 +1,35 @@
+-- Power readings published by smart plugs, and the operating state inferred from them.
+CREATE TYPE operating_state AS ENUM ('idle', 'running', 'fault');
+
+-- Maps the devices publishing readings to the machines they measure.
+CREATE TABLE telemetry_device (
+    device_id VARCHAR PRIMARY KEY,
+    room_id INTEGER NOT NULL,
+    machine_id BPCHAR NOT NULL,
+    created_time TIMESTAMP NOT NULL,
+    FOREIGN KEY (room_id, machine_id) REFERENCES machine (room_id, machine_id) ON DELETE CASCADE
+);
+
+CREATE TABLE machine_telemetry (
+    id BIGSERIAL PRIMARY KEY,
+    room_id INTEGER NOT NULL,
+    machine_id BPCHAR NOT NULL,
+    device_id VARCHAR NOT NULL,
+    time TIMESTAMP NOT NULL,
+    power_watts REAL NOT NULL,
+    state operating_state NOT NULL,
+    FOREIGN KEY (room_id, machine_id) REFERENCES machine (room_id, machine_id) ON DELETE CASCADE
+);
+
+CREATE INDEX machine_telemetry_machine_time_idx ON machine_telemetry (room_id, machine_id, time);
+
+-- The latest inferred state of each machine with a device, and since when it has been in it.
+CREATE TABLE machine_operating_state (
+    room_id INTEGER NOT NULL,
+    machine_id BPCHAR NOT NULL,
+    state operating_state NOT NULL,
+    since TIMESTAMP NOT NULL,
+    last_reading_time TIMESTAMP NOT NULL,
+    PRIMARY KEY (room_id, machine_id),
+    FOREIGN KEY (room_id, machine_id) REFERENCES machine (room_id, machine_id) ON DELETE CASCADE
+);
@@ -0,0 +1,31 @@
+-- Devices posting readings over HTTP authenticate with a key, of which only the SHA-256 hash is kept.
+ALTER TABLE telemetry_device ADD COLUMN key_hash BYTEA UNIQUE;
+
+-- Devices connecting over mutual TLS may instead authenticate with a client certificate,
+-- identified by the lowercase hex SHA-256 fingerprint.
+ALTER TABLE telemetry_device ADD COLUMN certificate_fingerprint VARCHAR UNIQUE;
+
+-- Readings are downsampled into one row per machine and interval, so a row now summarizes
+-- every reading taken during the interval starting at its time, by any of the machine's devices.
+ALTER TABLE machine_telemetry
+    DROP COLUMN device_id,
+    ADD COLUMN sample_count INTEGER NOT NULL DEFAULT 1 CHECK (sample_count > 0),
+    ALTER COLUMN power_watts DROP NOT NULL,
+    ADD COLUMN peak_power_watts REAL,
+    ADD COLUMN vibration REAL,
+    ADD COLUMN temperature_celsius REAL,
+    ADD COLUMN door_open BOOLEAN,
+    ALTER COLUMN state DROP NOT NULL;
+
+ALTER TABLE machine_telemetry ALTER COLUMN sample_count DROP DEFAULT;
+UPDATE machine_telemetry SET peak_power_watts = power_watts;
+
+DROP INDEX machine_telemetry_machine_time_idx;
+CREATE UNIQUE INDEX machine_telemetry_machine_time_idx ON machine_telemetry (room_id, machine_id, time);
+
+-- The mean of two means weighted by their sample counts, or whichever one is not NULL.
+CREATE FUNCTION weighted_mean(a REAL, a_count INTEGER, b REAL, b_count INTEGER) RETURNS REAL
+LANGUAGE SQL IMMUTABLE
+AS $$
+    SELECT COALESCE((a * a_count + b * b_count) / (a_count + b_count), a, b)
+$$;
@@ -0,0 +1,7 @@
+-- Reports filed by integrations and background jobs rather than by a person.
+ALTER TABLE report ADD COLUMN automated BOOLEAN NOT NULL DEFAULT false;
+
+-- Reports without a reporter were either approved from a guest report or filed automatically.
+UPDATE report SET automated = true
+WHERE reporter_username IS NULL
+    AND id NOT IN (SELECT report_id FROM guest_report WHERE report_id IS NOT NULL);
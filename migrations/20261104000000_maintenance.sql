@@ -0,0 +1,47 @@
+-- Technicians, along with admins, are the users who complete maintenance tasks.
+ALTER TABLE public.user ADD COLUMN technician BOOLEAN NOT NULL DEFAULT false;
+
+-- Preventive maintenance done on a schedule, either for every machine of a type or for a single machine.
+CREATE TYPE recurrence_unit AS ENUM ('day', 'week', 'month', 'year');
+
+CREATE TABLE maintenance_plan (
+    id SERIAL PRIMARY KEY,
+    organization_id INTEGER NOT NULL REFERENCES organization (id) ON DELETE CASCADE,
+    name VARCHAR NOT NULL,
+    description VARCHAR,
+    machine_type VARCHAR REFERENCES machine_type (name),
+    room_id INTEGER,
+    machine_id BPCHAR,
+    recurrence_interval INTEGER NOT NULL CHECK (recurrence_interval > 0),
+    recurrence_unit recurrence_unit NOT NULL,
+    start_date DATE NOT NULL,
+    -- Retired plans keep the record of their tasks but no longer schedule new ones.
+    active BOOLEAN NOT NULL DEFAULT true,
+    created_time TIMESTAMP NOT NULL,
+    CHECK ((room_id IS NULL) = (machine_id IS NULL)),
+    CHECK ((machine_type IS NULL) <> (machine_id IS NULL)),
+    FOREIGN KEY (room_id, machine_id) REFERENCES machine (room_id, machine_id) ON DELETE CASCADE
+);
+
+CREATE TYPE maintenance_task_status AS ENUM ('scheduled', 'completed');
+
+CREATE TABLE maintenance_task (
+    id SERIAL PRIMARY KEY,
+    plan_id INTEGER NOT NULL REFERENCES maintenance_plan (id) ON DELETE CASCADE,
+    room_id INTEGER NOT NULL,
+    machine_id BPCHAR NOT NULL,
+    due_date DATE NOT NULL,
+    status maintenance_task_status NOT NULL DEFAULT 'scheduled',
+    completed_time TIMESTAMP,
+    organization_id INTEGER NOT NULL,
+    technician_username VARCHAR,
+    notes VARCHAR,
+    CHECK ((status = 'completed') = (completed_time IS NOT NULL)),
+    UNIQUE (plan_id, room_id, machine_id, due_date),
+    FOREIGN KEY (room_id, machine_id) REFERENCES machine (room_id, machine_id) ON DELETE CASCADE,
+    FOREIGN KEY (organization_id, technician_username)
+        REFERENCES public.user (organization_id, username) ON DELETE SET NULL (technician_username)
+);
+
+CREATE INDEX maintenance_task_scheduled_due_date_idx ON maintenance_task (due_date)
+    WHERE status = 'scheduled';
@@ -0,0 +1,54 @@
+-- Repairs owned by a technician, covering one or more reports.
+CREATE TYPE work_order_status AS ENUM ('open', 'in_progress', 'on_hold', 'closed');
+
+CREATE TYPE work_order_priority AS ENUM ('low', 'normal', 'high', 'urgent');
+
+CREATE TABLE work_order (
+    id SERIAL PRIMARY KEY,
+    organization_id INTEGER NOT NULL REFERENCES organization (id) ON DELETE CASCADE,
+    title VARCHAR NOT NULL,
+    description VARCHAR,
+    status work_order_status NOT NULL DEFAULT 'open',
+    priority work_order_priority NOT NULL DEFAULT 'normal',
+    due_date DATE,
+    assignee_username VARCHAR,
+    created_time TIMESTAMP NOT NULL,
+    closed_time TIMESTAMP,
+    resolution VARCHAR,
+    CHECK ((status = 'closed') = (closed_time IS NOT NULL)),
+    FOREIGN KEY (organization_id, assignee_username)
+        REFERENCES public.user (organization_id, username) ON DELETE SET NULL (assignee_username)
+);
+
+CREATE INDEX work_order_organization_id_status_idx ON work_order (organization_id, status);
+
+CREATE TABLE work_order_report (
+    work_order_id INTEGER NOT NULL REFERENCES work_order (id) ON DELETE CASCADE,
+    report_id INTEGER NOT NULL REFERENCES report (id) ON DELETE CASCADE,
+    PRIMARY KEY (work_order_id, report_id)
+);
+
+CREATE INDEX work_order_report_report_id_idx ON work_order_report (report_id);
+
+CREATE TABLE work_order_labour (
+    id SERIAL PRIMARY KEY,
+    work_order_id INTEGER NOT NULL REFERENCES work_order (id) ON DELETE CASCADE,
+    organization_id INTEGER NOT NULL,
+    technician_username VARCHAR,
+    minutes INTEGER NOT NULL CHECK (minutes > 0),
+    description VARCHAR,
+    logged_time TIMESTAMP NOT NULL,
+    FOREIGN KEY (organization_id, technician_username)
+        REFERENCES public.user (organization_id, username) ON DELETE SET NULL (technician_username)
+);
+
+CREATE TABLE work_order_part (
+    id SERIAL PRIMARY KEY,
+    work_order_id INTEGER NOT NULL REFERENCES work_order (id) ON DELETE CASCADE,
+    name VARCHAR NOT NULL,
+    part_number VARCHAR,
+    quantity INTEGER NOT NULL CHECK (quantity > 0),
+    -- Kept in cents so totals add up exactly.
+    unit_cost_cents INTEGER CHECK (unit_cost_cents >= 0),
+    logged_time TIMESTAMP NOT NULL
+);
@@ -0,0 +1,83 @@
+-- When a report was archived, which is when it counts as resolved. Unknown for reports archived before.
+ALTER TABLE report ADD COLUMN archived_time TIMESTAMP;
+
+-- How quickly reports must be acknowledged and resolved, for a room, a report type or both.
+CREATE TABLE sla_policy (
+    id SERIAL PRIMARY KEY,
+    organization_id INTEGER NOT NULL REFERENCES organization (id) ON DELETE CASCADE,
+    name VARCHAR NOT NULL,
+    room_id INTEGER REFERENCES room (id) ON DELETE CASCADE,
+    report_type VARCHAR REFERENCES report_type (name),
+    acknowledge_minutes INTEGER CHECK (acknowledge_minutes > 0),
+    resolve_minutes INTEGER CHECK (resolve_minutes > 0),
+    escalation_email VARCHAR,
+    escalation_webhook_url VARCHAR,
+    created_time TIMESTAMP NOT NULL,
+    CHECK (room_id IS NOT NULL OR report_type IS NOT NULL),
+    CHECK (acknowledge_minutes IS NOT NULL OR resolve_minutes IS NOT NULL)
+);
+
+CREATE UNIQUE INDEX sla_policy_scope_idx
+    ON sla_policy (organization_id, COALESCE(room_id, 0), COALESCE(report_type, ''));
+
+-- The policy covering each report, along with when it was acknowledged and resolved.
+-- The most specific policy created before the report applies, preferring one for both its room and
+-- type, then one for its type, then one for its room. A report is acknowledged by the first of a
+-- comment from someone other than its reporter, a work order covering it, or it being archived.
+CREATE VIEW report_sla AS
+SELECT
+    report.id AS report_id,
+    report.room_id,
+    report.machine_id,
+    report.type AS report_type,
+    report.time,
+    room.organization_id,
+    policy.id AS policy_id,
+    policy.name AS policy_name,
+    policy.acknowledge_minutes,
+    policy.resolve_minutes,
+    policy.escalation_email,
+    policy.escalation_webhook_url,
+    LEAST(
+        (
+            SELECT MIN(comment.time)
+            FROM report_comment AS comment
+            WHERE comment.report_id = report.id
+                AND comment.author_username IS DISTINCT FROM report.reporter_username
+        ),
+        (
+            SELECT MIN(work_order.created_time)
+            FROM work_order_report
+            JOIN work_order ON work_order.id = work_order_report.work_order_id
+            WHERE work_order_report.report_id = report.id
+        ),
+        report.archived_time
+    ) AS acknowledged_time,
+    report.archived_time AS resolved_time
+FROM report
+JOIN room ON room.id = report.room_id
+LEFT JOIN LATERAL (
+    SELECT *
+    FROM sla_policy
+    WHERE sla_policy.organization_id = room.organization_id
+        AND (sla_policy.room_id IS NULL OR sla_policy.room_id = report.room_id)
+        AND (sla_policy.report_type IS NULL OR sla_policy.report_type = report.type)
+        AND sla_policy.created_time <= report.time
+    ORDER BY
+        sla_policy.room_id IS NOT NULL AND sla_policy.report_type IS NOT NULL DESC,
+        sla_policy.report_type IS NOT NULL DESC,
+        sla_policy.id
+    LIMIT 1
+) AS policy ON true
+-- Archived reports with no archived time cannot be measured.
+WHERE NOT report.archived OR report.archived_time IS NOT NULL;
+
+CREATE TYPE sla_target AS ENUM ('acknowledge', 'resolve');
+
+-- Escalations sent for reports which missed a target, so each breach is escalated once.
+CREATE TABLE sla_escalation (
+    report_id INTEGER NOT NULL REFERENCES report (id) ON DELETE CASCADE,
+    target sla_target NOT NULL,
+    time TIMESTAMP NOT NULL,
+    PRIMARY KEY (report_id, target)
+);
@@ -0,0 +1,8 @@
+-- Proof of work challenges which were already used for a guest report, kept until they expire
+-- so that a solved challenge cannot be replayed.
+CREATE TABLE spent_pow_challenge (
+    challenge VARCHAR PRIMARY KEY,
+    expires_time TIMESTAMP NOT NULL
+);
+
+CREATE INDEX spent_pow_challenge_expires_time_idx ON spent_pow_challenge (expires_time);
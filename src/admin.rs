@@ -0,0 +1,572 @@
+use std::{
+    env,
+    future::{ready, Ready},
+    rc::Rc,
+    time::Duration,
+};
+
+use actix_web::{
+    body::EitherBody,
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    get,
+    http::header,
+    post, put,
+    web::{Data, Json, Path},
+    Error, HttpResponse, Responder,
+};
+use futures_util::future::LocalBoxFuture;
+use ring::digest;
+use serde::{Deserialize, Serialize};
+use sqlx::query_as;
+use utoipa::ToSchema;
+
+use crate::{
+    error::database_error,
+    models::{
+        AppState, MachineType, MachineTypeDefinition, Organization, ReportType,
+        ReportTypeDefinition,
+    },
+    tls::ClientCertificate,
+};
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct MachineTypeUpdate {
+    display_name: String,
+    icon: Option<String>,
+    active: bool,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct OrganizationSubmission {
+    slug: String,
+    name: String,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ReportTypeUpdate {
+    display_name: String,
+    severity: i32,
+    icon: Option<String>,
+    active: bool,
+}
+
+#[derive(Clone)]
+pub struct AdminConfig {
+    /// SHA-256 hashes of the keys accepted as `Authorization: Bearer <key>`.
+    key_hashes: Vec<Vec<u8>>,
+    /// Lowercase hex SHA-256 fingerprints of the client certificates accepted over mutual TLS.
+    certificate_fingerprints: Vec<String>,
+}
+
+impl AdminConfig {
+    /// Reads the comma separated admin keys from ADMIN_API_KEYS, and the comma separated
+    /// fingerprints of admin client certificates from ADMIN_CERTIFICATE_FINGERPRINTS.
+    /// Without either every request to the admin endpoints is rejected.
+    pub fn from_env() -> AdminConfig {
+        let key_hashes = list_from_env("ADMIN_API_KEYS")
+            .iter()
+            .map(|key| hash_key(key))
+            .collect::<Vec<_>>();
+
+        // Fingerprints are commonly written in uppercase with colons between the bytes.
+        let certificate_fingerprints = list_from_env("ADMIN_CERTIFICATE_FINGERPRINTS")
+            .into_iter()
+            .map(|fingerprint| fingerprint.replace(':', "").to_lowercase())
+            .collect::<Vec<_>>();
+
+        if key_hashes.is_empty() && certificate_fingerprints.is_empty() {
+            eprintln!(
+                "WARNING: Neither ADMIN_API_KEYS nor ADMIN_CERTIFICATE_FINGERPRINTS is set, the admin endpoints cannot be used."
+            );
+        }
+
+        AdminConfig {
+            key_hashes,
+            certificate_fingerprints,
+        }
+    }
+
+    /// Whether the request carries one of the admin keys, or was made over a connection
+    /// authenticated with one of the admin client certificates.
+    fn authenticates(&self, request: &ServiceRequest) -> bool {
+        let certificate_accepted =
+            request
+                .conn_data::<ClientCertificate>()
+                .is_some_and(|certificate| {
+                    self.certificate_fingerprints
+                        .contains(&certificate.fingerprint())
+                });
+
+        if certificate_accepted {
+            return true;
+        }
+
+        let Some(key) = request
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+        else {
+            return false;
+        };
+
+        // Comparing hashes keeps the time taken independent of how much of a key was guessed.
+        let key_hash = hash_key(key.trim());
+        self.key_hashes.contains(&key_hash)
+    }
+}
+
+fn list_from_env(name: &str) -> Vec<String> {
+    env::var(name)
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn hash_key(key: &str) -> Vec<u8> {
+    digest::digest(&digest::SHA256, key.as_bytes())
+        .as_ref()
+        .to_vec()
+}
+
+/// Middleware guarding the admin endpoints, which rejects requests without admin credentials
+/// with `401 Unauthorized` before they reach a handler.
+pub struct AdminAuthentication;
+
+impl<S, B> Transform<S, ServiceRequest> for AdminAuthentication
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = AdminAuthenticationMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(AdminAuthenticationMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct AdminAuthenticationMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for AdminAuthenticationMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, request: ServiceRequest) -> Self::Future {
+        let authenticated = request
+            .app_data::<Data<AppState>>()
+            .is_some_and(|data| data.admin_config.authenticates(&request));
+
+        if !authenticated {
+            let response = HttpResponse::Unauthorized()
+                .insert_header((header::WWW_AUTHENTICATE, "Bearer"))
+                .json("Admin credentials are required.");
+            return Box::pin(ready(Ok(request
+                .into_response(response)
+                .map_into_right_body())));
+        }
+
+        let service = Rc::clone(&self.service);
+        Box::pin(async move { Ok(service.call(request).await?.map_into_left_body()) })
+    }
+}
+
+/// Clients limited within this window are listed by [get_rate_limits].
+const THROTTLED_WINDOW: Duration = Duration::from_secs(60 * 60);
+
+#[utoipa::path(
+    context_path = "/admin",
+    responses(
+        (status = 200, description = "List of clients rate limited within the last hour", body = Vec<ThrottledClient>, example = json!([{
+            "key": "report-ip:203.0.113.7",
+            "tokens": 0.2,
+            "limited_count": 4,
+            "last_limited_at": "2023-01-01T12:00:00.000Z"
+        }])),
+        (status = 401, description = "No valid admin credentials were given"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[get("/rate-limits")]
+async fn get_rate_limits(data: Data<AppState>) -> impl Responder {
+    match data.rate_limiter.throttled(THROTTLED_WINDOW).await {
+        Ok(throttled) => HttpResponse::Ok().json(throttled),
+        Err(err) => database_error("fetch throttled clients", err),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/admin",
+    responses(
+        (status = 200, description = "List of all machine types, including inactive ones", body = Vec<MachineTypeDefinition>, example = json!([{
+            "name": "Washer",
+            "display_name": "Washer",
+            "icon": "washer",
+            "active": true
+        }])),
+        (status = 401, description = "No valid admin credentials were given"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[get("/machine-types")]
+async fn get_machine_types(data: Data<AppState>) -> impl Responder {
+    match query_as!(
+        MachineTypeDefinition,
+        r#"
+        SELECT name AS "name: MachineType", display_name, icon, active
+        FROM machine_type
+        ORDER BY name
+        "#
+    )
+    .fetch_all(&data.database)
+    .await
+    {
+        Ok(machine_types) => HttpResponse::Ok().json(machine_types),
+        Err(err) => database_error("fetch machine types", err),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/admin",
+    request_body(content = MachineTypeDefinition, content_type = "application/json", example = json!({
+        "name": "WasherDryer",
+        "display_name": "Combo washer-dryer",
+        "icon": "washer-dryer",
+        "active": true
+    })),
+    responses(
+        (status = 201, description = "The machine type was added", body = MachineTypeDefinition, example = json!({
+            "name": "WasherDryer",
+            "display_name": "Combo washer-dryer",
+            "icon": "washer-dryer",
+            "active": true
+        })),
+        (status = 400, description = "The requested query was invalid"),
+        (status = 401, description = "No valid admin credentials were given"),
+        (status = 409, description = "The machine type already exists"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[post("/machine-types")]
+async fn add_machine_type(
+    data: Data<AppState>,
+    Json(machine_type): Json<MachineTypeDefinition>,
+) -> impl Responder {
+    if machine_type.name.0.trim().is_empty() {
+        return HttpResponse::BadRequest().json("A machine type name cannot be empty.");
+    }
+
+    match query_as!(
+        MachineTypeDefinition,
+        r#"
+        INSERT INTO machine_type (name, display_name, icon, active)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT DO NOTHING
+        RETURNING name AS "name: MachineType", display_name, icon, active
+        "#,
+        &machine_type.name as &MachineType,
+        &machine_type.display_name,
+        machine_type.icon,
+        machine_type.active
+    )
+    .fetch_optional(&data.database)
+    .await
+    {
+        Ok(Some(machine_type)) => HttpResponse::Created().json(machine_type),
+        Ok(None) => HttpResponse::Conflict().json(format!(
+            "Machine type {} already exists.",
+            machine_type.name.0
+        )),
+        Err(err) => database_error("insert machine type", err),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/admin",
+    request_body(content = MachineTypeUpdate, content_type = "application/json", example = json!({
+        "display_name": "Combo washer-dryer",
+        "icon": "washer-dryer",
+        "active": false
+    })),
+    responses(
+        (status = 200, description = "The machine type was updated", body = MachineTypeDefinition, example = json!({
+            "name": "WasherDryer",
+            "display_name": "Combo washer-dryer",
+            "icon": "washer-dryer",
+            "active": false
+        })),
+        (status = 401, description = "No valid admin credentials were given"),
+        (status = 404, description = "The machine type was not found"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[put("/machine-types/{name}")]
+async fn update_machine_type(
+    data: Data<AppState>,
+    path: Path<String>,
+    Json(update): Json<MachineTypeUpdate>,
+) -> impl Responder {
+    let name = path.into_inner();
+
+    match query_as!(
+        MachineTypeDefinition,
+        r#"
+        UPDATE machine_type
+        SET display_name = $2, icon = $3, active = $4
+        WHERE name = $1
+        RETURNING name AS "name: MachineType", display_name, icon, active
+        "#,
+        &name,
+        &update.display_name,
+        update.icon,
+        update.active
+    )
+    .fetch_optional(&data.database)
+    .await
+    {
+        Ok(Some(machine_type)) => HttpResponse::Ok().json(machine_type),
+        Ok(None) => HttpResponse::NotFound().json(format!("Machine type {name} was not found.")),
+        Err(err) => database_error("update machine type", err),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/admin",
+    responses(
+        (status = 200, description = "List of all report types, including inactive ones", body = Vec<ReportTypeDefinition>, example = json!([{
+            "name": "Broken",
+            "display_name": "Broken",
+            "severity": 2,
+            "icon": "error",
+            "active": true
+        }])),
+        (status = 401, description = "No valid admin credentials were given"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[get("/report-types")]
+async fn get_report_types(data: Data<AppState>) -> impl Responder {
+    match query_as!(
+        ReportTypeDefinition,
+        r#"
+        SELECT name AS "name: ReportType", display_name, severity, icon, active
+        FROM report_type
+        ORDER BY severity, name
+        "#
+    )
+    .fetch_all(&data.database)
+    .await
+    {
+        Ok(report_types) => HttpResponse::Ok().json(report_types),
+        Err(err) => database_error("fetch report types", err),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/admin",
+    request_body(content = ReportTypeDefinition, content_type = "application/json", example = json!({
+        "name": "PaymentFailure",
+        "display_name": "Payment failure",
+        "severity": 1,
+        "icon": "credit-card",
+        "active": true
+    })),
+    responses(
+        (status = 201, description = "The report type was added", body = ReportTypeDefinition, example = json!({
+            "name": "PaymentFailure",
+            "display_name": "Payment failure",
+            "severity": 1,
+            "icon": "credit-card",
+            "active": true
+        })),
+        (status = 400, description = "The requested query was invalid"),
+        (status = 401, description = "No valid admin credentials were given"),
+        (status = 409, description = "The report type already exists"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[post("/report-types")]
+async fn add_report_type(
+    data: Data<AppState>,
+    Json(report_type): Json<ReportTypeDefinition>,
+) -> impl Responder {
+    if report_type.name.0.trim().is_empty() {
+        return HttpResponse::BadRequest().json("A report type name cannot be empty.");
+    }
+
+    match query_as!(
+        ReportTypeDefinition,
+        r#"
+        INSERT INTO report_type (name, display_name, severity, icon, active)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT DO NOTHING
+        RETURNING name AS "name: ReportType", display_name, severity, icon, active
+        "#,
+        &report_type.name as &ReportType,
+        &report_type.display_name,
+        report_type.severity,
+        report_type.icon,
+        report_type.active
+    )
+    .fetch_optional(&data.database)
+    .await
+    {
+        Ok(Some(report_type)) => HttpResponse::Created().json(report_type),
+        Ok(None) => HttpResponse::Conflict().json(format!(
+            "Report type {} already exists.",
+            report_type.name.0
+        )),
+        Err(err) => database_error("insert report type", err),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/admin",
+    request_body(content = ReportTypeUpdate, content_type = "application/json", example = json!({
+        "display_name": "Payment failure",
+        "severity": 1,
+        "icon": "credit-card",
+        "active": false
+    })),
+    responses(
+        (status = 200, description = "The report type was updated", body = ReportTypeDefinition, example = json!({
+            "name": "PaymentFailure",
+            "display_name": "Payment failure",
+            "severity": 1,
+            "icon": "credit-card",
+            "active": false
+        })),
+        (status = 401, description = "No valid admin credentials were given"),
+        (status = 404, description = "The report type was not found"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[put("/report-types/{name}")]
+async fn update_report_type(
+    data: Data<AppState>,
+    path: Path<String>,
+    Json(update): Json<ReportTypeUpdate>,
+) -> impl Responder {
+    let name = path.into_inner();
+
+    match query_as!(
+        ReportTypeDefinition,
+        r#"
+        UPDATE report_type
+        SET display_name = $2, severity = $3, icon = $4, active = $5
+        WHERE name = $1
+        RETURNING name AS "name: ReportType", display_name, severity, icon, active
+        "#,
+        &name,
+        &update.display_name,
+        update.severity,
+        update.icon,
+        update.active
+    )
+    .fetch_optional(&data.database)
+    .await
+    {
+        Ok(Some(report_type)) => HttpResponse::Ok().json(report_type),
+        Ok(None) => HttpResponse::NotFound().json(format!("Report type {name} was not found.")),
+        Err(err) => database_error("update report type", err),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/admin",
+    responses(
+        (status = 200, description = "List of all organizations", body = Vec<Organization>, example = json!([{
+            "organization_id": 1,
+            "slug": "default",
+            "name": "Default"
+        }])),
+        (status = 401, description = "No valid admin credentials were given"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[get("/organizations")]
+async fn get_organizations(data: Data<AppState>) -> impl Responder {
+    match query_as!(
+        Organization,
+        r#"
+        SELECT id AS "organization_id: i32", slug, name
+        FROM organization
+        ORDER BY id
+        "#
+    )
+    .fetch_all(&data.database)
+    .await
+    {
+        Ok(organizations) => HttpResponse::Ok().json(organizations),
+        Err(err) => database_error("fetch organizations", err),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/admin",
+    request_body(content = OrganizationSubmission, content_type = "application/json", example = json!({
+        "slug": "oxford",
+        "name": "University of Oxford"
+    })),
+    responses(
+        (status = 201, description = "The organization was added", body = Organization, example = json!({
+            "organization_id": 2,
+            "slug": "oxford",
+            "name": "University of Oxford"
+        })),
+        (status = 400, description = "The requested query was invalid"),
+        (status = 401, description = "No valid admin credentials were given"),
+        (status = 409, description = "The organization slug is already in use"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[post("/organizations")]
+async fn add_organization(
+    data: Data<AppState>,
+    Json(organization): Json<OrganizationSubmission>,
+) -> impl Responder {
+    if organization.slug.trim().is_empty() {
+        return HttpResponse::BadRequest().json("An organization slug cannot be empty.");
+    }
+
+    match query_as!(
+        Organization,
+        r#"
+        INSERT INTO organization (slug, name)
+        VALUES ($1, $2)
+        ON CONFLICT DO NOTHING
+        RETURNING id AS "organization_id: i32", slug, name
+        "#,
+        &organization.slug,
+        &organization.name
+    )
+    .fetch_optional(&data.database)
+    .await
+    {
+        Ok(Some(organization)) => HttpResponse::Created().json(organization),
+        Ok(None) => HttpResponse::Conflict().json(format!(
+            "Organization {} already exists.",
+            organization.slug
+        )),
+        Err(err) => database_error("insert organization", err),
+    }
+}
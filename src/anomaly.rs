@@ -0,0 +1,320 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use sqlx::{query, Pool, Postgres};
+use time::PrimitiveDateTime;
+
+use crate::{
+    background::ShutdownSignal,
+    config,
+    models::{now, ReportType},
+    report::{self, ReportConfig},
+};
+
+/// Settings for detecting machines which behave unlike they usually do, parsed from the environment.
+#[derive(Debug, Clone)]
+pub struct AnomalyConfig {
+    /// How often machines are checked.
+    pub interval: Duration,
+    /// How far back the history recent usage is compared against goes.
+    pub baseline: Duration,
+    /// How far back usage is checked for anomalies.
+    pub recent: Duration,
+    /// How many standard deviations from the usual a value must be to be an anomaly.
+    pub z_score: f64,
+    /// The fewest cycles or readings a machine needs before its usage is checked.
+    pub min_samples: i64,
+    /// The fewest recent cycles whose duration may be unusual.
+    pub min_cycles: i64,
+    /// How far the mean power drawn while running may drift from the usual, as a fraction of it.
+    pub power_tolerance: f64,
+    /// The fewest recent reports which may be an unusually high report rate.
+    pub min_reports: i64,
+}
+
+impl AnomalyConfig {
+    /// Parses the anomaly detection configuration from the environment.
+    pub fn from_env() -> AnomalyConfig {
+        AnomalyConfig {
+            interval: Duration::from_secs(
+                config::env_or("ANOMALY_CHECK_INTERVAL_MINUTES", 60) * 60,
+            ),
+            baseline: Duration::from_secs(
+                config::env_or("ANOMALY_BASELINE_DAYS", 30) * 24 * 60 * 60,
+            ),
+            recent: Duration::from_secs(config::env_or("ANOMALY_RECENT_HOURS", 24) * 60 * 60),
+            z_score: config::env_or("ANOMALY_Z_SCORE", 3.0),
+            min_samples: config::env_or("ANOMALY_MIN_SAMPLES", 10),
+            min_cycles: config::env_or("ANOMALY_MIN_CYCLES", 3),
+            power_tolerance: config::env_or("ANOMALY_POWER_TOLERANCE", 0.3),
+            min_reports: config::env_or("ANOMALY_MIN_REPORTS", 3),
+        }
+    }
+}
+
+/// Finds machines whose recent cycles ran much longer or shorter than their usual cycles.
+///
+/// The median of the recent cycles is compared, so that a single odd cycle is not an anomaly.
+async fn find_duration_outliers(
+    database: &Pool<Postgres>,
+    config: &AnomalyConfig,
+    baseline_start: PrimitiveDateTime,
+    recent_start: PrimitiveDateTime,
+    anomalies: &mut BTreeMap<(i32, String), Vec<String>>,
+) -> Result<(), sqlx::Error> {
+    let outliers = query!(
+        r#"
+        WITH duration AS (
+            SELECT
+                room_id,
+                machine_id,
+                end_time,
+                EXTRACT(EPOCH FROM end_time - start_time)::FLOAT8 / 60 AS minutes
+            FROM machine_session
+            WHERE end_reason = 'finished' AND end_time >= $1
+        ),
+        baseline AS (
+            SELECT room_id, machine_id, AVG(minutes) AS mean, STDDEV_SAMP(minutes) AS deviation
+            FROM duration
+            WHERE end_time < $2
+            GROUP BY room_id, machine_id
+            HAVING COUNT(*) >= $3
+        )
+        SELECT
+            duration.room_id,
+            duration.machine_id,
+            COUNT(*) AS "cycles!",
+            PERCENTILE_CONT(0.5) WITHIN GROUP (ORDER BY duration.minutes) AS "minutes!",
+            baseline.mean AS "usual_minutes!",
+            baseline.deviation AS "deviation_minutes!"
+        FROM duration
+        JOIN baseline
+            ON baseline.room_id = duration.room_id AND baseline.machine_id = duration.machine_id
+        WHERE duration.end_time >= $2 AND baseline.deviation > 0
+        GROUP BY duration.room_id, duration.machine_id, baseline.mean, baseline.deviation
+        HAVING COUNT(*) >= $5
+            AND ABS(PERCENTILE_CONT(0.5) WITHIN GROUP (ORDER BY duration.minutes) - baseline.mean)
+                > $4 * baseline.deviation
+        "#,
+        baseline_start,
+        recent_start,
+        config.min_samples,
+        config.z_score,
+        config.min_cycles
+    )
+    .fetch_all(database)
+    .await?;
+
+    for outlier in outliers {
+        anomalies
+            .entry((outlier.room_id, outlier.machine_id))
+            .or_default()
+            .push(format!(
+                "Its {} recent cycles ran for a median of {:.0} minutes, against a usual {:.0} ± {:.0} minutes.",
+                outlier.cycles, outlier.minutes, outlier.usual_minutes, outlier.deviation_minutes
+            ));
+    }
+
+    Ok(())
+}
+
+/// Finds machines which have recently drawn much more or less power while running than usual,
+/// such as a dryer which stopped heating.
+async fn find_abnormal_power(
+    database: &Pool<Postgres>,
+    config: &AnomalyConfig,
+    baseline_start: PrimitiveDateTime,
+    recent_start: PrimitiveDateTime,
+    anomalies: &mut BTreeMap<(i32, String), Vec<String>>,
+) -> Result<(), sqlx::Error> {
+    let machines = query!(
+        r#"
+        SELECT
+            room_id,
+            machine_id,
+            (SUM(power_watts * sample_count) FILTER (WHERE time < $2))::FLOAT8
+                / (SUM(sample_count) FILTER (WHERE time < $2))::FLOAT8 AS "usual_watts!",
+            (SUM(power_watts * sample_count) FILTER (WHERE time >= $2))::FLOAT8
+                / (SUM(sample_count) FILTER (WHERE time >= $2))::FLOAT8 AS "watts!"
+        FROM machine_telemetry
+        WHERE time >= $1 AND state = 'running' AND power_watts IS NOT NULL
+        GROUP BY room_id, machine_id
+        HAVING SUM(sample_count) FILTER (WHERE time < $2) >= $3
+            AND SUM(sample_count) FILTER (WHERE time >= $2) >= $3
+        "#,
+        baseline_start,
+        recent_start,
+        config.min_samples
+    )
+    .fetch_all(database)
+    .await?;
+
+    for machine in machines {
+        if (machine.watts - machine.usual_watts).abs()
+            > config.power_tolerance * machine.usual_watts
+        {
+            anomalies
+                .entry((machine.room_id, machine.machine_id))
+                .or_default()
+                .push(format!(
+                    "While running it recently drew {:.0} W on average, against a usual {:.0} W.",
+                    machine.watts, machine.usual_watts
+                ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Finds machines which have recently been reported much more often than usual.
+async fn find_high_report_rate(
+    database: &Pool<Postgres>,
+    config: &AnomalyConfig,
+    baseline_start: PrimitiveDateTime,
+    recent_start: PrimitiveDateTime,
+    anomalies: &mut BTreeMap<(i32, String), Vec<String>>,
+) -> Result<(), sqlx::Error> {
+    let machines = query!(
+        r#"
+        SELECT
+            report.room_id,
+            report.machine_id,
+            COUNT(*) FILTER (WHERE report.time >= $2) AS "reports!",
+            COUNT(*) FILTER (WHERE report.time < $2) AS "usual_reports!"
+        FROM report
+        JOIN report_type ON report_type.name = report.type
+        WHERE report.time >= $1 AND NOT report.automated AND report_type.severity > 0
+        GROUP BY report.room_id, report.machine_id
+        HAVING COUNT(*) FILTER (WHERE report.time >= $2) >= $3
+        "#,
+        baseline_start,
+        recent_start,
+        config.min_reports
+    )
+    .fetch_all(database)
+    .await?;
+
+    let scale = config.recent.as_secs_f64() / (recent_start - baseline_start).as_seconds_f64();
+
+    for machine in machines {
+        // Reports arriving at the usual rate are roughly Poisson distributed.
+        let expected = machine.usual_reports as f64 * scale;
+        if machine.reports as f64 > expected + config.z_score * expected.sqrt() {
+            anomalies
+                .entry((machine.room_id, machine.machine_id))
+                .or_default()
+                .push(format!(
+                    "It was reported {} times recently, against a usual {expected:.1}.",
+                    machine.reports
+                ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks every machine's recent usage against its history, filing a Caution report for each
+/// machine behaving unusually, and returns how many reports were filed.
+///
+/// A machine is only reported once for anomalies in the same recent window, even if its report
+/// has since been archived.
+pub async fn detect_anomalies(
+    database: &Pool<Postgres>,
+    report_config: &ReportConfig,
+    config: &AnomalyConfig,
+) -> Result<u64, sqlx::Error> {
+    let current_time = now();
+    let recent_start = current_time - config.recent;
+    let baseline_start = current_time - config.baseline;
+
+    if baseline_start >= recent_start {
+        log::warn!("Skipped anomaly detection, the baseline must be longer than the recent window");
+        return Ok(0);
+    }
+
+    let mut anomalies = BTreeMap::new();
+    find_duration_outliers(
+        database,
+        config,
+        baseline_start,
+        recent_start,
+        &mut anomalies,
+    )
+    .await?;
+    find_abnormal_power(
+        database,
+        config,
+        baseline_start,
+        recent_start,
+        &mut anomalies,
+    )
+    .await?;
+    find_high_report_rate(
+        database,
+        config,
+        baseline_start,
+        recent_start,
+        &mut anomalies,
+    )
+    .await?;
+
+    let report_type = ReportType("Caution".to_string());
+    let mut filed = 0;
+
+    for ((room_id, machine_id), findings) in anomalies {
+        let already_reported = query!(
+            r#"
+            SELECT EXISTS (
+                SELECT 1
+                FROM report
+                WHERE room_id = $1 AND machine_id = $2 AND type = $3 AND automated AND time >= $4
+            ) AS "exists!"
+            "#,
+            room_id,
+            machine_id,
+            &report_type as &ReportType,
+            recent_start
+        )
+        .fetch_one(database)
+        .await?
+        .exists;
+
+        if already_reported {
+            continue;
+        }
+
+        let report = report::submit_automated_report(
+            database,
+            report_config,
+            &room_id,
+            &machine_id,
+            &report_type,
+            Some(format!("Detected automatically. {}", findings.join(" "))),
+        )
+        .await?;
+
+        if let Some(report) = report {
+            log::warn!(
+                "Filed report id {} for unusual behaviour of machine {machine_id} in room id {room_id}",
+                report.report_id
+            );
+            filed += 1;
+        }
+    }
+
+    Ok(filed)
+}
+
+pub async fn detect_periodically(
+    database: Pool<Postgres>,
+    report_config: ReportConfig,
+    config: AnomalyConfig,
+    mut shutdown: ShutdownSignal,
+) {
+    while shutdown.sleep(config.interval).await {
+        match detect_anomalies(&database, &report_config, &config).await {
+            Ok(0) => {}
+            Ok(filed) => log::info!("Filed {filed} reports for unusual machine behaviour"),
+            Err(err) => log::error!("Failed to detect machine anomalies: {err}"),
+        }
+    }
+}
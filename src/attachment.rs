@@ -0,0 +1,674 @@
+use std::io::Cursor;
+
+use actix_multipart::Multipart;
+use actix_web::{
+    delete, get, post,
+    web::{self, Bytes, BytesMut, Data, Path},
+    HttpResponse, Responder,
+};
+use futures_util::TryStreamExt;
+use image::{codecs::jpeg::JpegEncoder, ImageFormat, ImageReader, Limits};
+use serde::{Deserialize, Serialize};
+use sqlx::{query, query_as, Pool, Postgres};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    blob_store::BlobStore,
+    config,
+    error::{
+        constraint_violation, database_error, internal_server_error, storage_error, Violation,
+    },
+    models::{now, AppState, ReportAttachment},
+    report::is_report_present,
+    tenant::Tenant,
+    user::is_username_present,
+};
+
+/// The image types which may be attached to a report.
+const ALLOWED_FORMATS: [ImageFormat; 3] = [ImageFormat::Jpeg, ImageFormat::Png, ImageFormat::WebP];
+
+/// The largest width or height of an image which will be decoded to generate a thumbnail.
+const MAX_IMAGE_DIMENSION: u32 = 10_000;
+
+/// The longest username accepted in the `uploader_username` field.
+const MAX_USERNAME_BYTES: usize = 256;
+
+/// Attachment upload settings parsed from the environment.
+#[derive(Debug, Clone)]
+pub struct AttachmentConfig {
+    pub max_bytes: usize,
+    /// The largest width or height of generated thumbnails.
+    pub thumbnail_size: u32,
+}
+
+impl AttachmentConfig {
+    /// Parses the attachment configuration from the environment.
+    pub fn from_env() -> AttachmentConfig {
+        AttachmentConfig {
+            max_bytes: config::env_or("ATTACHMENT_MAX_BYTES", 10 * 1024 * 1024),
+            thumbnail_size: config::env_or("ATTACHMENT_THUMBNAIL_SIZE", 256),
+        }
+    }
+}
+
+/// The multipart form accepted when uploading an attachment.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct AttachmentUpload {
+    uploader_username: String,
+    #[schema(value_type = String, format = Binary)]
+    file: Vec<u8>,
+}
+
+struct UploadedFile {
+    file_name: String,
+    content_type: String,
+    bytes: Bytes,
+}
+
+/// Why an upload was rejected before anything was stored.
+enum UploadError {
+    Invalid(String),
+    TooLarge,
+}
+
+/// Reads the `uploader_username` and `file` fields of an upload, enforcing the size limit while streaming.
+async fn read_upload(
+    mut payload: Multipart,
+    max_bytes: usize,
+) -> Result<(Option<String>, Option<UploadedFile>), UploadError> {
+    let mut uploader_username = None;
+    let mut file = None;
+
+    let invalid = |err: actix_multipart::MultipartError| UploadError::Invalid(err.to_string());
+
+    while let Some(mut field) = payload.try_next().await.map_err(invalid)? {
+        match field.name() {
+            Some("uploader_username") => {
+                let mut bytes = BytesMut::new();
+                while let Some(chunk) = field.try_next().await.map_err(invalid)? {
+                    if bytes.len() + chunk.len() > MAX_USERNAME_BYTES {
+                        return Err(UploadError::Invalid(
+                            "The uploader_username field is too long.".to_string(),
+                        ));
+                    }
+                    bytes.extend_from_slice(&chunk);
+                }
+
+                match String::from_utf8(bytes.to_vec()) {
+                    Ok(username) => uploader_username = Some(username),
+                    Err(_) => {
+                        return Err(UploadError::Invalid(
+                            "The uploader_username field is not valid UTF-8.".to_string(),
+                        ))
+                    }
+                }
+            }
+            Some("file") => {
+                let file_name = field
+                    .content_disposition()
+                    .and_then(|disposition| disposition.get_filename())
+                    .unwrap_or("attachment")
+                    .to_string();
+                let content_type = field
+                    .content_type()
+                    .map(|mime| mime.essence_str().to_string())
+                    .unwrap_or_default();
+
+                let mut bytes = BytesMut::new();
+                while let Some(chunk) = field.try_next().await.map_err(invalid)? {
+                    if bytes.len() + chunk.len() > max_bytes {
+                        return Err(UploadError::TooLarge);
+                    }
+                    bytes.extend_from_slice(&chunk);
+                }
+
+                file = Some(UploadedFile {
+                    file_name,
+                    content_type,
+                    bytes: bytes.freeze(),
+                });
+            }
+            // Unknown fields are drained and ignored.
+            _ => while field.try_next().await.map_err(invalid)?.is_some() {},
+        }
+    }
+
+    Ok((uploader_username, file))
+}
+
+/// Decodes an image, returning its dimensions and a JPEG thumbnail no larger than `size` in either dimension.
+fn generate_thumbnail(
+    bytes: &[u8],
+    format: ImageFormat,
+    size: u32,
+) -> image::ImageResult<(u32, u32, Vec<u8>)> {
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_IMAGE_DIMENSION);
+    limits.max_image_height = Some(MAX_IMAGE_DIMENSION);
+
+    let mut reader = ImageReader::with_format(Cursor::new(bytes), format);
+    reader.limits(limits);
+    let image = reader.decode()?;
+
+    let mut thumbnail = Vec::new();
+    image
+        .thumbnail(size, size)
+        .to_rgb8()
+        .write_with_encoder(JpegEncoder::new_with_quality(&mut thumbnail, 80))?;
+
+    Ok((image.width(), image.height(), thumbnail))
+}
+
+/// The blobs stored for one attachment.
+struct AttachmentBlobs {
+    storage_key: String,
+    thumbnail_key: String,
+}
+
+fn blob_keys(attachments: Vec<AttachmentBlobs>) -> Vec<String> {
+    attachments
+        .into_iter()
+        .flat_map(|attachment| [attachment.storage_key, attachment.thumbnail_key])
+        .collect()
+}
+
+/// Lists the blob keys of every attachment of a report, so they can be removed along with the report.
+pub async fn report_blob_keys(
+    database: &Pool<Postgres>,
+    report_id: &i32,
+) -> Result<Vec<String>, sqlx::Error> {
+    query_as!(
+        AttachmentBlobs,
+        r#"
+        SELECT storage_key, thumbnail_key
+        FROM report_attachment
+        WHERE report_id = $1
+        "#,
+        report_id
+    )
+    .fetch_all(database)
+    .await
+    .map(blob_keys)
+}
+
+/// Lists the blob keys of every attachment of the reports about a machine, so they can be
+/// removed along with the machine.
+pub async fn machine_blob_keys(
+    database: &Pool<Postgres>,
+    room_id: &i32,
+    machine_id: &str,
+) -> Result<Vec<String>, sqlx::Error> {
+    query_as!(
+        AttachmentBlobs,
+        r#"
+        SELECT storage_key, thumbnail_key
+        FROM report_attachment
+        WHERE report_id IN (SELECT id FROM report WHERE room_id = $1 AND machine_id = $2)
+        "#,
+        room_id,
+        machine_id
+    )
+    .fetch_all(database)
+    .await
+    .map(blob_keys)
+}
+
+/// Lists the blob keys of every attachment of the reports in a room, so they can be removed
+/// along with the room.
+pub async fn room_blob_keys(
+    database: &Pool<Postgres>,
+    room_id: &i32,
+) -> Result<Vec<String>, sqlx::Error> {
+    query_as!(
+        AttachmentBlobs,
+        r#"
+        SELECT storage_key, thumbnail_key
+        FROM report_attachment
+        WHERE report_id IN (SELECT id FROM report WHERE room_id = $1)
+        "#,
+        room_id
+    )
+    .fetch_all(database)
+    .await
+    .map(blob_keys)
+}
+
+/// Lists the blob keys of every attachment uploaded by a user or added to their reports, so
+/// they can be removed along with the user.
+pub async fn user_blob_keys(
+    database: &Pool<Postgres>,
+    tenant: &Tenant,
+    username: &str,
+) -> Result<Vec<String>, sqlx::Error> {
+    query_as!(
+        AttachmentBlobs,
+        r#"
+        SELECT storage_key, thumbnail_key
+        FROM report_attachment
+        WHERE organization_id = $2
+            AND (
+                uploader_username = $1
+                OR report_id IN (
+                    SELECT id FROM report WHERE reporter_username = $1 AND organization_id = $2
+                )
+            )
+        "#,
+        username,
+        tenant.organization_id
+    )
+    .fetch_all(database)
+    .await
+    .map(blob_keys)
+}
+
+/// Removes blobs whose attachment rows are already gone, logging any which fail to delete.
+pub async fn remove_blobs(blob_store: &BlobStore, keys: &[String]) {
+    for key in keys {
+        if let Err(err) = blob_store.delete(key).await {
+            log::warn!("Failed to remove the attachment blob {key}: {err}");
+        }
+    }
+}
+
+#[utoipa::path(
+    context_path = "/report",
+    params(Tenant),
+    responses(
+        (status = 200, description = "List of all attachments of the requested report", body = Vec<ReportAttachment>, example = json!([{
+            "attachment_id": 1,
+            "report_id": 1,
+            "uploader_username": "admin",
+            "file_name": "display.jpg",
+            "content_type": "image/jpeg",
+            "size_bytes": 183204,
+            "width": 1600,
+            "height": 1200,
+            "time": "2023-01-01T12:00:00.000Z",
+          }])),
+        (status = 404, description = "The requested report was not found"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[get("/{report_id}/attachments")]
+async fn get_report_attachments(
+    data: Data<AppState>,
+    tenant: Tenant,
+    path: Path<i32>,
+) -> impl Responder {
+    let report_id = path.into_inner();
+
+    let report_present = match is_report_present(&data.database, &tenant, &report_id).await {
+        Ok(result) => result,
+        Err(err) => return database_error("check report presence", err),
+    };
+
+    if !report_present {
+        return HttpResponse::NotFound().json(format!("Report id {report_id} was not found."));
+    }
+
+    match query_as!(
+        ReportAttachment,
+        r#"
+        SELECT
+            id AS "attachment_id: i32",
+            report_id,
+            uploader_username,
+            file_name,
+            content_type,
+            size_bytes,
+            width,
+            height,
+            time
+        FROM report_attachment
+        WHERE report_id = $1
+        ORDER BY id
+        "#,
+        report_id
+    )
+    .fetch_all(&data.database)
+    .await
+    {
+        Ok(attachments) => HttpResponse::Ok().json(attachments),
+        Err(err) => database_error("fetch report attachments", err),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/report",
+    params(Tenant),
+    request_body(
+        content = AttachmentUpload,
+        content_type = "multipart/form-data",
+        description = "Multipart form containing the uploader's username and a JPEG, PNG or WebP image",
+    ),
+    responses(
+        (status = 201, description = "The attachment was added", body = ReportAttachment, example = json!({
+            "attachment_id": 1,
+            "report_id": 1,
+            "uploader_username": "admin",
+            "file_name": "display.jpg",
+            "content_type": "image/jpeg",
+            "size_bytes": 183204,
+            "width": 1600,
+            "height": 1200,
+            "time": "2023-01-01T12:00:00.000Z",
+          })),
+        (status = 400, description = "The requested query was invalid"),
+        (status = 404, description = "The requested report was not found"),
+        (status = 413, description = "The file is larger than the configured limit"),
+        (status = 415, description = "The file is not a JPEG, PNG or WebP image"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[post("/{report_id}/attachments")]
+async fn add_report_attachment(
+    data: Data<AppState>,
+    tenant: Tenant,
+    path: Path<i32>,
+    payload: Multipart,
+) -> impl Responder {
+    let report_id = path.into_inner();
+
+    let report_present = match is_report_present(&data.database, &tenant, &report_id).await {
+        Ok(result) => result,
+        Err(err) => return database_error("check report presence", err),
+    };
+
+    if !report_present {
+        return HttpResponse::NotFound().json(format!("Report id {report_id} was not found."));
+    }
+
+    let max_bytes = data.attachment_config.max_bytes;
+    let (uploader_username, file) = match read_upload(payload, max_bytes).await {
+        Ok(upload) => upload,
+        Err(UploadError::Invalid(message)) => return HttpResponse::BadRequest().json(message),
+        Err(UploadError::TooLarge) => {
+            return HttpResponse::PayloadTooLarge()
+                .json(format!("Attachments may be at most {max_bytes} bytes."))
+        }
+    };
+
+    let Some(uploader_username) = uploader_username else {
+        return HttpResponse::BadRequest().json("The uploader_username field is required.");
+    };
+    let Some(file) = file else {
+        return HttpResponse::BadRequest().json("The file field is required.");
+    };
+
+    let uploader_present =
+        match is_username_present(&data.database, &tenant, &uploader_username).await {
+            Ok(result) => result,
+            Err(err) => return database_error("check username presence", err),
+        };
+
+    if !uploader_present {
+        return HttpResponse::BadRequest()
+            .json(format!("The user {uploader_username} was not found."));
+    }
+
+    let format = match ImageFormat::from_mime_type(&file.content_type) {
+        Some(format) if ALLOWED_FORMATS.contains(&format) => format,
+        _ => {
+            return HttpResponse::UnsupportedMediaType().json(format!(
+                "Attachments must be JPEG, PNG or WebP images, not {}.",
+                file.content_type
+            ))
+        }
+    };
+
+    if image::guess_format(&file.bytes).ok() != Some(format) {
+        return HttpResponse::UnsupportedMediaType().json(format!(
+            "The file contents do not match its content type of {}.",
+            file.content_type
+        ));
+    }
+
+    let thumbnail_size = data.attachment_config.thumbnail_size;
+    let bytes = file.bytes.clone();
+    let (width, height, thumbnail) =
+        match web::block(move || generate_thumbnail(&bytes, format, thumbnail_size)).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(err)) => {
+                return HttpResponse::BadRequest()
+                    .json(format!("The image could not be read: {err}"))
+            }
+            Err(err) => {
+                log::error!("Failed to generate attachment thumbnail: {err}");
+                return internal_server_error();
+            }
+        };
+
+    let key = format!("reports/{report_id}/{}", Uuid::new_v4());
+    let thumbnail_key = format!("{key}-thumbnail");
+
+    if let Err(err) = data
+        .blob_store
+        .put(&key, file.bytes.clone(), &file.content_type)
+        .await
+    {
+        return storage_error("store attachment", err);
+    }
+    if let Err(err) = data
+        .blob_store
+        .put(&thumbnail_key, thumbnail.into(), "image/jpeg")
+        .await
+    {
+        remove_blobs(&data.blob_store, &[key]).await;
+        return storage_error("store attachment thumbnail", err);
+    }
+
+    let current_time = now();
+
+    let result = query_as!(
+        ReportAttachment,
+        r#"
+        INSERT INTO report_attachment (
+            report_id, uploader_username, file_name, content_type, size_bytes,
+            width, height, storage_key, thumbnail_key, time, organization_id
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+        RETURNING
+            id AS "attachment_id: i32",
+            report_id,
+            uploader_username,
+            file_name,
+            content_type,
+            size_bytes,
+            width,
+            height,
+            time
+        "#,
+        report_id,
+        &uploader_username,
+        &file.file_name,
+        &file.content_type,
+        file.bytes.len() as i64,
+        width as i32,
+        height as i32,
+        &key,
+        &thumbnail_key,
+        current_time,
+        tenant.organization_id
+    )
+    .fetch_one(&data.database)
+    .await;
+
+    if result.is_err() {
+        remove_blobs(&data.blob_store, &[key, thumbnail_key]).await;
+    }
+
+    match result {
+        Ok(attachment) => HttpResponse::Created().json(attachment),
+        Err(err) => match constraint_violation(&err) {
+            Some(Violation::ForeignKey) => HttpResponse::BadRequest().json(format!(
+                "Report id {report_id} or the user {uploader_username} no longer exists."
+            )),
+            _ => database_error("insert report attachment", err),
+        },
+    }
+}
+
+/// Serves one of the stored files of an attachment.
+async fn serve_attachment(
+    data: &AppState,
+    tenant: &Tenant,
+    report_id: i32,
+    attachment_id: i32,
+    thumbnail: bool,
+) -> HttpResponse {
+    let stored = match query!(
+        r#"
+        SELECT content_type, storage_key, thumbnail_key
+        FROM report_attachment
+        WHERE id = $1 AND report_id = $2 AND report_id IN (
+            SELECT report.id FROM report JOIN room ON room.id = report.room_id
+            WHERE room.organization_id = $3
+        )
+        "#,
+        attachment_id,
+        report_id,
+        tenant.organization_id
+    )
+    .fetch_optional(&data.database)
+    .await
+    {
+        Ok(Some(stored)) => stored,
+        Ok(None) => {
+            return HttpResponse::NotFound().json(format!(
+                "Attachment id {attachment_id} was not found on report id {report_id}."
+            ))
+        }
+        Err(err) => return database_error("fetch report attachment", err),
+    };
+
+    let (key, content_type) = match thumbnail {
+        true => (stored.thumbnail_key, "image/jpeg".to_string()),
+        false => (stored.storage_key, stored.content_type),
+    };
+
+    match data.blob_store.get(&key).await {
+        Ok(bytes) => HttpResponse::Ok().content_type(content_type).body(bytes),
+        Err(err) => storage_error("read attachment", err),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/report",
+    params(Tenant),
+    responses(
+        (status = 200, description = "The uploaded image", content_type = "image/*"),
+        (status = 404, description = "The requested attachment was not found"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[get("/{report_id}/attachments/{attachment_id}")]
+async fn get_report_attachment(
+    data: Data<AppState>,
+    tenant: Tenant,
+    path: Path<(i32, i32)>,
+) -> impl Responder {
+    let (report_id, attachment_id) = path.into_inner();
+    serve_attachment(&data, &tenant, report_id, attachment_id, false).await
+}
+
+#[utoipa::path(
+    context_path = "/report",
+    params(Tenant),
+    responses(
+        (status = 200, description = "A JPEG thumbnail of the uploaded image", content_type = "image/jpeg"),
+        (status = 404, description = "The requested attachment was not found"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[get("/{report_id}/attachments/{attachment_id}/thumbnail")]
+async fn get_report_attachment_thumbnail(
+    data: Data<AppState>,
+    tenant: Tenant,
+    path: Path<(i32, i32)>,
+) -> impl Responder {
+    let (report_id, attachment_id) = path.into_inner();
+    serve_attachment(&data, &tenant, report_id, attachment_id, true).await
+}
+
+#[utoipa::path(
+    context_path = "/report",
+    params(Tenant),
+    responses(
+        (status = 200, description = "The requested attachment was deleted", body = ReportAttachment, example = json!({
+            "attachment_id": 1,
+            "report_id": 1,
+            "uploader_username": "admin",
+            "file_name": "display.jpg",
+            "content_type": "image/jpeg",
+            "size_bytes": 183204,
+            "width": 1600,
+            "height": 1200,
+            "time": "2023-01-01T12:00:00.000Z",
+          })),
+        (status = 404, description = "The requested attachment was not found"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[delete("/{report_id}/attachments/{attachment_id}")]
+async fn delete_report_attachment(
+    data: Data<AppState>,
+    tenant: Tenant,
+    path: Path<(i32, i32)>,
+) -> impl Responder {
+    let (report_id, attachment_id) = path.into_inner();
+
+    let deleted = match query!(
+        r#"
+        DELETE FROM report_attachment
+        WHERE id = $1 AND report_id = $2 AND report_id IN (
+            SELECT report.id FROM report JOIN room ON room.id = report.room_id
+            WHERE room.organization_id = $3
+        )
+        RETURNING
+            id AS "attachment_id: i32",
+            report_id,
+            uploader_username,
+            file_name,
+            content_type,
+            size_bytes,
+            width,
+            height,
+            time,
+            storage_key,
+            thumbnail_key
+        "#,
+        attachment_id,
+        report_id,
+        tenant.organization_id
+    )
+    .fetch_optional(&data.database)
+    .await
+    {
+        Ok(Some(deleted)) => deleted,
+        Ok(None) => {
+            return HttpResponse::NotFound().json(format!(
+                "Attachment id {attachment_id} was not found on report id {report_id}."
+            ))
+        }
+        Err(err) => return database_error("delete report attachment", err),
+    };
+
+    remove_blobs(
+        &data.blob_store,
+        &[deleted.storage_key, deleted.thumbnail_key],
+    )
+    .await;
+
+    HttpResponse::Ok().json(ReportAttachment {
+        attachment_id: deleted.attachment_id,
+        report_id: deleted.report_id,
+        uploader_username: deleted.uploader_username,
+        file_name: deleted.file_name,
+        content_type: deleted.content_type,
+        size_bytes: deleted.size_bytes,
+        width: deleted.width,
+        height: deleted.height,
+        time: deleted.time,
+    })
+}
@@ -0,0 +1,87 @@
+use std::{future::Future, time::Duration};
+
+use actix_web::rt::{self, task::JoinHandle};
+use tokio::{
+    sync::watch,
+    time::{self, Instant},
+};
+
+/// Tracks long running background jobs so that they can be stopped cleanly on shutdown.
+///
+/// Every job receives a [ShutdownSignal] which it should watch, finishing its current unit
+/// of work and returning once the signal is triggered.
+pub struct BackgroundJobs {
+    shutdown: watch::Sender<bool>,
+    jobs: Vec<(&'static str, JoinHandle<()>)>,
+}
+
+/// Notifies a background job that the server is shutting down.
+#[derive(Clone)]
+pub struct ShutdownSignal(watch::Receiver<bool>);
+
+impl ShutdownSignal {
+    /// Returns true once shutdown has been requested.
+    pub fn is_triggered(&self) -> bool {
+        *self.0.borrow()
+    }
+
+    /// Waits until shutdown has been requested.
+    pub async fn triggered(&mut self) {
+        // An error means the sender was dropped, which can only happen during shutdown.
+        let _ = self.0.wait_for(|triggered| *triggered).await;
+    }
+
+    /// Sleeps for the given duration, returning early with `false` if shutdown is requested.
+    pub async fn sleep(&mut self, duration: Duration) -> bool {
+        tokio::select! {
+            _ = time::sleep(duration) => true,
+            _ = self.triggered() => false,
+        }
+    }
+}
+
+impl Default for BackgroundJobs {
+    fn default() -> Self {
+        BackgroundJobs::new()
+    }
+}
+
+impl BackgroundJobs {
+    pub fn new() -> BackgroundJobs {
+        let (shutdown, _) = watch::channel(false);
+        BackgroundJobs {
+            shutdown,
+            jobs: Vec::new(),
+        }
+    }
+
+    /// Spawns a job on the current runtime, handing it a [ShutdownSignal].
+    pub fn spawn<F, Fut>(&mut self, name: &'static str, job: F)
+    where
+        F: FnOnce(ShutdownSignal) -> Fut,
+        Fut: Future<Output = ()> + 'static,
+    {
+        let signal = ShutdownSignal(self.shutdown.subscribe());
+        log::info!("Starting background job {name}");
+        self.jobs.push((name, rt::spawn(job(signal))));
+    }
+
+    /// Signals every job to stop and waits up to `timeout` for all of them to finish.
+    /// Jobs which do not finish in time are aborted.
+    pub async fn shutdown(self, timeout: Duration) {
+        let _ = self.shutdown.send(true);
+        let deadline = Instant::now() + timeout;
+
+        for (name, job) in self.jobs {
+            let abort_handle = job.abort_handle();
+            match time::timeout_at(deadline, job).await {
+                Ok(Ok(())) => log::info!("Background job {name} finished"),
+                Ok(Err(err)) => log::error!("Background job {name} failed: {err}"),
+                Err(_) => {
+                    log::warn!("Background job {name} did not finish in time and was aborted");
+                    abort_handle.abort();
+                }
+            }
+        }
+    }
+}
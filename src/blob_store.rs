@@ -0,0 +1,169 @@
+use std::{
+    env, io,
+    path::{Path, PathBuf},
+};
+
+use actix_web::web::Bytes;
+#[cfg(feature = "s3")]
+use object_store::{
+    aws::{AmazonS3, AmazonS3Builder},
+    path::Path as ObjectPath,
+    Attribute, Attributes, ObjectStore, ObjectStoreExt, PutOptions,
+};
+use tokio::fs;
+
+enum Backend {
+    /// Blobs are stored as files below a directory.
+    Local(PathBuf),
+    /// Blobs are stored in an S3-compatible bucket.
+    #[cfg(feature = "s3")]
+    S3(AmazonS3),
+}
+
+/// Stores uploaded files, such as report attachments, under opaque keys.
+pub struct BlobStore {
+    backend: Backend,
+}
+
+impl BlobStore {
+    /// Creates a blob store, storing blobs in S3 when BLOB_STORE_BACKEND is `s3`
+    /// and in the BLOB_STORE_PATH directory otherwise.
+    pub fn from_env() -> io::Result<BlobStore> {
+        let backend = match env::var("BLOB_STORE_BACKEND")
+            .unwrap_or_default()
+            .to_lowercase()
+            .as_str()
+        {
+            "s3" => s3_backend()?,
+            _ => Backend::Local(
+                env::var("BLOB_STORE_PATH")
+                    .unwrap_or_else(|_| "attachments".to_string())
+                    .into(),
+            ),
+        };
+
+        Ok(BlobStore { backend })
+    }
+
+    /// Stores `bytes` under `key`, replacing any existing blob.
+    #[cfg_attr(not(feature = "s3"), allow(unused_variables))]
+    pub async fn put(&self, key: &str, bytes: Bytes, content_type: &str) -> io::Result<()> {
+        match &self.backend {
+            Backend::Local(root) => {
+                let path = local_path(root, key)?;
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent).await?;
+                }
+
+                // Written next to the destination first so readers never see a partial file.
+                let partial = path.with_extension("partial");
+                fs::write(&partial, &bytes).await?;
+                fs::rename(&partial, &path).await
+            }
+            #[cfg(feature = "s3")]
+            Backend::S3(store) => {
+                let mut attributes = Attributes::new();
+                attributes.insert(Attribute::ContentType, content_type.to_string().into());
+
+                store
+                    .put_opts(
+                        &ObjectPath::from(key),
+                        bytes.into(),
+                        PutOptions {
+                            attributes,
+                            ..PutOptions::default()
+                        },
+                    )
+                    .await
+                    .map(|_| ())
+                    .map_err(s3_error)
+            }
+        }
+    }
+
+    /// Reads the blob stored under `key`, failing with [io::ErrorKind::NotFound] if there is none.
+    pub async fn get(&self, key: &str) -> io::Result<Bytes> {
+        match &self.backend {
+            Backend::Local(root) => fs::read(local_path(root, key)?).await.map(Bytes::from),
+            #[cfg(feature = "s3")]
+            Backend::S3(store) => match store.get(&ObjectPath::from(key)).await {
+                Ok(result) => result.bytes().await.map_err(s3_error),
+                Err(err) => Err(s3_error(err)),
+            },
+        }
+    }
+
+    /// Removes the blob stored under `key`. Removing a blob which does not exist is not an error.
+    pub async fn delete(&self, key: &str) -> io::Result<()> {
+        let result = match &self.backend {
+            Backend::Local(root) => fs::remove_file(local_path(root, key)?).await,
+            #[cfg(feature = "s3")]
+            Backend::S3(store) => store.delete(&ObjectPath::from(key)).await.map_err(s3_error),
+        };
+
+        match result {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        }
+    }
+}
+
+/// Resolves a key to a path below `root`, refusing keys which would escape it.
+fn local_path(root: &Path, key: &str) -> io::Result<PathBuf> {
+    let valid = !key.is_empty()
+        && key
+            .split('/')
+            .all(|part| !part.is_empty() && part != "." && part != "..");
+
+    match valid {
+        true => Ok(root.join(key)),
+        false => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid blob key {key}"),
+        )),
+    }
+}
+
+#[cfg(feature = "s3")]
+fn s3_backend() -> io::Result<Backend> {
+    let bucket = env::var("S3_BUCKET").map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "S3_BUCKET must be set when BLOB_STORE_BACKEND is s3",
+        )
+    })?;
+
+    let mut builder = AmazonS3Builder::new()
+        .with_bucket_name(bucket)
+        .with_region(env::var("S3_REGION").unwrap_or_else(|_| "us-east-1".to_string()));
+
+    if let Ok(endpoint) = env::var("S3_ENDPOINT") {
+        builder = builder
+            .with_allow_http(endpoint.starts_with("http://"))
+            .with_endpoint(endpoint);
+    }
+    if let Ok(access_key_id) = env::var("S3_ACCESS_KEY_ID") {
+        builder = builder.with_access_key_id(access_key_id);
+    }
+    if let Ok(secret_access_key) = env::var("S3_SECRET_ACCESS_KEY") {
+        builder = builder.with_secret_access_key(secret_access_key);
+    }
+
+    builder.build().map(Backend::S3).map_err(s3_error)
+}
+
+#[cfg(not(feature = "s3"))]
+fn s3_backend() -> io::Result<Backend> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "the s3 blob store backend requires building with the s3 feature",
+    ))
+}
+
+#[cfg(feature = "s3")]
+fn s3_error(err: object_store::Error) -> io::Error {
+    match err {
+        object_store::Error::NotFound { .. } => io::Error::new(io::ErrorKind::NotFound, err),
+        err => io::Error::other(err),
+    }
+}
@@ -0,0 +1,506 @@
+use actix_web::{
+    delete, get, post,
+    web::{Data, Json, Path, Query},
+    HttpResponse, Responder,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{query, query_as, Pool, Postgres};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    error::{constraint_violation, database_error, Violation},
+    models::{AppState, Building, Report, ReportType, Room},
+    site::is_site_present,
+    tenant::Tenant,
+};
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct BuildingSubmission {
+    #[serde(default)]
+    site_id: Option<i32>,
+    name: String,
+    #[serde(default)]
+    address: Option<String>,
+    #[serde(default)]
+    latitude: Option<f64>,
+    #[serde(default)]
+    longitude: Option<f64>,
+}
+
+/// Filters applied when listing buildings.
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct BuildingQuery {
+    site_id: Option<i32>,
+}
+
+/// The number of open reports of one type.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ReportTypeCount {
+    pub report_type: ReportType,
+    pub count: i64,
+}
+
+/// A summary of the rooms, machines and open reports in a building.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct BuildingStatus {
+    pub building_id: i32,
+    pub room_count: i64,
+    pub machine_count: i64,
+    pub open_report_count: i64,
+    /// Machines with at least one open report.
+    pub machines_with_open_reports: i64,
+    pub open_reports_by_type: Vec<ReportTypeCount>,
+}
+
+pub async fn is_building_present(
+    database: &Pool<Postgres>,
+    tenant: &Tenant,
+    building_id: &i32,
+) -> Result<bool, sqlx::Error> {
+    match query!(
+        r#"
+        SELECT id
+        FROM building
+        WHERE id = $1 AND organization_id = $2
+        "#,
+        building_id,
+        tenant.organization_id
+    )
+    .fetch_optional(database)
+    .await
+    {
+        Ok(result) => Ok(result.is_some()),
+        Err(err) => Err(err),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/building",
+    params(Tenant, BuildingQuery),
+    responses(
+        (status = 200, description = "Lists all buildings matching the given filters", body = Vec<Building>, example = json!([{
+            "building_id": 1,
+            "site_id": 1,
+            "name": "Complex A",
+            "address": "1 College Road",
+            "latitude": 51.7548,
+            "longitude": -1.2544
+        }])),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[get("/")]
+async fn get_all_buildings(
+    data: Data<AppState>,
+    tenant: Tenant,
+    Query(building_query): Query<BuildingQuery>,
+) -> impl Responder {
+    match query_as!(
+        Building,
+        r#"
+        SELECT
+            id AS "building_id: i32",
+            site_id,
+            name,
+            address,
+            latitude,
+            longitude
+        FROM building
+        WHERE organization_id = $1
+            AND ($2::INTEGER IS NULL OR site_id = $2)
+        "#,
+        tenant.organization_id,
+        building_query.site_id
+    )
+    .fetch_all(&data.database)
+    .await
+    {
+        Ok(buildings) => HttpResponse::Ok().json(buildings),
+        Err(err) => database_error("fetch all buildings", err),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/building",
+    params(Tenant),
+    responses(
+        (status = 200, description = "The requested building", body = Building, example = json!({
+            "building_id": 1,
+            "site_id": 1,
+            "name": "Complex A",
+            "address": "1 College Road",
+            "latitude": 51.7548,
+            "longitude": -1.2544
+        })),
+        (status = 404, description = "The requested building was not found"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[get("/{building_id}")]
+async fn get_building(data: Data<AppState>, tenant: Tenant, path: Path<i32>) -> impl Responder {
+    let building_id = path.into_inner();
+
+    match query_as!(
+        Building,
+        r#"
+        SELECT
+            id AS "building_id: i32",
+            site_id,
+            name,
+            address,
+            latitude,
+            longitude
+        FROM building
+        WHERE id = $1 AND organization_id = $2
+        "#,
+        building_id,
+        tenant.organization_id
+    )
+    .fetch_optional(&data.database)
+    .await
+    {
+        Err(err) => database_error("fetch building", err),
+        Ok(building) => match building {
+            Some(building) => HttpResponse::Ok().json(&building),
+            None => HttpResponse::NotFound()
+                .json(format!("The building id {building_id} was not found.")),
+        },
+    }
+}
+
+#[utoipa::path(
+    context_path = "/building",
+    params(Tenant),
+    request_body(content = BuildingSubmission, content_type = "application/json", example = json!({
+        "site_id": 1,
+        "name": "Complex A",
+        "address": "1 College Road",
+        "latitude": 51.7548,
+        "longitude": -1.2544
+    })),
+    responses(
+        (status = 201, description = "The requested building was created", body = Building, example = json!({
+            "building_id": 1,
+            "site_id": 1,
+            "name": "Complex A",
+            "address": "1 College Road",
+            "latitude": 51.7548,
+            "longitude": -1.2544
+        })),
+        (status = 400, description = "The requested site does not exist or the coordinates are invalid"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[post("/")]
+async fn add_building(
+    data: Data<AppState>,
+    tenant: Tenant,
+    Json(building_submission): Json<BuildingSubmission>,
+) -> impl Responder {
+    if let Some(site_id) = building_submission.site_id {
+        let site_present = match is_site_present(&data.database, &tenant, &site_id).await {
+            Ok(result) => result,
+            Err(err) => return database_error("check site presence", err),
+        };
+
+        if !site_present {
+            return HttpResponse::BadRequest().json(format!("Site id {site_id} was not found."));
+        }
+    }
+
+    match query_as!(
+        Building,
+        r#"
+        INSERT INTO building (site_id, name, address, latitude, longitude, organization_id)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING
+            id AS "building_id: i32",
+            site_id,
+            name,
+            address,
+            latitude,
+            longitude
+        "#,
+        building_submission.site_id,
+        &building_submission.name,
+        building_submission.address,
+        building_submission.latitude,
+        building_submission.longitude,
+        tenant.organization_id
+    )
+    .fetch_one(&data.database)
+    .await
+    {
+        Ok(building) => HttpResponse::Created().json(building),
+        Err(err) => match constraint_violation(&err) {
+            Some(Violation::Check) => HttpResponse::BadRequest().json(
+                "The latitude must be between -90 and 90, and the longitude between -180 and 180.",
+            ),
+            Some(Violation::ForeignKey) => {
+                HttpResponse::BadRequest().json("The site of the building no longer exists.")
+            }
+            _ => database_error("insert building", err),
+        },
+    }
+}
+
+#[utoipa::path(
+    context_path = "/building",
+    params(Tenant),
+    responses(
+        (status = 200, description = "The requested building was deleted, its rooms are kept without a building", body = Building, example = json!({
+            "building_id": 1,
+            "site_id": 1,
+            "name": "Complex A",
+            "address": "1 College Road",
+            "latitude": 51.7548,
+            "longitude": -1.2544
+        })),
+        (status = 404, description = "The requested building was not found"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[delete("/{building_id}")]
+async fn delete_building(data: Data<AppState>, tenant: Tenant, path: Path<i32>) -> impl Responder {
+    let building_id = path.into_inner();
+
+    match query_as!(
+        Building,
+        r#"
+        DELETE FROM building
+        WHERE id = $1 AND organization_id = $2
+        RETURNING
+            id AS "building_id: i32",
+            site_id,
+            name,
+            address,
+            latitude,
+            longitude
+        "#,
+        &building_id,
+        tenant.organization_id
+    )
+    .fetch_optional(&data.database)
+    .await
+    {
+        Ok(Some(building)) => HttpResponse::Ok().json(building),
+        Ok(None) => {
+            HttpResponse::NotFound().json(format!("Building id {building_id} was not found."))
+        }
+        Err(err) => database_error("delete building", err),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/building",
+    params(Tenant),
+    responses(
+        (status = 200, description = "List of all rooms in the requested building", body = Vec<Room>, example = json!([{
+            "room_id": 1,
+            "name": "Room 1",
+            "description": "Room 1 in Complex A",
+            "building_id": 1
+        }])),
+        (status = 404, description = "The requested building was not found"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[get("/{building_id}/rooms")]
+async fn get_building_rooms(
+    data: Data<AppState>,
+    tenant: Tenant,
+    path: Path<i32>,
+) -> impl Responder {
+    let building_id = path.into_inner();
+
+    let building_present = match is_building_present(&data.database, &tenant, &building_id).await {
+        Ok(result) => result,
+        Err(err) => return database_error("check building presence", err),
+    };
+
+    if !building_present {
+        return HttpResponse::NotFound().json(format!("Building id {building_id} was not found."));
+    }
+
+    match query_as!(
+        Room,
+        r#"
+        SELECT id as "room_id: i32", name, description, building_id
+        FROM room
+        WHERE building_id = $1
+        "#,
+        &building_id
+    )
+    .fetch_all(&data.database)
+    .await
+    {
+        Ok(rooms) => HttpResponse::Ok().json(rooms),
+        Err(err) => database_error("fetch building rooms", err),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/building",
+    params(Tenant),
+    responses(
+        (status = 200, description = "List of all unarchived reports for machines in the requested building", body = Vec<Report>, example = json!([{
+            "report_id": 1,
+            "room_id": 1,
+            "machine_id": "A",
+            "reporter_username": "admin",
+            "report_type": "Broken",
+            "description": "No heat",
+            "time": "2023-01-01T12:00:00.000Z",
+            "archived": false,
+            "automated": false,
+            "confirmation_count": 0,
+            "comment_count": 0,
+            "attachments": [],
+        }])),
+        (status = 404, description = "The requested building was not found"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[get("/{building_id}/reports")]
+async fn get_building_reports(
+    data: Data<AppState>,
+    tenant: Tenant,
+    path: Path<i32>,
+) -> impl Responder {
+    let building_id = path.into_inner();
+
+    let building_present = match is_building_present(&data.database, &tenant, &building_id).await {
+        Ok(result) => result,
+        Err(err) => return database_error("check building presence", err),
+    };
+
+    if !building_present {
+        return HttpResponse::NotFound().json(format!("Building id {building_id} was not found."));
+    }
+
+    match query_as!(
+        Report,
+        r#"
+        SELECT
+            report.id AS "report_id: i32",
+            report.room_id,
+            report.machine_id,
+            report.reporter_username,
+            report.time,
+            report.type AS "report_type: ReportType",
+            report.description,
+            report.archived,
+            report.automated,
+            (SELECT COUNT(*) FROM report_confirmation WHERE report_id = report.id) AS "confirmation_count!: i64",
+            (SELECT COUNT(*) FROM report_comment WHERE report_id = report.id) AS "comment_count!: i64",
+            report_attachments(report.id) AS "attachments!: _"
+        FROM report
+        JOIN room ON room.id = report.room_id
+        WHERE room.building_id = $1
+            AND report.archived = false
+        "#,
+        &building_id
+    )
+    .fetch_all(&data.database)
+    .await
+    {
+        Ok(reports) => HttpResponse::Ok().json(reports),
+        Err(err) => database_error("fetch building reports", err),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/building",
+    params(Tenant),
+    responses(
+        (status = 200, description = "Summary of the rooms, machines and open reports in the requested building", body = BuildingStatus, example = json!({
+            "building_id": 1,
+            "room_count": 3,
+            "machine_count": 12,
+            "open_report_count": 2,
+            "machines_with_open_reports": 1,
+            "open_reports_by_type": [{
+                "report_type": "Broken",
+                "count": 2
+            }]
+        })),
+        (status = 404, description = "The requested building was not found"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[get("/{building_id}/status")]
+async fn get_building_status(
+    data: Data<AppState>,
+    tenant: Tenant,
+    path: Path<i32>,
+) -> impl Responder {
+    let building_id = path.into_inner();
+
+    let building_present = match is_building_present(&data.database, &tenant, &building_id).await {
+        Ok(result) => result,
+        Err(err) => return database_error("check building presence", err),
+    };
+
+    if !building_present {
+        return HttpResponse::NotFound().json(format!("Building id {building_id} was not found."));
+    }
+
+    let counts = match query!(
+        r#"
+        SELECT
+            (SELECT COUNT(*) FROM room WHERE building_id = $1) AS "room_count!",
+            (
+                SELECT COUNT(*)
+                FROM machine
+                JOIN room ON room.id = machine.room_id
+                WHERE room.building_id = $1
+            ) AS "machine_count!",
+            (
+                SELECT COUNT(*)
+                FROM report
+                JOIN room ON room.id = report.room_id
+                WHERE room.building_id = $1 AND report.archived = false
+            ) AS "open_report_count!",
+            (
+                SELECT COUNT(DISTINCT (report.room_id, report.machine_id))
+                FROM report
+                JOIN room ON room.id = report.room_id
+                WHERE room.building_id = $1 AND report.archived = false
+            ) AS "machines_with_open_reports!"
+        "#,
+        &building_id
+    )
+    .fetch_one(&data.database)
+    .await
+    {
+        Ok(counts) => counts,
+        Err(err) => return database_error("fetch building status", err),
+    };
+
+    match query_as!(
+        ReportTypeCount,
+        r#"
+        SELECT report.type AS "report_type: ReportType", COUNT(*) AS "count!"
+        FROM report
+        JOIN room ON room.id = report.room_id
+        WHERE room.building_id = $1 AND report.archived = false
+        GROUP BY report.type
+        ORDER BY report.type
+        "#,
+        &building_id
+    )
+    .fetch_all(&data.database)
+    .await
+    {
+        Ok(open_reports_by_type) => HttpResponse::Ok().json(BuildingStatus {
+            building_id,
+            room_count: counts.room_count,
+            machine_count: counts.machine_count,
+            open_report_count: counts.open_report_count,
+            machines_with_open_reports: counts.machines_with_open_reports,
+            open_reports_by_type,
+        }),
+        Err(err) => database_error("fetch building report counts", err),
+    }
+}
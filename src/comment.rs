@@ -0,0 +1,355 @@
+use actix_web::{
+    delete, get, patch, post,
+    web::{Data, Json, Path},
+    HttpResponse, Responder,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{query, query_as, Pool, Postgres};
+use utoipa::ToSchema;
+
+use crate::{
+    error::{constraint_violation, database_error, Violation},
+    models::{now, AppState, ReportComment},
+    report::is_report_present,
+    tenant::Tenant,
+    user::is_username_present,
+};
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct CommentSubmission {
+    author_username: String,
+    body: String,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct CommentEdit {
+    author_username: String,
+    body: String,
+}
+
+/// Returns the author of a comment on the given report, or `None` if there is no such comment.
+async fn comment_author(
+    database: &Pool<Postgres>,
+    tenant: &Tenant,
+    report_id: &i32,
+    comment_id: &i32,
+) -> Result<Option<String>, sqlx::Error> {
+    match query!(
+        r#"
+        SELECT author_username
+        FROM report_comment
+        WHERE id = $1 AND report_id = $2 AND report_id IN (
+            SELECT report.id FROM report JOIN room ON room.id = report.room_id
+            WHERE room.organization_id = $3
+        )
+        "#,
+        comment_id,
+        report_id,
+        tenant.organization_id
+    )
+    .fetch_optional(database)
+    .await
+    {
+        Ok(result) => Ok(result.map(|comment| comment.author_username)),
+        Err(err) => Err(err),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/report",
+    params(Tenant),
+    responses(
+        (status = 200, description = "List of all comments on the requested report, oldest first", body = Vec<ReportComment>, example = json!([{
+            "comment_id": 1,
+            "report_id": 1,
+            "author_username": "technician",
+            "body": "Which cycle was it on?",
+            "time": "2023-01-01T12:30:00.000Z",
+            "edited_time": null,
+          }])),
+        (status = 404, description = "The requested report was not found"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[get("/{report_id}/comments")]
+async fn get_report_comments(
+    data: Data<AppState>,
+    tenant: Tenant,
+    path: Path<i32>,
+) -> impl Responder {
+    let report_id = path.into_inner();
+
+    let report_present = match is_report_present(&data.database, &tenant, &report_id).await {
+        Ok(result) => result,
+        Err(err) => return database_error("check report presence", err),
+    };
+
+    if !report_present {
+        return HttpResponse::NotFound().json(format!("Report id {report_id} was not found."));
+    }
+
+    match query_as!(
+        ReportComment,
+        r#"
+        SELECT
+            id AS "comment_id: i32",
+            report_id,
+            author_username,
+            body,
+            time,
+            edited_time
+        FROM report_comment
+        WHERE report_id = $1
+        ORDER BY time
+        "#,
+        report_id
+    )
+    .fetch_all(&data.database)
+    .await
+    {
+        Ok(comments) => HttpResponse::Ok().json(comments),
+        Err(err) => database_error("fetch report comments", err),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/report",
+    params(Tenant),
+    request_body(
+        content = CommentSubmission,
+        content_type = "application/json",
+        description = "JSON object containing the author's username and the comment",
+        example = json!({
+            "author_username": "technician",
+            "body": "Which cycle was it on?",
+          })
+    ),
+    responses(
+        (status = 201, description = "The comment was added", body = ReportComment, example = json!({
+            "comment_id": 1,
+            "report_id": 1,
+            "author_username": "technician",
+            "body": "Which cycle was it on?",
+            "time": "2023-01-01T12:30:00.000Z",
+            "edited_time": null,
+          })),
+        (status = 400, description = "The requested query was invalid"),
+        (status = 404, description = "The requested report was not found"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[post("/{report_id}/comments")]
+async fn add_report_comment(
+    data: Data<AppState>,
+    tenant: Tenant,
+    path: Path<i32>,
+    Json(comment_submission): Json<CommentSubmission>,
+) -> impl Responder {
+    let report_id = path.into_inner();
+
+    if comment_submission.body.trim().is_empty() {
+        return HttpResponse::BadRequest().json("A comment cannot be empty.");
+    }
+
+    let report_present = match is_report_present(&data.database, &tenant, &report_id).await {
+        Ok(result) => result,
+        Err(err) => return database_error("check report presence", err),
+    };
+
+    if !report_present {
+        return HttpResponse::NotFound().json(format!("Report id {report_id} was not found."));
+    }
+
+    let author_present =
+        match is_username_present(&data.database, &tenant, &comment_submission.author_username)
+            .await
+        {
+            Ok(result) => result,
+            Err(err) => return database_error("check username presence", err),
+        };
+
+    if !author_present {
+        return HttpResponse::BadRequest().json(format!(
+            "The user {} was not found.",
+            &comment_submission.author_username
+        ));
+    }
+
+    let current_time = now();
+
+    match query_as!(
+        ReportComment,
+        r#"
+        INSERT INTO report_comment (report_id, author_username, body, time, organization_id)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING
+            id AS "comment_id: i32",
+            report_id,
+            author_username,
+            body,
+            time,
+            edited_time
+        "#,
+        report_id,
+        &comment_submission.author_username,
+        &comment_submission.body,
+        current_time,
+        tenant.organization_id
+    )
+    .fetch_one(&data.database)
+    .await
+    {
+        Ok(comment) => HttpResponse::Created().json(comment),
+        Err(err) => match constraint_violation(&err) {
+            Some(Violation::ForeignKey) => HttpResponse::BadRequest().json(format!(
+                "Report id {report_id} or the user {} no longer exists.",
+                &comment_submission.author_username
+            )),
+            _ => database_error("insert report comment", err),
+        },
+    }
+}
+
+#[utoipa::path(
+    context_path = "/report",
+    params(Tenant),
+    request_body(
+        content = CommentEdit,
+        content_type = "application/json",
+        description = "JSON object containing the author's username and the new comment, only the author of a comment may edit it",
+        example = json!({
+            "author_username": "technician",
+            "body": "Replaced the heating element.",
+          })
+    ),
+    responses(
+        (status = 200, description = "The comment was edited", body = ReportComment, example = json!({
+            "comment_id": 1,
+            "report_id": 1,
+            "author_username": "technician",
+            "body": "Replaced the heating element.",
+            "time": "2023-01-01T12:30:00.000Z",
+            "edited_time": "2023-01-01T13:00:00.000Z",
+          })),
+        (status = 400, description = "The requested query was invalid"),
+        (status = 403, description = "The comment was written by another user"),
+        (status = 404, description = "The requested comment was not found"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[patch("/{report_id}/comments/{comment_id}")]
+async fn edit_report_comment(
+    data: Data<AppState>,
+    tenant: Tenant,
+    path: Path<(i32, i32)>,
+    Json(comment_edit): Json<CommentEdit>,
+) -> impl Responder {
+    let (report_id, comment_id) = path.into_inner();
+
+    if comment_edit.body.trim().is_empty() {
+        return HttpResponse::BadRequest().json("A comment cannot be empty.");
+    }
+
+    let author = match comment_author(&data.database, &tenant, &report_id, &comment_id).await {
+        Ok(result) => result,
+        Err(err) => return database_error("fetch comment author", err),
+    };
+
+    match author {
+        None => {
+            return HttpResponse::NotFound().json(format!(
+                "Comment id {comment_id} was not found on report id {report_id}."
+            ))
+        }
+        Some(author) if author != comment_edit.author_username => {
+            return HttpResponse::Forbidden().json(format!(
+                "Comment id {comment_id} can only be edited by {author}."
+            ))
+        }
+        Some(_) => {}
+    }
+
+    let current_time = now();
+
+    match query_as!(
+        ReportComment,
+        r#"
+        UPDATE report_comment
+        SET body = $1, edited_time = $2
+        WHERE id = $3
+        RETURNING
+            id AS "comment_id: i32",
+            report_id,
+            author_username,
+            body,
+            time,
+            edited_time
+        "#,
+        &comment_edit.body,
+        current_time,
+        comment_id
+    )
+    .fetch_one(&data.database)
+    .await
+    {
+        Ok(comment) => HttpResponse::Ok().json(comment),
+        Err(err) => database_error("edit report comment", err),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/report",
+    params(Tenant),
+    responses(
+        (status = 200, description = "The requested comment was deleted", body = ReportComment, example = json!({
+            "comment_id": 1,
+            "report_id": 1,
+            "author_username": "technician",
+            "body": "Which cycle was it on?",
+            "time": "2023-01-01T12:30:00.000Z",
+            "edited_time": null,
+          })),
+        (status = 404, description = "The requested comment was not found"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[delete("/{report_id}/comments/{comment_id}")]
+async fn delete_report_comment(
+    data: Data<AppState>,
+    tenant: Tenant,
+    path: Path<(i32, i32)>,
+) -> impl Responder {
+    let (report_id, comment_id) = path.into_inner();
+
+    match query_as!(
+        ReportComment,
+        r#"
+        DELETE FROM report_comment
+        WHERE id = $1 AND report_id = $2 AND report_id IN (
+            SELECT report.id FROM report JOIN room ON room.id = report.room_id
+            WHERE room.organization_id = $3
+        )
+        RETURNING
+            id AS "comment_id: i32",
+            report_id,
+            author_username,
+            body,
+            time,
+            edited_time
+        "#,
+        comment_id,
+        report_id,
+        tenant.organization_id
+    )
+    .fetch_optional(&data.database)
+    .await
+    {
+        Err(err) => database_error("delete report comment", err),
+        Ok(comment) => match comment {
+            Some(comment) => HttpResponse::Ok().json(comment),
+            None => HttpResponse::NotFound().json(format!(
+                "Comment id {comment_id} was not found on report id {report_id}."
+            )),
+        },
+    }
+}
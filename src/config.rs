@@ -0,0 +1,26 @@
+use std::{env, fmt::Display, str::FromStr, time::Duration};
+
+/// Parses the named [environment variable](std::env::var), falling back to `default`
+/// when it is unset or cannot be parsed.
+pub fn env_or<T>(name: &str, default: T) -> T
+where
+    T: FromStr + Display,
+{
+    match env::var(name) {
+        Err(_) => default,
+        Ok(value) => match value.parse() {
+            Ok(value) => value,
+            Err(_) => {
+                eprintln!(
+                    "WARNING: Unable to parse {name}={value}, using the default of {default}."
+                );
+                default
+            }
+        },
+    }
+}
+
+/// Parses the named [environment variable](std::env::var) as a number of milliseconds.
+pub fn env_duration_ms_or(name: &str, default: Duration) -> Duration {
+    Duration::from_millis(env_or(name, default.as_millis() as u64))
+}
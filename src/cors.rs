@@ -0,0 +1,139 @@
+use std::env;
+
+use actix_cors::Cors;
+use actix_web::http::{header::HeaderName, Method, Uri};
+
+use crate::{config, request_id::REQUEST_ID_HEADER};
+
+/// Cross-origin resource sharing settings parsed from the environment.
+///
+/// Without any CORS_ALLOWED_ORIGINS every cross-origin request is denied.
+#[derive(Debug, Clone)]
+pub struct CorsConfig {
+    pub allowed_origins: Vec<String>,
+    pub allowed_methods: Vec<Method>,
+    pub allowed_headers: Vec<HeaderName>,
+    pub allow_credentials: bool,
+    pub max_age: usize,
+}
+
+/// Splits a comma separated environment variable, ignoring empty entries.
+fn env_list(name: &str, default: &str) -> Vec<String> {
+    env::var(name)
+        .unwrap_or_else(|_| default.to_string())
+        .split(',')
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+        .collect()
+}
+
+impl CorsConfig {
+    /// Parses the CORS configuration from the environment.
+    ///
+    /// # Errors
+    /// Returns an error when the allowed origins are not valid, see [CorsConfig::validate].
+    pub fn from_env() -> Result<CorsConfig, String> {
+        let allowed_methods = env_list("CORS_ALLOWED_METHODS", "GET,POST,PUT,PATCH,DELETE")
+            .into_iter()
+            .filter_map(|method| match method.to_uppercase().parse::<Method>() {
+                Ok(method) => Some(method),
+                Err(_) => {
+                    eprintln!("WARNING: Ignoring invalid CORS method {method}.");
+                    None
+                }
+            })
+            .collect();
+
+        let allowed_headers = env_list(
+            "CORS_ALLOWED_HEADERS",
+            "accept,authorization,content-type,x-organization,x-request-id",
+        )
+        .into_iter()
+        .filter_map(|header| match header.parse::<HeaderName>() {
+            Ok(header) => Some(header),
+            Err(_) => {
+                eprintln!("WARNING: Ignoring invalid CORS header {header}.");
+                None
+            }
+        })
+        .collect();
+
+        let cors_config = CorsConfig {
+            allowed_origins: env_list("CORS_ALLOWED_ORIGINS", ""),
+            allowed_methods,
+            allowed_headers,
+            allow_credentials: config::env_or("CORS_ALLOW_CREDENTIALS", false),
+            max_age: config::env_or("CORS_MAX_AGE_SECS", 3600),
+        };
+
+        cors_config.validate()?;
+        Ok(cors_config)
+    }
+
+    /// Checks that the allowed origins are either only `*`, or a list of origins written as
+    /// browsers send them, such as `https://example.com:8443`, and that `*` is not combined with
+    /// credentials.
+    ///
+    /// The middleware would otherwise fail every worker at startup on an invalid origin, quietly
+    /// drop a `*` listed alongside other origins, and let any site make credentialed requests
+    /// by echoing its origin back when `*` is combined with credentials.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.allow_credentials && self.allowed_origins.iter().any(|origin| origin == "*") {
+            return Err(
+                "CORS_ALLOW_CREDENTIALS cannot be combined with a CORS_ALLOWED_ORIGINS of *, list every allowed origin instead"
+                    .to_string(),
+            );
+        }
+
+        if self.allowed_origins.len() > 1 && self.allowed_origins.iter().any(|origin| origin == "*")
+        {
+            return Err(
+                "CORS_ALLOWED_ORIGINS cannot combine * with other origins, list either * alone or every allowed origin"
+                    .to_string(),
+            );
+        }
+
+        for origin in self.allowed_origins.iter().filter(|origin| *origin != "*") {
+            let valid =
+                origin
+                    .parse::<Uri>()
+                    .is_ok_and(|uri| match (uri.scheme_str(), uri.authority()) {
+                        (Some(scheme), Some(authority)) => {
+                            *origin == format!("{scheme}://{authority}")
+                        }
+                        _ => false,
+                    });
+
+            if !valid {
+                return Err(format!(
+                    "The CORS origin {origin} is not valid, origins are written as scheme://host[:port] without a path"
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Builds the CORS middleware from a [validated](CorsConfig::validate) configuration.
+    /// An origin of `*` allows every origin.
+    pub fn middleware(&self) -> Cors {
+        let mut cors = Cors::default()
+            .allowed_methods(self.allowed_methods.clone())
+            .allowed_headers(self.allowed_headers.clone())
+            .expose_headers([REQUEST_ID_HEADER])
+            .max_age(self.max_age);
+
+        for origin in &self.allowed_origins {
+            cors = match origin.as_str() {
+                "*" => cors.allow_any_origin(),
+                origin => cors.allowed_origin(origin),
+            };
+        }
+
+        if self.allow_credentials {
+            cors = cors.supports_credentials();
+        }
+
+        cors
+    }
+}
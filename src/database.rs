@@ -0,0 +1,72 @@
+use std::time::Duration;
+
+use sqlx::{migrate::Migrator, query, Pool, Postgres};
+
+/// The maximum number of connections held open by the connection pool.
+pub const MAX_CONNECTIONS: u32 = 10;
+
+/// How long a single attempt to reach the database may take while waiting for it at startup.
+const CONNECT_ATTEMPT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The migrations embedded from the `migrations` directory, applied when the server starts.
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
+/// Returns the version of the newest migration embedded in this build.
+pub fn latest_migration_version() -> Option<i64> {
+    MIGRATOR.iter().map(|migration| migration.version).max()
+}
+
+/// Returns the version of the newest migration successfully applied to the database.
+pub async fn applied_migration_version(
+    database: &Pool<Postgres>,
+) -> Result<Option<i64>, sqlx::Error> {
+    match query!(
+        r#"
+        SELECT MAX(version) AS version
+        FROM _sqlx_migrations
+        WHERE success = true
+        "#
+    )
+    .fetch_one(database)
+    .await
+    {
+        Ok(result) => Ok(result.version),
+        Err(err) => Err(err),
+    }
+}
+
+/// Waits for the database to accept connections, retrying up to `attempts` times.
+///
+/// The delay between attempts starts at `initial_backoff` and doubles after every failure,
+/// up to `max_backoff`. The error of the final attempt is returned if every attempt fails.
+pub async fn wait_for_database(
+    database: &Pool<Postgres>,
+    attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+) -> Result<(), sqlx::Error> {
+    let mut backoff = initial_backoff;
+    let mut attempt = 1;
+
+    loop {
+        let result =
+            match actix_web::rt::time::timeout(CONNECT_ATTEMPT_TIMEOUT, database.acquire()).await {
+                Ok(result) => result.map(|_| ()),
+                Err(_) => Err(sqlx::Error::PoolTimedOut),
+            };
+
+        match result {
+            Ok(()) => return Ok(()),
+            Err(err) if attempt >= attempts => return Err(err),
+            Err(err) => {
+                log::warn!(
+                    "Database is not available (attempt {attempt} of {attempts}), retrying in {}ms: {err}",
+                    backoff.as_millis()
+                );
+                actix_web::rt::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(max_backoff);
+                attempt += 1;
+            }
+        }
+    }
+}
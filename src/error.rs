@@ -0,0 +1,59 @@
+use std::io;
+
+use actix_web::HttpResponse;
+
+use crate::request_id;
+
+/// The message returned to clients when an unexpected server side error occurs.
+pub const INTERNAL_SERVER_ERROR_MESSAGE: &str = "An internal server error occurred";
+
+/// Logs a database error along with what was being attempted,
+/// and returns a generic internal server error response which does not leak database details.
+///
+/// The body contains the request id so that a reported failure can be found in the server logs.
+pub fn database_error(context: &str, err: sqlx::Error) -> HttpResponse {
+    log::error!("Failed to {context}: {err}");
+    internal_server_error()
+}
+
+/// Logs a [blob store](crate::blob_store::BlobStore) error along with what was being attempted,
+/// and returns the same generic internal server error response as [database_error].
+pub fn storage_error(context: &str, err: io::Error) -> HttpResponse {
+    log::error!("Failed to {context}: {err}");
+    internal_server_error()
+}
+
+/// The generic internal server error response, containing the request id when there is one.
+pub fn internal_server_error() -> HttpResponse {
+    match request_id::current() {
+        Some(request_id) => HttpResponse::InternalServerError().json(format!(
+            "{INTERNAL_SERVER_ERROR_MESSAGE} (request id {})",
+            request_id.as_str()
+        )),
+        None => HttpResponse::InternalServerError().json(INTERNAL_SERVER_ERROR_MESSAGE),
+    }
+}
+
+/// The kind of integrity constraint a rejected write violated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Violation {
+    /// A unique constraint or index, such as a primary key.
+    Unique,
+    /// A foreign key, usually because the referenced row was removed in the meantime.
+    ForeignKey,
+    /// A check constraint on the values written.
+    Check,
+}
+
+/// Which kind of constraint `err` reports a violation of, if the write failed because of one.
+///
+/// Handlers map the violations they expect to specific client errors,
+/// and pass anything else to [database_error] so that database messages never reach clients.
+pub fn constraint_violation(err: &sqlx::Error) -> Option<Violation> {
+    match err.as_database_error()?.code()?.as_ref() {
+        "23505" => Some(Violation::Unique),
+        "23503" => Some(Violation::ForeignKey),
+        "23514" => Some(Violation::Check),
+        _ => None,
+    }
+}
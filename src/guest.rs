@@ -0,0 +1,696 @@
+use std::{fmt::Write, time::Duration};
+
+use actix_web::{
+    get, post,
+    web::{Data, Json, Path, Query},
+    HttpRequest, HttpResponse, Responder,
+};
+use ring::{
+    digest, hmac,
+    rand::{SecureRandom, SystemRandom},
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{query, query_as, Pool, Postgres};
+use time::{OffsetDateTime, PrimitiveDateTime};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    background::ShutdownSignal,
+    config,
+    error::{constraint_violation, database_error, Violation},
+    machine,
+    models::{now, AppState, GuestReport, ModerationStatus, ReportType},
+    rate_limit::{self, Decision},
+    report::{self, is_report_type_active, Reporter},
+    tenant::Tenant,
+};
+
+/// How long a proof of work challenge may be solved and used for.
+const CHALLENGE_LIFETIME: Duration = Duration::from_secs(10 * 60);
+
+/// How often spent challenges which have expired are forgotten.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// The longest contact email address which will be accepted.
+const MAX_EMAIL_LENGTH: usize = 254;
+
+/// The response to every accepted guest submission, including those silently discarded as spam.
+const RECEIVED_MESSAGE: &str = "The report was received and will be published once approved.";
+
+/// Guest report settings parsed from the environment.
+#[derive(Clone)]
+pub struct GuestReportConfig {
+    /// Leading zero bits required of a proof of work hash, or 0 when no proof of work is required.
+    pub pow_difficulty: u32,
+    /// Signs proof of work challenges so that they cannot be made up by clients.
+    pow_key: hmac::Key,
+}
+
+impl GuestReportConfig {
+    /// Parses the guest report configuration from the environment.
+    ///
+    /// Challenges are signed with GUEST_REPORT_POW_SECRET, or with a random key when it is unset,
+    /// in which case they can only be used with the instance which issued them.
+    pub fn from_env() -> GuestReportConfig {
+        let pow_key = match std::env::var("GUEST_REPORT_POW_SECRET") {
+            Ok(secret) => hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes()),
+            Err(_) => hmac::Key::generate(hmac::HMAC_SHA256, &SystemRandom::new())
+                .expect("the system random number generator is available"),
+        };
+
+        GuestReportConfig {
+            pow_difficulty: config::env_or("GUEST_REPORT_POW_DIFFICULTY", 0).min(64),
+            pow_key,
+        }
+    }
+
+    /// Issues a new challenge, valid until `expires`.
+    fn challenge(&self, expires: OffsetDateTime) -> String {
+        let mut nonce = [0u8; 16];
+        let _ = SystemRandom::new().fill(&mut nonce);
+
+        let payload = format!("{}.{}", expires.unix_timestamp(), to_hex(&nonce));
+        let signature = hmac::sign(&self.pow_key, payload.as_bytes());
+
+        format!("{payload}.{}", to_hex(signature.as_ref()))
+    }
+
+    /// Checks that `proof` solves an unexpired challenge issued by [GuestReportConfig::challenge],
+    /// returning when the challenge expires.
+    ///
+    /// A solved challenge stays valid until then, so callers also [spend](spend_challenge) it.
+    fn verify(&self, proof: &ProofOfWork) -> Option<PrimitiveDateTime> {
+        let (payload, signature) = proof.challenge.rsplit_once('.')?;
+        let signature = from_hex(signature)?;
+        hmac::verify(&self.pow_key, payload.as_bytes(), &signature).ok()?;
+
+        let expires = payload
+            .split_once('.')
+            .and_then(|(expires, _)| expires.parse::<i64>().ok())
+            .and_then(|expires| OffsetDateTime::from_unix_timestamp(expires).ok())
+            .filter(|expires| *expires >= OffsetDateTime::now_utc())?;
+
+        let hash = digest::digest(
+            &digest::SHA256,
+            format!("{}:{}", proof.challenge, proof.nonce).as_bytes(),
+        );
+        (leading_zero_bits(hash.as_ref()) >= self.pow_difficulty)
+            .then(|| PrimitiveDateTime::new(expires.date(), expires.time()))
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{byte:02x}");
+        hex
+    })
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(hex.get(index..index + 2)?, 16).ok())
+        .collect()
+}
+
+fn leading_zero_bits(bytes: &[u8]) -> u32 {
+    let mut bits = 0;
+    for byte in bytes {
+        bits += byte.leading_zeros();
+        if *byte != 0 {
+            break;
+        }
+    }
+    bits
+}
+
+/// Records that a solved challenge was used, returning `false` if it already was.
+async fn spend_challenge(
+    database: &Pool<Postgres>,
+    challenge: &str,
+    expires_time: PrimitiveDateTime,
+) -> Result<bool, sqlx::Error> {
+    query!(
+        r#"
+        INSERT INTO spent_pow_challenge (challenge, expires_time)
+        VALUES ($1, $2)
+        ON CONFLICT DO NOTHING
+        "#,
+        challenge,
+        expires_time
+    )
+    .execute(database)
+    .await
+    .map(|result| result.rows_affected() == 1)
+}
+
+/// Forgets spent challenges which have expired and can no longer be used anyway.
+async fn sweep_spent_challenges(database: &Pool<Postgres>) -> Result<u64, sqlx::Error> {
+    query!(
+        r#"
+        DELETE FROM spent_pow_challenge
+        WHERE expires_time < $1
+        "#,
+        now()
+    )
+    .execute(database)
+    .await
+    .map(|result| result.rows_affected())
+}
+
+/// Forgets expired spent challenges until shutdown is requested.
+pub async fn sweep_spent_challenges_periodically(
+    database: Pool<Postgres>,
+    mut shutdown: ShutdownSignal,
+) {
+    while shutdown.sleep(SWEEP_INTERVAL).await {
+        if let Err(err) = sweep_spent_challenges(&database).await {
+            log::error!("Failed to sweep spent proof of work challenges: {err}");
+        }
+    }
+}
+
+/// A deliberately loose check that catches typing mistakes rather than validating addresses.
+pub fn is_plausible_email(email: &str) -> bool {
+    match email.split_once('@') {
+        Some((local, domain)) => {
+            email.len() <= MAX_EMAIL_LENGTH
+                && !local.is_empty()
+                && domain.contains('.')
+                && !email.chars().any(char::is_whitespace)
+        }
+        None => false,
+    }
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct GuestReportSubmission {
+    machine_id: String,
+    room_id: i32,
+    report_type: ReportType,
+    description: Option<String>,
+    /// Lets moderators follow up on the report, and is never shown publicly.
+    #[serde(default)]
+    contact_email: Option<String>,
+    /// A honeypot which clients hide from people, submissions filling it in are discarded.
+    #[serde(default)]
+    website: Option<String>,
+    /// Required when the server is configured with a proof of work difficulty.
+    #[serde(default)]
+    proof_of_work: Option<ProofOfWork>,
+}
+
+/// A solved challenge: the SHA-256 hash of `{challenge}:{nonce}` must start with the required
+/// number of zero bits.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ProofOfWork {
+    challenge: String,
+    nonce: String,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct PowChallenge {
+    challenge: String,
+    /// Leading zero bits required of the hash, 0 when no proof of work is required.
+    difficulty: u32,
+}
+
+/// Filters applied when listing guest reports.
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GuestReportQuery {
+    /// Defaults to pending reports.
+    status: Option<ModerationStatus>,
+}
+
+#[utoipa::path(
+    context_path = "/report",
+    responses(
+        (status = 200, description = "A proof of work challenge for a guest report submission", body = PowChallenge, example = json!({
+            "challenge": "1672574400.3f1c9a7e5b2d4c6e8a0b1c2d3e4f5a6b.9e2f...",
+            "difficulty": 16
+        }))
+    )
+)]
+#[get("/guest/challenge")]
+async fn get_guest_challenge(data: Data<AppState>) -> impl Responder {
+    let config = &data.guest_report_config;
+
+    HttpResponse::Ok().json(PowChallenge {
+        challenge: config.challenge(OffsetDateTime::now_utc() + CHALLENGE_LIFETIME),
+        difficulty: config.pow_difficulty,
+    })
+}
+
+#[utoipa::path(
+    context_path = "/report",
+    params(Tenant),
+    request_body(
+        content = GuestReportSubmission,
+        content_type = "application/json",
+        description = "JSON object containing the room id, machine id, report type, an optional description and an optional contact email",
+        example = json!({
+            "room_id": 1,
+            "machine_id": "A",
+            "report_type": "Broken",
+            "description": "No heat",
+            "contact_email": "visitor@example.com",
+            "proof_of_work": {
+                "challenge": "1672574400.3f1c9a7e5b2d4c6e8a0b1c2d3e4f5a6b.9e2f...",
+                "nonce": "48213"
+            }
+          })
+    ),
+    responses(
+        (status = 202, description = "The report was queued for moderation", body = String, example = json!(RECEIVED_MESSAGE)),
+        (status = 400, description = "The requested query was invalid, or the proof of work was missing or wrong"),
+        (status = 429, description = "Too many guest reports were submitted by this client"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[post("/guest")]
+async fn submit_guest_report(
+    data: Data<AppState>,
+    tenant: Tenant,
+    request: HttpRequest,
+    Json(submission): Json<GuestReportSubmission>,
+) -> impl Responder {
+    let rate_limiter = &data.rate_limiter;
+    let client_ip = rate_limiter.client_ip(&request);
+
+    if let Decision::Limited { retry_after } = rate_limiter
+        .check(
+            &format!("guest-report-ip:{client_ip}"),
+            &rate_limiter.config.guest_report_per_ip,
+        )
+        .await
+    {
+        return rate_limit::too_many_requests(retry_after);
+    }
+
+    if submission
+        .website
+        .is_some_and(|website| !website.is_empty())
+    {
+        log::info!("Discarded guest report from {client_ip} which filled in the honeypot field");
+        return HttpResponse::Accepted().json(RECEIVED_MESSAGE);
+    }
+
+    let solved_challenge = match data.guest_report_config.pow_difficulty > 0 {
+        true => {
+            let solved = submission.proof_of_work.as_ref().and_then(|proof| {
+                data.guest_report_config
+                    .verify(proof)
+                    .map(|expires_time| (&proof.challenge, expires_time))
+            });
+
+            if solved.is_none() {
+                return HttpResponse::BadRequest().json(
+                    "A solved challenge from /report/guest/challenge is required in proof_of_work.",
+                );
+            }
+            solved
+        }
+        false => None,
+    };
+
+    let contact_email = submission
+        .contact_email
+        .map(|email| email.trim().to_string())
+        .filter(|email| !email.is_empty());
+
+    if let Some(email) = &contact_email {
+        if !is_plausible_email(email) {
+            return HttpResponse::BadRequest().json(format!("{email} is not an email address."));
+        }
+    }
+
+    let machine_present = match machine::is_machine_present(
+        &data.database,
+        &tenant,
+        &submission.room_id,
+        &submission.machine_id,
+    )
+    .await
+    {
+        Ok(result) => result,
+        Err(err) => return database_error("check machine presence", err),
+    };
+
+    if !machine_present {
+        return HttpResponse::BadRequest().json(format!(
+            "Room id {} does not contain machine id {}.",
+            &submission.room_id, &submission.machine_id
+        ));
+    }
+
+    let report_type_active =
+        match is_report_type_active(&data.database, &submission.report_type).await {
+            Ok(result) => result,
+            Err(err) => return database_error("check report type", err),
+        };
+
+    if !report_type_active {
+        return HttpResponse::BadRequest().json(format!(
+            "The report type {} does not exist or is no longer in use.",
+            &submission.report_type.0
+        ));
+    }
+
+    if let Some((challenge, expires_time)) = solved_challenge {
+        match spend_challenge(&data.database, challenge, expires_time).await {
+            Ok(true) => {}
+            Ok(false) => {
+                return HttpResponse::BadRequest().json(
+                    "The challenge in proof_of_work was already used, request a new one from /report/guest/challenge.",
+                )
+            }
+            Err(err) => return database_error("spend proof of work challenge", err),
+        }
+    }
+
+    let current_time = now();
+
+    match query!(
+        r#"
+        INSERT INTO guest_report (room_id, machine_id, type, description, contact_email, client_ip, time)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        "#,
+        &submission.room_id,
+        &submission.machine_id,
+        &submission.report_type as &ReportType,
+        submission.description,
+        contact_email,
+        &client_ip,
+        current_time
+    )
+    .execute(&data.database)
+    .await
+    {
+        Ok(_) => HttpResponse::Accepted().json(RECEIVED_MESSAGE),
+        Err(err) => match constraint_violation(&err) {
+            Some(Violation::ForeignKey) => HttpResponse::BadRequest().json(format!(
+                "Room id {} no longer contains machine id {}.",
+                &submission.room_id, &submission.machine_id
+            )),
+            _ => database_error("insert guest report", err),
+        },
+    }
+}
+
+#[utoipa::path(
+    context_path = "/admin",
+    params(Tenant, GuestReportQuery),
+    responses(
+        (status = 200, description = "List of guest reports with the requested status, oldest first", body = Vec<GuestReport>, example = json!([{
+            "guest_report_id": 1,
+            "room_id": 1,
+            "machine_id": "A",
+            "report_type": "Broken",
+            "description": "No heat",
+            "contact_email": "visitor@example.com",
+            "client_ip": "192.0.2.1",
+            "time": "2023-01-01T12:00:00.000Z",
+            "status": "Pending",
+            "moderated_time": null,
+            "report_id": null
+        }])),
+        (status = 401, description = "No valid admin credentials were given"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[get("/guest-reports")]
+async fn get_guest_reports(
+    data: Data<AppState>,
+    tenant: Tenant,
+    Query(guest_report_query): Query<GuestReportQuery>,
+) -> impl Responder {
+    let status = guest_report_query
+        .status
+        .unwrap_or(ModerationStatus::Pending);
+
+    match query_as!(
+        GuestReport,
+        r#"
+        SELECT
+            id AS "guest_report_id: i32",
+            room_id,
+            machine_id,
+            type AS "report_type: ReportType",
+            description,
+            contact_email,
+            client_ip,
+            time,
+            status AS "status: ModerationStatus",
+            moderated_time,
+            report_id
+        FROM guest_report
+        WHERE status = $1
+            AND room_id IN (SELECT id FROM room WHERE organization_id = $2)
+        ORDER BY time
+        "#,
+        status as ModerationStatus,
+        tenant.organization_id
+    )
+    .fetch_all(&data.database)
+    .await
+    {
+        Ok(guest_reports) => HttpResponse::Ok().json(guest_reports),
+        Err(err) => database_error("fetch guest reports", err),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/admin",
+    params(Tenant),
+    responses(
+        (status = 201, description = "The guest report was approved and published as a report without a reporter", body = Report, example = json!({
+            "report_id": 1,
+            "room_id": 1,
+            "machine_id": "A",
+            "reporter_username": null,
+            "report_type": "Broken",
+            "description": "No heat",
+            "time": "2023-01-01T12:00:00.000Z",
+            "archived": false,
+            "automated": false,
+            "confirmation_count": 0,
+            "comment_count": 0,
+            "attachments": [],
+          })),
+        (status = 200, description = "The guest report was approved and linked to the open report which already covers it", body = Report),
+        (status = 401, description = "No valid admin credentials were given"),
+        (status = 404, description = "The requested guest report was not found"),
+        (status = 409, description = "The requested guest report was already moderated"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[post("/guest-reports/{guest_report_id}/approve")]
+async fn approve_guest_report(
+    data: Data<AppState>,
+    tenant: Tenant,
+    path: Path<i32>,
+) -> impl Responder {
+    let guest_report_id = path.into_inner();
+
+    let mut transaction = match data.database.begin().await {
+        Ok(transaction) => transaction,
+        Err(err) => return database_error("begin guest report approval", err),
+    };
+
+    let guest_report = match query!(
+        r#"
+        SELECT
+            room_id,
+            machine_id,
+            type AS "report_type: ReportType",
+            description,
+            time,
+            status AS "status: ModerationStatus"
+        FROM guest_report
+        WHERE id = $1
+            AND room_id IN (SELECT id FROM room WHERE organization_id = $2)
+        FOR UPDATE
+        "#,
+        guest_report_id,
+        tenant.organization_id
+    )
+    .fetch_optional(&mut transaction)
+    .await
+    {
+        Ok(Some(guest_report)) => guest_report,
+        Ok(None) => {
+            return HttpResponse::NotFound()
+                .json(format!("Guest report id {guest_report_id} was not found."))
+        }
+        Err(err) => return database_error("fetch guest report", err),
+    };
+
+    if guest_report.status != ModerationStatus::Pending {
+        return HttpResponse::Conflict().json(format!(
+            "Guest report id {guest_report_id} was already moderated."
+        ));
+    }
+
+    if let Err(err) = report::lock_machine(
+        &mut transaction,
+        &guest_report.room_id,
+        &guest_report.machine_id,
+    )
+    .await
+    {
+        return database_error("lock machine", err);
+    }
+
+    let current_time = now();
+
+    let duplicate = match report::find_open_duplicate(
+        &mut transaction,
+        &guest_report.room_id,
+        &guest_report.machine_id,
+        &guest_report.report_type,
+        current_time - data.report_config.duplicate_window,
+    )
+    .await
+    {
+        Ok(result) => result,
+        Err(err) => return database_error("check for duplicate reports", err),
+    };
+
+    let (report, created) = match duplicate {
+        Some(report) => (report, false),
+        None => match report::insert_report(
+            &mut transaction,
+            &guest_report.room_id,
+            &guest_report.machine_id,
+            Reporter::Guest,
+            &guest_report.report_type,
+            guest_report.description,
+            guest_report.time,
+        )
+        .await
+        {
+            Ok(report) => (report, true),
+            Err(err) => return database_error("insert approved guest report", err),
+        },
+    };
+
+    if let Err(err) = query!(
+        r#"
+        UPDATE guest_report
+        SET status = 'approved', moderated_time = $2, report_id = $3
+        WHERE id = $1
+        "#,
+        guest_report_id,
+        current_time,
+        report.report_id
+    )
+    .execute(&mut transaction)
+    .await
+    {
+        return database_error("approve guest report", err);
+    }
+
+    match transaction.commit().await {
+        Ok(()) if created => HttpResponse::Created().json(report),
+        Ok(()) => HttpResponse::Ok().json(report),
+        Err(err) => database_error("commit guest report approval", err),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/admin",
+    params(Tenant),
+    responses(
+        (status = 200, description = "The guest report was rejected", body = GuestReport, example = json!({
+            "guest_report_id": 1,
+            "room_id": 1,
+            "machine_id": "A",
+            "report_type": "Broken",
+            "description": "No heat",
+            "contact_email": "visitor@example.com",
+            "client_ip": "192.0.2.1",
+            "time": "2023-01-01T12:00:00.000Z",
+            "status": "Rejected",
+            "moderated_time": "2023-01-01T13:00:00.000Z",
+            "report_id": null
+        })),
+        (status = 401, description = "No valid admin credentials were given"),
+        (status = 404, description = "The requested guest report was not found"),
+        (status = 409, description = "The requested guest report was already moderated"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[post("/guest-reports/{guest_report_id}/reject")]
+async fn reject_guest_report(
+    data: Data<AppState>,
+    tenant: Tenant,
+    path: Path<i32>,
+) -> impl Responder {
+    let guest_report_id = path.into_inner();
+
+    let status = match query!(
+        r#"
+        SELECT status AS "status: ModerationStatus"
+        FROM guest_report
+        WHERE id = $1
+            AND room_id IN (SELECT id FROM room WHERE organization_id = $2)
+        "#,
+        guest_report_id,
+        tenant.organization_id
+    )
+    .fetch_optional(&data.database)
+    .await
+    {
+        Ok(Some(guest_report)) => guest_report.status,
+        Ok(None) => {
+            return HttpResponse::NotFound()
+                .json(format!("Guest report id {guest_report_id} was not found."))
+        }
+        Err(err) => return database_error("fetch guest report", err),
+    };
+
+    if status != ModerationStatus::Pending {
+        return HttpResponse::Conflict().json(format!(
+            "Guest report id {guest_report_id} was already moderated."
+        ));
+    }
+
+    let current_time = now();
+
+    match query_as!(
+        GuestReport,
+        r#"
+        UPDATE guest_report
+        SET status = 'rejected', moderated_time = $2
+        WHERE id = $1 AND status = 'pending'
+        RETURNING
+            id AS "guest_report_id: i32",
+            room_id,
+            machine_id,
+            type AS "report_type: ReportType",
+            description,
+            contact_email,
+            client_ip,
+            time,
+            status AS "status: ModerationStatus",
+            moderated_time,
+            report_id
+        "#,
+        guest_report_id,
+        current_time
+    )
+    .fetch_optional(&data.database)
+    .await
+    {
+        Ok(Some(guest_report)) => HttpResponse::Ok().json(guest_report),
+        Ok(None) => HttpResponse::Conflict().json(format!(
+            "Guest report id {guest_report_id} was already moderated."
+        )),
+        Err(err) => database_error("reject guest report", err),
+    }
+}
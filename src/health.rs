@@ -0,0 +1,146 @@
+use std::time::{Duration, Instant};
+
+use actix_web::{get, web::Data, HttpResponse, Responder};
+use serde::{Deserialize, Serialize};
+use sqlx::query;
+use utoipa::ToSchema;
+
+use crate::{database, models::AppState};
+
+/// How long the readiness probe waits on the database before reporting it as unreachable.
+const READINESS_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct Readiness {
+    ready: bool,
+    database: DatabaseStatus,
+    pool: PoolStatus,
+    migrations: MigrationStatus,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct DatabaseStatus {
+    reachable: bool,
+    latency_ms: Option<f64>,
+    error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct PoolStatus {
+    size: u32,
+    idle: usize,
+    max_connections: u32,
+    saturation: f64,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct MigrationStatus {
+    applied_version: Option<i64>,
+    latest_version: Option<i64>,
+    up_to_date: bool,
+}
+
+#[utoipa::path(
+    context_path = "/health",
+    responses(
+        (status = 200, description = "The server process is alive", body = String, example = json!("Alive"))
+    )
+)]
+#[get("/live")]
+async fn live() -> impl Responder {
+    HttpResponse::Ok().json("Alive")
+}
+
+#[utoipa::path(
+    context_path = "/health",
+    responses(
+        (status = 200, description = "The server is ready to handle requests", body = Readiness, example = json!({
+            "ready": true,
+            "database": {"reachable": true, "latency_ms": 1.2, "error": null},
+            "pool": {"size": 2, "idle": 1, "max_connections": 10, "saturation": 0.1},
+            "migrations": {"applied_version": 20261018000000i64, "latest_version": 20261018000000i64, "up_to_date": true}
+        })),
+        (status = 503, description = "The database is unreachable or the schema is out of date", body = Readiness)
+    )
+)]
+#[get("/ready")]
+async fn ready(data: Data<AppState>) -> impl Responder {
+    // Both queries share the timeout, so a stalled migration table cannot hang the probe either.
+    let checks = async {
+        let start = Instant::now();
+        query!("SELECT 1 AS one").fetch_one(&data.database).await?;
+        let latency = start.elapsed();
+
+        let applied_version = database::applied_migration_version(&data.database).await;
+        Ok::<_, sqlx::Error>((latency, applied_version))
+    };
+
+    let (database, applied_version) =
+        match actix_web::rt::time::timeout(READINESS_TIMEOUT, checks).await {
+            Ok(Ok((latency, applied_version))) => {
+                let database = DatabaseStatus {
+                    reachable: true,
+                    latency_ms: Some(latency.as_secs_f64() * 1000.0),
+                    error: None,
+                };
+                let applied_version = match applied_version {
+                    Ok(version) => version,
+                    Err(err) => {
+                        log::warn!("Readiness check failed to fetch the migration version: {err}");
+                        None
+                    }
+                };
+                (database, applied_version)
+            }
+            Ok(Err(err)) => {
+                log::warn!("Readiness check failed to query the database: {err}");
+                let database = DatabaseStatus {
+                    reachable: false,
+                    latency_ms: None,
+                    error: Some("The database query failed".to_string()),
+                };
+                (database, None)
+            }
+            Err(_) => {
+                log::warn!("Readiness check timed out waiting for the database");
+                let database = DatabaseStatus {
+                    reachable: false,
+                    latency_ms: None,
+                    error: Some(format!(
+                        "The database did not respond within {}ms",
+                        READINESS_TIMEOUT.as_millis()
+                    )),
+                };
+                (database, None)
+            }
+        };
+
+    let latest_version = database::latest_migration_version();
+    let migrations = MigrationStatus {
+        applied_version,
+        latest_version,
+        up_to_date: applied_version >= latest_version,
+    };
+
+    let size = data.database.size();
+    let idle = data.database.num_idle();
+    let max_connections = database::MAX_CONNECTIONS;
+    let pool = PoolStatus {
+        size,
+        idle,
+        max_connections,
+        saturation: size.saturating_sub(idle as u32) as f64 / max_connections.max(1) as f64,
+    };
+
+    let readiness = Readiness {
+        ready: database.reachable && migrations.up_to_date,
+        database,
+        pool,
+        migrations,
+    };
+
+    match readiness.ready {
+        true => HttpResponse::Ok().json(readiness),
+        false => HttpResponse::ServiceUnavailable().json(readiness),
+    }
+}
@@ -1,5 +1,37 @@
+pub mod admin;
+pub mod anomaly;
+pub mod attachment;
+pub mod background;
+pub mod blob_store;
+pub mod building;
+pub mod comment;
+pub mod config;
+pub mod cors;
+pub mod database;
+pub mod error;
+pub mod guest;
+pub mod health;
+pub mod logging;
 pub mod machine;
+pub mod machine_telemetry;
+pub mod maintenance;
 pub mod models;
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
+pub mod notification;
+pub mod qr;
+pub mod rate_limit;
 pub mod report;
+pub mod request_id;
+pub mod reservation;
 pub mod room;
+pub mod routes;
+pub mod session;
+pub mod site;
+pub mod sla;
+#[cfg(feature = "opentelemetry")]
+pub mod telemetry;
+pub mod tenant;
+pub mod tls;
 pub mod user;
+pub mod work_order;
@@ -0,0 +1,623 @@
+use std::{
+    env,
+    future::{ready, Ready},
+    io::Write,
+    process,
+    rc::Rc,
+    sync::Mutex,
+    time::Instant,
+};
+
+use actix_web::{
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http::StatusCode,
+    Error,
+};
+use futures_util::future::LocalBoxFuture;
+use log::{
+    kv::{self, Key, Value, VisitSource},
+    Level, LevelFilter, Log, Metadata, Record,
+};
+use serde_json::{Map, Number};
+use syslog::{Facility, Formatter3164, LoggerBackend};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::request_id::{self, RequestId};
+
+/// The log target used for the per-request access log.
+pub const ACCESS_LOG_TARGET: &str = "access";
+
+/// The log target sqlx uses to report each executed statement.
+const SQLX_QUERY_TARGET: &str = "sqlx::query";
+
+/// The backend which log records are written to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogBackend {
+    /// Human readable lines written to stderr.
+    Stderr,
+    /// One JSON object per line written to stderr.
+    Json,
+    /// The local [syslog] daemon.
+    Syslog,
+}
+
+impl LogBackend {
+    /// Parses the LOG_BACKEND [environment variable](std::env::var), defaulting to [LogBackend::Stderr].
+    pub fn from_env() -> LogBackend {
+        match env::var("LOG_BACKEND") {
+            Err(_) => LogBackend::Stderr,
+            Ok(value) => match value.to_lowercase().as_str() {
+                "json" => LogBackend::Json,
+                "syslog" => LogBackend::Syslog,
+                "stderr" => LogBackend::Stderr,
+                _ => {
+                    eprintln!("WARNING: Unknown LOG_BACKEND {value}, falling back to stderr.");
+                    LogBackend::Stderr
+                }
+            },
+        }
+    }
+}
+
+/// Parses the LOG_LEVEL [environment variable](std::env::var), defaulting to [LevelFilter::Info]
+/// so that the access log is written unless it is turned down.
+pub fn log_level_from_env() -> LevelFilter {
+    match env::var("LOG_LEVEL") {
+        Err(_) => LevelFilter::Info,
+        Ok(value) => match value.to_uppercase().as_str() {
+            "ERROR" => LevelFilter::Error,
+            "WARNING" => LevelFilter::Warn,
+            "INFO" => LevelFilter::Info,
+            "DEBUG" => LevelFilter::Debug,
+            "TRACE" => LevelFilter::Trace,
+            "OFF" => LevelFilter::Off,
+            _ => {
+                eprintln!("WARNING: Unknown LOG_LEVEL {value}, falling back to INFO.");
+                LevelFilter::Info
+            }
+        },
+    }
+}
+
+/// Initialize the logging system using the backend and level configured in the environment.
+///
+/// If the syslog backend cannot be initialized, logging falls back to plain text on stderr
+/// so that server logs are never silently discarded.
+/// sqlx reports every statement at the `INFO` level, so statements are only written when the
+/// level is `DEBUG` or finer.
+/// When `trace_queries` is set, sqlx statements are captured regardless of the log level
+/// so that they can be recorded as tracing spans.
+pub fn initialize(app_name: &str, trace_queries: bool) {
+    let log_level = log_level_from_env();
+
+    let sink = match LogBackend::from_env() {
+        LogBackend::Stderr => Sink::Plain,
+        LogBackend::Json => Sink::Json,
+        LogBackend::Syslog => {
+            let formatter = Formatter3164 {
+                facility: Facility::LOG_SYSLOG,
+                hostname: None,
+                process: app_name.to_string(),
+                pid: process::id(),
+            };
+
+            match syslog::unix(formatter) {
+                Ok(logger) => Sink::Syslog(Mutex::new(logger)),
+                Err(err) => {
+                    eprintln!(
+                        "WARNING: Failed to initialize syslog ({err}), falling back to stderr."
+                    );
+                    Sink::Plain
+                }
+            }
+        }
+    };
+
+    let logger = Logger {
+        sink,
+        level: log_level,
+        trace_queries,
+    };
+
+    if log::set_boxed_logger(Box::new(logger)).is_err() {
+        eprintln!("WARNING: Failed to initialize logging system! Server logs will be unavaliable!");
+        return;
+    }
+
+    match trace_queries {
+        true => log::set_max_level(log_level.max(LevelFilter::Info)),
+        false => log::set_max_level(log_level),
+    }
+}
+
+/// Where formatted log records are written to.
+enum Sink {
+    /// Human readable lines on stderr.
+    Plain,
+    /// One JSON object per line on stderr.
+    Json,
+    /// The local syslog daemon.
+    Syslog(Mutex<syslog::Logger<LoggerBackend, Formatter3164>>),
+}
+
+/// A [Log] implementation which tags every record with the current request id.
+struct Logger {
+    sink: Sink,
+    level: LevelFilter,
+    trace_queries: bool,
+}
+
+impl Logger {
+    fn is_query_record(metadata: &Metadata) -> bool {
+        metadata.target() == SQLX_QUERY_TARGET
+    }
+
+    /// Whether a record is written to the sink, as opposed to only being captured for tracing.
+    fn is_written(&self, metadata: &Metadata) -> bool {
+        match Logger::is_query_record(metadata) {
+            true => self.level >= LevelFilter::Debug,
+            false => metadata.level() <= self.level,
+        }
+    }
+
+    fn format_plain(record: &Record, request_id: Option<&RequestId>) -> String {
+        let mut line = format!("{} {}: {}", record.level(), record.target(), record.args());
+
+        let mut visitor = PlainVisitor(&mut line);
+        let _ = record.key_values().visit(&mut visitor);
+
+        if let Some(request_id) = request_id {
+            push_plain_field(&mut line, "request_id", request_id.as_str());
+        }
+
+        line
+    }
+
+    fn format_json(record: &Record, request_id: Option<&RequestId>, timestamp: &str) -> String {
+        let mut object = Map::new();
+        object.insert("timestamp".to_string(), timestamp.into());
+        object.insert("level".to_string(), record.level().as_str().into());
+        object.insert("target".to_string(), record.target().into());
+        object.insert("message".to_string(), record.args().to_string().into());
+
+        let mut visitor = JsonVisitor(&mut object);
+        let _ = record.key_values().visit(&mut visitor);
+
+        if let Some(request_id) = request_id {
+            object.insert("request_id".to_string(), request_id.as_str().into());
+        }
+
+        serde_json::Value::Object(object).to_string()
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.is_written(metadata) || (self.trace_queries && Logger::is_query_record(metadata))
+    }
+
+    fn log(&self, record: &Record) {
+        if self.trace_queries && Logger::is_query_record(record.metadata()) {
+            #[cfg(feature = "opentelemetry")]
+            crate::telemetry::record_query(record);
+        }
+
+        if !self.is_written(record.metadata()) {
+            return;
+        }
+
+        let request_id = request_id::current();
+
+        match &self.sink {
+            Sink::Plain => {
+                let timestamp = OffsetDateTime::now_utc()
+                    .format(&Rfc3339)
+                    .unwrap_or_default();
+                let line = Logger::format_plain(record, request_id.as_ref());
+                let _ = writeln!(std::io::stderr().lock(), "{timestamp} {line}");
+            }
+            Sink::Json => {
+                let timestamp = OffsetDateTime::now_utc()
+                    .format(&Rfc3339)
+                    .unwrap_or_default();
+                let line = Logger::format_json(record, request_id.as_ref(), &timestamp);
+                let _ = writeln!(std::io::stderr().lock(), "{line}");
+            }
+            Sink::Syslog(logger) => {
+                let message = Logger::format_plain(record, request_id.as_ref());
+                let mut logger = match logger.lock() {
+                    Ok(logger) => logger,
+                    Err(poisoned) => poisoned.into_inner(),
+                };
+                let _ = match record.level() {
+                    Level::Error => logger.err(message),
+                    Level::Warn => logger.warning(message),
+                    Level::Info => logger.info(message),
+                    Level::Debug | Level::Trace => logger.debug(message),
+                };
+            }
+        }
+    }
+
+    fn flush(&self) {
+        let _ = std::io::stderr().flush();
+    }
+}
+
+struct PlainVisitor<'a>(&'a mut String);
+
+impl<'kvs> VisitSource<'kvs> for PlainVisitor<'_> {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+        push_plain_field(self.0, key.as_str(), &value.to_string());
+        Ok(())
+    }
+}
+
+/// Appends ` key=value` to a plain text log line.
+///
+/// Values which are empty or contain whitespace, quotes, `=` or control characters are quoted
+/// and escaped, so that client supplied values cannot pass for additional fields.
+fn push_plain_field(line: &mut String, key: &str, value: &str) {
+    let needs_quoting = value.is_empty()
+        || value
+            .chars()
+            .any(|c| c.is_whitespace() || c.is_control() || c == '"' || c == '=');
+
+    match needs_quoting {
+        true => line.push_str(&format!(" {key}={value:?}")),
+        false => line.push_str(&format!(" {key}={value}")),
+    }
+}
+
+struct JsonVisitor<'a>(&'a mut Map<String, serde_json::Value>);
+
+impl<'kvs> VisitSource<'kvs> for JsonVisitor<'_> {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+        let value = if let Some(value) = value.to_bool() {
+            serde_json::Value::Bool(value)
+        } else if let Some(value) = value.to_u64() {
+            serde_json::Value::Number(value.into())
+        } else if let Some(value) = value.to_i64() {
+            serde_json::Value::Number(value.into())
+        } else if let Some(value) = value.to_f64().and_then(Number::from_f64) {
+            serde_json::Value::Number(value)
+        } else {
+            serde_json::Value::String(value.to_string())
+        };
+
+        self.0.insert(key.to_string(), value);
+        Ok(())
+    }
+}
+
+/// Middleware which writes one access log line per request, containing the method,
+/// the matched route pattern, the response status and the latency.
+/// The request id is attached by the logger itself, like on every other line.
+pub struct AccessLog;
+
+impl<S, B> Transform<S, ServiceRequest> for AccessLog
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = AccessLogMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(AccessLogMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct AccessLogMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for AccessLogMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, request: ServiceRequest) -> Self::Future {
+        let start = Instant::now();
+        let method = request.method().to_string();
+        let path = request.path().to_string();
+
+        let service = Rc::clone(&self.service);
+
+        Box::pin(async move {
+            match service.call(request).await {
+                Ok(response) => {
+                    let route = response
+                        .request()
+                        .match_pattern()
+                        .unwrap_or_else(|| path.clone());
+                    log_access(&method, &route, &path, response.status(), start);
+                    Ok(response)
+                }
+                // Errors are turned into responses further out, so the route is no longer known.
+                Err(err) => {
+                    let status = err.as_response_error().status_code();
+                    log_access(&method, &path, &path, status, start);
+                    Err(err)
+                }
+            }
+        })
+    }
+}
+
+/// Writes the access log line of a request which started at `start`.
+fn log_access(method: &str, route: &str, path: &str, status: StatusCode, start: Instant) {
+    let status = status.as_u16();
+    let latency_ms = start.elapsed().as_secs_f64() * 1000.0;
+
+    log::info!(
+        target: ACCESS_LOG_TARGET,
+        method = method,
+        route = route,
+        path = path,
+        status = status,
+        latency_ms = latency_ms;
+        "{method} {path} {status}"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, env, sync::Once};
+
+    use actix_web::{
+        dev::ServiceResponse,
+        error::ErrorForbidden,
+        test::{self, TestRequest},
+        web, App, HttpResponse,
+    };
+    use log::{kv::ToValue, Level, LevelFilter, Log, Metadata, Record};
+    use serde_json::{json, Value};
+
+    use super::{
+        log_level_from_env, AccessLog, LogBackend, Logger, Sink, ACCESS_LOG_TARGET,
+        SQLX_QUERY_TARGET,
+    };
+    use crate::request_id::{self, RequestIdentifier};
+
+    thread_local! {
+        static ACCESS_LINES: RefCell<Vec<Value>> = const { RefCell::new(Vec::new()) };
+    }
+
+    /// Keeps the JSON form of every access log line written on the current thread.
+    struct CapturingLogger;
+
+    impl Log for CapturingLogger {
+        fn enabled(&self, metadata: &Metadata) -> bool {
+            metadata.target() == ACCESS_LOG_TARGET
+        }
+
+        fn log(&self, record: &Record) {
+            if !self.enabled(record.metadata()) {
+                return;
+            }
+
+            let line = Logger::format_json(record, request_id::current().as_ref(), "now");
+            let line = serde_json::from_str(&line).expect("the line is JSON");
+            ACCESS_LINES.with(|lines| lines.borrow_mut().push(line));
+        }
+
+        fn flush(&self) {}
+    }
+
+    /// Installs the capturing logger and returns the access log lines written by `run`.
+    async fn access_lines<F: std::future::Future<Output = ()>>(run: F) -> Vec<Value> {
+        static INSTALL: Once = Once::new();
+        INSTALL.call_once(|| {
+            log::set_boxed_logger(Box::new(CapturingLogger)).expect("no logger is installed");
+            log::set_max_level(LevelFilter::Info);
+        });
+
+        ACCESS_LINES.with(|lines| lines.borrow_mut().clear());
+        run.await;
+        ACCESS_LINES.with(|lines| lines.take())
+    }
+
+    #[test]
+    fn plain_lines_quote_values_which_could_pass_for_fields() {
+        let line = Logger::format_plain(
+            &Record::builder()
+                .level(Level::Info)
+                .target("access")
+                .args(format_args!("GET / 200"))
+                .key_values(&[
+                    ("status", 200.to_value()),
+                    ("path", "/room/1".to_value()),
+                    ("agent", "abc status=500".to_value()),
+                    ("empty", "".to_value()),
+                ])
+                .build(),
+            None,
+        );
+
+        assert_eq!(
+            line,
+            r#"INFO access: GET / 200 status=200 path=/room/1 agent="abc status=500" empty="""#
+        );
+    }
+
+    #[test]
+    fn json_lines_keep_the_type_of_each_value() {
+        let line = Logger::format_json(
+            &Record::builder()
+                .level(Level::Warn)
+                .target("laundry_api")
+                .args(format_args!("something happened"))
+                .key_values(&[
+                    ("unsigned", 3u64.to_value()),
+                    ("signed", (-4i64).to_value()),
+                    ("float", 1.5f64.to_value()),
+                    ("flag", true.to_value()),
+                    ("text", "a \"quoted\" value".to_value()),
+                ])
+                .build(),
+            None,
+            "2024-01-01T00:00:00Z",
+        );
+
+        assert_eq!(
+            serde_json::from_str::<Value>(&line).expect("the line is JSON"),
+            json!({
+                "timestamp": "2024-01-01T00:00:00Z",
+                "level": "WARN",
+                "target": "laundry_api",
+                "message": "something happened",
+                "unsigned": 3,
+                "signed": -4,
+                "float": 1.5,
+                "flag": true,
+                "text": "a \"quoted\" value"
+            })
+        );
+    }
+
+    #[test]
+    fn statements_are_only_written_at_debug_level() {
+        let statement = Metadata::builder()
+            .level(Level::Info)
+            .target(SQLX_QUERY_TARGET)
+            .build();
+        let access = Metadata::builder()
+            .level(Level::Info)
+            .target(ACCESS_LOG_TARGET)
+            .build();
+        let logger = |level: LevelFilter, trace_queries: bool| Logger {
+            sink: Sink::Plain,
+            level,
+            trace_queries,
+        };
+
+        let info = logger(LevelFilter::Info, false);
+        assert!(!info.enabled(&statement));
+        assert!(info.enabled(&access));
+
+        // Statements captured for tracing are still not written.
+        let tracing = logger(LevelFilter::Info, true);
+        assert!(tracing.enabled(&statement));
+        assert!(!tracing.is_written(&statement));
+        assert!(tracing.is_written(&access));
+
+        let debug = logger(LevelFilter::Debug, false);
+        assert!(debug.enabled(&statement));
+        assert!(debug.is_written(&statement));
+    }
+
+    /// Both variables are read in one test, since tests share the process environment.
+    #[test]
+    fn level_and_backend_are_read_from_the_environment() {
+        env::remove_var("LOG_LEVEL");
+        env::remove_var("LOG_BACKEND");
+        assert_eq!(log_level_from_env(), LevelFilter::Info);
+        assert_eq!(LogBackend::from_env(), LogBackend::Stderr);
+
+        env::set_var("LOG_LEVEL", "debug");
+        env::set_var("LOG_BACKEND", "JSON");
+        assert_eq!(log_level_from_env(), LevelFilter::Debug);
+        assert_eq!(LogBackend::from_env(), LogBackend::Json);
+
+        env::set_var("LOG_LEVEL", "warning");
+        env::set_var("LOG_BACKEND", "syslog");
+        assert_eq!(log_level_from_env(), LevelFilter::Warn);
+        assert_eq!(LogBackend::from_env(), LogBackend::Syslog);
+
+        env::set_var("LOG_LEVEL", "verbose");
+        env::set_var("LOG_BACKEND", "journald");
+        assert_eq!(log_level_from_env(), LevelFilter::Info);
+        assert_eq!(LogBackend::from_env(), LogBackend::Stderr);
+
+        env::remove_var("LOG_LEVEL");
+        env::remove_var("LOG_BACKEND");
+    }
+
+    #[actix_web::test]
+    async fn access_lines_describe_successful_responses() {
+        let lines = access_lines(async {
+            let app = test::init_service(
+                App::new()
+                    .route(
+                        "/room/{room_id}",
+                        web::get().to(|| async { HttpResponse::Ok().finish() }),
+                    )
+                    .wrap(AccessLog)
+                    .wrap(RequestIdentifier),
+            )
+            .await;
+
+            let request = TestRequest::get()
+                .uri("/room/7")
+                .insert_header(("X-Request-Id", "abc-123"))
+                .to_request();
+            test::call_service(&app, request).await;
+        })
+        .await;
+
+        assert_eq!(lines.len(), 1);
+        let line = &lines[0];
+        assert_eq!(line["message"], "GET /room/7 200");
+        assert_eq!(line["method"], "GET");
+        assert_eq!(line["route"], "/room/{room_id}");
+        assert_eq!(line["path"], "/room/7");
+        assert_eq!(line["status"], 200);
+        assert!(line["latency_ms"].is_f64());
+        assert_eq!(line["request_id"], "abc-123");
+    }
+
+    #[actix_web::test]
+    async fn access_lines_describe_failed_requests() {
+        let lines = access_lines(async {
+            let app = test::init_service(
+                App::new()
+                    .route(
+                        "/room/{room_id}",
+                        web::get().to(|| async { HttpResponse::Ok().finish() }),
+                    )
+                    .wrap_fn(|_, _| async {
+                        Err::<ServiceResponse, _>(ErrorForbidden("Not allowed"))
+                    })
+                    .wrap(AccessLog)
+                    .wrap(RequestIdentifier),
+            )
+            .await;
+
+            let request = TestRequest::get().uri("/room/7").to_request();
+            let _ = test::try_call_service(&app, request).await;
+
+            let request = TestRequest::get().uri("/missing").to_request();
+            let _ = test::try_call_service(&app, request).await;
+        })
+        .await;
+
+        assert_eq!(lines.len(), 2);
+
+        let line = &lines[0];
+        assert_eq!(line["method"], "GET");
+        assert_eq!(line["route"], "/room/7");
+        assert_eq!(line["path"], "/room/7");
+        assert_eq!(line["status"], 403);
+        assert!(line["latency_ms"].is_f64());
+        // A request id is generated when the client does not send one.
+        assert!(!line["request_id"].as_str().unwrap_or_default().is_empty());
+
+        assert_eq!(lines[1]["route"], "/missing");
+        assert_eq!(lines[1]["status"], 403);
+        assert_ne!(lines[0]["request_id"], lines[1]["request_id"]);
+    }
+}
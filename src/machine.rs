@@ -1,15 +1,19 @@
 use actix_web::{
-    delete, get, post,
-    web::{Data, Json, Path},
+    delete, get, post, put,
+    web::{Data, Json, Path, Query},
     HttpResponse, Responder,
 };
 use serde::{Deserialize, Serialize};
 use sqlx::{query, query_as, Pool, Postgres};
-use utoipa::ToSchema;
+use time::Date;
+use utoipa::{IntoParams, ToSchema};
 
 use crate::{
-    models::{AppState, Machine, MachineType, Report, ReportType},
+    attachment,
+    error::{constraint_violation, database_error, Violation},
+    models::{iso_date, AppState, Machine, MachineType, PaymentType, Report, ReportType},
     room,
+    tenant::Tenant,
 };
 
 #[derive(Serialize, Deserialize, ToSchema)]
@@ -17,10 +21,44 @@ pub struct MachineSubmission {
     room_id: i32,
     machine_id: String,
     machine_type: MachineType,
+    #[serde(flatten)]
+    metadata: MachineMetadata,
+}
+
+/// The optional details of a machine, which are replaced as a whole when edited.
+#[derive(Default, Serialize, Deserialize, ToSchema)]
+pub struct MachineMetadata {
+    #[serde(default)]
+    manufacturer: Option<String>,
+    #[serde(default)]
+    model: Option<String>,
+    #[serde(default)]
+    serial_number: Option<String>,
+    #[serde(default, with = "iso_date::option")]
+    install_date: Option<Date>,
+    #[serde(default, with = "iso_date::option")]
+    warranty_expiry: Option<Date>,
+    #[serde(default)]
+    capacity_kg: Option<f32>,
+    #[serde(default)]
+    payment_type: Option<PaymentType>,
+    #[serde(default)]
+    notes: Option<String>,
+}
+
+/// Filters applied when listing machines. Text filters are case insensitive.
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct MachineQuery {
+    manufacturer: Option<String>,
+    model: Option<String>,
+    payment_type: Option<PaymentType>,
+    machine_type: Option<MachineType>,
 }
 
 pub async fn is_machine_present(
     database: &Pool<Postgres>,
+    tenant: &Tenant,
     room_id: &i32,
     machine_id: &String,
 ) -> Result<bool, sqlx::Error> {
@@ -30,9 +68,11 @@ pub async fn is_machine_present(
         FROM machine
         WHERE room_id = $1
             AND machine_id = $2
+            AND room_id IN (SELECT id FROM room WHERE organization_id = $3)
         "#,
         room_id,
-        machine_id
+        machine_id,
+        tenant.organization_id
     )
     .fetch_optional(database)
     .await
@@ -42,51 +82,117 @@ pub async fn is_machine_present(
     }
 }
 
+/// Whether new machines may use `machine_type`, which must exist and be active.
+pub async fn is_machine_type_active(
+    database: &Pool<Postgres>,
+    machine_type: &MachineType,
+) -> Result<bool, sqlx::Error> {
+    match query!(
+        r#"
+        SELECT active
+        FROM machine_type
+        WHERE name = $1
+        "#,
+        machine_type as &MachineType
+    )
+    .fetch_optional(database)
+    .await
+    {
+        Ok(result) => Ok(result.is_some_and(|machine_type| machine_type.active)),
+        Err(err) => Err(err),
+    }
+}
+
 #[utoipa::path(
     context_path = "/machine",
+    params(Tenant, MachineQuery),
     responses(
-        (status = 200, description = "List of all machines", body = Vec<Machine>, example = json!([{
+        (status = 200, description = "List of all machines matching the given filters", body = Vec<Machine>, example = json!([{
             "room_id": 1,
             "machine_id": "A",
-            "machine_type": "Dryer"
+            "machine_type": "Dryer",
+            "manufacturer": "Speed Queen",
+            "model": "DR7",
+            "serial_number": "2203012345",
+            "install_date": "2022-03-01",
+            "warranty_expiry": "2027-03-01",
+            "capacity_kg": 9.0,
+            "payment_type": "Card",
+            "notes": "Stacked on top of washer B",
         }])),
         (status = 500, description = "An internal server error occurred")
     )
 )]
 #[get("/")]
-async fn get_all_machines(data: Data<AppState>) -> impl Responder {
+async fn get_all_machines(
+    data: Data<AppState>,
+    tenant: Tenant,
+    Query(machine_query): Query<MachineQuery>,
+) -> impl Responder {
     match query_as!(
         Machine,
         r#"
         SELECT
             room_id,
             machine_id,
-            type as "machine_type: MachineType"
+            type as "machine_type: MachineType",
+            manufacturer,
+            model,
+            serial_number,
+            install_date,
+            warranty_expiry,
+            capacity_kg,
+            payment_type AS "payment_type: PaymentType",
+            notes
         FROM machine
+        WHERE ($1::VARCHAR IS NULL OR lower(manufacturer) = lower($1))
+            AND ($2::VARCHAR IS NULL OR lower(model) = lower($2))
+            AND ($3::payment_type IS NULL OR payment_type = $3)
+            AND ($4::VARCHAR IS NULL OR type = $4)
+            AND room_id IN (SELECT id FROM room WHERE organization_id = $5)
+        ORDER BY room_id, machine_id
         "#,
+        machine_query.manufacturer,
+        machine_query.model,
+        machine_query.payment_type as Option<PaymentType>,
+        machine_query.machine_type as Option<MachineType>,
+        tenant.organization_id
     )
     .fetch_all(&data.database)
     .await
     {
         Ok(machines) => HttpResponse::Ok().json(machines),
-        Err(err) => HttpResponse::InternalServerError().json(err.to_string()),
+        Err(err) => database_error("fetch all machines", err),
     }
 }
 
 #[utoipa::path(
     context_path = "/machine",
+    params(Tenant),
     responses(
         (status = 200, description = "The requested machine", body = Machine, example = json!({
             "room_id": 1,
             "machine_id": "A",
-            "machine_type": "Dryer"
+            "machine_type": "Dryer",
+            "manufacturer": "Speed Queen",
+            "model": "DR7",
+            "serial_number": "2203012345",
+            "install_date": "2022-03-01",
+            "warranty_expiry": "2027-03-01",
+            "capacity_kg": 9.0,
+            "payment_type": "Card",
+            "notes": "Stacked on top of washer B",
         })),
         (status = 404, description = "The requested machine was not found"),
         (status = 500, description = "An internal server error occurred")
     )
 )]
 #[get("/{room_id}/{machine_id}")]
-async fn get_machine(data: Data<AppState>, path: Path<(i32, String)>) -> impl Responder {
+async fn get_machine(
+    data: Data<AppState>,
+    tenant: Tenant,
+    path: Path<(i32, String)>,
+) -> impl Responder {
     let (room_id, machine_id) = path.into_inner();
 
     match query_as!(
@@ -95,18 +201,28 @@ async fn get_machine(data: Data<AppState>, path: Path<(i32, String)>) -> impl Re
         SELECT
             room_id,
             machine_id,
-            type as "machine_type: MachineType"
+            type as "machine_type: MachineType",
+            manufacturer,
+            model,
+            serial_number,
+            install_date,
+            warranty_expiry,
+            capacity_kg,
+            payment_type AS "payment_type: PaymentType",
+            notes
         FROM machine
         WHERE room_id = $1
             AND machine_id = $2
+            AND room_id IN (SELECT id FROM room WHERE organization_id = $3)
         "#,
         room_id,
-        machine_id
+        machine_id,
+        tenant.organization_id
     )
     .fetch_optional(&data.database)
     .await
     {
-        Err(err) => HttpResponse::InternalServerError().json(err.to_string()),
+        Err(err) => database_error("fetch machine", err),
         Ok(machine) => match machine {
             Some(machine) => HttpResponse::Ok().json(&machine),
             None => HttpResponse::NotFound().json(format!(
@@ -118,18 +234,35 @@ async fn get_machine(data: Data<AppState>, path: Path<(i32, String)>) -> impl Re
 
 #[utoipa::path(
     context_path = "/machine",
+    params(Tenant),
     request_body(content = MachineSubmission, content_type = "application/json", example = json!({
         "room_id": 1,
         "machine_id": "A",
-        "machine_type": "Dryer"
+        "machine_type": "Dryer",
+        "manufacturer": "Speed Queen",
+        "model": "DR7",
+        "serial_number": "2203012345",
+        "install_date": "2022-03-01",
+        "warranty_expiry": "2027-03-01",
+        "capacity_kg": 9.0,
+        "payment_type": "Card",
+        "notes": "Stacked on top of washer B",
     })),
     responses(
         (status = 201, description = "The requested machine was created", body = Machine, example = json!({
             "room_id": 1,
             "machine_id": "A",
-            "machine_type": "Dryer"
+            "machine_type": "Dryer",
+            "manufacturer": "Speed Queen",
+            "model": "DR7",
+            "serial_number": "2203012345",
+            "install_date": "2022-03-01",
+            "warranty_expiry": "2027-03-01",
+            "capacity_kg": 9.0,
+            "payment_type": "Card",
+            "notes": "Stacked on top of washer B",
         })),
-        (status = 400, description = "The requested room does not exist"),
+        (status = 400, description = "The requested room or machine type does not exist"),
         (status = 409, description = "The requested machine already exists"),
         (status = 500, description = "An internal server error occurred")
     )
@@ -137,12 +270,13 @@ async fn get_machine(data: Data<AppState>, path: Path<(i32, String)>) -> impl Re
 #[post("/")]
 async fn add_machine(
     data: Data<AppState>,
+    tenant: Tenant,
     Json(machine_submission): Json<MachineSubmission>,
 ) -> impl Responder {
     let room_present =
-        match room::is_room_present(&data.database, &machine_submission.room_id).await {
+        match room::is_room_present(&data.database, &tenant, &machine_submission.room_id).await {
             Ok(result) => result,
-            Err(err) => return HttpResponse::InternalServerError().json(err.to_string()),
+            Err(err) => return database_error("check room presence", err),
         };
 
     if !room_present {
@@ -152,15 +286,29 @@ async fn add_machine(
         ));
     }
 
+    let machine_type_active =
+        match is_machine_type_active(&data.database, &machine_submission.machine_type).await {
+            Ok(result) => result,
+            Err(err) => return database_error("check machine type", err),
+        };
+
+    if !machine_type_active {
+        return HttpResponse::BadRequest().json(format!(
+            "The machine type {} does not exist or is no longer in use.",
+            &machine_submission.machine_type.0
+        ));
+    }
+
     let machine_present = match is_machine_present(
         &data.database,
+        &tenant,
         &machine_submission.room_id,
         &machine_submission.machine_id,
     )
     .await
     {
         Ok(result) => result,
-        Err(err) => return HttpResponse::InternalServerError().json(err.to_string()),
+        Err(err) => return database_error("check machine presence", err),
     };
 
     if machine_present {
@@ -173,45 +321,193 @@ async fn add_machine(
     match query_as!(
         Machine,
         r#"
-        INSERT INTO machine (room_id, machine_id, type)
-        VALUES ($1, $2, $3)
+        INSERT INTO machine (
+            room_id, machine_id, type, manufacturer, model, serial_number,
+            install_date, warranty_expiry, capacity_kg, payment_type, notes
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
         RETURNING
             room_id,
             machine_id,
-            type AS "machine_type: MachineType"
+            type AS "machine_type: MachineType",
+            manufacturer,
+            model,
+            serial_number,
+            install_date,
+            warranty_expiry,
+            capacity_kg,
+            payment_type AS "payment_type: PaymentType",
+            notes
         "#,
         &machine_submission.room_id,
         &machine_submission.machine_id,
-        &machine_submission.machine_type as &MachineType
+        &machine_submission.machine_type as &MachineType,
+        machine_submission.metadata.manufacturer,
+        machine_submission.metadata.model,
+        machine_submission.metadata.serial_number,
+        machine_submission.metadata.install_date,
+        machine_submission.metadata.warranty_expiry,
+        machine_submission.metadata.capacity_kg,
+        machine_submission.metadata.payment_type as Option<PaymentType>,
+        machine_submission.metadata.notes
     )
     .fetch_one(&data.database)
     .await
     {
         Ok(machine) => HttpResponse::Created().json(machine),
-        Err(err) => HttpResponse::InternalServerError().json(err.to_string()),
+        Err(err) => match constraint_violation(&err) {
+            Some(Violation::Unique) => HttpResponse::Conflict().json(format!(
+                "Machine id {} already exists in room id {}.",
+                &machine_submission.machine_id, &machine_submission.room_id
+            )),
+            Some(Violation::Check) => {
+                HttpResponse::BadRequest().json("The capacity must be positive.")
+            }
+            Some(Violation::ForeignKey) => HttpResponse::BadRequest().json(format!(
+                "Room id {} or the machine type {} no longer exists.",
+                &machine_submission.room_id, &machine_submission.machine_type.0
+            )),
+            _ => database_error("insert machine", err),
+        },
     }
 }
 
 #[utoipa::path(
     context_path = "/machine",
+    params(Tenant),
+    request_body(
+        content = MachineMetadata,
+        content_type = "application/json",
+        description = "JSON object containing the details of the machine, omitted details are cleared",
+        example = json!({
+            "manufacturer": "Speed Queen",
+            "model": "DR7",
+            "serial_number": "2203012345",
+            "install_date": "2022-03-01",
+            "warranty_expiry": "2027-03-01",
+            "capacity_kg": 9.0,
+            "payment_type": "Card",
+            "notes": "Stacked on top of washer B",
+        })
+    ),
+    responses(
+        (status = 200, description = "The details of the requested machine were replaced", body = Machine, example = json!({
+            "room_id": 1,
+            "machine_id": "A",
+            "machine_type": "Dryer",
+            "manufacturer": "Speed Queen",
+            "model": "DR7",
+            "serial_number": "2203012345",
+            "install_date": "2022-03-01",
+            "warranty_expiry": "2027-03-01",
+            "capacity_kg": 9.0,
+            "payment_type": "Card",
+            "notes": "Stacked on top of washer B",
+        })),
+        (status = 400, description = "The requested query was invalid"),
+        (status = 404, description = "The requested machine was not found"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[put("/{room_id}/{machine_id}/metadata")]
+async fn update_machine_metadata(
+    data: Data<AppState>,
+    tenant: Tenant,
+    path: Path<(i32, String)>,
+    Json(metadata): Json<MachineMetadata>,
+) -> impl Responder {
+    let (room_id, machine_id) = path.into_inner();
+
+    match query_as!(
+        Machine,
+        r#"
+        UPDATE machine
+        SET manufacturer = $3,
+            model = $4,
+            serial_number = $5,
+            install_date = $6,
+            warranty_expiry = $7,
+            capacity_kg = $8,
+            payment_type = $9,
+            notes = $10
+        WHERE room_id = $1
+            AND machine_id = $2
+            AND room_id IN (SELECT id FROM room WHERE organization_id = $11)
+        RETURNING
+            room_id,
+            machine_id,
+            type AS "machine_type: MachineType",
+            manufacturer,
+            model,
+            serial_number,
+            install_date,
+            warranty_expiry,
+            capacity_kg,
+            payment_type AS "payment_type: PaymentType",
+            notes
+        "#,
+        &room_id,
+        &machine_id,
+        metadata.manufacturer,
+        metadata.model,
+        metadata.serial_number,
+        metadata.install_date,
+        metadata.warranty_expiry,
+        metadata.capacity_kg,
+        metadata.payment_type as Option<PaymentType>,
+        metadata.notes,
+        tenant.organization_id
+    )
+    .fetch_optional(&data.database)
+    .await
+    {
+        Ok(Some(machine)) => HttpResponse::Ok().json(machine),
+        Ok(None) => HttpResponse::NotFound().json(format!(
+            "Machine id {machine_id} was not found in room id {room_id}."
+        )),
+        Err(err) => match constraint_violation(&err) {
+            Some(Violation::Check) => {
+                HttpResponse::BadRequest().json("The capacity must be positive.")
+            }
+            _ => database_error("update machine metadata", err),
+        },
+    }
+}
+
+#[utoipa::path(
+    context_path = "/machine",
+    params(Tenant),
     responses(
         (status = 200, description = "The requested machine was deleted", body = Machine, example = json!({
             "room_id": 1,
             "machine_id": "A",
-            "machine_type": "Dryer"
+            "machine_type": "Dryer",
+            "manufacturer": "Speed Queen",
+            "model": "DR7",
+            "serial_number": "2203012345",
+            "install_date": "2022-03-01",
+            "warranty_expiry": "2027-03-01",
+            "capacity_kg": 9.0,
+            "payment_type": "Card",
+            "notes": "Stacked on top of washer B",
         })),
         (status = 404, description = "The requested machine was not found"),
         (status = 500, description = "An internal server error occurred")
     )
 )]
 #[delete("/{room_id}/{machine_id}")]
-async fn delete_machine(data: Data<AppState>, path: Path<(i32, String)>) -> impl Responder {
+async fn delete_machine(
+    data: Data<AppState>,
+    tenant: Tenant,
+    path: Path<(i32, String)>,
+) -> impl Responder {
     let (room_id, machine_id) = path.into_inner();
 
-    let machine_present = match is_machine_present(&data.database, &room_id, &machine_id).await {
-        Ok(result) => result,
-        Err(err) => return HttpResponse::InternalServerError().json(err.to_string()),
-    };
+    let machine_present =
+        match is_machine_present(&data.database, &tenant, &room_id, &machine_id).await {
+            Ok(result) => result,
+            Err(err) => return database_error("check machine presence", err),
+        };
 
     if !machine_present {
         return HttpResponse::NotFound().json(format!(
@@ -219,30 +515,50 @@ async fn delete_machine(data: Data<AppState>, path: Path<(i32, String)>) -> impl
         ));
     }
 
+    let blob_keys = match attachment::machine_blob_keys(&data.database, &room_id, &machine_id).await
+    {
+        Ok(keys) => keys,
+        Err(err) => return database_error("fetch machine attachments", err),
+    };
+
     match query_as!(
         Machine,
         r#"
         DELETE FROM machine
         WHERE room_id = $1
             AND machine_id = $2
+            AND room_id IN (SELECT id FROM room WHERE organization_id = $3)
         RETURNING
             room_id,
             machine_id,
-            type AS "machine_type: MachineType"
+            type AS "machine_type: MachineType",
+            manufacturer,
+            model,
+            serial_number,
+            install_date,
+            warranty_expiry,
+            capacity_kg,
+            payment_type AS "payment_type: PaymentType",
+            notes
         "#,
         &room_id,
-        &machine_id
+        &machine_id,
+        tenant.organization_id
     )
     .fetch_one(&data.database)
     .await
     {
-        Ok(machine) => HttpResponse::Ok().json(machine),
-        Err(err) => HttpResponse::InternalServerError().json(err.to_string()),
+        Ok(machine) => {
+            attachment::remove_blobs(&data.blob_store, &blob_keys).await;
+            HttpResponse::Ok().json(machine)
+        }
+        Err(err) => database_error("delete machine", err),
     }
 }
 
 #[utoipa::path(
     context_path = "/machine",
+    params(Tenant),
     responses(
         (status = 200, description = "List of all unarchived reports for the requested machine", body = Vec<Report>, example = json!([{
             "report_id": 1,
@@ -253,19 +569,28 @@ async fn delete_machine(data: Data<AppState>, path: Path<(i32, String)>) -> impl
             "description": "No heat",
             "time": "2023-01-01T12:00:00.000Z",
             "archived": false,
+            "automated": false,
+            "confirmation_count": 0,
+            "comment_count": 0,
+            "attachments": [],
         }])),
         (status = 400, description = "The requested query was invalid"),
         (status = 500, description = "An internal server occurred")
     )
 )]
 #[get("/{room_id}/{machine_id}/reports")]
-async fn get_machine_reports(data: Data<AppState>, path: Path<(i32, String)>) -> impl Responder {
+async fn get_machine_reports(
+    data: Data<AppState>,
+    tenant: Tenant,
+    path: Path<(i32, String)>,
+) -> impl Responder {
     let (room_id, machine_id) = path.into_inner();
 
-    let machine_present = match is_machine_present(&data.database, &room_id, &machine_id).await {
-        Ok(result) => result,
-        Err(err) => return HttpResponse::InternalServerError().json(err.to_string()),
-    };
+    let machine_present =
+        match is_machine_present(&data.database, &tenant, &room_id, &machine_id).await {
+            Ok(result) => result,
+            Err(err) => return database_error("check machine presence", err),
+        };
 
     if !machine_present {
         return HttpResponse::BadRequest().json(format!(
@@ -284,7 +609,11 @@ async fn get_machine_reports(data: Data<AppState>, path: Path<(i32, String)>) ->
             time,
             type AS "report_type: ReportType",
             description,
-            archived
+            archived,
+            automated,
+            (SELECT COUNT(*) FROM report_confirmation WHERE report_id = report.id) AS "confirmation_count!: i64",
+            (SELECT COUNT(*) FROM report_comment WHERE report_id = report.id) AS "comment_count!: i64",
+            report_attachments(report.id) AS "attachments!: _"
         FROM report
         WHERE room_id = $1
             AND machine_id = $2
@@ -297,12 +626,13 @@ async fn get_machine_reports(data: Data<AppState>, path: Path<(i32, String)>) ->
     .await
     {
         Ok(reports) => HttpResponse::Ok().json(reports),
-        Err(err) => HttpResponse::InternalServerError().json(err.to_string()),
+        Err(err) => database_error("fetch machine reports", err),
     }
 }
 
 #[utoipa::path(
     context_path = "/machine",
+    params(Tenant),
     responses(
         (status = 200, description = "List of all archived reports for the requested machine", body = Vec<Report>, example = json!([{
             "report_id": 1,
@@ -313,6 +643,10 @@ async fn get_machine_reports(data: Data<AppState>, path: Path<(i32, String)>) ->
             "description": "No heat",
             "time": "2023-01-01T12:00:00.000Z",
             "archived": true,
+            "automated": false,
+            "confirmation_count": 0,
+            "comment_count": 0,
+            "attachments": [],
         }])),
         (status = 400, description = "The requested query was invalid"),
         (status = 500, description = "An internal server occurred")
@@ -321,14 +655,16 @@ async fn get_machine_reports(data: Data<AppState>, path: Path<(i32, String)>) ->
 #[get("/{room_id}/{machine_id}/reports/archived")]
 async fn get_machine_archived_reports(
     data: Data<AppState>,
+    tenant: Tenant,
     path: Path<(i32, String)>,
 ) -> impl Responder {
     let (room_id, machine_id) = path.into_inner();
 
-    let machine_present = match is_machine_present(&data.database, &room_id, &machine_id).await {
-        Ok(result) => result,
-        Err(err) => return HttpResponse::InternalServerError().json(err.to_string()),
-    };
+    let machine_present =
+        match is_machine_present(&data.database, &tenant, &room_id, &machine_id).await {
+            Ok(result) => result,
+            Err(err) => return database_error("check machine presence", err),
+        };
 
     if !machine_present {
         return HttpResponse::BadRequest().json(format!(
@@ -347,7 +683,11 @@ async fn get_machine_archived_reports(
             time,
             type AS "report_type: ReportType",
             description,
-            archived
+            archived,
+            automated,
+            (SELECT COUNT(*) FROM report_confirmation WHERE report_id = report.id) AS "confirmation_count!: i64",
+            (SELECT COUNT(*) FROM report_comment WHERE report_id = report.id) AS "comment_count!: i64",
+            report_attachments(report.id) AS "attachments!: _"
         FROM report
         WHERE room_id = $1
             AND machine_id = $2
@@ -360,6 +700,6 @@ async fn get_machine_archived_reports(
     .await
     {
         Ok(reports) => HttpResponse::Ok().json(reports),
-        Err(err) => HttpResponse::InternalServerError().json(err.to_string()),
+        Err(err) => database_error("fetch machine archived reports", err),
     }
 }
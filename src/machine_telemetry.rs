@@ -0,0 +1,959 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use actix_web::{
+    delete, get,
+    http::header,
+    post,
+    web::{Data, Json, Path, Query},
+    HttpRequest, HttpResponse, Responder,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use ring::{
+    digest,
+    rand::{SecureRandom, SystemRandom},
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{query, query_as, Pool, Postgres};
+use time::{OffsetDateTime, PrimitiveDateTime};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    config,
+    error::{constraint_violation, database_error, Violation},
+    machine,
+    models::{
+        iso_datetime, now, AppState, OperatingState, ReportType, TelemetryDevice, TelemetrySample,
+    },
+    report::{self, ReportConfig},
+    tenant::Tenant,
+    tls::ClientCertificate,
+};
+
+/// The most readings a device may submit at once.
+const MAX_BATCH_READINGS: usize = 1000;
+
+/// The largest telemetry body accepted, which leaves room for [MAX_BATCH_READINGS] readings with
+/// every field set.
+pub const MAX_BATCH_BYTES: usize = MAX_BATCH_READINGS * 256;
+
+/// How far ahead of the server's clock a reading may be timestamped.
+const MAX_CLOCK_SKEW: Duration = Duration::from_secs(5 * 60);
+
+/// How much history is returned when no start is requested.
+const DEFAULT_HISTORY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// The most samples returned at once.
+const MAX_SAMPLES: i64 = 10_000;
+
+/// Thresholds used to infer what a machine is doing from the readings of its devices.
+#[derive(Debug, Clone)]
+pub struct TelemetryConfig {
+    /// The power above which a machine is considered to be running a cycle.
+    pub running_watts: f32,
+    /// The power above which a machine is considered faulty, such as a shorted heater.
+    pub fault_watts: f32,
+    /// The vibration above which a machine is considered running when its power is not measured.
+    pub running_vibration: f32,
+    /// The temperature above which a machine is considered faulty, such as an overheating dryer.
+    pub fault_celsius: f32,
+    /// How long a machine may run without a break before it is considered stuck.
+    pub max_run: Duration,
+    /// The interval readings are downsampled to for storage.
+    pub sample_interval: Duration,
+}
+
+impl TelemetryConfig {
+    /// Parses the telemetry configuration from the environment.
+    pub fn from_env() -> TelemetryConfig {
+        TelemetryConfig {
+            running_watts: config::env_or("TELEMETRY_RUNNING_WATTS", 10.0),
+            fault_watts: config::env_or("TELEMETRY_FAULT_WATTS", 3500.0),
+            running_vibration: config::env_or("TELEMETRY_RUNNING_VIBRATION", 0.05),
+            fault_celsius: config::env_or("TELEMETRY_FAULT_CELSIUS", 95.0),
+            max_run: Duration::from_secs(config::env_or("TELEMETRY_MAX_RUN_MINUTES", 180) * 60),
+            sample_interval: Duration::from_secs(
+                config::env_or("TELEMETRY_SAMPLE_SECONDS", 60).max(1),
+            ),
+        }
+    }
+
+    /// Infers the state of a machine from a single reading, which tells nothing unless it
+    /// measured power or vibration or shows a fault.
+    pub fn derive_state(&self, reading: &TelemetryReading) -> Option<OperatingState> {
+        if self.describe_fault(reading).is_some() {
+            return Some(OperatingState::Fault);
+        }
+
+        let running = match (reading.power_watts, reading.vibration) {
+            (Some(power_watts), _) => power_watts >= self.running_watts,
+            (None, Some(vibration)) => vibration >= self.running_vibration,
+            (None, None) => return None,
+        };
+
+        match running {
+            true => Some(OperatingState::Running),
+            false => Some(OperatingState::Idle),
+        }
+    }
+
+    /// Describes why a reading shows a fault, or returns `None` if it does not.
+    fn describe_fault(&self, reading: &TelemetryReading) -> Option<String> {
+        if let Some(power_watts) = reading
+            .power_watts
+            .filter(|power| *power >= self.fault_watts)
+        {
+            return Some(format!(
+                "The machine drew {power_watts:.0} W, above the {:.0} W expected of any cycle.",
+                self.fault_watts
+            ));
+        }
+
+        reading
+            .temperature_celsius
+            .filter(|temperature| *temperature >= self.fault_celsius)
+            .map(|temperature| {
+                format!(
+                    "The machine reached {temperature:.0} °C, above the {:.0} °C expected of any cycle.",
+                    self.fault_celsius
+                )
+            })
+    }
+
+    /// The start of the storage interval containing `time`.
+    fn sample_time(&self, time: PrimitiveDateTime) -> PrimitiveDateTime {
+        let seconds = time.assume_utc().unix_timestamp();
+        let interval = self.sample_interval.as_secs() as i64;
+
+        match OffsetDateTime::from_unix_timestamp(seconds - seconds.rem_euclid(interval)) {
+            Ok(start) => PrimitiveDateTime::new(start.date(), start.time()),
+            Err(_) => time,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct TelemetryDeviceSubmission {
+    device_id: String,
+    room_id: i32,
+    machine_id: String,
+    /// The SHA-256 fingerprint of a client certificate the device may authenticate with over
+    /// mutual TLS instead of its key, in hex with or without colons.
+    #[serde(default)]
+    certificate_fingerprint: Option<String>,
+}
+
+/// A device with the key it authenticates with, which is only shown when it is issued.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct TelemetryDeviceCredentials {
+    device: TelemetryDevice,
+    /// Sent by the device as `Authorization: Bearer <key>`.
+    key: String,
+}
+
+/// A measurement taken by a device, which includes whichever values the device measures.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct TelemetryReading {
+    /// When the reading was taken in UTC, defaulting to when it was received.
+    #[serde(default, with = "iso_datetime::option")]
+    pub time: Option<PrimitiveDateTime>,
+    pub power_watts: Option<f32>,
+    /// Vibration measured by an accelerometer, in g.
+    pub vibration: Option<f32>,
+    pub door_open: Option<bool>,
+    pub temperature_celsius: Option<f32>,
+}
+
+impl TelemetryReading {
+    fn is_empty(&self) -> bool {
+        self.power_watts.is_none()
+            && self.vibration.is_none()
+            && self.door_open.is_none()
+            && self.temperature_celsius.is_none()
+    }
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct TelemetryBatch {
+    readings: Vec<TelemetryReading>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct TelemetryReceipt {
+    accepted: usize,
+    /// The state of the machine after the readings, if any of its readings have told it.
+    state: Option<OperatingState>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TelemetryQuery {
+    /// The start of the samples to return in UTC, defaulting to the last 24 hours.
+    #[serde(default, with = "iso_datetime::option")]
+    since: Option<PrimitiveDateTime>,
+}
+
+/// A machine's telemetry over time and the state inferred from it.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct MachineTelemetry {
+    state: Option<OperatingState>,
+    #[serde(with = "iso_datetime::option")]
+    state_since: Option<PrimitiveDateTime>,
+    samples: Vec<TelemetrySample>,
+}
+
+/// The mean of the values of a field which were measured.
+#[derive(Default)]
+struct Mean {
+    sum: f32,
+    count: u32,
+}
+
+impl Mean {
+    fn add(&mut self, value: Option<f32>) {
+        if let Some(value) = value {
+            self.sum += value;
+            self.count += 1;
+        }
+    }
+
+    fn value(&self) -> Option<f32> {
+        (self.count > 0).then(|| self.sum / self.count as f32)
+    }
+}
+
+/// The readings falling into one storage interval, combined.
+#[derive(Default)]
+struct Sample {
+    count: i32,
+    power_watts: Mean,
+    peak_power_watts: Option<f32>,
+    vibration: Mean,
+    temperature_celsius: Mean,
+    door_open: Option<bool>,
+    state: Option<OperatingState>,
+}
+
+impl Sample {
+    fn add(&mut self, reading: &TelemetryReading, state: Option<OperatingState>) {
+        self.count += 1;
+        self.power_watts.add(reading.power_watts);
+        self.peak_power_watts = match (self.peak_power_watts, reading.power_watts) {
+            (Some(peak), Some(power_watts)) => Some(peak.max(power_watts)),
+            (peak, power_watts) => peak.or(power_watts),
+        };
+        self.vibration.add(reading.vibration);
+        self.temperature_celsius.add(reading.temperature_celsius);
+        self.door_open = self.door_open.max(reading.door_open);
+        self.state = self.state.max(state);
+    }
+}
+
+fn hash_key(key: &str) -> Vec<u8> {
+    digest::digest(&digest::SHA256, key.as_bytes())
+        .as_ref()
+        .to_vec()
+}
+
+/// Normalizes a certificate fingerprint to lowercase hex without colons, or returns `None` if it
+/// is not a SHA-256 fingerprint.
+fn normalize_fingerprint(fingerprint: &str) -> Option<String> {
+    let fingerprint = fingerprint.trim().replace(':', "").to_lowercase();
+    (fingerprint.len() == 64 && fingerprint.chars().all(|c| c.is_ascii_hexdigit()))
+        .then_some(fingerprint)
+}
+
+/// Generates a new device key, returning it along with the hash to store.
+fn generate_key() -> Option<(String, Vec<u8>)> {
+    let mut key = [0; 32];
+    SystemRandom::new().fill(&mut key).ok()?;
+
+    let key = URL_SAFE_NO_PAD.encode(key);
+    let key_hash = hash_key(&key);
+    Some((key, key_hash))
+}
+
+/// Finds the device with `device_id`, which tells which machine its readings are taken from.
+pub async fn find_device(
+    database: &Pool<Postgres>,
+    device_id: &str,
+) -> Result<Option<TelemetryDevice>, sqlx::Error> {
+    query_as!(
+        TelemetryDevice,
+        r#"
+        SELECT device_id, room_id, machine_id, certificate_fingerprint, created_time
+        FROM telemetry_device
+        WHERE device_id = $1
+        "#,
+        device_id
+    )
+    .fetch_optional(database)
+    .await
+}
+
+/// Finds the device which was issued `key`.
+async fn find_device_by_key(
+    database: &Pool<Postgres>,
+    key: &str,
+) -> Result<Option<TelemetryDevice>, sqlx::Error> {
+    query_as!(
+        TelemetryDevice,
+        r#"
+        SELECT device_id, room_id, machine_id, certificate_fingerprint, created_time
+        FROM telemetry_device
+        WHERE key_hash = $1
+        "#,
+        hash_key(key)
+    )
+    .fetch_optional(database)
+    .await
+}
+
+/// Finds the device registered with the client certificate `certificate`.
+async fn find_device_by_certificate(
+    database: &Pool<Postgres>,
+    certificate: &ClientCertificate,
+) -> Result<Option<TelemetryDevice>, sqlx::Error> {
+    query_as!(
+        TelemetryDevice,
+        r#"
+        SELECT device_id, room_id, machine_id, certificate_fingerprint, created_time
+        FROM telemetry_device
+        WHERE certificate_fingerprint = $1
+        "#,
+        certificate.fingerprint()
+    )
+    .fetch_optional(database)
+    .await
+}
+
+/// Stores readings taken by `device`, downsampled to [TelemetryConfig::sample_interval], and
+/// updates the operating state of its machine, returning that state if it is known.
+///
+/// A machine which keeps running for longer than [TelemetryConfig::max_run] is considered
+/// faulty, and a fault only clears once the machine goes idle. When a machine becomes faulty
+/// a Broken report is filed for it, unless an open one already exists.
+pub async fn record_readings(
+    database: &Pool<Postgres>,
+    telemetry_config: &TelemetryConfig,
+    report_config: &ReportConfig,
+    device: &TelemetryDevice,
+    mut readings: Vec<TelemetryReading>,
+) -> Result<Option<OperatingState>, sqlx::Error> {
+    let TelemetryDevice {
+        device_id,
+        room_id,
+        machine_id,
+        ..
+    } = device;
+
+    let received_time = now();
+    readings.sort_by_key(|reading| reading.time.unwrap_or(received_time));
+
+    let mut transaction = database.begin().await?;
+
+    let mut current = query!(
+        r#"
+        SELECT state AS "state: OperatingState", since, last_reading_time
+        FROM machine_operating_state
+        WHERE room_id = $1 AND machine_id = $2
+        FOR UPDATE
+        "#,
+        room_id,
+        machine_id
+    )
+    .fetch_optional(&mut transaction)
+    .await?
+    .map(|current| (current.state, current.since, current.last_reading_time));
+
+    let mut samples: BTreeMap<PrimitiveDateTime, Sample> = BTreeMap::new();
+    let mut fault_reason = None;
+
+    for reading in &readings {
+        let time = reading.time.unwrap_or(received_time);
+        let derived_state = telemetry_config.derive_state(reading);
+
+        samples
+            .entry(telemetry_config.sample_time(time))
+            .or_default()
+            .add(reading, derived_state);
+
+        let Some(mut state) = derived_state else {
+            continue;
+        };
+
+        // Readings delivered late are kept for history but do not move the state backwards.
+        if current.is_some_and(|(_, _, last_reading_time)| time < last_reading_time) {
+            continue;
+        }
+
+        let mut reason = telemetry_config.describe_fault(reading);
+        let mut since = time;
+
+        if let Some((previous_state, previous_since, _)) = current {
+            match (previous_state, state) {
+                (OperatingState::Fault, OperatingState::Running | OperatingState::Fault) => {
+                    state = OperatingState::Fault;
+                    reason = None;
+                }
+                (OperatingState::Running, OperatingState::Running)
+                    if time - previous_since >= telemetry_config.max_run =>
+                {
+                    state = OperatingState::Fault;
+                    reason = Some(format!(
+                        "The machine has been running for more than {} minutes without a break.",
+                        telemetry_config.max_run.as_secs() / 60
+                    ));
+                }
+                _ => {}
+            }
+
+            if previous_state == state {
+                since = previous_since;
+            }
+        }
+
+        fault_reason = reason.or(fault_reason);
+        current = Some((state, since, time));
+    }
+
+    for (time, sample) in samples {
+        query!(
+            r#"
+            INSERT INTO machine_telemetry (
+                room_id, machine_id, time, sample_count, power_watts, peak_power_watts,
+                vibration, temperature_celsius, door_open, state
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            ON CONFLICT (room_id, machine_id, time) DO UPDATE
+            SET sample_count = machine_telemetry.sample_count + EXCLUDED.sample_count,
+                power_watts = weighted_mean(
+                    machine_telemetry.power_watts, machine_telemetry.sample_count,
+                    EXCLUDED.power_watts, EXCLUDED.sample_count
+                ),
+                peak_power_watts = GREATEST(machine_telemetry.peak_power_watts, EXCLUDED.peak_power_watts),
+                vibration = weighted_mean(
+                    machine_telemetry.vibration, machine_telemetry.sample_count,
+                    EXCLUDED.vibration, EXCLUDED.sample_count
+                ),
+                temperature_celsius = weighted_mean(
+                    machine_telemetry.temperature_celsius, machine_telemetry.sample_count,
+                    EXCLUDED.temperature_celsius, EXCLUDED.sample_count
+                ),
+                door_open = GREATEST(machine_telemetry.door_open, EXCLUDED.door_open),
+                state = GREATEST(machine_telemetry.state, EXCLUDED.state)
+            "#,
+            room_id,
+            machine_id,
+            time,
+            sample.count,
+            sample.power_watts.value(),
+            sample.peak_power_watts,
+            sample.vibration.value(),
+            sample.temperature_celsius.value(),
+            sample.door_open,
+            sample.state as Option<OperatingState>
+        )
+        .execute(&mut transaction)
+        .await?;
+    }
+
+    if let Some((state, since, last_reading_time)) = current {
+        query!(
+            r#"
+            INSERT INTO machine_operating_state (room_id, machine_id, state, since, last_reading_time)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (room_id, machine_id) DO UPDATE
+            SET state = EXCLUDED.state, since = EXCLUDED.since, last_reading_time = EXCLUDED.last_reading_time
+            "#,
+            room_id,
+            machine_id,
+            state as OperatingState,
+            since,
+            last_reading_time
+        )
+        .execute(&mut transaction)
+        .await?;
+    }
+
+    transaction.commit().await?;
+
+    if let Some(reason) = fault_reason {
+        let report = report::submit_automated_report(
+            database,
+            report_config,
+            room_id,
+            machine_id,
+            &ReportType("Broken".to_string()),
+            Some(format!(
+                "Reported automatically by device {device_id}. {reason}"
+            )),
+        )
+        .await?;
+
+        if let Some(report) = report {
+            log::warn!(
+                "Filed report id {} for machine {machine_id} in room id {room_id}: {reason}",
+                report.report_id
+            );
+        }
+    }
+
+    Ok(current.map(|(state, _, _)| state))
+}
+
+#[utoipa::path(
+    context_path = "/admin",
+    params(Tenant),
+    responses(
+        (status = 200, description = "List of all telemetry devices", body = Vec<TelemetryDevice>, example = json!([{
+            "device_id": "plug-1",
+            "room_id": 1,
+            "machine_id": "A",
+            "certificate_fingerprint": null,
+            "created_time": "2023-01-01T12:00:00"
+        }])),
+        (status = 401, description = "No valid admin credentials were given"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[get("/telemetry-devices")]
+async fn get_telemetry_devices(data: Data<AppState>, tenant: Tenant) -> impl Responder {
+    match query_as!(
+        TelemetryDevice,
+        r#"
+        SELECT device_id, room_id, machine_id, certificate_fingerprint, created_time
+        FROM telemetry_device
+        WHERE room_id IN (SELECT id FROM room WHERE organization_id = $1)
+        ORDER BY device_id
+        "#,
+        tenant.organization_id
+    )
+    .fetch_all(&data.database)
+    .await
+    {
+        Ok(devices) => HttpResponse::Ok().json(devices),
+        Err(err) => database_error("fetch telemetry devices", err),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/admin",
+    params(Tenant),
+    request_body(content = TelemetryDeviceSubmission, content_type = "application/json", example = json!({
+        "device_id": "plug-1",
+        "room_id": 1,
+        "machine_id": "A",
+        "certificate_fingerprint": null
+    })),
+    responses(
+        (status = 201, description = "The device was added, along with the key it posts readings with", body = TelemetryDeviceCredentials, example = json!({
+            "device": {
+                "device_id": "plug-1",
+                "room_id": 1,
+                "machine_id": "A",
+                "certificate_fingerprint": null,
+                "created_time": "2023-01-01T12:00:00"
+            },
+            "key": "q8Lx2T0v3kYwJ4mZr6nHc1sVb9pQe7uA5fGd0iKjXyE"
+        })),
+        (status = 400, description = "The requested query was invalid"),
+        (status = 401, description = "No valid admin credentials were given"),
+        (status = 409, description = "A device with this id or certificate fingerprint already exists"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[post("/telemetry-devices")]
+async fn add_telemetry_device(
+    data: Data<AppState>,
+    tenant: Tenant,
+    Json(submission): Json<TelemetryDeviceSubmission>,
+) -> impl Responder {
+    if submission.device_id.trim().is_empty() {
+        return HttpResponse::BadRequest().json("The device id must not be empty.");
+    }
+
+    let certificate_fingerprint = match submission.certificate_fingerprint.as_deref() {
+        None => None,
+        Some(fingerprint) => match normalize_fingerprint(fingerprint) {
+            Some(fingerprint) => Some(fingerprint),
+            None => {
+                return HttpResponse::BadRequest()
+                    .json("The certificate fingerprint must be a hex encoded SHA-256 digest.")
+            }
+        },
+    };
+
+    match machine::is_machine_present(
+        &data.database,
+        &tenant,
+        &submission.room_id,
+        &submission.machine_id,
+    )
+    .await
+    {
+        Ok(true) => {}
+        Ok(false) => {
+            return HttpResponse::BadRequest().json(format!(
+                "Room id {} does not contain machine id {}.",
+                submission.room_id, submission.machine_id
+            ))
+        }
+        Err(err) => return database_error("check machine presence", err),
+    }
+
+    let Some((key, key_hash)) = generate_key() else {
+        log::error!("Failed to generate a telemetry device key");
+        return HttpResponse::InternalServerError().finish();
+    };
+
+    match query_as!(
+        TelemetryDevice,
+        r#"
+        INSERT INTO telemetry_device (
+            device_id, room_id, machine_id, created_time, key_hash, certificate_fingerprint
+        )
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT DO NOTHING
+        RETURNING device_id, room_id, machine_id, certificate_fingerprint, created_time
+        "#,
+        submission.device_id,
+        submission.room_id,
+        submission.machine_id,
+        now(),
+        key_hash,
+        certificate_fingerprint
+    )
+    .fetch_optional(&data.database)
+    .await
+    {
+        Ok(Some(device)) => {
+            HttpResponse::Created().json(TelemetryDeviceCredentials { device, key })
+        }
+        Ok(None) => HttpResponse::Conflict().json(format!(
+            "The device id {} or its certificate is already registered.",
+            submission.device_id
+        )),
+        Err(err) => match constraint_violation(&err) {
+            Some(Violation::Unique) => HttpResponse::Conflict().json(format!(
+                "The device id {} or its certificate is already registered.",
+                submission.device_id
+            )),
+            Some(Violation::ForeignKey) => HttpResponse::BadRequest().json(format!(
+                "Room id {} no longer contains machine id {}.",
+                submission.room_id, submission.machine_id
+            )),
+            _ => database_error("insert telemetry device", err),
+        },
+    }
+}
+
+#[utoipa::path(
+    context_path = "/admin",
+    params(Tenant),
+    responses(
+        (status = 200, description = "A new key was issued to the device, and its previous key no longer works", body = TelemetryDeviceCredentials, example = json!({
+            "device": {
+                "device_id": "plug-1",
+                "room_id": 1,
+                "machine_id": "A",
+                "certificate_fingerprint": null,
+                "created_time": "2023-01-01T12:00:00"
+            },
+            "key": "q8Lx2T0v3kYwJ4mZr6nHc1sVb9pQe7uA5fGd0iKjXyE"
+        })),
+        (status = 401, description = "No valid admin credentials were given"),
+        (status = 404, description = "The requested device was not found"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[post("/telemetry-devices/{device_id}/key")]
+async fn rotate_telemetry_device_key(
+    data: Data<AppState>,
+    tenant: Tenant,
+    path: Path<String>,
+) -> impl Responder {
+    let device_id = path.into_inner();
+
+    let Some((key, key_hash)) = generate_key() else {
+        log::error!("Failed to generate a telemetry device key");
+        return HttpResponse::InternalServerError().finish();
+    };
+
+    match query_as!(
+        TelemetryDevice,
+        r#"
+        UPDATE telemetry_device
+        SET key_hash = $1
+        WHERE device_id = $2 AND room_id IN (SELECT id FROM room WHERE organization_id = $3)
+        RETURNING device_id, room_id, machine_id, certificate_fingerprint, created_time
+        "#,
+        key_hash,
+        device_id,
+        tenant.organization_id
+    )
+    .fetch_optional(&data.database)
+    .await
+    {
+        Ok(Some(device)) => HttpResponse::Ok().json(TelemetryDeviceCredentials { device, key }),
+        Ok(None) => {
+            HttpResponse::NotFound().json(format!("The device id {device_id} was not found."))
+        }
+        Err(err) => database_error("rotate telemetry device key", err),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/admin",
+    params(Tenant),
+    responses(
+        (status = 200, description = "The device was removed, its readings are kept", body = TelemetryDevice, example = json!({
+            "device_id": "plug-1",
+            "room_id": 1,
+            "machine_id": "A",
+            "certificate_fingerprint": null,
+            "created_time": "2023-01-01T12:00:00"
+        })),
+        (status = 401, description = "No valid admin credentials were given"),
+        (status = 404, description = "The requested device was not found"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[delete("/telemetry-devices/{device_id}")]
+async fn delete_telemetry_device(
+    data: Data<AppState>,
+    tenant: Tenant,
+    path: Path<String>,
+) -> impl Responder {
+    let device_id = path.into_inner();
+
+    match query_as!(
+        TelemetryDevice,
+        r#"
+        DELETE FROM telemetry_device
+        WHERE device_id = $1 AND room_id IN (SELECT id FROM room WHERE organization_id = $2)
+        RETURNING device_id, room_id, machine_id, certificate_fingerprint, created_time
+        "#,
+        device_id,
+        tenant.organization_id
+    )
+    .fetch_optional(&data.database)
+    .await
+    {
+        Ok(Some(device)) => HttpResponse::Ok().json(device),
+        Ok(None) => {
+            HttpResponse::NotFound().json(format!("The device id {device_id} was not found."))
+        }
+        Err(err) => database_error("delete telemetry device", err),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/machine",
+    params(Tenant),
+    request_body(content = TelemetryBatch, content_type = "application/json", example = json!({
+        "readings": [
+            {"time": "2023-01-01T12:00:00", "power_watts": 1850.0, "vibration": 0.4, "door_open": false},
+            {"time": "2023-01-01T12:00:10", "power_watts": 1910.5, "temperature_celsius": 61.0}
+        ]
+    })),
+    responses(
+        (status = 200, description = "The readings were recorded", body = TelemetryReceipt, example = json!({
+            "accepted": 2,
+            "state": "Running"
+        })),
+        (status = 400, description = "The requested query was invalid"),
+        (status = 401, description = "No valid device key was given as `Authorization: Bearer <key>`, nor a registered client certificate"),
+        (status = 403, description = "The device does not measure this machine"),
+        (status = 404, description = "The requested machine was not found"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[post("/{room_id}/{machine_id}/telemetry")]
+async fn submit_telemetry(
+    data: Data<AppState>,
+    tenant: Tenant,
+    request: HttpRequest,
+    path: Path<(i32, String)>,
+    Json(batch): Json<TelemetryBatch>,
+) -> impl Responder {
+    let (room_id, machine_id) = path.into_inner();
+
+    let key = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    // A key takes precedence, so a device can still authenticate with it over mutual TLS.
+    let device = match (key, request.conn_data::<ClientCertificate>()) {
+        (Some(key), _) => match find_device_by_key(&data.database, key.trim()).await {
+            Ok(Some(device)) => device,
+            Ok(None) => return HttpResponse::Unauthorized().json("The device key is not valid."),
+            Err(err) => return database_error("check device key", err),
+        },
+        (None, Some(certificate)) => {
+            match find_device_by_certificate(&data.database, certificate).await {
+                Ok(Some(device)) => device,
+                Ok(None) => {
+                    return HttpResponse::Unauthorized()
+                        .json("The client certificate is not registered to a device.")
+                }
+                Err(err) => return database_error("check device certificate", err),
+            }
+        }
+        (None, None) => {
+            return HttpResponse::Unauthorized()
+                .json("A device key or client certificate is required.")
+        }
+    };
+
+    if device.room_id != room_id || device.machine_id != machine_id {
+        return HttpResponse::Forbidden().json(format!(
+            "Device {} does not measure machine id {machine_id} in room id {room_id}.",
+            device.device_id
+        ));
+    }
+
+    match machine::is_machine_present(&data.database, &tenant, &room_id, &machine_id).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return HttpResponse::NotFound().json(format!(
+                "Room id {room_id} does not contain machine id {machine_id}."
+            ))
+        }
+        Err(err) => return database_error("check machine presence", err),
+    }
+
+    if batch.readings.is_empty() || batch.readings.len() > MAX_BATCH_READINGS {
+        return HttpResponse::BadRequest().json(format!(
+            "A batch must contain between 1 and {MAX_BATCH_READINGS} readings."
+        ));
+    }
+
+    let latest_time = now() + MAX_CLOCK_SKEW;
+    for (index, reading) in batch.readings.iter().enumerate() {
+        if reading.is_empty() {
+            return HttpResponse::BadRequest().json(format!("Reading {index} has no values."));
+        }
+
+        if reading.time.is_some_and(|time| time > latest_time) {
+            return HttpResponse::BadRequest()
+                .json(format!("Reading {index} is timestamped in the future."));
+        }
+    }
+
+    let accepted = batch.readings.len();
+
+    match record_readings(
+        &data.database,
+        &data.telemetry_config,
+        &data.report_config,
+        &device,
+        batch.readings,
+    )
+    .await
+    {
+        Ok(state) => HttpResponse::Ok().json(TelemetryReceipt { accepted, state }),
+        Err(err) => match constraint_violation(&err) {
+            Some(Violation::ForeignKey) => HttpResponse::NotFound().json(format!(
+                "Room id {room_id} no longer contains machine id {machine_id}."
+            )),
+            _ => database_error("record telemetry", err),
+        },
+    }
+}
+
+#[utoipa::path(
+    context_path = "/machine",
+    params(Tenant, TelemetryQuery),
+    responses(
+        (status = 200, description = "The machine's current state and its telemetry, downsampled and oldest first", body = MachineTelemetry, example = json!({
+            "state": "Running",
+            "state_since": "2023-01-01T12:00:00",
+            "samples": [{
+                "time": "2023-01-01T12:00:00",
+                "sample_count": 6,
+                "power_watts": 1876.2,
+                "peak_power_watts": 1910.5,
+                "vibration": 0.4,
+                "temperature_celsius": 61.0,
+                "door_open": false,
+                "state": "Running"
+            }]
+        })),
+        (status = 404, description = "The requested machine was not found"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[get("/{room_id}/{machine_id}/telemetry")]
+async fn get_machine_telemetry(
+    data: Data<AppState>,
+    tenant: Tenant,
+    path: Path<(i32, String)>,
+    Query(telemetry_query): Query<TelemetryQuery>,
+) -> impl Responder {
+    let (room_id, machine_id) = path.into_inner();
+
+    match machine::is_machine_present(&data.database, &tenant, &room_id, &machine_id).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return HttpResponse::NotFound().json(format!(
+                "Room id {room_id} does not contain machine id {machine_id}."
+            ))
+        }
+        Err(err) => return database_error("check machine presence", err),
+    }
+
+    let since = telemetry_query
+        .since
+        .unwrap_or_else(|| now() - DEFAULT_HISTORY);
+
+    let samples = match query_as!(
+        TelemetrySample,
+        r#"
+        SELECT
+            time,
+            sample_count,
+            power_watts,
+            peak_power_watts,
+            vibration,
+            temperature_celsius,
+            door_open,
+            state AS "state: OperatingState"
+        FROM machine_telemetry
+        WHERE room_id = $1 AND machine_id = $2 AND time >= $3
+        ORDER BY time
+        LIMIT $4
+        "#,
+        room_id,
+        machine_id,
+        since,
+        MAX_SAMPLES
+    )
+    .fetch_all(&data.database)
+    .await
+    {
+        Ok(samples) => samples,
+        Err(err) => return database_error("fetch machine telemetry", err),
+    };
+
+    match query!(
+        r#"
+        SELECT state AS "state: OperatingState", since
+        FROM machine_operating_state
+        WHERE room_id = $1 AND machine_id = $2
+        "#,
+        room_id,
+        machine_id
+    )
+    .fetch_optional(&data.database)
+    .await
+    {
+        Ok(current) => HttpResponse::Ok().json(MachineTelemetry {
+            state: current.as_ref().map(|current| current.state),
+            state_since: current.map(|current| current.since),
+            samples,
+        }),
+        Err(err) => database_error("fetch machine operating state", err),
+    }
+}
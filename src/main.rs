@@ -1,38 +1,74 @@
-use std::{env, process};
+use std::{env, process, sync::Arc, time::Duration};
 
+#[cfg(feature = "opentelemetry")]
+use actix_web::middleware::Condition;
 use actix_web::{get, web, App, HttpResponse, HttpServer, Responder};
+use futures_util::future;
+#[cfg(feature = "mqtt")]
+use laundry_api::mqtt::{self, MqttConfig};
+#[cfg(feature = "opentelemetry")]
+use laundry_api::telemetry;
 use laundry_api::{
-    machine::{self, MachineSubmission},
-    models::{AppState, Machine, MachineType, Report, ReportType, Room, User},
-    report::{self, ArchiveSubmission, ReportSubmission},
-    room::{self, RoomSubmission},
+    admin::{self, AdminConfig, MachineTypeUpdate, OrganizationSubmission, ReportTypeUpdate},
+    anomaly::{self, AnomalyConfig},
+    attachment::{self, AttachmentConfig, AttachmentUpload},
+    background::BackgroundJobs,
+    blob_store::BlobStore,
+    building::{self, BuildingStatus, BuildingSubmission, ReportTypeCount},
+    comment::{self, CommentEdit, CommentSubmission},
+    config,
+    cors::CorsConfig,
+    database,
+    guest::{self, GuestReportConfig, GuestReportSubmission, PowChallenge, ProofOfWork},
+    health::{self, DatabaseStatus, MigrationStatus, PoolStatus, Readiness},
+    logging::{self, AccessLog},
+    machine::{self, MachineMetadata, MachineSubmission},
+    machine_telemetry::{
+        self, MachineTelemetry, TelemetryBatch, TelemetryConfig, TelemetryDeviceCredentials,
+        TelemetryDeviceSubmission, TelemetryReading, TelemetryReceipt,
+    },
+    maintenance::{
+        self, CompletedTask, MaintenanceConfig, MaintenancePlanSubmission, TaskCompletion,
+    },
+    models::{
+        AppState, AttachmentMetadata, Building, GuestReport, Machine, MachineSession, MachineType,
+        MachineTypeDefinition, MaintenancePlan, MaintenanceTask, MaintenanceTaskStatus,
+        ModerationStatus, OperatingState, Organization, PaymentType, RecurrenceUnit, Report,
+        ReportAttachment, ReportComment, ReportConfirmation, ReportType, ReportTypeDefinition,
+        Reservation, ReservationPolicy, ReservationStatus, Room, SessionEndReason, Site, SlaPolicy,
+        SlaTarget, TelemetryDevice, TelemetrySample, User, WaitlistEntry, WaitlistStatus,
+        WorkOrder, WorkOrderLabour, WorkOrderPart, WorkOrderPriority, WorkOrderStatus,
+    },
+    notification::{
+        self, NotificationEvent, NotificationKind, NotificationPreferences, Notifier,
+        PushSubscription, PushSubscriptionKeys, VapidPublicKey,
+    },
+    qr::{self, QrConfig},
+    rate_limit::{RateLimitPerIp, RateLimiter, ThrottledClient},
+    report::{self, ArchiveSubmission, MergeSubmission, ReportConfig, ReportSubmission},
+    request_id::RequestIdentifier,
+    reservation::{self, ReservationSubmission, WaitlistSubmission},
+    room::{self, BuildingAssignment, RoomSubmission},
+    routes,
+    session::{self, MachineAvailability, MachineState, SessionConfig, SessionSubmission},
+    site::{self, SiteSubmission},
+    sla::{self, ReportSla, SlaBreach, SlaCompliance, SlaConfig, SlaPolicySubmission},
+    tenant::TenantConfig,
+    tls::{self, HttpsRedirect, ReloadingCertResolver, TlsConfig},
     user::{self, UserSubmission},
+    work_order::{
+        self, ClosedWorkOrder, LabourSubmission, PartSubmission, WorkOrderClosure,
+        WorkOrderSubmission, WorkOrderUpdate,
+    },
 };
-use sqlx::{PgPool, Pool, Postgres};
+use sqlx::{postgres::PgPoolOptions, Pool, Postgres};
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 
 const APP_NAME: &str = "Laundry API";
 
-/// Initialize the logging system, using [syslog] as the backend.
-fn initalize_syslog() {
-    let log_level = match env::var("LOG_LEVEL") {
-        Err(_) => log::LevelFilter::Warn,
-        Ok(value) => match value.to_uppercase().as_str() {
-            "ERROR" => log::LevelFilter::Error,
-            "WARNING" => log::LevelFilter::Warn,
-            "INFO" => log::LevelFilter::Info,
-            "DEBUG" => log::LevelFilter::Debug,
-            "TRACE" => log::LevelFilter::Trace,
-            "OFF" => log::LevelFilter::Off,
-            _ => log::LevelFilter::Warn,
-        },
-    };
-
-    if syslog::init(syslog::Facility::LOG_SYSLOG, log_level, Some(APP_NAME)).is_err() {
-        eprintln!("WARNING: Failed to initialize logging system! Server logs will be unavaliable!");
-    }
-}
+/// The longest delay between two attempts to reach the database at startup.
+const MAX_DATABASE_CONNECT_BACKOFF: Duration = Duration::from_secs(30);
 
 /// Parses and returns a connection pool to the configured database.
 /// The database URL is derived from the DATABASE_URL [environment variable](std::env::var).
@@ -49,7 +85,10 @@ fn connect_postgres_database() -> Pool<Postgres> {
         }
     };
 
-    match PgPool::connect_lazy(database_url.as_str()) {
+    match PgPoolOptions::new()
+        .max_connections(database::MAX_CONNECTIONS)
+        .connect_lazy(database_url.as_str())
+    {
         Ok(pool) => pool,
         Err(err) => {
             eprintln!("Failed to connect to the database: {err}");
@@ -66,17 +105,49 @@ async fn ping() -> impl Responder {
 
 #[actix_web::main]
 async fn main() {
-    initalize_syslog();
+    #[cfg(feature = "opentelemetry")]
+    let tracer_provider = telemetry::initialize(APP_NAME);
+    #[cfg(feature = "opentelemetry")]
+    let tracing_enabled = tracer_provider.is_some();
+    #[cfg(not(feature = "opentelemetry"))]
+    let tracing_enabled = false;
+
+    logging::initialize(APP_NAME, tracing_enabled);
 
     #[derive(OpenApi)]
     #[openapi(
         paths(
+            admin::get_rate_limits,
+            admin::get_machine_types,
+            admin::add_machine_type,
+            admin::update_machine_type,
+            admin::get_report_types,
+            admin::add_report_type,
+            admin::update_report_type,
+            admin::get_organizations,
+            admin::add_organization,
+            guest::get_guest_reports,
+            guest::approve_guest_report,
+            guest::reject_guest_report,
+            reservation::get_reservation_policy,
+            reservation::update_reservation_policy,
+            machine_telemetry::get_telemetry_devices,
+            machine_telemetry::add_telemetry_device,
+            machine_telemetry::rotate_telemetry_device_key,
+            machine_telemetry::delete_telemetry_device,
+            health::live,
+            health::ready,
             machine::get_all_machines,
             machine::get_machine,
             machine::add_machine,
             machine::delete_machine,
+            machine::update_machine_metadata,
             machine::get_machine_reports,
             machine::get_machine_archived_reports,
+            session::start_session,
+            session::finish_session,
+            machine_telemetry::submit_telemetry,
+            machine_telemetry::get_machine_telemetry,
             room::get_all_rooms,
             room::get_room,
             room::add_room,
@@ -84,20 +155,90 @@ async fn main() {
             room::get_room_machines,
             room::get_room_reports,
             room::get_room_archived_reports,
+            room::assign_room_building,
+            session::get_room_availability,
+            reservation::join_waitlist,
+            reservation::get_room_waitlist,
+            reservation::leave_waitlist,
+            reservation::add_reservation,
+            reservation::get_room_reservations,
+            reservation::cancel_reservation,
+            qr::get_machine_qr_png,
+            qr::get_machine_qr_svg,
+            qr::get_room_qr_sheet,
+            building::get_all_buildings,
+            building::get_building,
+            building::add_building,
+            building::delete_building,
+            building::get_building_rooms,
+            building::get_building_reports,
+            building::get_building_status,
+            site::get_all_sites,
+            site::get_site,
+            site::add_site,
+            site::delete_site,
+            site::get_site_buildings,
             user::get_all_users,
             user::get_user,
             user::add_user,
             user::delete_user,
             user::get_user_reports,
             user::get_user_archived_reports,
+            notification::get_vapid_public_key,
+            notification::get_notification_preferences,
+            notification::update_notification_preferences,
             report::get_all_reports,
             report::get_all_archived_reports,
             report::get_report,
             report::submit_report,
             report::delete_report,
             report::archive_report,
+            report::get_report_confirmations,
+            report::merge_reports,
+            guest::get_guest_challenge,
+            guest::submit_guest_report,
+            comment::get_report_comments,
+            comment::add_report_comment,
+            comment::edit_report_comment,
+            comment::delete_report_comment,
+            attachment::get_report_attachments,
+            attachment::add_report_attachment,
+            attachment::get_report_attachment,
+            attachment::get_report_attachment_thumbnail,
+            attachment::delete_report_attachment,
+            maintenance::get_maintenance_plans,
+            maintenance::add_maintenance_plan,
+            maintenance::retire_maintenance_plan,
+            maintenance::get_upcoming_maintenance,
+            maintenance::get_maintenance_tasks,
+            maintenance::complete_maintenance_task,
+            work_order::get_work_orders,
+            work_order::get_work_order,
+            work_order::add_work_order,
+            work_order::update_work_order,
+            work_order::close_work_order,
+            work_order::get_work_order_labour,
+            work_order::log_work_order_labour,
+            work_order::get_work_order_parts,
+            work_order::log_work_order_part,
+            sla::get_sla_policies,
+            sla::add_sla_policy,
+            sla::delete_sla_policy,
+            sla::get_sla_compliance,
+            sla::get_report_sla,
         ),
         components(schemas(
+            Readiness,
+            DatabaseStatus,
+            PoolStatus,
+            MigrationStatus,
+            ThrottledClient,
+            MachineTypeDefinition,
+            MachineTypeUpdate,
+            Organization,
+            OrganizationSubmission,
+            ReportTypeDefinition,
+            ReportTypeUpdate,
             Machine,
             Room,
             Report,
@@ -107,74 +248,340 @@ async fn main() {
             ReportSubmission,
             UserSubmission,
             RoomSubmission,
+            BuildingAssignment,
+            Building,
+            BuildingSubmission,
+            BuildingStatus,
+            ReportTypeCount,
+            Site,
+            SiteSubmission,
             MachineSubmission,
+            MachineMetadata,
+            MachineSession,
+            SessionEndReason,
+            SessionSubmission,
+            MachineAvailability,
+            MachineState,
+            WaitlistEntry,
+            WaitlistStatus,
+            WaitlistSubmission,
+            Reservation,
+            ReservationStatus,
+            ReservationSubmission,
+            ReservationPolicy,
+            NotificationPreferences,
+            PushSubscription,
+            PushSubscriptionKeys,
+            NotificationEvent,
+            NotificationKind,
+            VapidPublicKey,
+            TelemetryDevice,
+            TelemetryDeviceSubmission,
+            TelemetryDeviceCredentials,
+            TelemetryReading,
+            TelemetryBatch,
+            TelemetryReceipt,
+            TelemetrySample,
+            MachineTelemetry,
+            OperatingState,
+            MaintenancePlan,
+            MaintenancePlanSubmission,
+            RecurrenceUnit,
+            MaintenanceTask,
+            MaintenanceTaskStatus,
+            TaskCompletion,
+            CompletedTask,
+            WorkOrder,
+            WorkOrderStatus,
+            WorkOrderPriority,
+            WorkOrderSubmission,
+            WorkOrderUpdate,
+            WorkOrderClosure,
+            ClosedWorkOrder,
+            WorkOrderLabour,
+            LabourSubmission,
+            WorkOrderPart,
+            PartSubmission,
+            SlaPolicy,
+            SlaPolicySubmission,
+            SlaTarget,
+            ReportSla,
+            SlaCompliance,
+            SlaBreach,
+            PaymentType,
             ArchiveSubmission,
+            ReportConfirmation,
+            MergeSubmission,
+            GuestReport,
+            GuestReportSubmission,
+            ModerationStatus,
+            ProofOfWork,
+            PowChallenge,
+            ReportComment,
+            CommentSubmission,
+            CommentEdit,
+            ReportAttachment,
+            AttachmentMetadata,
+            AttachmentUpload,
         ))
     )]
     struct ApiDoc;
     let openapi = ApiDoc::openapi();
 
+    let blob_store = match BlobStore::from_env() {
+        Ok(blob_store) => Arc::new(blob_store),
+        Err(err) => {
+            eprintln!("ERROR! Failed to configure the blob store: {err}");
+            process::exit(1);
+        }
+    };
+
+    let notifier = match Notifier::from_env() {
+        Ok(notifier) => Arc::new(notifier),
+        Err(err) => {
+            eprintln!("ERROR! Failed to configure notifications: {err}");
+            process::exit(1);
+        }
+    };
+
+    let cors_config = match CorsConfig::from_env() {
+        Ok(cors_config) => cors_config,
+        Err(err) => {
+            eprintln!("ERROR! Failed to configure CORS: {err}");
+            process::exit(1);
+        }
+    };
+
+    let tls_config = match TlsConfig::from_env() {
+        Ok(tls_config) => tls_config,
+        Err(err) => {
+            eprintln!("ERROR! Failed to configure TLS: {err}");
+            process::exit(1);
+        }
+    };
+
+    let database = connect_postgres_database();
     let app_state = AppState {
-        database: connect_postgres_database(),
+        rate_limiter: Arc::new(RateLimiter::from_env(&database)),
+        report_config: ReportConfig::from_env(),
+        blob_store,
+        attachment_config: AttachmentConfig::from_env(),
+        tenant_config: TenantConfig::from_env(),
+        qr_config: QrConfig::from_env(),
+        guest_report_config: GuestReportConfig::from_env(),
+        session_config: SessionConfig::from_env(),
+        notifier,
+        telemetry_config: TelemetryConfig::from_env(),
+        maintenance_config: MaintenanceConfig::from_env(),
+        admin_config: AdminConfig::from_env(),
+        database,
     };
 
+    let database_connect_attempts = config::env_or("DATABASE_CONNECT_ATTEMPTS", 10);
+    let database_connect_backoff =
+        config::env_duration_ms_or("DATABASE_CONNECT_BACKOFF_MS", Duration::from_millis(500));
+    let shutdown_timeout = Duration::from_secs(config::env_or("SHUTDOWN_TIMEOUT_SECS", 30));
+
+    if let Err(err) = database::wait_for_database(
+        &app_state.database,
+        database_connect_attempts,
+        database_connect_backoff,
+        MAX_DATABASE_CONNECT_BACKOFF,
+    )
+    .await
+    {
+        eprintln!("ERROR! Gave up waiting for the database to become available: {err}");
+        process::exit(1);
+    }
+
+    if let Err(err) = database::MIGRATOR.run(&app_state.database).await {
+        eprintln!("ERROR! Failed to apply database migrations: {err}");
+        process::exit(1);
+    }
+
+    let database_pool = app_state.database.clone();
+    let mut background_jobs = BackgroundJobs::new();
+
+    let rate_limiter = Arc::clone(&app_state.rate_limiter);
+    background_jobs.spawn("rate-limit-prune", move |shutdown| {
+        rate_limiter.prune_periodically(shutdown)
+    });
+
+    let session_database = app_state.database.clone();
+    let session_config = app_state.session_config.clone();
+    background_jobs.spawn("machine-session-expiry", move |shutdown| {
+        session::expire_periodically(session_database, session_config, shutdown)
+    });
+
+    let guest_database = app_state.database.clone();
+    background_jobs.spawn("guest-challenge-sweep", move |shutdown| {
+        guest::sweep_spent_challenges_periodically(guest_database, shutdown)
+    });
+
+    let reservation_database = app_state.database.clone();
+    background_jobs.spawn("waitlist-dispatch", move |shutdown| {
+        reservation::dispatch_periodically(reservation_database, shutdown)
+    });
+
+    let notifier = Arc::clone(&app_state.notifier);
+    let notification_database = app_state.database.clone();
+    background_jobs.spawn("session-notifications", move |shutdown| {
+        notifier.notify_periodically(notification_database, shutdown)
+    });
+
+    let anomaly_database = app_state.database.clone();
+    let anomaly_report_config = app_state.report_config.clone();
+    let anomaly_config = AnomalyConfig::from_env();
+    background_jobs.spawn("anomaly-detection", move |shutdown| {
+        anomaly::detect_periodically(
+            anomaly_database,
+            anomaly_report_config,
+            anomaly_config,
+            shutdown,
+        )
+    });
+
+    let maintenance_database = app_state.database.clone();
+    let maintenance_config = app_state.maintenance_config.clone();
+    background_jobs.spawn("maintenance-scheduling", move |shutdown| {
+        maintenance::schedule_periodically(maintenance_database, maintenance_config, shutdown)
+    });
+
+    let sla_database = app_state.database.clone();
+    let sla_notifier = Arc::clone(&app_state.notifier);
+    let sla_config = SlaConfig::from_env();
+    background_jobs.spawn("sla-escalation", move |shutdown| {
+        sla::escalate_periodically(sla_database, sla_notifier, sla_config, shutdown)
+    });
+
+    #[cfg(feature = "mqtt")]
+    if let Some(mqtt_config) = MqttConfig::from_env() {
+        let mqtt_database = app_state.database.clone();
+        let telemetry_config = app_state.telemetry_config.clone();
+        let report_config = app_state.report_config.clone();
+        background_jobs.spawn("mqtt-ingestion", move |shutdown| {
+            mqtt::ingest(
+                mqtt_config,
+                mqtt_database,
+                telemetry_config,
+                report_config,
+                shutdown,
+            )
+        });
+    }
+
     let http_server = HttpServer::new(move || {
-        App::new()
+        let app = App::new()
+            .wrap(RateLimitPerIp(Arc::clone(&app_state.rate_limiter)))
+            .wrap(cors_config.middleware())
+            .wrap(AccessLog);
+        #[cfg(feature = "opentelemetry")]
+        let app = app.wrap(Condition::new(tracing_enabled, telemetry::Tracing));
+
+        app.wrap(RequestIdentifier)
             .service(ping)
-            .service(
-                web::scope("/machine")
-                    .service(machine::get_all_machines)
-                    .service(machine::get_machine)
-                    .service(machine::add_machine)
-                    .service(machine::delete_machine)
-                    .service(machine::get_machine_reports)
-                    .service(machine::get_machine_archived_reports),
-            )
-            .service(
-                web::scope("/room")
-                    .service(room::get_all_rooms)
-                    .service(room::get_room)
-                    .service(room::add_room)
-                    .service(room::delete_room)
-                    .service(room::get_room_machines)
-                    .service(room::get_room_reports)
-                    .service(room::get_room_archived_reports),
-            )
-            .service(
-                web::scope("/user")
-                    .service(user::get_all_users)
-                    .service(user::get_user)
-                    .service(user::add_user)
-                    .service(user::delete_user)
-                    .service(user::get_user_reports)
-                    .service(user::get_user_archived_reports),
-            )
-            .service(
-                web::scope("/report")
-                    .service(report::get_all_reports)
-                    .service(report::get_all_archived_reports)
-                    .service(report::get_report)
-                    .service(report::submit_report)
-                    .service(report::delete_report)
-                    .service(report::archive_report),
-            )
+            .configure(routes::configure)
             .service(SwaggerUi::new("/docs/{_:.*}").url("/api-doc/openapi.json", openapi.clone()))
             .app_data(web::Data::new(app_state.clone()))
     });
 
-    let http_server = match http_server.bind(("0.0.0.0", 8080)) {
-        Ok(server) => server,
-        Err(err) => {
-            eprintln!("ERROR! Failed to bind the webserver: {err}");
-            process::exit(1);
+    let http_server = http_server
+        .on_connect(tls::store_client_certificate)
+        .shutdown_timeout(shutdown_timeout.as_secs());
+
+    let (http_server, redirect_server) = match tls_config {
+        None => match http_server.bind(("0.0.0.0", 8080)) {
+            Ok(server) => (server, None),
+            Err(err) => {
+                eprintln!("ERROR! Failed to bind the webserver: {err}");
+                process::exit(1);
+            }
+        },
+        Some(tls_config) => {
+            let resolver =
+                match ReloadingCertResolver::new(&tls_config.cert_path, &tls_config.key_path) {
+                    Ok(resolver) => Arc::new(resolver),
+                    Err(err) => {
+                        eprintln!("ERROR! Failed to load the TLS certificate: {err}");
+                        process::exit(1);
+                    }
+                };
+
+            let server_config = match tls_config.server_config(Arc::clone(&resolver)) {
+                Ok(server_config) => server_config,
+                Err(err) => {
+                    eprintln!("ERROR! Failed to configure TLS: {err}");
+                    process::exit(1);
+                }
+            };
+
+            let reload_interval = tls_config.reload_interval;
+            background_jobs.spawn("tls-certificate-reload", move |shutdown| {
+                resolver.watch(reload_interval, shutdown)
+            });
+
+            let http_server = match http_server
+                .bind_rustls_0_22(("0.0.0.0", tls_config.https_port), server_config)
+            {
+                Ok(server) => server,
+                Err(err) => {
+                    eprintln!("ERROR! Failed to bind the webserver: {err}");
+                    process::exit(1);
+                }
+            };
+
+            let redirect_server = match (tls_config.redirect_http, tls_config.public_host) {
+                (false, _) | (true, None) => None,
+                (true, Some(host)) => {
+                    let redirect = HttpsRedirect {
+                        host,
+                        port: tls_config.https_port,
+                    };
+                    let redirect_server = HttpServer::new(move || {
+                        App::new()
+                            .app_data(web::Data::new(redirect.clone()))
+                            .default_service(web::to(tls::redirect_to_https))
+                    })
+                    .shutdown_timeout(shutdown_timeout.as_secs());
+
+                    match redirect_server.bind(("0.0.0.0", 8080)) {
+                        Ok(server) => Some(server),
+                        Err(err) => {
+                            eprintln!("ERROR! Failed to bind the HTTP redirect server: {err}");
+                            process::exit(1);
+                        }
+                    }
+                }
+            };
+
+            (http_server, redirect_server)
         }
     };
 
-    match http_server.run().await {
-        Ok(_) => (),
-        Err(err) => {
-            eprintln!("ERROR! Gave up waiting for HttpServer to run: {err}");
-            process::exit(1);
+    let result = match redirect_server {
+        None => http_server.run().await,
+        Some(redirect_server) => {
+            let (result, redirect_result) =
+                future::join(http_server.run(), redirect_server.run()).await;
+            result.and(redirect_result)
         }
     };
+
+    if let Err(err) = result {
+        eprintln!("ERROR! Gave up waiting for HttpServer to run: {err}");
+        process::exit(1);
+    }
+
+    log::info!("HTTP server stopped, waiting for background jobs to finish");
+    background_jobs.shutdown(shutdown_timeout).await;
+
+    database_pool.close().await;
+    log::info!("Database connections closed, shutdown complete");
+
+    #[cfg(feature = "opentelemetry")]
+    if let Some(tracer_provider) = tracer_provider {
+        if let Err(err) = tracer_provider.shutdown() {
+            eprintln!("WARNING: Failed to flush pending traces: {err}");
+        }
+    }
 }
@@ -0,0 +1,772 @@
+use std::time::Duration;
+
+use actix_web::{
+    delete, get, post,
+    web::{Data, Json, Path, Query},
+    HttpResponse, Responder,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{query, query_as, Pool, Postgres};
+use time::{util::days_in_year_month, Date, Month};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    background::ShutdownSignal,
+    config,
+    error::{constraint_violation, database_error, Violation},
+    machine,
+    models::{
+        iso_date, now, AppState, MachineType, MaintenancePlan, MaintenanceTask,
+        MaintenanceTaskStatus, RecurrenceUnit,
+    },
+    report,
+    tenant::Tenant,
+    user,
+};
+
+/// How often tasks are scheduled for plans and machines which need them.
+const SCHEDULE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Clone)]
+pub struct MaintenanceConfig {
+    /// How far ahead tasks are scheduled.
+    pub horizon: Duration,
+}
+
+impl MaintenanceConfig {
+    /// Parses the maintenance configuration from the environment.
+    pub fn from_env() -> MaintenanceConfig {
+        MaintenanceConfig {
+            horizon: Duration::from_secs(
+                config::env_or("MAINTENANCE_HORIZON_DAYS", 30) * 24 * 60 * 60,
+            ),
+        }
+    }
+}
+
+/// A plan covering either every machine of `machine_type`, or the machine `machine_id` in `room_id`.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct MaintenancePlanSubmission {
+    name: String,
+    description: Option<String>,
+    #[serde(default)]
+    machine_type: Option<MachineType>,
+    #[serde(default)]
+    room_id: Option<i32>,
+    #[serde(default)]
+    machine_id: Option<String>,
+    recurrence_interval: i32,
+    recurrence_unit: RecurrenceUnit,
+    #[serde(with = "iso_date")]
+    start_date: Date,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct TaskCompletion {
+    technician_username: String,
+    notes: Option<String>,
+    /// Whether to archive the machine's open reports filed before the task was completed.
+    #[serde(default)]
+    archive_reports: bool,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct CompletedTask {
+    task: MaintenanceTask,
+    archived_report_ids: Vec<i32>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct UpcomingQuery {
+    /// How many days ahead to look, defaulting to how far ahead tasks are scheduled.
+    days: Option<u32>,
+    room_id: Option<i32>,
+}
+
+/// Filters applied when listing maintenance tasks.
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TaskQuery {
+    room_id: Option<i32>,
+    machine_id: Option<String>,
+    status: Option<MaintenanceTaskStatus>,
+}
+
+/// Adds `months` to `date`, moving to the end of the month if it is shorter than `date`'s day.
+fn add_months(date: Date, months: i32) -> Option<Date> {
+    let months = date.year() * 12 + date.month() as i32 - 1 + months;
+    let year = months.div_euclid(12);
+    let month = Month::try_from((months.rem_euclid(12) + 1) as u8).ok()?;
+    let day = date.day().min(days_in_year_month(year, month));
+    Date::from_calendar_date(year, month, day).ok()
+}
+
+/// The date the `index`th task of a plan is due, counting from its start date.
+fn occurrence(start_date: Date, interval: i32, unit: RecurrenceUnit, index: i32) -> Option<Date> {
+    let steps = interval.checked_mul(index)?;
+    match unit {
+        RecurrenceUnit::Day => start_date.checked_add(time::Duration::days(steps.into())),
+        RecurrenceUnit::Week => start_date.checked_add(time::Duration::weeks(steps.into())),
+        RecurrenceUnit::Month => add_months(start_date, steps),
+        RecurrenceUnit::Year => add_months(start_date, steps.checked_mul(12)?),
+    }
+}
+
+/// Schedules the tasks of active plans due within `horizon`, for `plan_id` only if it is set,
+/// and returns how many were scheduled.
+///
+/// Each machine's tasks continue from its latest task, or from today for machines which have
+/// none, so that plans starting in the past do not schedule a backlog of overdue tasks.
+pub async fn schedule_tasks(
+    database: &Pool<Postgres>,
+    horizon: Duration,
+    plan_id: Option<i32>,
+) -> Result<u64, sqlx::Error> {
+    let today = now().date();
+    let until = today + horizon;
+
+    let targets = query!(
+        r#"
+        SELECT
+            plan.id AS plan_id,
+            plan.recurrence_interval,
+            plan.recurrence_unit AS "recurrence_unit: RecurrenceUnit",
+            plan.start_date,
+            machine.room_id,
+            machine.machine_id,
+            (
+                SELECT MAX(due_date)
+                FROM maintenance_task
+                WHERE plan_id = plan.id
+                    AND room_id = machine.room_id
+                    AND machine_id = machine.machine_id
+            ) AS latest_due_date
+        FROM maintenance_plan plan
+        JOIN room ON room.organization_id = plan.organization_id
+        JOIN machine ON machine.room_id = room.id
+            AND (
+                machine.type = plan.machine_type
+                OR (machine.room_id = plan.room_id AND machine.machine_id = plan.machine_id)
+            )
+        WHERE plan.active AND ($1::INTEGER IS NULL OR plan.id = $1)
+        "#,
+        plan_id
+    )
+    .fetch_all(database)
+    .await?;
+
+    let mut scheduled = 0;
+
+    for target in targets {
+        let after = target
+            .latest_due_date
+            .unwrap_or(today - time::Duration::days(1));
+
+        for index in 0.. {
+            let Some(due_date) = occurrence(
+                target.start_date,
+                target.recurrence_interval,
+                target.recurrence_unit,
+                index,
+            ) else {
+                break;
+            };
+
+            if due_date > until {
+                break;
+            }
+
+            if due_date <= after {
+                continue;
+            }
+
+            scheduled += query!(
+                r#"
+                INSERT INTO maintenance_task (plan_id, room_id, machine_id, due_date, organization_id)
+                VALUES ($1, $2, $3, $4, (SELECT organization_id FROM maintenance_plan WHERE id = $1))
+                ON CONFLICT DO NOTHING
+                "#,
+                target.plan_id,
+                target.room_id,
+                target.machine_id,
+                due_date
+            )
+            .execute(database)
+            .await?
+            .rows_affected();
+        }
+    }
+
+    Ok(scheduled)
+}
+
+pub async fn schedule_periodically(
+    database: Pool<Postgres>,
+    config: MaintenanceConfig,
+    mut shutdown: ShutdownSignal,
+) {
+    while shutdown.sleep(SCHEDULE_INTERVAL).await {
+        match schedule_tasks(&database, config.horizon, None).await {
+            Ok(0) => {}
+            Ok(scheduled) => log::info!("Scheduled {scheduled} maintenance tasks"),
+            Err(err) => log::error!("Failed to schedule maintenance tasks: {err}"),
+        }
+    }
+}
+
+#[utoipa::path(
+    context_path = "/maintenance",
+    params(Tenant),
+    responses(
+        (status = 200, description = "List of all maintenance plans, including retired ones", body = Vec<MaintenancePlan>, example = json!([{
+            "plan_id": 1,
+            "name": "Lint trap check",
+            "description": "Clean the lint trap and check the duct",
+            "machine_type": "Dryer",
+            "room_id": null,
+            "machine_id": null,
+            "recurrence_interval": 1,
+            "recurrence_unit": "Month",
+            "start_date": "2023-01-01",
+            "active": true,
+            "created_time": "2023-01-01T12:00:00"
+        }])),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[get("/plans")]
+async fn get_maintenance_plans(data: Data<AppState>, tenant: Tenant) -> impl Responder {
+    match query_as!(
+        MaintenancePlan,
+        r#"
+        SELECT
+            id AS plan_id,
+            name,
+            description,
+            machine_type AS "machine_type: MachineType",
+            room_id,
+            machine_id,
+            recurrence_interval,
+            recurrence_unit AS "recurrence_unit: RecurrenceUnit",
+            start_date,
+            active,
+            created_time
+        FROM maintenance_plan
+        WHERE organization_id = $1
+        ORDER BY id
+        "#,
+        tenant.organization_id
+    )
+    .fetch_all(&data.database)
+    .await
+    {
+        Ok(plans) => HttpResponse::Ok().json(plans),
+        Err(err) => database_error("fetch maintenance plans", err),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/admin",
+    params(Tenant),
+    request_body(content = MaintenancePlanSubmission, content_type = "application/json", example = json!({
+        "name": "Belt replacement",
+        "description": null,
+        "room_id": 1,
+        "machine_id": "A",
+        "recurrence_interval": 1,
+        "recurrence_unit": "Year",
+        "start_date": "2023-06-01"
+    })),
+    responses(
+        (status = 201, description = "The plan was added and its upcoming tasks scheduled", body = MaintenancePlan, example = json!({
+            "plan_id": 2,
+            "name": "Belt replacement",
+            "description": null,
+            "machine_type": null,
+            "room_id": 1,
+            "machine_id": "A",
+            "recurrence_interval": 1,
+            "recurrence_unit": "Year",
+            "start_date": "2023-06-01",
+            "active": true,
+            "created_time": "2023-01-01T12:00:00"
+        })),
+        (status = 400, description = "The requested query was invalid"),
+        (status = 401, description = "No valid admin credentials were given"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[post("/maintenance-plans")]
+async fn add_maintenance_plan(
+    data: Data<AppState>,
+    tenant: Tenant,
+    Json(submission): Json<MaintenancePlanSubmission>,
+) -> impl Responder {
+    match (
+        &submission.machine_type,
+        submission.room_id,
+        &submission.machine_id,
+    ) {
+        (Some(_), None, None) => {}
+        (None, Some(room_id), Some(machine_id)) => {
+            match machine::is_machine_present(&data.database, &tenant, &room_id, machine_id).await {
+                Ok(true) => {}
+                Ok(false) => {
+                    return HttpResponse::BadRequest().json(format!(
+                        "Room id {room_id} does not contain machine id {machine_id}."
+                    ))
+                }
+                Err(err) => return database_error("check machine presence", err),
+            }
+        }
+        _ => {
+            return HttpResponse::BadRequest()
+                .json("A plan must cover either a machine type, or a room id and machine id.")
+        }
+    }
+
+    let plan = match query_as!(
+        MaintenancePlan,
+        r#"
+        INSERT INTO maintenance_plan (
+            organization_id, name, description, machine_type, room_id, machine_id,
+            recurrence_interval, recurrence_unit, start_date, created_time
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        RETURNING
+            id AS plan_id,
+            name,
+            description,
+            machine_type AS "machine_type: MachineType",
+            room_id,
+            machine_id,
+            recurrence_interval,
+            recurrence_unit AS "recurrence_unit: RecurrenceUnit",
+            start_date,
+            active,
+            created_time
+        "#,
+        tenant.organization_id,
+        submission.name,
+        submission.description,
+        submission.machine_type as Option<MachineType>,
+        submission.room_id,
+        submission.machine_id,
+        submission.recurrence_interval,
+        submission.recurrence_unit as RecurrenceUnit,
+        submission.start_date,
+        now()
+    )
+    .fetch_one(&data.database)
+    .await
+    {
+        Ok(plan) => plan,
+        Err(err) => {
+            return match constraint_violation(&err) {
+                Some(Violation::Check) => {
+                    HttpResponse::BadRequest().json("The recurrence interval must be positive.")
+                }
+                Some(Violation::ForeignKey) => HttpResponse::BadRequest()
+                    .json("The machine type does not exist, or the machine was removed."),
+                _ => database_error("insert maintenance plan", err),
+            }
+        }
+    };
+
+    match schedule_tasks(
+        &data.database,
+        data.maintenance_config.horizon,
+        Some(plan.plan_id),
+    )
+    .await
+    {
+        Ok(_) => HttpResponse::Created().json(plan),
+        Err(err) => database_error("schedule maintenance tasks", err),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/admin",
+    params(Tenant),
+    responses(
+        (status = 200, description = "The plan was retired and its scheduled tasks removed, its completed tasks are kept", body = MaintenancePlan, example = json!({
+            "plan_id": 1,
+            "name": "Lint trap check",
+            "description": "Clean the lint trap and check the duct",
+            "machine_type": "Dryer",
+            "room_id": null,
+            "machine_id": null,
+            "recurrence_interval": 1,
+            "recurrence_unit": "Month",
+            "start_date": "2023-01-01",
+            "active": false,
+            "created_time": "2023-01-01T12:00:00"
+        })),
+        (status = 401, description = "No valid admin credentials were given"),
+        (status = 404, description = "The requested plan was not found"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[delete("/maintenance-plans/{plan_id}")]
+async fn retire_maintenance_plan(
+    data: Data<AppState>,
+    tenant: Tenant,
+    path: Path<i32>,
+) -> impl Responder {
+    let plan_id = path.into_inner();
+
+    let mut transaction = match data.database.begin().await {
+        Ok(transaction) => transaction,
+        Err(err) => return database_error("begin maintenance plan retirement", err),
+    };
+
+    let plan = match query_as!(
+        MaintenancePlan,
+        r#"
+        UPDATE maintenance_plan
+        SET active = false
+        WHERE id = $1 AND organization_id = $2
+        RETURNING
+            id AS plan_id,
+            name,
+            description,
+            machine_type AS "machine_type: MachineType",
+            room_id,
+            machine_id,
+            recurrence_interval,
+            recurrence_unit AS "recurrence_unit: RecurrenceUnit",
+            start_date,
+            active,
+            created_time
+        "#,
+        plan_id,
+        tenant.organization_id
+    )
+    .fetch_optional(&mut transaction)
+    .await
+    {
+        Ok(Some(plan)) => plan,
+        Ok(None) => {
+            return HttpResponse::NotFound()
+                .json(format!("Maintenance plan id {plan_id} was not found."))
+        }
+        Err(err) => return database_error("retire maintenance plan", err),
+    };
+
+    if let Err(err) = query!(
+        r#"
+        DELETE FROM maintenance_task
+        WHERE plan_id = $1 AND status = 'scheduled'
+        "#,
+        plan_id
+    )
+    .execute(&mut transaction)
+    .await
+    {
+        return database_error("remove scheduled maintenance tasks", err);
+    }
+
+    match transaction.commit().await {
+        Ok(()) => HttpResponse::Ok().json(plan),
+        Err(err) => database_error("commit maintenance plan retirement", err),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/maintenance",
+    params(Tenant, UpcomingQuery),
+    responses(
+        (status = 200, description = "List of scheduled tasks due within the requested days, overdue ones included, soonest first", body = Vec<MaintenanceTask>, example = json!([{
+            "task_id": 1,
+            "plan_id": 1,
+            "plan_name": "Lint trap check",
+            "room_id": 1,
+            "machine_id": "A",
+            "due_date": "2023-01-01",
+            "status": "Scheduled",
+            "overdue": true,
+            "completed_time": null,
+            "technician_username": null,
+            "notes": null
+        }])),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[get("/upcoming")]
+async fn get_upcoming_maintenance(
+    data: Data<AppState>,
+    tenant: Tenant,
+    Query(upcoming_query): Query<UpcomingQuery>,
+) -> impl Responder {
+    let today = now().date();
+    let until = match upcoming_query.days {
+        Some(days) => today + time::Duration::days(days.into()),
+        None => today + data.maintenance_config.horizon,
+    };
+
+    match query_as!(
+        MaintenanceTask,
+        r#"
+        SELECT
+            task.id AS task_id,
+            task.plan_id,
+            plan.name AS plan_name,
+            task.room_id,
+            task.machine_id,
+            task.due_date,
+            task.status AS "status: MaintenanceTaskStatus",
+            task.due_date < $1 AS "overdue!",
+            task.completed_time,
+            task.technician_username,
+            task.notes
+        FROM maintenance_task task
+        JOIN maintenance_plan plan ON plan.id = task.plan_id
+        WHERE task.status = 'scheduled'
+            AND task.due_date <= $2
+            AND ($3::INTEGER IS NULL OR task.room_id = $3)
+            AND plan.organization_id = $4
+        ORDER BY task.due_date, task.room_id, task.machine_id
+        "#,
+        today,
+        until,
+        upcoming_query.room_id,
+        tenant.organization_id
+    )
+    .fetch_all(&data.database)
+    .await
+    {
+        Ok(tasks) => HttpResponse::Ok().json(tasks),
+        Err(err) => database_error("fetch upcoming maintenance", err),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/maintenance",
+    params(Tenant, TaskQuery),
+    responses(
+        (status = 200, description = "List of maintenance tasks matching the filters, latest due first", body = Vec<MaintenanceTask>, example = json!([{
+            "task_id": 1,
+            "plan_id": 1,
+            "plan_name": "Lint trap check",
+            "room_id": 1,
+            "machine_id": "A",
+            "due_date": "2023-01-01",
+            "status": "Completed",
+            "overdue": false,
+            "completed_time": "2023-01-02T09:30:00",
+            "technician_username": "admin",
+            "notes": "Duct was partly blocked"
+        }])),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[get("/tasks")]
+async fn get_maintenance_tasks(
+    data: Data<AppState>,
+    tenant: Tenant,
+    Query(task_query): Query<TaskQuery>,
+) -> impl Responder {
+    match query_as!(
+        MaintenanceTask,
+        r#"
+        SELECT
+            task.id AS task_id,
+            task.plan_id,
+            plan.name AS plan_name,
+            task.room_id,
+            task.machine_id,
+            task.due_date,
+            task.status AS "status: MaintenanceTaskStatus",
+            (task.status = 'scheduled' AND task.due_date < $1) AS "overdue!",
+            task.completed_time,
+            task.technician_username,
+            task.notes
+        FROM maintenance_task task
+        JOIN maintenance_plan plan ON plan.id = task.plan_id
+        WHERE ($2::INTEGER IS NULL OR task.room_id = $2)
+            AND ($3::VARCHAR IS NULL OR task.machine_id = $3)
+            AND ($4::maintenance_task_status IS NULL OR task.status = $4)
+            AND plan.organization_id = $5
+        ORDER BY task.due_date DESC, task.room_id, task.machine_id
+        "#,
+        now().date(),
+        task_query.room_id,
+        task_query.machine_id,
+        task_query.status as Option<MaintenanceTaskStatus>,
+        tenant.organization_id
+    )
+    .fetch_all(&data.database)
+    .await
+    {
+        Ok(tasks) => HttpResponse::Ok().json(tasks),
+        Err(err) => database_error("fetch maintenance tasks", err),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/admin",
+    params(Tenant),
+    request_body(content = TaskCompletion, content_type = "application/json", example = json!({
+        "technician_username": "admin",
+        "notes": "Duct was partly blocked",
+        "archive_reports": true
+    })),
+    responses(
+        (status = 200, description = "The task was completed", body = CompletedTask, example = json!({
+            "task": {
+                "task_id": 1,
+                "plan_id": 1,
+                "plan_name": "Lint trap check",
+                "room_id": 1,
+                "machine_id": "A",
+                "due_date": "2023-01-01",
+                "status": "Completed",
+                "overdue": false,
+                "completed_time": "2023-01-02T09:30:00",
+                "technician_username": "admin",
+                "notes": "Duct was partly blocked"
+            },
+            "archived_report_ids": [3]
+        })),
+        (status = 400, description = "The requested query was invalid or the completing user is neither a technician nor an admin"),
+        (status = 401, description = "No valid admin credentials were given"),
+        (status = 404, description = "The requested task was not found"),
+        (status = 409, description = "The task was already completed"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[post("/maintenance-tasks/{task_id}/complete")]
+async fn complete_maintenance_task(
+    data: Data<AppState>,
+    tenant: Tenant,
+    path: Path<i32>,
+    Json(completion): Json<TaskCompletion>,
+) -> impl Responder {
+    let task_id = path.into_inner();
+
+    match user::is_technician(&data.database, &tenant, &completion.technician_username).await {
+        Ok(Some(true)) => {}
+        Ok(Some(false)) => {
+            return HttpResponse::BadRequest().json(format!(
+                "The user {} is neither a technician nor an admin.",
+                completion.technician_username
+            ))
+        }
+        Ok(None) => {
+            return HttpResponse::BadRequest().json(format!(
+                "The user {} was not found.",
+                completion.technician_username
+            ))
+        }
+        Err(err) => return database_error("check technician", err),
+    }
+
+    let mut transaction = match data.database.begin().await {
+        Ok(transaction) => transaction,
+        Err(err) => return database_error("begin maintenance task completion", err),
+    };
+
+    let task = match query!(
+        r#"
+        SELECT room_id, machine_id, status AS "status: MaintenanceTaskStatus"
+        FROM maintenance_task
+        WHERE id = $1
+            AND plan_id IN (SELECT id FROM maintenance_plan WHERE organization_id = $2)
+        FOR UPDATE
+        "#,
+        task_id,
+        tenant.organization_id
+    )
+    .fetch_optional(&mut transaction)
+    .await
+    {
+        Ok(Some(task)) => task,
+        Ok(None) => {
+            return HttpResponse::NotFound()
+                .json(format!("Maintenance task id {task_id} was not found."))
+        }
+        Err(err) => return database_error("fetch maintenance task", err),
+    };
+
+    if task.status == MaintenanceTaskStatus::Completed {
+        return HttpResponse::Conflict().json(format!(
+            "Maintenance task id {task_id} was already completed."
+        ));
+    }
+
+    let completed_time = now();
+
+    let task = match query_as!(
+        MaintenanceTask,
+        r#"
+        WITH task AS (
+            UPDATE maintenance_task
+            SET status = 'completed', completed_time = $2, technician_username = $3, notes = $4
+            WHERE id = $1
+            RETURNING *
+        )
+        SELECT
+            task.id AS task_id,
+            task.plan_id,
+            plan.name AS plan_name,
+            task.room_id,
+            task.machine_id,
+            task.due_date,
+            task.status AS "status: MaintenanceTaskStatus",
+            false AS "overdue!",
+            task.completed_time,
+            task.technician_username,
+            task.notes
+        FROM task
+        JOIN maintenance_plan plan ON plan.id = task.plan_id
+        "#,
+        task_id,
+        completed_time,
+        completion.technician_username,
+        completion.notes
+    )
+    .fetch_one(&mut transaction)
+    .await
+    {
+        Ok(completed_task) => completed_task,
+        Err(err) => return database_error("complete maintenance task", err),
+    };
+
+    let mut archived_report_ids = Vec::new();
+
+    if completion.archive_reports {
+        let report_ids = match query!(
+            r#"
+            SELECT id
+            FROM report
+            WHERE room_id = $1 AND machine_id = $2 AND archived = false AND time <= $3
+            "#,
+            task.room_id,
+            task.machine_id,
+            completed_time
+        )
+        .fetch_all(&mut transaction)
+        .await
+        {
+            Ok(reports) => reports.into_iter().map(|report| report.id),
+            Err(err) => return database_error("fetch open reports", err),
+        };
+
+        for report_id in report_ids {
+            match report::archive(&mut transaction, &tenant, &report_id).await {
+                Ok(Some(report)) => archived_report_ids.push(report.report_id),
+                Ok(None) => {}
+                Err(err) => return database_error("archive report", err),
+            }
+        }
+    }
+
+    match transaction.commit().await {
+        Ok(()) => HttpResponse::Ok().json(CompletedTask {
+            task,
+            archived_report_ids,
+        }),
+        Err(err) => database_error("commit maintenance task completion", err),
+    }
+}
@@ -1,11 +1,73 @@
+use std::sync::Arc;
+
 use serde::{Deserialize, Serialize};
-use sqlx::{Pool, Postgres, Type};
-use time::PrimitiveDateTime;
+use sqlx::{types::Json, Pool, Postgres, Type};
+use time::{Date, OffsetDateTime, PrimitiveDateTime, Time};
 use utoipa::ToSchema;
 
+use crate::{
+    admin::AdminConfig, attachment::AttachmentConfig, blob_store::BlobStore,
+    guest::GuestReportConfig, machine_telemetry::TelemetryConfig, maintenance::MaintenanceConfig,
+    notification::Notifier, qr::QrConfig, rate_limit::RateLimiter, report::ReportConfig,
+    session::SessionConfig, tenant::TenantConfig,
+};
+
 #[derive(Clone)]
 pub struct AppState {
     pub database: Pool<Postgres>,
+    pub rate_limiter: Arc<RateLimiter>,
+    pub report_config: ReportConfig,
+    pub blob_store: Arc<BlobStore>,
+    pub attachment_config: AttachmentConfig,
+    pub tenant_config: TenantConfig,
+    pub qr_config: QrConfig,
+    pub guest_report_config: GuestReportConfig,
+    pub session_config: SessionConfig,
+    pub notifier: Arc<Notifier>,
+    pub telemetry_config: TelemetryConfig,
+    pub maintenance_config: MaintenanceConfig,
+    pub admin_config: AdminConfig,
+}
+
+/// The current time in UTC, as stored in the `TIMESTAMP` columns of the database.
+pub fn now() -> PrimitiveDateTime {
+    let now = OffsetDateTime::now_utc();
+    PrimitiveDateTime::new(now.date(), now.time())
+}
+
+/// Serializes dates as `YYYY-MM-DD` strings so that clients can submit them as they read them.
+pub mod iso_date {
+    time::serde::format_description!(format, Date, "[year]-[month]-[day]");
+
+    pub use format::{deserialize, serialize};
+
+    pub mod option {
+        pub use super::format::option::{deserialize, serialize};
+    }
+}
+
+/// Serializes timestamps as `YYYY-MM-DDTHH:MM:SS` strings in UTC, for times clients submit.
+pub mod iso_datetime {
+    time::serde::format_description!(
+        format,
+        PrimitiveDateTime,
+        "[year]-[month]-[day]T[hour]:[minute]:[second]"
+    );
+
+    pub use format::{deserialize, serialize};
+
+    pub mod option {
+        pub use super::format::option::{deserialize, serialize};
+    }
+}
+
+/// Serializes times of day as `HH:MM` strings.
+pub mod iso_time {
+    time::serde::format_description!(format, Time, "[hour]:[minute]");
+
+    pub mod option {
+        pub use super::format::option::{deserialize, serialize};
+    }
 }
 
 #[derive(Serialize, Deserialize, ToSchema)]
@@ -13,13 +75,51 @@ pub struct Machine {
     pub room_id: i32,
     pub machine_id: String,
     pub machine_type: MachineType,
+    pub manufacturer: Option<String>,
+    pub model: Option<String>,
+    pub serial_number: Option<String>,
+    #[serde(default, with = "iso_date::option")]
+    pub install_date: Option<Date>,
+    #[serde(default, with = "iso_date::option")]
+    pub warranty_expiry: Option<Date>,
+    pub capacity_kg: Option<f32>,
+    pub payment_type: Option<PaymentType>,
+    pub notes: Option<String>,
+}
+
+/// The name of a [MachineTypeDefinition], such as `Washer` or `Dryer`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Type, ToSchema)]
+#[serde(transparent)]
+#[sqlx(transparent)]
+#[schema(example = "Washer")]
+pub struct MachineType(pub String);
+
+/// An admin-managed kind of machine.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct MachineTypeDefinition {
+    pub name: MachineType,
+    pub display_name: String,
+    pub icon: Option<String>,
+    /// Inactive types are kept for existing machines, but new machines cannot use them.
+    pub active: bool,
 }
 
 #[derive(Serialize, Deserialize, Type, ToSchema)]
-#[sqlx(type_name = "machine_type", rename_all = "lowercase")]
-pub enum MachineType {
-    Washer,
-    Dryer,
+#[sqlx(type_name = "payment_type", rename_all = "snake_case")]
+pub enum PaymentType {
+    Coin,
+    Card,
+    CoinAndCard,
+    Free,
+}
+
+/// Where a guest report is in the moderation queue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type, ToSchema)]
+#[sqlx(type_name = "moderation_status", rename_all = "snake_case")]
+pub enum ModerationStatus {
+    Pending,
+    Approved,
+    Rejected,
 }
 
 #[derive(Serialize, Deserialize, ToSchema)]
@@ -27,12 +127,40 @@ pub struct Room {
     pub room_id: i32,
     pub name: String,
     pub description: Option<String>,
+    pub building_id: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct Building {
+    pub building_id: i32,
+    pub site_id: Option<i32>,
+    pub name: String,
+    pub address: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+}
+
+/// A tenant of the deployment, such as a university, selected by its slug.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct Organization {
+    pub organization_id: i32,
+    pub slug: String,
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct Site {
+    pub site_id: i32,
+    pub name: String,
+    pub description: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, ToSchema)]
 pub struct User {
     pub username: String,
     pub admin: bool,
+    /// Whether the user completes maintenance tasks.
+    pub technician: bool,
 }
 
 #[derive(Serialize, Deserialize, ToSchema)]
@@ -40,17 +168,398 @@ pub struct Report {
     pub report_id: i32,
     pub room_id: i32,
     pub machine_id: String,
-    pub reporter_username: String,
+    /// `None` for reports submitted by guests without an account.
+    pub reporter_username: Option<String>,
     pub report_type: ReportType,
     pub time: PrimitiveDateTime,
     pub description: Option<String>,
     pub archived: bool,
+    /// Whether the report was filed by an integration or background job rather than a person.
+    pub automated: bool,
+    /// How many other users have confirmed this report instead of filing a duplicate.
+    pub confirmation_count: i64,
+    pub comment_count: i64,
+    #[schema(value_type = Vec<AttachmentMetadata>)]
+    pub attachments: Json<Vec<AttachmentMetadata>>,
+}
+
+/// A report submitted without an account, which becomes a [Report] once approved by a moderator.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct GuestReport {
+    pub guest_report_id: i32,
+    pub room_id: i32,
+    pub machine_id: String,
+    pub report_type: ReportType,
+    pub description: Option<String>,
+    pub contact_email: Option<String>,
+    pub client_ip: String,
+    pub time: PrimitiveDateTime,
+    pub status: ModerationStatus,
+    pub moderated_time: Option<PrimitiveDateTime>,
+    /// The report created when this guest report was approved.
+    pub report_id: Option<i32>,
+}
+
+/// Why a [MachineSession] ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type, ToSchema)]
+#[sqlx(type_name = "session_end_reason", rename_all = "snake_case")]
+pub enum SessionEndReason {
+    /// The cycle was reported finished.
+    Finished,
+    /// Nobody reported the cycle finished, so it was ended some time after its expected end.
+    Expired,
+}
+
+/// A cycle run on a machine.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct MachineSession {
+    pub session_id: i32,
+    pub room_id: i32,
+    pub machine_id: String,
+    /// `None` for sessions started by a sensor integration.
+    pub username: Option<String>,
+    pub start_time: PrimitiveDateTime,
+    pub expected_end_time: PrimitiveDateTime,
+    pub end_time: Option<PrimitiveDateTime>,
+    pub end_reason: Option<SessionEndReason>,
+}
+
+/// Where a [WaitlistEntry] is in the queue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type, ToSchema)]
+#[sqlx(type_name = "waitlist_status", rename_all = "snake_case")]
+pub enum WaitlistStatus {
+    Waiting,
+    /// A machine was offered to the entry and is held for it until the claim expires.
+    Claimed,
+    /// A session was started on the claimed machine.
+    Used,
+    Expired,
+    Cancelled,
+}
+
+/// A user waiting for the next free machine of a type in a room.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct WaitlistEntry {
+    pub entry_id: i32,
+    pub room_id: i32,
+    pub machine_type: MachineType,
+    pub username: String,
+    #[serde(with = "iso_datetime")]
+    pub join_time: PrimitiveDateTime,
+    pub status: WaitlistStatus,
+    /// The machine held for the entry while it is claimed.
+    pub machine_id: Option<String>,
+    #[serde(with = "iso_datetime::option")]
+    pub claim_expires_time: Option<PrimitiveDateTime>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type, ToSchema)]
+#[sqlx(type_name = "reservation_status", rename_all = "snake_case")]
+pub enum ReservationStatus {
+    Booked,
+    /// A session was started on the machine by the user who booked it.
+    CheckedIn,
+    Cancelled,
+    /// The user did not start a session soon enough after the reservation began.
+    NoShow,
+}
+
+/// A machine booked by a user for a time slot.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct Reservation {
+    pub reservation_id: i32,
+    pub room_id: i32,
+    pub machine_id: String,
+    pub username: String,
+    #[serde(with = "iso_datetime")]
+    pub start_time: PrimitiveDateTime,
+    #[serde(with = "iso_datetime")]
+    pub end_time: PrimitiveDateTime,
+    pub status: ReservationStatus,
+    #[serde(with = "iso_datetime")]
+    pub created_time: PrimitiveDateTime,
+}
+
+/// An organization's limits on waitlists and reservations.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ReservationPolicy {
+    /// How many upcoming reservations a user may hold at once.
+    pub max_active_reservations: i32,
+    pub max_duration_minutes: i32,
+    /// How long a machine offered from the waitlist is held.
+    pub claim_minutes: i32,
+    /// How long after its start a reservation nobody checked in to is released.
+    pub no_show_minutes: i32,
+    /// The start of the daily hours, in UTC, during which no reservation may take place.
+    #[serde(default, with = "iso_time::option")]
+    #[schema(value_type = Option<String>, example = "23:00")]
+    pub blackout_start: Option<Time>,
+    #[serde(default, with = "iso_time::option")]
+    #[schema(value_type = Option<String>, example = "07:00")]
+    pub blackout_end: Option<Time>,
+}
+
+impl Default for ReservationPolicy {
+    fn default() -> Self {
+        ReservationPolicy {
+            max_active_reservations: 2,
+            max_duration_minutes: 120,
+            claim_minutes: 10,
+            no_show_minutes: 15,
+            blackout_start: None,
+            blackout_end: None,
+        }
+    }
+}
+
+/// What a machine is doing according to its telemetry devices, from least to most severe.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, Type, ToSchema,
+)]
+#[sqlx(type_name = "operating_state", rename_all = "snake_case")]
+pub enum OperatingState {
+    Idle,
+    Running,
+    /// The machine drew more power than any normal cycle, or has been running for too long.
+    Fault,
+}
+
+/// A smart plug or sensor publishing readings for a machine.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct TelemetryDevice {
+    pub device_id: String,
+    pub room_id: i32,
+    pub machine_id: String,
+    /// The SHA-256 fingerprint of the client certificate the device may authenticate with.
+    pub certificate_fingerprint: Option<String>,
+    #[serde(with = "iso_datetime")]
+    pub created_time: PrimitiveDateTime,
+}
+
+/// The readings of a machine's devices during one interval, downsampled for storage.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct TelemetrySample {
+    /// The start of the interval.
+    #[serde(with = "iso_datetime")]
+    pub time: PrimitiveDateTime,
+    /// How many readings were taken during the interval.
+    pub sample_count: i32,
+    /// The mean power drawn during the interval.
+    pub power_watts: Option<f32>,
+    pub peak_power_watts: Option<f32>,
+    pub vibration: Option<f32>,
+    pub temperature_celsius: Option<f32>,
+    /// Whether the door was open at any point during the interval.
+    pub door_open: Option<bool>,
+    /// The most severe state shown by a reading during the interval.
+    pub state: Option<OperatingState>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type, ToSchema)]
+#[sqlx(type_name = "recurrence_unit", rename_all = "snake_case")]
+pub enum RecurrenceUnit {
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+/// Maintenance done on a schedule, for every machine of a type or for a single machine.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct MaintenancePlan {
+    pub plan_id: i32,
+    pub name: String,
+    pub description: Option<String>,
+    /// Set for plans covering every machine of a type.
+    pub machine_type: Option<MachineType>,
+    /// Set along with `machine_id` for plans covering a single machine.
+    pub room_id: Option<i32>,
+    pub machine_id: Option<String>,
+    /// Tasks are due every `recurrence_interval` of `recurrence_unit`, counted from `start_date`.
+    pub recurrence_interval: i32,
+    pub recurrence_unit: RecurrenceUnit,
+    #[serde(with = "iso_date")]
+    pub start_date: Date,
+    /// Retired plans no longer schedule tasks.
+    pub active: bool,
+    #[serde(with = "iso_datetime")]
+    pub created_time: PrimitiveDateTime,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type, ToSchema)]
+#[sqlx(type_name = "maintenance_task_status", rename_all = "snake_case")]
+pub enum MaintenanceTaskStatus {
+    Scheduled,
+    Completed,
+}
+
+/// A machine's maintenance due on a date under a [MaintenancePlan].
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct MaintenanceTask {
+    pub task_id: i32,
+    pub plan_id: i32,
+    pub plan_name: String,
+    pub room_id: i32,
+    pub machine_id: String,
+    #[serde(with = "iso_date")]
+    pub due_date: Date,
+    pub status: MaintenanceTaskStatus,
+    /// Whether the task is still scheduled after its due date.
+    pub overdue: bool,
+    #[serde(with = "iso_datetime::option")]
+    pub completed_time: Option<PrimitiveDateTime>,
+    /// The technician who completed the task.
+    pub technician_username: Option<String>,
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type, ToSchema)]
+#[sqlx(type_name = "work_order_status", rename_all = "snake_case")]
+pub enum WorkOrderStatus {
+    Open,
+    InProgress,
+    OnHold,
+    Closed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type, ToSchema)]
+#[sqlx(type_name = "work_order_priority", rename_all = "snake_case")]
+pub enum WorkOrderPriority {
+    Low,
+    Normal,
+    High,
+    Urgent,
+}
+
+/// A repair owned by a technician, covering one or more reports.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct WorkOrder {
+    pub work_order_id: i32,
+    pub title: String,
+    pub description: Option<String>,
+    pub status: WorkOrderStatus,
+    pub priority: WorkOrderPriority,
+    #[serde(with = "iso_date::option")]
+    pub due_date: Option<Date>,
+    /// Whether the work order is still open after its due date.
+    pub overdue: bool,
+    /// The technician the work order is assigned to.
+    pub assignee_username: Option<String>,
+    pub report_ids: Vec<i32>,
+    /// The labour logged against the work order, in total.
+    pub labour_minutes: i64,
+    /// The cost of the parts logged against the work order, leaving out parts with no cost.
+    pub parts_cost_cents: i64,
+    #[serde(with = "iso_datetime")]
+    pub created_time: PrimitiveDateTime,
+    #[serde(with = "iso_datetime::option")]
+    pub closed_time: Option<PrimitiveDateTime>,
+    pub resolution: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct WorkOrderLabour {
+    pub labour_id: i32,
+    pub work_order_id: i32,
+    pub technician_username: Option<String>,
+    pub minutes: i32,
+    pub description: Option<String>,
+    #[serde(with = "iso_datetime")]
+    pub logged_time: PrimitiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct WorkOrderPart {
+    pub part_id: i32,
+    pub work_order_id: i32,
+    pub name: String,
+    pub part_number: Option<String>,
+    pub quantity: i32,
+    pub unit_cost_cents: Option<i32>,
+    #[serde(with = "iso_datetime")]
+    pub logged_time: PrimitiveDateTime,
+}
+
+/// How quickly reports in a room, of a type, or both must be acknowledged and resolved.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct SlaPolicy {
+    pub policy_id: i32,
+    pub name: String,
+    pub room_id: Option<i32>,
+    pub report_type: Option<ReportType>,
+    pub acknowledge_minutes: Option<i32>,
+    pub resolve_minutes: Option<i32>,
+    /// Where breaches are escalated to, by email and by a POST of an `SlaBreach`.
+    pub escalation_email: Option<String>,
+    pub escalation_webhook_url: Option<String>,
+    #[serde(with = "iso_datetime")]
+    pub created_time: PrimitiveDateTime,
 }
 
-#[derive(Debug, Serialize, Deserialize, Type, ToSchema)]
-#[sqlx(type_name = "report_type", rename_all = "lowercase")]
-pub enum ReportType {
-    Operational,
-    Caution,
-    Broken,
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type, ToSchema)]
+#[sqlx(type_name = "sla_target", rename_all = "snake_case")]
+pub enum SlaTarget {
+    Acknowledge,
+    Resolve,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ReportConfirmation {
+    pub report_id: i32,
+    pub reporter_username: String,
+    pub time: PrimitiveDateTime,
+    pub description: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ReportComment {
+    pub comment_id: i32,
+    pub report_id: i32,
+    pub author_username: String,
+    pub body: String,
+    pub time: PrimitiveDateTime,
+    pub edited_time: Option<PrimitiveDateTime>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ReportAttachment {
+    pub attachment_id: i32,
+    pub report_id: i32,
+    pub uploader_username: String,
+    pub file_name: String,
+    pub content_type: String,
+    pub size_bytes: i64,
+    pub width: i32,
+    pub height: i32,
+    pub time: PrimitiveDateTime,
+}
+
+/// The summary of an attachment included with its [Report].
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct AttachmentMetadata {
+    pub attachment_id: i32,
+    pub file_name: String,
+    pub content_type: String,
+    pub size_bytes: i64,
+    pub width: i32,
+    pub height: i32,
+}
+
+/// The name of a [ReportTypeDefinition], such as `Operational`, `Caution` or `Broken`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Type, ToSchema)]
+#[serde(transparent)]
+#[sqlx(transparent)]
+#[schema(example = "Broken")]
+pub struct ReportType(pub String);
+
+/// An admin-managed category of report.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ReportTypeDefinition {
+    pub name: ReportType,
+    pub display_name: String,
+    /// Higher is more severe, `Operational` is 0 and `Broken` is 2.
+    pub severity: i32,
+    pub icon: Option<String>,
+    /// Inactive types are kept for existing reports, but new reports cannot use them.
+    pub active: bool,
 }
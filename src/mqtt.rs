@@ -0,0 +1,230 @@
+use std::time::Duration;
+
+use rumqttc::{AsyncClient, Event, MqttOptions, Packet, Publish, QoS, SubscribeFilter};
+use serde_json::Value;
+use sqlx::{Pool, Postgres};
+
+use crate::{
+    background::ShutdownSignal,
+    config,
+    machine_telemetry::{self, TelemetryConfig, TelemetryReading},
+    report::ReportConfig,
+};
+
+/// How long to wait before reconnecting after the connection to the broker is lost.
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Settings for ingesting telemetry from an MQTT broker, parsed from the environment.
+#[derive(Debug, Clone)]
+pub struct MqttConfig {
+    pub host: String,
+    pub port: u16,
+    pub client_id: String,
+    pub credentials: Option<(String, String)>,
+    /// Topic filters to subscribe to. The level matched by the first `+` of a filter is the
+    /// id of the device which published the message.
+    pub topics: Vec<String>,
+}
+
+impl MqttConfig {
+    /// Parses the MQTT configuration from the environment, returning `None` if no broker is set.
+    pub fn from_env() -> Option<MqttConfig> {
+        let host = std::env::var("MQTT_HOST").ok()?;
+
+        let credentials = match std::env::var("MQTT_USERNAME") {
+            Ok(username) => Some((username, std::env::var("MQTT_PASSWORD").unwrap_or_default())),
+            Err(_) => None,
+        };
+
+        let topics = std::env::var("MQTT_TOPICS")
+            .unwrap_or_else(|_| "laundry/+/power".to_string())
+            .split(',')
+            .map(str::trim)
+            .filter(|topic| !topic.is_empty())
+            .map(String::from)
+            .collect();
+
+        Some(MqttConfig {
+            host,
+            port: config::env_or("MQTT_PORT", 1883),
+            client_id: config::env_or("MQTT_CLIENT_ID", "laundry-api".to_string()),
+            credentials,
+            topics,
+        })
+    }
+
+    fn options(&self) -> MqttOptions {
+        let mut options = MqttOptions::new(&self.client_id, &self.host, self.port);
+        options.set_keep_alive(Duration::from_secs(30));
+        if let Some((username, password)) = &self.credentials {
+            options.set_credentials(username, password);
+        }
+        options
+    }
+
+    /// Finds the id of the device which published to `topic`, using the first filter it matches.
+    pub fn device_id<'a>(&self, topic: &'a str) -> Option<&'a str> {
+        self.topics.iter().find_map(|filter| {
+            let mut levels = topic.split('/');
+            let mut device_id = None;
+
+            for pattern in filter.split('/') {
+                if pattern == "#" {
+                    return Some(device_id.unwrap_or(topic));
+                }
+
+                let level = levels.next()?;
+                match pattern {
+                    "+" => {
+                        device_id.get_or_insert(level);
+                    }
+                    pattern if pattern != level => return None,
+                    _ => {}
+                }
+            }
+
+            match levels.next() {
+                None => Some(device_id.unwrap_or(topic)),
+                Some(_) => None,
+            }
+        })
+    }
+}
+
+/// Reads the power draw in watts from a message, along with the device id if it includes one.
+///
+/// Accepts a bare number, a JSON object with `power_watts` or `power`, and the `SENSOR`
+/// messages of Tasmota smart plugs which nest it under `ENERGY.Power`. Readings which are not
+/// finite, such as `NaN` or `inf`, are refused.
+pub fn parse_payload(payload: &[u8]) -> Option<(f32, Option<String>)> {
+    let payload = std::str::from_utf8(payload).ok()?.trim();
+
+    if let Ok(power_watts) = payload.parse::<f32>() {
+        return power_watts.is_finite().then_some((power_watts, None));
+    }
+
+    let message: Value = serde_json::from_str(payload).ok()?;
+    let power_watts = message
+        .get("power_watts")
+        .or_else(|| message.get("power"))
+        .or_else(|| message.get("ENERGY").and_then(|energy| energy.get("Power")))?
+        .as_f64()?;
+    let device_id = message
+        .get("device_id")
+        .and_then(Value::as_str)
+        .map(String::from);
+
+    let power_watts = power_watts as f32;
+    power_watts.is_finite().then_some((power_watts, device_id))
+}
+
+/// Records the reading in a message published to the broker.
+///
+/// The device is identified by the topic it published to, which the broker can restrict to
+/// each device, so a message naming a different device in its payload is ignored.
+pub async fn handle_publish(
+    publish: Publish,
+    config: &MqttConfig,
+    database: &Pool<Postgres>,
+    telemetry_config: &TelemetryConfig,
+    report_config: &ReportConfig,
+) {
+    let Some((power_watts, payload_device_id)) = parse_payload(&publish.payload) else {
+        log::warn!("Ignored unreadable MQTT message on {}", publish.topic);
+        return;
+    };
+
+    let Some(device_id) = config.device_id(&publish.topic) else {
+        log::warn!(
+            "Ignored MQTT message on {} with no device id",
+            publish.topic
+        );
+        return;
+    };
+
+    if payload_device_id.is_some_and(|payload_device_id| payload_device_id != device_id) {
+        log::warn!(
+            "Ignored MQTT message on {} naming another device than {device_id}",
+            publish.topic
+        );
+        return;
+    }
+
+    let device = match machine_telemetry::find_device(database, device_id).await {
+        Ok(Some(device)) => device,
+        Ok(None) => {
+            log::debug!("Ignored MQTT message from unknown device {device_id}");
+            return;
+        }
+        Err(err) => {
+            log::error!("Failed to look up telemetry device {device_id}: {err}");
+            return;
+        }
+    };
+
+    let reading = TelemetryReading {
+        power_watts: Some(power_watts),
+        ..TelemetryReading::default()
+    };
+
+    if let Err(err) = machine_telemetry::record_readings(
+        database,
+        telemetry_config,
+        report_config,
+        &device,
+        vec![reading],
+    )
+    .await
+    {
+        log::error!("Failed to record telemetry from device {device_id}: {err}");
+    }
+}
+
+/// Subscribes to the configured topics and records the readings published to them until
+/// shutdown, reconnecting whenever the connection to the broker is lost.
+pub async fn ingest(
+    config: MqttConfig,
+    database: Pool<Postgres>,
+    telemetry_config: TelemetryConfig,
+    report_config: ReportConfig,
+    mut shutdown: ShutdownSignal,
+) {
+    let (client, mut event_loop) = AsyncClient::new(config.options(), 16);
+
+    loop {
+        let event = tokio::select! {
+            event = event_loop.poll() => event,
+            _ = shutdown.triggered() => break,
+        };
+
+        match event {
+            Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                log::info!("Connected to MQTT broker {}:{}", config.host, config.port);
+                let filters = config
+                    .topics
+                    .iter()
+                    .map(|topic| SubscribeFilter::new(topic.clone(), QoS::AtLeastOnce));
+                if let Err(err) = client.subscribe_many(filters).await {
+                    log::error!("Failed to subscribe to MQTT topics: {err}");
+                }
+            }
+            Ok(Event::Incoming(Packet::Publish(publish))) => {
+                handle_publish(
+                    publish,
+                    &config,
+                    &database,
+                    &telemetry_config,
+                    &report_config,
+                )
+                .await
+            }
+            Ok(_) => {}
+            Err(err) => {
+                log::warn!("Lost connection to MQTT broker: {err}");
+                if !shutdown.sleep(RECONNECT_DELAY).await {
+                    break;
+                }
+            }
+        }
+    }
+}
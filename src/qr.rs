@@ -0,0 +1,295 @@
+use std::{fmt::Write, io::Cursor};
+
+use actix_web::{
+    get,
+    web::{Data, Path},
+    HttpResponse, Responder,
+};
+use image::{GrayImage, ImageFormat, Luma};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use qrcode::{Color, EcLevel, QrCode};
+use sqlx::query;
+
+use crate::{
+    config,
+    error::{database_error, internal_server_error},
+    machine,
+    models::AppState,
+    tenant::Tenant,
+};
+
+/// Light modules required around a code for scanners to find it.
+const QUIET_ZONE: usize = 4;
+
+/// Labels per row of a QR sheet, and the size of each label in millimetres.
+const SHEET_COLUMNS: usize = 3;
+const LABEL_WIDTH_MM: usize = 70;
+const LABEL_HEIGHT_MM: usize = 74;
+const LABEL_CODE_MM: usize = 54;
+
+/// Settings for the QR codes stuck on machines.
+#[derive(Debug, Clone)]
+pub struct QrConfig {
+    /// The URL encoded in a machine's QR code, with `{room_id}` and `{machine_id}` placeholders.
+    pub url_template: String,
+    /// The width and height in pixels of each module of a PNG QR code.
+    pub module_pixels: u32,
+}
+
+impl QrConfig {
+    /// Parses the QR code configuration from the environment.
+    pub fn from_env() -> QrConfig {
+        QrConfig {
+            url_template: std::env::var("QR_URL_TEMPLATE").unwrap_or_else(|_| {
+                "http://localhost:8080/report/new?room_id={room_id}&machine_id={machine_id}"
+                    .to_string()
+            }),
+            module_pixels: config::env_or("QR_MODULE_PIXELS", 8).max(1),
+        }
+    }
+
+    /// The URL opened by scanning the code on a machine.
+    fn machine_url(&self, room_id: i32, machine_id: &str) -> String {
+        self.url_template
+            .replace("{room_id}", &room_id.to_string())
+            .replace(
+                "{machine_id}",
+                &utf8_percent_encode(machine_id, NON_ALPHANUMERIC).to_string(),
+            )
+    }
+
+    fn machine_code(&self, room_id: i32, machine_id: &str) -> Option<QrCode> {
+        let url = self.machine_url(room_id, machine_id);
+
+        match QrCode::with_error_correction_level(&url, EcLevel::M) {
+            Ok(code) => Some(code),
+            Err(err) => {
+                log::error!("Failed to encode {url} as a QR code: {err}");
+                None
+            }
+        }
+    }
+}
+
+/// Renders a code as a greyscale PNG, including its quiet zone.
+fn render_png(code: &QrCode, module_pixels: u32) -> Result<Vec<u8>, image::ImageError> {
+    let width = code.width();
+    let colors = code.to_colors();
+    let size = (width + 2 * QUIET_ZONE) as u32 * module_pixels;
+
+    let image = GrayImage::from_fn(size, size, |x, y| {
+        let column = (x / module_pixels) as usize;
+        let row = (y / module_pixels) as usize;
+
+        let dark = (QUIET_ZONE..QUIET_ZONE + width).contains(&column)
+            && (QUIET_ZONE..QUIET_ZONE + width).contains(&row)
+            && colors[(row - QUIET_ZONE) * width + column - QUIET_ZONE] == Color::Dark;
+
+        match dark {
+            true => Luma([0]),
+            false => Luma([255]),
+        }
+    });
+
+    let mut png = Cursor::new(Vec::new());
+    image.write_to(&mut png, ImageFormat::Png)?;
+    Ok(png.into_inner())
+}
+
+/// Renders a code as an `<svg>` element of the given position and size, including its quiet zone.
+/// Units are those of the enclosing document.
+fn render_svg(code: &QrCode, x: usize, y: usize, size: usize) -> String {
+    let width = code.width();
+    let extent = width + 2 * QUIET_ZONE;
+    let mut path = String::new();
+
+    for (index, color) in code.to_colors().into_iter().enumerate() {
+        if color == Color::Dark {
+            let _ = write!(path, "M{} {}h1v1h-1z", index % width, index / width);
+        }
+    }
+
+    format!(
+        r#"<svg x="{x}" y="{y}" width="{size}" height="{size}" viewBox="-{QUIET_ZONE} -{QUIET_ZONE} {extent} {extent}" shape-rendering="crispEdges"><rect x="-{QUIET_ZONE}" y="-{QUIET_ZONE}" width="{extent}" height="{extent}" fill="white"/><path d="{path}" fill="black"/></svg>"#
+    )
+}
+
+/// Escapes text for use in SVG content.
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Looks up a machine's QR code, responding with the error to return if it cannot be made.
+async fn machine_code(
+    data: &AppState,
+    tenant: &Tenant,
+    room_id: i32,
+    machine_id: &String,
+) -> Result<QrCode, HttpResponse> {
+    let machine_present =
+        match machine::is_machine_present(&data.database, tenant, &room_id, machine_id).await {
+            Ok(result) => result,
+            Err(err) => return Err(database_error("check machine presence", err)),
+        };
+
+    if !machine_present {
+        return Err(HttpResponse::NotFound().json(format!(
+            "Machine id {machine_id} was not found in room id {room_id}."
+        )));
+    }
+
+    data.qr_config
+        .machine_code(room_id, machine_id)
+        .ok_or_else(internal_server_error)
+}
+
+#[utoipa::path(
+    context_path = "/machine",
+    params(Tenant),
+    responses(
+        (status = 200, description = "A QR code of the URL for reporting the requested machine", content_type = "image/png"),
+        (status = 404, description = "The requested machine was not found"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[get("/{room_id}/{machine_id}/qr.png")]
+async fn get_machine_qr_png(
+    data: Data<AppState>,
+    tenant: Tenant,
+    path: Path<(i32, String)>,
+) -> impl Responder {
+    let (room_id, machine_id) = path.into_inner();
+
+    let code = match machine_code(&data, &tenant, room_id, &machine_id).await {
+        Ok(code) => code,
+        Err(response) => return response,
+    };
+
+    match render_png(&code, data.qr_config.module_pixels) {
+        Ok(png) => HttpResponse::Ok().content_type("image/png").body(png),
+        Err(err) => {
+            log::error!("Failed to render QR code: {err}");
+            internal_server_error()
+        }
+    }
+}
+
+#[utoipa::path(
+    context_path = "/machine",
+    params(Tenant),
+    responses(
+        (status = 200, description = "A QR code of the URL for reporting the requested machine", content_type = "image/svg+xml"),
+        (status = 404, description = "The requested machine was not found"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[get("/{room_id}/{machine_id}/qr.svg")]
+async fn get_machine_qr_svg(
+    data: Data<AppState>,
+    tenant: Tenant,
+    path: Path<(i32, String)>,
+) -> impl Responder {
+    let (room_id, machine_id) = path.into_inner();
+
+    let code = match machine_code(&data, &tenant, room_id, &machine_id).await {
+        Ok(code) => code,
+        Err(response) => return response,
+    };
+
+    let extent = code.width() + 2 * QUIET_ZONE;
+    let svg = render_svg(&code, 0, 0, extent);
+
+    HttpResponse::Ok()
+        .content_type("image/svg+xml")
+        .body(format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 {extent} {extent}">{svg}</svg>"#
+        ))
+}
+
+#[utoipa::path(
+    context_path = "/room",
+    params(Tenant),
+    responses(
+        (status = 200, description = "A printable sheet of labels with the QR code, id and room name of every machine in the requested room", content_type = "image/svg+xml"),
+        (status = 404, description = "The requested room was not found"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[get("/{room_id}/qr-sheet.svg")]
+async fn get_room_qr_sheet(
+    data: Data<AppState>,
+    tenant: Tenant,
+    path: Path<i32>,
+) -> impl Responder {
+    let room_id = path.into_inner();
+
+    let room = match query!(
+        r#"
+        SELECT name
+        FROM room
+        WHERE id = $1 AND organization_id = $2
+        "#,
+        room_id,
+        tenant.organization_id
+    )
+    .fetch_optional(&data.database)
+    .await
+    {
+        Ok(Some(room)) => room,
+        Ok(None) => {
+            return HttpResponse::NotFound().json(format!("Room id {room_id} was not found."))
+        }
+        Err(err) => return database_error("fetch room", err),
+    };
+
+    let machines = match query!(
+        r#"
+        SELECT machine_id
+        FROM machine
+        WHERE room_id = $1
+        ORDER BY machine_id
+        "#,
+        room_id
+    )
+    .fetch_all(&data.database)
+    .await
+    {
+        Ok(machines) => machines,
+        Err(err) => return database_error("fetch room machines", err),
+    };
+
+    let rows = machines.len().div_ceil(SHEET_COLUMNS).max(1);
+    let width = SHEET_COLUMNS * LABEL_WIDTH_MM;
+    let height = rows * LABEL_HEIGHT_MM;
+    let room_name = escape_xml(&room.name);
+
+    let mut sheet = format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}mm" height="{height}mm" viewBox="0 0 {width} {height}" font-family="sans-serif" text-anchor="middle">"#
+    );
+
+    for (index, machine) in machines.iter().enumerate() {
+        let Some(code) = data.qr_config.machine_code(room_id, &machine.machine_id) else {
+            return internal_server_error();
+        };
+
+        let x = index % SHEET_COLUMNS * LABEL_WIDTH_MM;
+        let y = index / SHEET_COLUMNS * LABEL_HEIGHT_MM;
+        let centre = x + LABEL_WIDTH_MM / 2;
+        let code_x = x + (LABEL_WIDTH_MM - LABEL_CODE_MM) / 2;
+
+        sheet.push_str(&render_svg(&code, code_x, y + 2, LABEL_CODE_MM));
+        let _ = write!(
+            sheet,
+            r#"<text x="{centre}" y="{}" font-size="6" font-weight="bold">{}</text><text x="{centre}" y="{}" font-size="4">{room_name}</text>"#,
+            y + LABEL_CODE_MM + 7,
+            escape_xml(&machine.machine_id),
+            y + LABEL_CODE_MM + 13,
+        );
+    }
+
+    sheet.push_str("</svg>");
+    HttpResponse::Ok().content_type("image/svg+xml").body(sheet)
+}
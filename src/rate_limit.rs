@@ -0,0 +1,460 @@
+use std::{
+    collections::HashMap,
+    env,
+    future::{ready, Ready},
+    rc::Rc,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use actix_web::{
+    body::EitherBody,
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http::header,
+    Error, HttpRequest, HttpResponse,
+};
+use futures_util::future::LocalBoxFuture;
+use serde::{Deserialize, Serialize};
+use sqlx::{query, query_as, Pool, Postgres};
+use time::PrimitiveDateTime;
+use utoipa::ToSchema;
+
+use crate::{background::ShutdownSignal, config, models::now};
+
+/// Buckets which have not been touched for this long are full again and can be forgotten.
+const BUCKET_IDLE_LIFETIME: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How often idle buckets are pruned.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Paths which are never rate limited, so that orchestrators can always probe the server.
+const EXEMPT_PATH_PREFIXES: [&str; 2] = ["/health", "/ping"];
+
+/// A token bucket limit, allowing `burst` requests at once and refilling at `per_second`.
+#[derive(Debug, Clone, Copy)]
+pub struct Limit {
+    pub burst: f64,
+    pub per_second: f64,
+}
+
+impl Limit {
+    pub fn per_minute(requests: u32) -> Limit {
+        Limit {
+            burst: requests as f64,
+            per_second: requests as f64 / 60.0,
+        }
+    }
+
+    pub fn per_hour(requests: u32) -> Limit {
+        Limit {
+            burst: requests as f64,
+            per_second: requests as f64 / 3600.0,
+        }
+    }
+
+    /// How long until a bucket holding `tokens` has refilled enough for another request.
+    fn retry_after(&self, tokens: f64) -> Duration {
+        match self.per_second > 0.0 {
+            true => Duration::from_secs_f64(((1.0 - tokens) / self.per_second).max(0.0)),
+            false => BUCKET_IDLE_LIFETIME,
+        }
+    }
+}
+
+/// The outcome of checking a rate limit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Decision {
+    Allowed,
+    Limited { retry_after: Duration },
+}
+
+/// Rate limit settings parsed from the environment.
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimitConfig {
+    /// Applied to every request from a single client IP address.
+    pub per_ip: Limit,
+    /// Applied to report submissions from a single client IP address.
+    pub report_per_ip: Limit,
+    /// Applied to new reports submitted on behalf of a single user, from whichever client.
+    pub report_per_user: Limit,
+    /// Applied to guest report submissions from a single client IP address.
+    pub guest_report_per_ip: Limit,
+    /// How many trusted proxies in front of the server append to the X-Forwarded-For header, none
+    /// meaning the client IP is the address of the connection.
+    pub trusted_proxies: usize,
+}
+
+impl RateLimitConfig {
+    pub fn from_env() -> RateLimitConfig {
+        RateLimitConfig {
+            per_ip: Limit::per_minute(config::env_or("RATE_LIMIT_PER_IP_PER_MINUTE", 300)),
+            report_per_ip: Limit::per_hour(config::env_or("REPORT_RATE_LIMIT_PER_IP_PER_HOUR", 20)),
+            report_per_user: Limit::per_hour(config::env_or(
+                "REPORT_RATE_LIMIT_PER_USER_PER_HOUR",
+                10,
+            )),
+            guest_report_per_ip: Limit::per_hour(config::env_or(
+                "GUEST_REPORT_RATE_LIMIT_PER_IP_PER_HOUR",
+                5,
+            )),
+            trusted_proxies: config::env_or("TRUSTED_PROXIES", 0),
+        }
+    }
+}
+
+/// A client which has recently been rate limited.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ThrottledClient {
+    key: String,
+    tokens: f64,
+    limited_count: i32,
+    last_limited_at: Option<PrimitiveDateTime>,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated_at: PrimitiveDateTime,
+    limited_count: i32,
+    last_limited_at: Option<PrimitiveDateTime>,
+}
+
+impl Bucket {
+    fn full(limit: &Limit, now: PrimitiveDateTime) -> Bucket {
+        Bucket {
+            tokens: limit.burst,
+            updated_at: now,
+            limited_count: 0,
+            last_limited_at: None,
+        }
+    }
+
+    /// Refills the bucket for the time elapsed since it was last used, then tries to take a token.
+    fn take(&mut self, limit: &Limit, now: PrimitiveDateTime) -> Decision {
+        let elapsed = (now - self.updated_at).as_seconds_f64().max(0.0);
+        self.tokens = (self.tokens + elapsed * limit.per_second).min(limit.burst);
+        self.updated_at = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            return Decision::Allowed;
+        }
+
+        self.limited_count += 1;
+        self.last_limited_at = Some(now);
+        Decision::Limited {
+            retry_after: limit.retry_after(self.tokens),
+        }
+    }
+}
+
+enum Backend {
+    /// Buckets are only shared between the workers of this process.
+    Memory(Mutex<HashMap<String, Bucket>>),
+    /// Buckets are stored in Postgres and shared between every instance.
+    Postgres(Pool<Postgres>),
+}
+
+/// Token bucket rate limiter keyed by arbitrary strings, such as `ip:127.0.0.1`.
+pub struct RateLimiter {
+    pub config: RateLimitConfig,
+    backend: Backend,
+}
+
+impl RateLimiter {
+    /// Creates a rate limiter, storing buckets in Postgres when RATE_LIMIT_BACKEND is `postgres`
+    /// and in memory otherwise.
+    pub fn from_env(database: &Pool<Postgres>) -> RateLimiter {
+        let backend = match env::var("RATE_LIMIT_BACKEND")
+            .unwrap_or_default()
+            .to_lowercase()
+            .as_str()
+        {
+            "postgres" => Backend::Postgres(database.clone()),
+            _ => Backend::Memory(Mutex::new(HashMap::new())),
+        };
+
+        RateLimiter {
+            config: RateLimitConfig::from_env(),
+            backend,
+        }
+    }
+
+    /// Takes a token from the bucket identified by `key`.
+    ///
+    /// Failures to reach shared state are logged and the request is allowed,
+    /// so that a database outage does not lock every client out.
+    pub async fn check(&self, key: &str, limit: &Limit) -> Decision {
+        let decision = match &self.backend {
+            Backend::Memory(buckets) => {
+                let mut buckets = match buckets.lock() {
+                    Ok(buckets) => buckets,
+                    Err(poisoned) => poisoned.into_inner(),
+                };
+                let now = now();
+                buckets
+                    .entry(key.to_string())
+                    .or_insert_with(|| Bucket::full(limit, now))
+                    .take(limit, now)
+            }
+            Backend::Postgres(database) => match check_postgres(database, key, limit).await {
+                Ok(decision) => decision,
+                Err(err) => {
+                    log::error!("Failed to check the rate limit for {key}: {err}");
+                    Decision::Allowed
+                }
+            },
+        };
+
+        if let Decision::Limited { retry_after } = decision {
+            log::warn!(
+                "Rate limited {key}, retry after {}s",
+                retry_after.as_secs_f64().ceil()
+            );
+        }
+
+        decision
+    }
+
+    /// Lists every client which has been rate limited within `window`.
+    pub async fn throttled(&self, window: Duration) -> Result<Vec<ThrottledClient>, sqlx::Error> {
+        let since = now() - window;
+
+        match &self.backend {
+            Backend::Memory(buckets) => {
+                let buckets = match buckets.lock() {
+                    Ok(buckets) => buckets,
+                    Err(poisoned) => poisoned.into_inner(),
+                };
+                let mut throttled: Vec<ThrottledClient> = buckets
+                    .iter()
+                    .filter(|(_, bucket)| {
+                        bucket
+                            .last_limited_at
+                            .is_some_and(|last_limited_at| last_limited_at >= since)
+                    })
+                    .map(|(key, bucket)| ThrottledClient {
+                        key: key.clone(),
+                        tokens: bucket.tokens,
+                        limited_count: bucket.limited_count,
+                        last_limited_at: bucket.last_limited_at,
+                    })
+                    .collect();
+                throttled.sort_by_key(|client| std::cmp::Reverse(client.last_limited_at));
+                Ok(throttled)
+            }
+            Backend::Postgres(database) => {
+                query_as!(
+                    ThrottledClient,
+                    r#"
+                    SELECT key, tokens, limited_count, last_limited_at
+                    FROM rate_limit_bucket
+                    WHERE last_limited_at >= $1
+                    ORDER BY last_limited_at DESC
+                    "#,
+                    since
+                )
+                .fetch_all(database)
+                .await
+            }
+        }
+    }
+
+    /// Forgets buckets which have been idle long enough to be full again.
+    pub async fn prune(&self) -> Result<(), sqlx::Error> {
+        let cutoff = now() - BUCKET_IDLE_LIFETIME;
+
+        match &self.backend {
+            Backend::Memory(buckets) => {
+                let mut buckets = match buckets.lock() {
+                    Ok(buckets) => buckets,
+                    Err(poisoned) => poisoned.into_inner(),
+                };
+                buckets.retain(|_, bucket| bucket.updated_at >= cutoff);
+                Ok(())
+            }
+            Backend::Postgres(database) => query!(
+                r#"
+                DELETE FROM rate_limit_bucket
+                WHERE updated_at < $1
+                "#,
+                cutoff
+            )
+            .execute(database)
+            .await
+            .map(|_| ()),
+        }
+    }
+
+    /// Background job which periodically prunes idle buckets.
+    pub async fn prune_periodically(self: Arc<Self>, mut shutdown: ShutdownSignal) {
+        while shutdown.sleep(PRUNE_INTERVAL).await {
+            if let Err(err) = self.prune().await {
+                log::error!("Failed to prune rate limit buckets: {err}");
+            }
+        }
+    }
+
+    /// The address rate limits are applied to for the given request.
+    ///
+    /// Every trusted proxy appends the address it received the request from to X-Forwarded-For,
+    /// so the client is the entry as many hops from the right as there are trusted proxies.
+    /// Entries further left are whatever the client sent, and are never used.
+    pub fn client_ip(&self, request: &HttpRequest) -> String {
+        let peer_addr = request
+            .connection_info()
+            .peer_addr()
+            .unwrap_or("unknown")
+            .to_string();
+
+        if self.config.trusted_proxies == 0 {
+            return peer_addr;
+        }
+
+        let hops: Vec<&str> = request
+            .headers()
+            .get_all(header::X_FORWARDED_FOR)
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .collect();
+
+        match hops.len().checked_sub(self.config.trusted_proxies) {
+            Some(index) if !hops[index].is_empty() => hops[index].to_string(),
+            _ => peer_addr,
+        }
+    }
+}
+
+async fn check_postgres(
+    database: &Pool<Postgres>,
+    key: &str,
+    limit: &Limit,
+) -> Result<Decision, sqlx::Error> {
+    let now = now();
+    let mut transaction = database.begin().await?;
+
+    query!(
+        r#"
+        INSERT INTO rate_limit_bucket (key, tokens, updated_at)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (key) DO NOTHING
+        "#,
+        key,
+        limit.burst,
+        now
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    let mut bucket = query_as!(
+        Bucket,
+        r#"
+        SELECT tokens, updated_at, limited_count, last_limited_at
+        FROM rate_limit_bucket
+        WHERE key = $1
+        FOR UPDATE
+        "#,
+        key
+    )
+    .fetch_one(&mut transaction)
+    .await?;
+
+    let decision = bucket.take(limit, now);
+
+    query!(
+        r#"
+        UPDATE rate_limit_bucket
+        SET tokens = $2, updated_at = $3, limited_count = $4, last_limited_at = $5
+        WHERE key = $1
+        "#,
+        key,
+        bucket.tokens,
+        bucket.updated_at,
+        bucket.limited_count,
+        bucket.last_limited_at
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    transaction.commit().await?;
+    Ok(decision)
+}
+
+/// Builds a `429 Too Many Requests` response with a `Retry-After` header.
+pub fn too_many_requests(retry_after: Duration) -> HttpResponse {
+    let seconds = retry_after.as_secs_f64().ceil().max(1.0) as u64;
+
+    HttpResponse::TooManyRequests()
+        .insert_header((header::RETRY_AFTER, seconds.to_string()))
+        .json(format!(
+            "Too many requests, please retry in {seconds} seconds."
+        ))
+}
+
+/// Middleware applying [RateLimitConfig::per_ip] to every request.
+pub struct RateLimitPerIp(pub Arc<RateLimiter>);
+
+impl<S, B> Transform<S, ServiceRequest> for RateLimitPerIp
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = RateLimitPerIpMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RateLimitPerIpMiddleware {
+            service: Rc::new(service),
+            limiter: Arc::clone(&self.0),
+        }))
+    }
+}
+
+pub struct RateLimitPerIpMiddleware<S> {
+    service: Rc<S>,
+    limiter: Arc<RateLimiter>,
+}
+
+impl<S, B> Service<ServiceRequest> for RateLimitPerIpMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, request: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+        let limiter = Arc::clone(&self.limiter);
+
+        Box::pin(async move {
+            let exempt = EXEMPT_PATH_PREFIXES
+                .iter()
+                .any(|prefix| request.path().starts_with(prefix));
+
+            if !exempt {
+                let key = format!("ip:{}", limiter.client_ip(request.request()));
+                let limit = limiter.config.per_ip;
+
+                if let Decision::Limited { retry_after } = limiter.check(&key, &limit).await {
+                    return Ok(request
+                        .into_response(too_many_requests(retry_after))
+                        .map_into_right_body());
+                }
+            }
+
+            service
+                .call(request)
+                .await
+                .map(ServiceResponse::map_into_left_body)
+        })
+    }
+}
@@ -1,18 +1,76 @@
+use std::time::Duration;
+
 use actix_web::{
-    delete, get, post,
+    delete, get,
+    http::header,
+    post,
     web::{Data, Json, Path},
-    HttpResponse, Responder,
+    HttpRequest, HttpResponse, Responder,
 };
 use serde::{Deserialize, Serialize};
-use sqlx::{query, query_as, Pool, Postgres};
-use time::{OffsetDateTime, PrimitiveDateTime};
+use sqlx::{query, query_as, Executor, Pool, Postgres};
+use time::PrimitiveDateTime;
 use utoipa::ToSchema;
 
 use crate::{
+    attachment, config,
+    error::{constraint_violation, database_error, Violation},
     machine,
-    models::{AppState, Report, ReportType},
+    models::{now, AppState, Report, ReportConfirmation, ReportType},
+    rate_limit::{self, Decision},
+    tenant::Tenant,
+    user,
 };
 
+/// How a submission matching an open report for the same machine and report type is handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicateMode {
+    /// The submission is recorded as a confirmation of the open report.
+    Confirm,
+    /// The submission is rejected, pointing the client at the open report.
+    Reject,
+}
+
+/// Who a report is filed on behalf of.
+#[derive(Debug, Clone, Copy)]
+pub enum Reporter<'a> {
+    /// A registered user.
+    User(&'a String),
+    /// A guest whose submission was approved by an admin.
+    Guest,
+    /// An integration rather than a person, such as telemetry or anomaly detection.
+    Automated,
+}
+
+/// Report handling settings parsed from the environment.
+#[derive(Debug, Clone)]
+pub struct ReportConfig {
+    /// How far back an open report is considered a duplicate of a new submission.
+    pub duplicate_window: Duration,
+    pub duplicate_mode: DuplicateMode,
+}
+
+impl ReportConfig {
+    /// Parses the report configuration from the environment.
+    pub fn from_env() -> ReportConfig {
+        let duplicate_mode = match std::env::var("DUPLICATE_REPORT_MODE")
+            .unwrap_or_default()
+            .to_lowercase()
+            .as_str()
+        {
+            "reject" => DuplicateMode::Reject,
+            _ => DuplicateMode::Confirm,
+        };
+
+        ReportConfig {
+            duplicate_window: Duration::from_secs(
+                config::env_or("DUPLICATE_REPORT_WINDOW_HOURS", 24) * 60 * 60,
+            ),
+            duplicate_mode,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, ToSchema)]
 pub struct ReportSubmission {
     machine_id: String,
@@ -27,17 +85,25 @@ pub struct ArchiveSubmission {
     report_id: i32,
 }
 
-async fn is_report_present(
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct MergeSubmission {
+    source_report_id: i32,
+    target_report_id: i32,
+}
+
+pub async fn is_report_present(
     database: &Pool<Postgres>,
+    tenant: &Tenant,
     report_id: &i32,
 ) -> Result<bool, sqlx::Error> {
     match query!(
         r#"
         SELECT id
         FROM report
-        WHERE id = $1
+        WHERE id = $1 AND room_id IN (SELECT id FROM room WHERE organization_id = $2)
         "#,
-        report_id
+        report_id,
+        tenant.organization_id
     )
     .fetch_optional(database)
     .await
@@ -47,8 +113,242 @@ async fn is_report_present(
     }
 }
 
+/// Whether new reports may use `report_type`, which must exist and be active.
+pub async fn is_report_type_active(
+    database: &Pool<Postgres>,
+    report_type: &ReportType,
+) -> Result<bool, sqlx::Error> {
+    match query!(
+        r#"
+        SELECT active
+        FROM report_type
+        WHERE name = $1
+        "#,
+        report_type as &ReportType
+    )
+    .fetch_optional(database)
+    .await
+    {
+        Ok(result) => Ok(result.is_some_and(|report_type| report_type.active)),
+        Err(err) => Err(err),
+    }
+}
+
+/// Locks a machine until the end of the transaction `executor` belongs to.
+///
+/// Report submissions lock the machine before looking for a duplicate, so that two submissions
+/// of the same problem are serialized and the second finds the report filed by the first.
+pub async fn lock_machine<'c, E>(
+    executor: E,
+    room_id: &i32,
+    machine_id: &str,
+) -> Result<(), sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    query!(
+        r#"
+        SELECT machine_id
+        FROM machine
+        WHERE room_id = $1 AND machine_id = $2
+        FOR UPDATE
+        "#,
+        room_id,
+        machine_id
+    )
+    .fetch_optional(executor)
+    .await
+    .map(|_| ())
+}
+
+/// Finds the most recent unarchived report of `report_type` for a machine submitted at or after `since`.
+pub async fn find_open_duplicate<'c, E>(
+    executor: E,
+    room_id: &i32,
+    machine_id: &str,
+    report_type: &ReportType,
+    since: PrimitiveDateTime,
+) -> Result<Option<Report>, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    query_as!(
+        Report,
+        r#"
+        SELECT
+            id AS "report_id: i32",
+            room_id,
+            machine_id,
+            reporter_username,
+            time,
+            type AS "report_type: ReportType",
+            description,
+            archived,
+            automated,
+            (SELECT COUNT(*) FROM report_confirmation WHERE report_id = report.id) AS "confirmation_count!: i64",
+            (SELECT COUNT(*) FROM report_comment WHERE report_id = report.id) AS "comment_count!: i64",
+            report_attachments(report.id) AS "attachments!: _"
+        FROM report
+        WHERE room_id = $1 AND machine_id = $2 AND type = $3 AND archived = false AND time >= $4
+        ORDER BY time DESC
+        LIMIT 1
+        "#,
+        room_id,
+        machine_id,
+        report_type as &ReportType,
+        since
+    )
+    .fetch_optional(executor)
+    .await
+}
+
+/// Inserts a new report filed on behalf of `reporter`.
+///
+/// Callers [lock the machine](lock_machine) and check for an
+/// [open duplicate](find_open_duplicate) in the same transaction first.
+pub async fn insert_report<'c, E>(
+    executor: E,
+    room_id: &i32,
+    machine_id: &str,
+    reporter: Reporter<'_>,
+    report_type: &ReportType,
+    description: Option<String>,
+    time: PrimitiveDateTime,
+) -> Result<Report, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let (reporter_username, automated) = match reporter {
+        Reporter::User(username) => (Some(username), false),
+        Reporter::Guest => (None, false),
+        Reporter::Automated => (None, true),
+    };
+
+    query_as!(
+        Report,
+        r#"
+        INSERT INTO report (
+            room_id, machine_id, reporter_username, type, description, time, automated,
+            organization_id
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, (SELECT organization_id FROM room WHERE id = $1))
+        RETURNING
+            id AS "report_id: i32",
+            room_id,
+            machine_id,
+            reporter_username,
+            time,
+            type AS "report_type: ReportType",
+            description,
+            archived,
+            automated,
+            (SELECT COUNT(*) FROM report_confirmation WHERE report_id = report.id) AS "confirmation_count!: i64",
+            (SELECT COUNT(*) FROM report_comment WHERE report_id = report.id) AS "comment_count!: i64",
+            report_attachments(report.id) AS "attachments!: _"
+        "#,
+        room_id,
+        machine_id,
+        reporter_username,
+        report_type as &ReportType,
+        description,
+        time,
+        automated
+    )
+    .fetch_one(executor)
+    .await
+}
+
+/// Files a report on behalf of an integration rather than a user.
+///
+/// Returns `None` without filing anything if `report_type` is not active or an open report
+/// within the duplicate window already covers the machine.
+pub async fn submit_automated_report(
+    database: &Pool<Postgres>,
+    report_config: &ReportConfig,
+    room_id: &i32,
+    machine_id: &str,
+    report_type: &ReportType,
+    description: Option<String>,
+) -> Result<Option<Report>, sqlx::Error> {
+    if !is_report_type_active(database, report_type).await? {
+        return Ok(None);
+    }
+
+    let current_time = now();
+
+    let mut transaction = database.begin().await?;
+    lock_machine(&mut transaction, room_id, machine_id).await?;
+
+    let duplicate = find_open_duplicate(
+        &mut transaction,
+        room_id,
+        machine_id,
+        report_type,
+        current_time - report_config.duplicate_window,
+    )
+    .await?;
+
+    if duplicate.is_some() {
+        return Ok(None);
+    }
+
+    let report = insert_report(
+        &mut transaction,
+        room_id,
+        machine_id,
+        Reporter::Automated,
+        report_type,
+        description,
+        current_time,
+    )
+    .await?;
+
+    transaction.commit().await?;
+    Ok(Some(report))
+}
+
+/// Archives a report, returning it or `None` if it was not found.
+pub async fn archive<'c, E>(
+    executor: E,
+    tenant: &Tenant,
+    report_id: &i32,
+) -> Result<Option<Report>, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let current_time = now();
+
+    query_as!(
+        Report,
+        r#"
+        UPDATE report
+        SET archived = true, archived_time = COALESCE(archived_time, $3)
+        WHERE id = $1 AND room_id IN (SELECT id FROM room WHERE organization_id = $2)
+        RETURNING
+            id as "report_id: i32",
+            room_id,
+            machine_id,
+            reporter_username,
+            time,
+            type as "report_type: ReportType",
+            description,
+            archived,
+            automated,
+            (SELECT COUNT(*) FROM report_confirmation WHERE report_id = report.id) AS "confirmation_count!: i64",
+            (SELECT COUNT(*) FROM report_comment WHERE report_id = report.id) AS "comment_count!: i64",
+            report_attachments(report.id) AS "attachments!: _"
+        "#,
+        report_id,
+        tenant.organization_id,
+        current_time
+    )
+    .fetch_optional(executor)
+    .await
+}
+
 #[utoipa::path(
     context_path = "/report",
+    params(Tenant),
     responses(
         (status = 200, description = "List of all unarchived reports", body = Vec<Report>, example = json!([{
             "report_id": 1,
@@ -59,12 +359,16 @@ async fn is_report_present(
             "description": "No heat",
             "time": "2023-01-01T12:00:00.000Z",
             "archived": false,
+            "automated": false,
+            "confirmation_count": 0,
+            "comment_count": 0,
+            "attachments": [],
           }])),
         (status = 500, description = "An internal server error occurred")
     )
 )]
 #[get("/")]
-async fn get_all_reports(data: Data<AppState>) -> impl Responder {
+async fn get_all_reports(data: Data<AppState>, tenant: Tenant) -> impl Responder {
     match query_as!(
         Report,
         r#"
@@ -76,21 +380,28 @@ async fn get_all_reports(data: Data<AppState>) -> impl Responder {
             time,
             type AS "report_type: ReportType",
             description,
-            archived
+            archived,
+            automated,
+            (SELECT COUNT(*) FROM report_confirmation WHERE report_id = report.id) AS "confirmation_count!: i64",
+            (SELECT COUNT(*) FROM report_comment WHERE report_id = report.id) AS "comment_count!: i64",
+            report_attachments(report.id) AS "attachments!: _"
         FROM report
         WHERE archived = false
+            AND room_id IN (SELECT id FROM room WHERE organization_id = $1)
         "#,
+        tenant.organization_id
     )
     .fetch_all(&data.database)
     .await
     {
         Ok(reports) => HttpResponse::Ok().json(reports),
-        Err(err) => HttpResponse::InternalServerError().json(err.to_string()),
+        Err(err) => database_error("fetch all reports", err),
     }
 }
 
 #[utoipa::path(
     context_path = "/report",
+    params(Tenant),
     responses(
         (status = 200, description = "List of all archived reports", body = Vec<Report>, example = json!([{
             "report_id": 1,
@@ -101,12 +412,16 @@ async fn get_all_reports(data: Data<AppState>) -> impl Responder {
             "description": "No heat",
             "time": "2023-01-01T12:00:00.000Z",
             "archived": true,
+            "automated": false,
+            "confirmation_count": 0,
+            "comment_count": 0,
+            "attachments": [],
           }])),
         (status = 500, description = "An internal server error occurred")
     )
 )]
 #[get("/archived")]
-async fn get_all_archived_reports(data: Data<AppState>) -> impl Responder {
+async fn get_all_archived_reports(data: Data<AppState>, tenant: Tenant) -> impl Responder {
     match query_as!(
         Report,
         r#"
@@ -118,21 +433,28 @@ async fn get_all_archived_reports(data: Data<AppState>) -> impl Responder {
             time,
             type AS "report_type: ReportType",
             description,
-            archived
+            archived,
+            automated,
+            (SELECT COUNT(*) FROM report_confirmation WHERE report_id = report.id) AS "confirmation_count!: i64",
+            (SELECT COUNT(*) FROM report_comment WHERE report_id = report.id) AS "comment_count!: i64",
+            report_attachments(report.id) AS "attachments!: _"
         FROM report
         WHERE archived = true
+            AND room_id IN (SELECT id FROM room WHERE organization_id = $1)
         "#,
+        tenant.organization_id
     )
     .fetch_all(&data.database)
     .await
     {
         Ok(reports) => HttpResponse::Ok().json(reports),
-        Err(err) => HttpResponse::InternalServerError().json(err.to_string()),
+        Err(err) => database_error("fetch all archived reports", err),
     }
 }
 
 #[utoipa::path(
     context_path = "/report",
+    params(Tenant),
     responses(
         (status = 200, description = "The requested report", body = Report, example = json!({
             "report_id": 1,
@@ -143,13 +465,17 @@ async fn get_all_archived_reports(data: Data<AppState>) -> impl Responder {
             "description": "No heat",
             "time": "2023-01-01T12:00:00.000Z",
             "archived": false,
+            "automated": false,
+            "confirmation_count": 0,
+            "comment_count": 0,
+            "attachments": [],
           })),
         (status = 404, description = "The requested report was not found"),
         (status = 500, description = "An internal server error occurred")
     )
 )]
 #[get("/{report_id}")]
-async fn get_report(data: Data<AppState>, path: Path<i32>) -> impl Responder {
+async fn get_report(data: Data<AppState>, tenant: Tenant, path: Path<i32>) -> impl Responder {
     let report_id = path.into_inner();
 
     match query_as!(
@@ -163,16 +489,21 @@ async fn get_report(data: Data<AppState>, path: Path<i32>) -> impl Responder {
             time,
             type AS "report_type: ReportType",
             description,
-            archived
+            archived,
+            automated,
+            (SELECT COUNT(*) FROM report_confirmation WHERE report_id = report.id) AS "confirmation_count!: i64",
+            (SELECT COUNT(*) FROM report_comment WHERE report_id = report.id) AS "comment_count!: i64",
+            report_attachments(report.id) AS "attachments!: _"
         FROM report
-        WHERE id = $1
+        WHERE id = $1 AND room_id IN (SELECT id FROM room WHERE organization_id = $2)
         "#,
-        report_id
+        report_id,
+        tenant.organization_id
     )
     .fetch_optional(&data.database)
     .await
     {
-        Err(err) => HttpResponse::InternalServerError().json(err.to_string()),
+        Err(err) => database_error("fetch report", err),
         Ok(report) => match report {
             Some(report) => HttpResponse::Ok().json(&report),
             None => {
@@ -184,6 +515,7 @@ async fn get_report(data: Data<AppState>, path: Path<i32>) -> impl Responder {
 
 #[utoipa::path(
     context_path = "/report",
+    params(Tenant),
     request_body(
         content = ReportSubmission,
         content_type = "application/json",
@@ -206,25 +538,35 @@ async fn get_report(data: Data<AppState>, path: Path<i32>) -> impl Responder {
             "description": "No heat",
             "time": "2023-01-01T12:00:00.000Z",
             "archived": false,
+            "automated": false,
+            "confirmation_count": 0,
+            "comment_count": 0,
+            "attachments": [],
           })),
+        (status = 200, description = "An open report already covers this machine and report type, and the submission was recorded as a confirmation of it", body = Report),
         (status = 400, description = "The requested query was invalid"),
+        (status = 409, description = "An open report already covers this machine and report type, and duplicate reports are rejected"),
+        (status = 429, description = "Too many reports were submitted by this client or about this machine"),
         (status = 500, description = "An internal server error occurred")
     )
 )]
 #[post("/")]
 async fn submit_report(
     data: Data<AppState>,
+    tenant: Tenant,
+    request: HttpRequest,
     Json(report_submission): Json<ReportSubmission>,
 ) -> impl Responder {
     let machine_present = match machine::is_machine_present(
         &data.database,
+        &tenant,
         &report_submission.room_id,
         &report_submission.machine_id,
     )
     .await
     {
         Ok(result) => result,
-        Err(err) => return HttpResponse::InternalServerError().json(err.to_string()),
+        Err(err) => return database_error("check machine presence", err),
     };
 
     if !machine_present {
@@ -234,43 +576,174 @@ async fn submit_report(
         ));
     }
 
-    let current_time = OffsetDateTime::now_utc();
+    let reporter_present = match user::is_username_present(
+        &data.database,
+        &tenant,
+        &report_submission.reporter_username,
+    )
+    .await
+    {
+        Ok(result) => result,
+        Err(err) => return database_error("check username presence", err),
+    };
 
-    match query_as!(
-        Report,
-        r#"
-        INSERT INTO report (room_id, machine_id, reporter_username, type, description, time)
-        VALUES ($1, $2, $3, $4, $5, $6)
-        RETURNING
-            id AS "report_id: i32",
-            room_id,
-            machine_id,
-            reporter_username,
-            time,
-            type AS "report_type: ReportType",
-            description,
-            archived
-        "#,
+    if !reporter_present {
+        return HttpResponse::BadRequest().json(format!(
+            "The user {} was not found.",
+            &report_submission.reporter_username
+        ));
+    }
+
+    let report_type_active =
+        match is_report_type_active(&data.database, &report_submission.report_type).await {
+            Ok(result) => result,
+            Err(err) => return database_error("check report type", err),
+        };
+
+    if !report_type_active {
+        return HttpResponse::BadRequest().json(format!(
+            "The report type {} does not exist or is no longer in use.",
+            &report_submission.report_type.0
+        ));
+    }
+
+    // Only valid submissions use up tokens, so that rejected submissions cannot drain the bucket
+    // of the client they claim to come from.
+    let rate_limiter = &data.rate_limiter;
+    let client_ip = rate_limiter.client_ip(&request);
+
+    if let Decision::Limited { retry_after } = rate_limiter
+        .check(
+            &format!("report-ip:{client_ip}"),
+            &rate_limiter.config.report_per_ip,
+        )
+        .await
+    {
+        return rate_limit::too_many_requests(retry_after);
+    }
+
+    let current_time = now();
+
+    let mut transaction = match data.database.begin().await {
+        Ok(transaction) => transaction,
+        Err(err) => return database_error("begin report submission", err),
+    };
+
+    if let Err(err) = lock_machine(
+        &mut transaction,
         &report_submission.room_id,
         &report_submission.machine_id,
-        &report_submission.reporter_username,
-        &report_submission.report_type as &ReportType,
+    )
+    .await
+    {
+        return database_error("lock machine", err);
+    }
+
+    let duplicate = match find_open_duplicate(
+        &mut transaction,
+        &report_submission.room_id,
+        &report_submission.machine_id,
+        &report_submission.report_type,
+        current_time - data.report_config.duplicate_window,
+    )
+    .await
+    {
+        Ok(result) => result,
+        Err(err) => return database_error("check for duplicate reports", err),
+    };
+
+    if let Some(mut report) = duplicate {
+        if data.report_config.duplicate_mode == DuplicateMode::Reject {
+            return HttpResponse::Conflict()
+                .insert_header((header::LOCATION, format!("/report/{}", report.report_id)))
+                .json(format!(
+                    "Report id {} already reports this machine as {}.",
+                    report.report_id, report.report_type.0
+                ));
+        }
+
+        if report.reporter_username.as_ref() == Some(&report_submission.reporter_username) {
+            return HttpResponse::Ok().json(report);
+        }
+
+        return match query!(
+            r#"
+            INSERT INTO report_confirmation (
+                report_id, reporter_username, time, description, organization_id
+            )
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT DO NOTHING
+            "#,
+            report.report_id,
+            &report_submission.reporter_username,
+            current_time,
+            report_submission.description,
+            tenant.organization_id
+        )
+        .execute(&mut transaction)
+        .await
+        {
+            Ok(result) => match transaction.commit().await {
+                Ok(()) => {
+                    report.confirmation_count += result.rows_affected() as i64;
+                    HttpResponse::Ok().json(report)
+                }
+                Err(err) => database_error("commit report confirmation", err),
+            },
+            Err(err) => match constraint_violation(&err) {
+                Some(Violation::ForeignKey) => HttpResponse::BadRequest().json(format!(
+                    "The user {} no longer exists.",
+                    &report_submission.reporter_username
+                )),
+                _ => database_error("insert report confirmation", err),
+            },
+        };
+    }
+
+    // Confirmations of an open report are not limited per user, so that however many residents
+    // confirm a broken machine, nobody is kept from reporting something new.
+    let user_key = format!(
+        "report-user:{}:{}",
+        tenant.organization_id, report_submission.reporter_username
+    );
+    if let Decision::Limited { retry_after } = rate_limiter
+        .check(&user_key, &rate_limiter.config.report_per_user)
+        .await
+    {
+        return rate_limit::too_many_requests(retry_after);
+    }
+
+    match insert_report(
+        &mut transaction,
+        &report_submission.room_id,
+        &report_submission.machine_id,
+        Reporter::User(&report_submission.reporter_username),
+        &report_submission.report_type,
         report_submission.description,
-        PrimitiveDateTime::new(current_time.date(), current_time.time())
+        current_time,
     )
-    .fetch_one(&data.database)
     .await
     {
-        Ok(report) => HttpResponse::Created().json(report),
-        Err(err) => match err {
-            sqlx::Error::Database(err) => HttpResponse::BadRequest().json(err.to_string()),
-            _ => HttpResponse::InternalServerError().json(err.to_string()),
+        Ok(report) => match transaction.commit().await {
+            Ok(()) => HttpResponse::Created().json(report),
+            Err(err) => database_error("commit report", err),
+        },
+        Err(err) => match constraint_violation(&err) {
+            Some(Violation::ForeignKey) => HttpResponse::BadRequest().json(format!(
+                "Machine id {} in room id {}, the user {} or the report type {} no longer exists.",
+                &report_submission.machine_id,
+                &report_submission.room_id,
+                &report_submission.reporter_username,
+                &report_submission.report_type.0
+            )),
+            _ => database_error("insert report", err),
         },
     }
 }
 
 #[utoipa::path(
     context_path = "/report",
+    params(Tenant),
     responses(
         (status = 200, description = "The requested report was deleted", body = Report, example = json!({
             "report_id": 1,
@@ -281,29 +754,38 @@ async fn submit_report(
             "description": "No heat",
             "time": "2023-01-01T12:00:00.000Z",
             "archived": false,
+            "automated": false,
+            "confirmation_count": 0,
+            "comment_count": 0,
+            "attachments": [],
           })),
         (status = 404, description = "The requested report was not found"),
         (status = 500, description = "An internal server error occurred")
     )
 )]
 #[delete("/{report_id}")]
-async fn delete_report(data: Data<AppState>, path: Path<i32>) -> impl Responder {
+async fn delete_report(data: Data<AppState>, tenant: Tenant, path: Path<i32>) -> impl Responder {
     let report_id = path.into_inner();
 
-    let report_present = match is_report_present(&data.database, &report_id).await {
+    let report_present = match is_report_present(&data.database, &tenant, &report_id).await {
         Ok(result) => result,
-        Err(err) => return HttpResponse::InternalServerError().json(err.to_string()),
+        Err(err) => return database_error("check report presence", err),
     };
 
     if !report_present {
         return HttpResponse::NotFound().json(format!("Report id {report_id} was not found."));
     }
 
+    let blob_keys = match attachment::report_blob_keys(&data.database, &report_id).await {
+        Ok(keys) => keys,
+        Err(err) => return database_error("fetch report attachments", err),
+    };
+
     match query_as!(
         Report,
         r#"
     DELETE FROM report
-    WHERE id = $1
+    WHERE id = $1 AND room_id IN (SELECT id FROM room WHERE organization_id = $2)
     RETURNING
         id as "report_id: i32",
         room_id,
@@ -312,20 +794,30 @@ async fn delete_report(data: Data<AppState>, path: Path<i32>) -> impl Responder
         time,
         type as "report_type: ReportType",
         description,
-        archived
+        archived,
+        automated,
+        (SELECT COUNT(*) FROM report_confirmation WHERE report_id = report.id) AS "confirmation_count!: i64",
+        (SELECT COUNT(*) FROM report_comment WHERE report_id = report.id) AS "comment_count!: i64",
+        report_attachments(report.id) AS "attachments!: _"
     "#,
-        report_id
+        report_id,
+        tenant.organization_id
     )
-    .fetch_one(&data.database)
+    .fetch_optional(&data.database)
     .await
     {
-        Ok(report) => HttpResponse::Ok().json(report),
-        Err(err) => HttpResponse::InternalServerError().json(err.to_string()),
+        Ok(Some(report)) => {
+            attachment::remove_blobs(&data.blob_store, &blob_keys).await;
+            HttpResponse::Ok().json(report)
+        }
+        Ok(None) => HttpResponse::NotFound().json(format!("Report id {report_id} was not found.")),
+        Err(err) => database_error("delete report", err),
     }
 }
 
 #[utoipa::path(
     context_path = "/report",
+    params(Tenant),
     request_body(
         content = ArchiveSubmission,
         content_type = "application/json",
@@ -344,6 +836,10 @@ async fn delete_report(data: Data<AppState>, path: Path<i32>) -> impl Responder
             "description": "No heat",
             "time": "2023-01-01T12:00:00.000Z",
             "archived": true,
+            "automated": false,
+            "confirmation_count": 0,
+            "comment_count": 0,
+            "attachments": [],
         })),
         (status = 400, description = "The requested query was invalid"),
         (status = 500, description = "An internal server error occurred")
@@ -352,43 +848,259 @@ async fn delete_report(data: Data<AppState>, path: Path<i32>) -> impl Responder
 #[post("/archive")]
 async fn archive_report(
     data: Data<AppState>,
+    tenant: Tenant,
     Json(archive_submission): Json<ArchiveSubmission>,
 ) -> impl Responder {
-    let report_present =
-        match is_report_present(&data.database, &archive_submission.report_id).await {
-            Ok(result) => result,
-            Err(err) => return HttpResponse::InternalServerError().json(err.to_string()),
-        };
+    match archive(&data.database, &tenant, &archive_submission.report_id).await {
+        Ok(Some(report)) => HttpResponse::Ok().json(report),
+        Ok(None) => HttpResponse::BadRequest().json(format!(
+            "Report id {} was not found.",
+            &archive_submission.report_id
+        )),
+        Err(err) => database_error("archive report", err),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/report",
+    params(Tenant),
+    responses(
+        (status = 200, description = "List of all confirmations of the requested report", body = Vec<ReportConfirmation>, example = json!([{
+            "report_id": 1,
+            "reporter_username": "resident",
+            "time": "2023-01-01T12:30:00.000Z",
+            "description": "Still no heat",
+          }])),
+        (status = 404, description = "The requested report was not found"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[get("/{report_id}/confirmations")]
+async fn get_report_confirmations(
+    data: Data<AppState>,
+    tenant: Tenant,
+    path: Path<i32>,
+) -> impl Responder {
+    let report_id = path.into_inner();
+
+    let report_present = match is_report_present(&data.database, &tenant, &report_id).await {
+        Ok(result) => result,
+        Err(err) => return database_error("check report presence", err),
+    };
 
     if !report_present {
+        return HttpResponse::NotFound().json(format!("Report id {report_id} was not found."));
+    }
+
+    match query_as!(
+        ReportConfirmation,
+        r#"
+        SELECT report_id, reporter_username, time, description
+        FROM report_confirmation
+        WHERE report_id = $1
+        ORDER BY time
+        "#,
+        report_id
+    )
+    .fetch_all(&data.database)
+    .await
+    {
+        Ok(confirmations) => HttpResponse::Ok().json(confirmations),
+        Err(err) => database_error("fetch report confirmations", err),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/admin",
+    params(Tenant),
+    request_body(
+        content = MergeSubmission,
+        content_type = "application/json",
+        description = "JSON object containing the id of the duplicate report to merge and the id of the report to merge it into",
+        example = json!({
+            "source_report_id": 2,
+            "target_report_id": 1,
+        })
+    ),
+    responses(
+        (status = 200, description = "The source report was merged into the target report and deleted", body = Report, example = json!({
+            "report_id": 1,
+            "room_id": 1,
+            "machine_id": "A",
+            "reporter_username": "admin",
+            "report_type": "Broken",
+            "description": "No heat",
+            "time": "2023-01-01T12:00:00.000Z",
+            "archived": false,
+            "automated": false,
+            "confirmation_count": 1,
+            "comment_count": 0,
+            "attachments": [],
+        })),
+        (status = 400, description = "The requested query was invalid"),
+        (status = 401, description = "No valid admin credentials were given"),
+        (status = 409, description = "The reports are not of the same report type"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[post("/reports/merge")]
+async fn merge_reports(
+    data: Data<AppState>,
+    tenant: Tenant,
+    Json(merge_submission): Json<MergeSubmission>,
+) -> impl Responder {
+    let source_report_id = merge_submission.source_report_id;
+    let target_report_id = merge_submission.target_report_id;
+
+    if source_report_id == target_report_id {
+        return HttpResponse::BadRequest().json("A report cannot be merged into itself.");
+    }
+
+    let mut transaction = match data.database.begin().await {
+        Ok(transaction) => transaction,
+        Err(err) => return database_error("begin report merge", err),
+    };
+
+    let reports = match query!(
+        r#"
+        SELECT id, room_id, machine_id, type AS "report_type: ReportType"
+        FROM report
+        WHERE (id = $1 OR id = $2) AND room_id IN (SELECT id FROM room WHERE organization_id = $3)
+        FOR UPDATE
+        "#,
+        source_report_id,
+        target_report_id,
+        tenant.organization_id
+    )
+    .fetch_all(&mut transaction)
+    .await
+    {
+        Ok(reports) => reports,
+        Err(err) => return database_error("fetch reports to merge", err),
+    };
+
+    for report_id in [source_report_id, target_report_id] {
+        if !reports.iter().any(|report| report.id == report_id) {
+            return HttpResponse::BadRequest()
+                .json(format!("Report id {report_id} was not found."));
+        }
+    }
+
+    if reports[0].room_id != reports[1].room_id || reports[0].machine_id != reports[1].machine_id {
         return HttpResponse::BadRequest().json(format!(
-            "Report id {} was not found.",
-            &archive_submission.report_id
+            "Report ids {source_report_id} and {target_report_id} are not for the same machine."
         ));
     }
 
-    match query_as!(
-        Report,
+    if reports[0].report_type != reports[1].report_type {
+        return HttpResponse::Conflict().json(format!(
+            "Report ids {source_report_id} and {target_report_id} are not of the same report type."
+        ));
+    }
+
+    if let Err(err) = query!(
         r#"
-        UPDATE report
-        SET archived = true
+        INSERT INTO report_confirmation (
+            report_id, reporter_username, time, description, organization_id
+        )
+        SELECT $2, confirmation.reporter_username, confirmation.time, confirmation.description, $3
+        FROM (
+            SELECT reporter_username, time, description
+            FROM report
+            WHERE id = $1
+            UNION ALL
+            SELECT reporter_username, time, description
+            FROM report_confirmation
+            WHERE report_id = $1
+        ) AS confirmation
+        WHERE confirmation.reporter_username IS NOT NULL
+            AND confirmation.reporter_username
+                IS DISTINCT FROM (SELECT reporter_username FROM report WHERE id = $2)
+        ON CONFLICT DO NOTHING
+        "#,
+        source_report_id,
+        target_report_id,
+        tenant.organization_id
+    )
+    .execute(&mut transaction)
+    .await
+    {
+        return database_error("copy confirmations of merged report", err);
+    }
+
+    if let Err(err) = query!(
+        r#"
+        UPDATE report_comment
+        SET report_id = $2
+        WHERE report_id = $1
+        "#,
+        source_report_id,
+        target_report_id
+    )
+    .execute(&mut transaction)
+    .await
+    {
+        return database_error("move comments of merged report", err);
+    }
+
+    if let Err(err) = query!(
+        r#"
+        UPDATE report_attachment
+        SET report_id = $2
+        WHERE report_id = $1
+        "#,
+        source_report_id,
+        target_report_id
+    )
+    .execute(&mut transaction)
+    .await
+    {
+        return database_error("move attachments of merged report", err);
+    }
+
+    if let Err(err) = query!(
+        r#"
+        DELETE FROM report
         WHERE id = $1
-        RETURNING
-            id as "report_id: i32",
+        "#,
+        source_report_id
+    )
+    .execute(&mut transaction)
+    .await
+    {
+        return database_error("delete merged report", err);
+    }
+
+    let report = match query_as!(
+        Report,
+        r#"
+        SELECT
+            id AS "report_id: i32",
             room_id,
             machine_id,
             reporter_username,
             time,
-            type as "report_type: ReportType",
+            type AS "report_type: ReportType",
             description,
-            archived
+            archived,
+            automated,
+            (SELECT COUNT(*) FROM report_confirmation WHERE report_id = report.id) AS "confirmation_count!: i64",
+            (SELECT COUNT(*) FROM report_comment WHERE report_id = report.id) AS "comment_count!: i64",
+            report_attachments(report.id) AS "attachments!: _"
+        FROM report
+        WHERE id = $1
         "#,
-        &archive_submission.report_id
+        target_report_id
     )
-    .fetch_one(&data.database)
+    .fetch_one(&mut transaction)
     .await
     {
-        Ok(report) => HttpResponse::Ok().json(report),
-        Err(err) => HttpResponse::InternalServerError().json(err.to_string()),
+        Ok(report) => report,
+        Err(err) => return database_error("fetch merged report", err),
+    };
+
+    match transaction.commit().await {
+        Ok(()) => HttpResponse::Ok().json(report),
+        Err(err) => database_error("commit report merge", err),
     }
 }
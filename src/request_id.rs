@@ -0,0 +1,110 @@
+use std::{
+    future::{ready, Ready},
+    rc::Rc,
+};
+
+use actix_web::{
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http::header::{HeaderName, HeaderValue},
+    Error, HttpMessage,
+};
+use futures_util::future::LocalBoxFuture;
+use uuid::Uuid;
+
+/// The header used to receive and return the request id.
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+/// The longest client supplied request id which will be accepted.
+const MAX_REQUEST_ID_LENGTH: usize = 128;
+
+tokio::task_local! {
+    static CURRENT_REQUEST_ID: RequestId;
+}
+
+/// The id used to correlate a single request across log lines, error bodies and traces.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(String);
+
+impl RequestId {
+    /// Uses the client supplied id if it is reasonable, otherwise generates a new random id.
+    fn from_header(value: Option<&HeaderValue>) -> RequestId {
+        match value.and_then(|value| value.to_str().ok()) {
+            Some(id)
+                if !id.is_empty()
+                    && id.len() <= MAX_REQUEST_ID_LENGTH
+                    && id.chars().all(|c| c.is_ascii_graphic()) =>
+            {
+                RequestId(id.to_string())
+            }
+            _ => RequestId(Uuid::new_v4().to_string()),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Returns the id of the request currently being handled, if called from within a request.
+pub fn current() -> Option<RequestId> {
+    CURRENT_REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+/// Middleware which accepts or generates an `X-Request-Id` for every request.
+///
+/// The id is stored in the request extensions, made available through [current] for the
+/// duration of the request, and echoed back in the response headers.
+pub struct RequestIdentifier;
+
+impl<S, B> Transform<S, ServiceRequest> for RequestIdentifier
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = RequestIdentifierMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestIdentifierMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct RequestIdentifierMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestIdentifierMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, request: ServiceRequest) -> Self::Future {
+        let request_id = RequestId::from_header(request.headers().get(REQUEST_ID_HEADER));
+        request.extensions_mut().insert(request_id.clone());
+
+        let service = Rc::clone(&self.service);
+
+        Box::pin(CURRENT_REQUEST_ID.scope(request_id.clone(), async move {
+            let mut response = service.call(request).await?;
+
+            if let Ok(value) = HeaderValue::from_str(request_id.as_str()) {
+                response.headers_mut().insert(REQUEST_ID_HEADER, value);
+            }
+
+            Ok(response)
+        }))
+    }
+}
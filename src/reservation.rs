@@ -0,0 +1,1089 @@
+use std::time::Duration;
+
+use actix_web::{
+    delete, get, post, put,
+    web::{Data, Json, Path},
+    HttpResponse, Responder,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{query, query_as, PgConnection, Pool, Postgres};
+use time::PrimitiveDateTime;
+use utoipa::ToSchema;
+
+use crate::{
+    background::ShutdownSignal,
+    error::{constraint_violation, database_error, Violation},
+    models::{
+        iso_datetime, now, AppState, MachineType, Reservation, ReservationPolicy,
+        ReservationStatus, WaitlistEntry, WaitlistStatus,
+    },
+    room,
+    tenant::Tenant,
+    user,
+};
+
+/// How often claims and no-shows are expired and free machines offered to waitlists.
+const DISPATCH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Open reports of a type at least this severe keep a machine from being offered, which is the
+/// severity of `Broken`.
+const OUT_OF_SERVICE_SEVERITY: i32 = 2;
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct WaitlistSubmission {
+    username: String,
+    machine_type: MachineType,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ReservationSubmission {
+    machine_id: String,
+    username: String,
+    #[serde(with = "iso_datetime")]
+    start_time: PrimitiveDateTime,
+    #[serde(with = "iso_datetime")]
+    end_time: PrimitiveDateTime,
+}
+
+/// The reservation policy of an organization, or the defaults if it has not set one.
+pub async fn fetch_policy(
+    database: &Pool<Postgres>,
+    tenant: &Tenant,
+) -> Result<ReservationPolicy, sqlx::Error> {
+    query_as!(
+        ReservationPolicy,
+        r#"
+        SELECT
+            max_active_reservations,
+            max_duration_minutes,
+            claim_minutes,
+            no_show_minutes,
+            blackout_start,
+            blackout_end
+        FROM reservation_policy
+        WHERE organization_id = $1
+        "#,
+        tenant.organization_id
+    )
+    .fetch_optional(database)
+    .await
+    .map(Option::unwrap_or_default)
+}
+
+/// Whether `start..end` overlaps the daily blackout hours of `policy`, which may span midnight.
+fn overlaps_blackout(
+    policy: &ReservationPolicy,
+    start: PrimitiveDateTime,
+    end: PrimitiveDateTime,
+) -> bool {
+    let (Some(blackout_start), Some(blackout_end)) = (policy.blackout_start, policy.blackout_end)
+    else {
+        return false;
+    };
+
+    if blackout_start == blackout_end {
+        return false;
+    }
+
+    // The blackout starting the day before may still be running when the slot starts.
+    let mut day = start.date() - time::Duration::days(1);
+    while day <= end.date() {
+        let from = PrimitiveDateTime::new(day, blackout_start);
+        let until = match blackout_start < blackout_end {
+            true => PrimitiveDateTime::new(day, blackout_end),
+            false => PrimitiveDateTime::new(day + time::Duration::days(1), blackout_end),
+        };
+
+        if start < until && from < end {
+            return true;
+        }
+        day += time::Duration::days(1);
+    }
+
+    false
+}
+
+/// Expires lapsed claims and no-shows, then offers free machines to the longest waiting
+/// entries, of one room or of every room. Returns how many machines were offered.
+///
+/// A machine is free when no session is running on it, it is not claimed, no reservation
+/// takes place before the claim would expire, and it neither has an open report of a type as
+/// severe as Broken nor is shown to be faulty by its telemetry.
+pub async fn dispatch(database: &Pool<Postgres>, room_id: Option<i32>) -> Result<u64, sqlx::Error> {
+    let now = now();
+    let defaults = ReservationPolicy::default();
+    let mut transaction = database.begin().await?;
+
+    query!(
+        r#"
+        UPDATE waitlist_entry
+        SET status = 'expired'
+        WHERE status = 'claimed' AND (claim_expires_time < $1 OR machine_id IS NULL)
+        "#,
+        now
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    query!(
+        r#"
+        UPDATE reservation
+        SET status = 'no_show'
+        FROM room
+        LEFT JOIN reservation_policy ON reservation_policy.organization_id = room.organization_id
+        WHERE room.id = reservation.room_id
+            AND reservation.status = 'booked'
+            AND reservation.start_time
+                + make_interval(mins => COALESCE(reservation_policy.no_show_minutes, $2)) < $1
+        "#,
+        now,
+        defaults.no_show_minutes
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    let entries = query!(
+        r#"
+        SELECT
+            waitlist_entry.id,
+            waitlist_entry.room_id,
+            waitlist_entry.machine_type,
+            COALESCE(reservation_policy.claim_minutes, $2) AS "claim_minutes!"
+        FROM waitlist_entry
+        JOIN room ON room.id = waitlist_entry.room_id
+        LEFT JOIN reservation_policy ON reservation_policy.organization_id = room.organization_id
+        WHERE waitlist_entry.status = 'waiting'
+            AND ($1::INTEGER IS NULL OR waitlist_entry.room_id = $1)
+        ORDER BY waitlist_entry.join_time
+        FOR UPDATE OF waitlist_entry SKIP LOCKED
+        "#,
+        room_id,
+        defaults.claim_minutes
+    )
+    .fetch_all(&mut transaction)
+    .await?;
+
+    let mut offered = 0;
+    for entry in entries {
+        let claim_expires_time = now + Duration::from_secs(entry.claim_minutes as u64 * 60);
+
+        let machine = query!(
+            r#"
+            SELECT machine_id
+            FROM machine
+            WHERE room_id = $1
+                AND type = $2
+                AND NOT EXISTS (
+                    SELECT 1
+                    FROM machine_session
+                    WHERE machine_session.room_id = machine.room_id
+                        AND machine_session.machine_id = machine.machine_id
+                        AND machine_session.end_time IS NULL
+                )
+                AND NOT EXISTS (
+                    SELECT 1
+                    FROM waitlist_entry
+                    WHERE waitlist_entry.room_id = machine.room_id
+                        AND waitlist_entry.machine_id = machine.machine_id
+                        AND waitlist_entry.status = 'claimed'
+                )
+                AND NOT EXISTS (
+                    SELECT 1
+                    FROM reservation
+                    WHERE reservation.room_id = machine.room_id
+                        AND reservation.machine_id = machine.machine_id
+                        AND reservation.status IN ('booked', 'checked_in')
+                        AND reservation.start_time < $4
+                        AND reservation.end_time > $3
+                )
+                AND NOT EXISTS (
+                    SELECT 1
+                    FROM report
+                    JOIN report_type ON report_type.name = report.type
+                    WHERE report.room_id = machine.room_id
+                        AND report.machine_id = machine.machine_id
+                        AND report_type.severity >= $5
+                        AND report.archived = false
+                )
+                AND NOT EXISTS (
+                    SELECT 1
+                    FROM machine_operating_state
+                    WHERE machine_operating_state.room_id = machine.room_id
+                        AND machine_operating_state.machine_id = machine.machine_id
+                        AND machine_operating_state.state = 'fault'
+                )
+            ORDER BY machine_id
+            LIMIT 1
+            FOR UPDATE SKIP LOCKED
+            "#,
+            entry.room_id,
+            entry.machine_type,
+            now,
+            claim_expires_time,
+            OUT_OF_SERVICE_SEVERITY
+        )
+        .fetch_optional(&mut transaction)
+        .await?;
+
+        let Some(machine) = machine else {
+            continue;
+        };
+
+        query!(
+            r#"
+            UPDATE waitlist_entry
+            SET status = 'claimed', machine_id = $2, claim_expires_time = $3
+            WHERE id = $1
+            "#,
+            entry.id,
+            machine.machine_id,
+            claim_expires_time
+        )
+        .execute(&mut transaction)
+        .await?;
+
+        offered += 1;
+    }
+
+    transaction.commit().await?;
+    Ok(offered)
+}
+
+/// Dispatches waitlists of every room until shutdown is requested.
+pub async fn dispatch_periodically(database: Pool<Postgres>, mut shutdown: ShutdownSignal) {
+    while shutdown.sleep(DISPATCH_INTERVAL).await {
+        match dispatch(&database, None).await {
+            Ok(0) => {}
+            Ok(offered) => log::info!("Offered {offered} machines to waitlists"),
+            Err(err) => log::error!("Failed to dispatch waitlists: {err}"),
+        }
+    }
+}
+
+/// Checks whether `username` may run a cycle on a machine from `start` until `end`,
+/// returning why not if the machine is claimed by or reserved for someone else.
+pub async fn find_conflicting_hold(
+    connection: &mut PgConnection,
+    room_id: &i32,
+    machine_id: &String,
+    username: &String,
+    start: PrimitiveDateTime,
+    end: PrimitiveDateTime,
+) -> Result<Option<String>, sqlx::Error> {
+    let claim = query!(
+        r#"
+        SELECT claim_expires_time AS "claim_expires_time!"
+        FROM waitlist_entry
+        WHERE room_id = $1
+            AND machine_id = $2
+            AND status = 'claimed'
+            AND username <> $3
+            AND claim_expires_time >= $4
+        "#,
+        room_id,
+        machine_id,
+        username,
+        start
+    )
+    .fetch_optional(&mut *connection)
+    .await?;
+
+    if let Some(claim) = claim {
+        return Ok(Some(format!(
+            "Machine id {machine_id} in room id {room_id} is held for the waitlist until {}.",
+            claim.claim_expires_time
+        )));
+    }
+
+    let reservation = query!(
+        r#"
+        SELECT start_time
+        FROM reservation
+        WHERE room_id = $1
+            AND machine_id = $2
+            AND status IN ('booked', 'checked_in')
+            AND username <> $3
+            AND start_time < $5
+            AND end_time > $4
+        ORDER BY start_time
+        LIMIT 1
+        "#,
+        room_id,
+        machine_id,
+        username,
+        start,
+        end
+    )
+    .fetch_optional(&mut *connection)
+    .await?;
+
+    Ok(reservation.map(|reservation| {
+        format!(
+            "Machine id {machine_id} in room id {room_id} is reserved from {}.",
+            reservation.start_time
+        )
+    }))
+}
+
+/// Records that `username` started a cycle on a machine, using their claim on it
+/// and checking in to their reservation of it.
+pub async fn use_holds(
+    connection: &mut PgConnection,
+    room_id: &i32,
+    machine_id: &String,
+    username: &String,
+    start: PrimitiveDateTime,
+    end: PrimitiveDateTime,
+) -> Result<(), sqlx::Error> {
+    query!(
+        r#"
+        UPDATE waitlist_entry
+        SET status = 'used'
+        WHERE room_id = $1 AND machine_id = $2 AND username = $3 AND status = 'claimed'
+        "#,
+        room_id,
+        machine_id,
+        username
+    )
+    .execute(&mut *connection)
+    .await?;
+
+    query!(
+        r#"
+        UPDATE reservation
+        SET status = 'checked_in'
+        WHERE room_id = $1
+            AND machine_id = $2
+            AND username = $3
+            AND status = 'booked'
+            AND start_time < $5
+            AND end_time > $4
+        "#,
+        room_id,
+        machine_id,
+        username,
+        start,
+        end
+    )
+    .execute(&mut *connection)
+    .await?;
+
+    Ok(())
+}
+
+#[utoipa::path(
+    context_path = "/room",
+    params(Tenant),
+    request_body(content = WaitlistSubmission, content_type = "application/json", example = json!({
+        "username": "admin",
+        "machine_type": "Dryer"
+    })),
+    responses(
+        (status = 201, description = "The user joined the waitlist, and was offered a machine straight away if one is free", body = WaitlistEntry, example = json!({
+            "entry_id": 1,
+            "room_id": 1,
+            "machine_type": "Dryer",
+            "username": "admin",
+            "join_time": "2023-01-01T12:00:00",
+            "status": "Claimed",
+            "machine_id": "B",
+            "claim_expires_time": "2023-01-01T12:10:00"
+        })),
+        (status = 400, description = "The requested query was invalid"),
+        (status = 404, description = "The requested room id was not found"),
+        (status = 409, description = "The user is already waiting for this type of machine in the room"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[post("/{room_id}/waitlist")]
+async fn join_waitlist(
+    data: Data<AppState>,
+    tenant: Tenant,
+    path: Path<i32>,
+    Json(waitlist_submission): Json<WaitlistSubmission>,
+) -> impl Responder {
+    let room_id = path.into_inner();
+
+    let room_present = match room::is_room_present(&data.database, &tenant, &room_id).await {
+        Ok(result) => result,
+        Err(err) => return database_error("check room presence", err),
+    };
+
+    if !room_present {
+        return HttpResponse::NotFound().json(format!("Room id {room_id} was not found."));
+    }
+
+    let username = &waitlist_submission.username;
+    let username_present = match user::is_username_present(&data.database, &tenant, username).await
+    {
+        Ok(result) => result,
+        Err(err) => return database_error("check username presence", err),
+    };
+
+    if !username_present {
+        return HttpResponse::BadRequest().json(format!("Username {username} was not found."));
+    }
+
+    let machine_type_present = match query!(
+        r#"
+        SELECT machine_id
+        FROM machine
+        WHERE room_id = $1 AND type = $2
+        LIMIT 1
+        "#,
+        &room_id,
+        &waitlist_submission.machine_type as &MachineType
+    )
+    .fetch_optional(&data.database)
+    .await
+    {
+        Ok(result) => result.is_some(),
+        Err(err) => return database_error("check room machine types", err),
+    };
+
+    if !machine_type_present {
+        return HttpResponse::BadRequest().json(format!(
+            "Room id {room_id} has no machines of type {}.",
+            &waitlist_submission.machine_type.0
+        ));
+    }
+
+    let entry_id = match query!(
+        r#"
+        INSERT INTO waitlist_entry (room_id, machine_type, username, join_time, organization_id)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (room_id, machine_type, username) WHERE status IN ('waiting', 'claimed')
+            DO NOTHING
+        RETURNING id
+        "#,
+        &room_id,
+        &waitlist_submission.machine_type as &MachineType,
+        username,
+        now(),
+        tenant.organization_id
+    )
+    .fetch_optional(&data.database)
+    .await
+    {
+        Ok(Some(entry)) => entry.id,
+        Ok(None) => {
+            return HttpResponse::Conflict().json(format!(
+                "Username {username} is already waiting for a {} in room id {room_id}.",
+                &waitlist_submission.machine_type.0
+            ))
+        }
+        Err(err) => return database_error("insert waitlist entry", err),
+    };
+
+    if let Err(err) = dispatch(&data.database, Some(room_id)).await {
+        return database_error("dispatch waitlist", err);
+    }
+
+    match query_as!(
+        WaitlistEntry,
+        r#"
+        SELECT
+            id AS "entry_id: i32",
+            room_id,
+            machine_type AS "machine_type: MachineType",
+            username,
+            join_time,
+            status AS "status: WaitlistStatus",
+            machine_id,
+            claim_expires_time
+        FROM waitlist_entry
+        WHERE id = $1
+        "#,
+        entry_id
+    )
+    .fetch_one(&data.database)
+    .await
+    {
+        Ok(entry) => HttpResponse::Created().json(entry),
+        Err(err) => database_error("fetch waitlist entry", err),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/room",
+    params(Tenant),
+    responses(
+        (status = 200, description = "List of waiting and claimed entries in the requested room's waitlists, longest waiting first", body = Vec<WaitlistEntry>, example = json!([{
+            "entry_id": 1,
+            "room_id": 1,
+            "machine_type": "Dryer",
+            "username": "admin",
+            "join_time": "2023-01-01T12:00:00",
+            "status": "Waiting",
+            "machine_id": null,
+            "claim_expires_time": null
+        }])),
+        (status = 404, description = "The requested room id was not found"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[get("/{room_id}/waitlist")]
+async fn get_room_waitlist(
+    data: Data<AppState>,
+    tenant: Tenant,
+    path: Path<i32>,
+) -> impl Responder {
+    let room_id = path.into_inner();
+
+    let room_present = match room::is_room_present(&data.database, &tenant, &room_id).await {
+        Ok(result) => result,
+        Err(err) => return database_error("check room presence", err),
+    };
+
+    if !room_present {
+        return HttpResponse::NotFound().json(format!("Room id {room_id} was not found."));
+    }
+
+    match query_as!(
+        WaitlistEntry,
+        r#"
+        SELECT
+            id AS "entry_id: i32",
+            room_id,
+            machine_type AS "machine_type: MachineType",
+            username,
+            join_time,
+            status AS "status: WaitlistStatus",
+            machine_id,
+            claim_expires_time
+        FROM waitlist_entry
+        WHERE room_id = $1 AND status IN ('waiting', 'claimed')
+        ORDER BY join_time
+        "#,
+        &room_id
+    )
+    .fetch_all(&data.database)
+    .await
+    {
+        Ok(entries) => HttpResponse::Ok().json(entries),
+        Err(err) => database_error("fetch room waitlist", err),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/room",
+    params(Tenant),
+    responses(
+        (status = 200, description = "The requested waitlist entry was cancelled, releasing any machine claimed by it", body = WaitlistEntry, example = json!({
+            "entry_id": 1,
+            "room_id": 1,
+            "machine_type": "Dryer",
+            "username": "admin",
+            "join_time": "2023-01-01T12:00:00",
+            "status": "Cancelled",
+            "machine_id": null,
+            "claim_expires_time": null
+        })),
+        (status = 404, description = "The requested waitlist entry was not found or is no longer waiting"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[delete("/{room_id}/waitlist/{entry_id}")]
+async fn leave_waitlist(
+    data: Data<AppState>,
+    tenant: Tenant,
+    path: Path<(i32, i32)>,
+) -> impl Responder {
+    let (room_id, entry_id) = path.into_inner();
+
+    let entry = match query_as!(
+        WaitlistEntry,
+        r#"
+        UPDATE waitlist_entry
+        SET status = 'cancelled'
+        WHERE id = $1
+            AND room_id = $2
+            AND status IN ('waiting', 'claimed')
+            AND room_id IN (SELECT id FROM room WHERE organization_id = $3)
+        RETURNING
+            id AS "entry_id: i32",
+            room_id,
+            machine_type AS "machine_type: MachineType",
+            username,
+            join_time,
+            status AS "status: WaitlistStatus",
+            machine_id,
+            claim_expires_time
+        "#,
+        entry_id,
+        room_id,
+        tenant.organization_id
+    )
+    .fetch_optional(&data.database)
+    .await
+    {
+        Ok(Some(entry)) => entry,
+        Ok(None) => {
+            return HttpResponse::NotFound().json(format!(
+                "Waitlist entry id {entry_id} was not found in room id {room_id} or is no longer waiting."
+            ))
+        }
+        Err(err) => return database_error("cancel waitlist entry", err),
+    };
+
+    // A released claim can go to the next in line straight away.
+    if let Err(err) = dispatch(&data.database, Some(room_id)).await {
+        log::error!("Failed to dispatch the waitlist of room id {room_id}: {err}");
+    }
+
+    HttpResponse::Ok().json(entry)
+}
+
+#[utoipa::path(
+    context_path = "/room",
+    params(Tenant),
+    request_body(content = ReservationSubmission, content_type = "application/json", example = json!({
+        "machine_id": "A",
+        "username": "admin",
+        "start_time": "2023-01-01T12:00:00",
+        "end_time": "2023-01-01T13:00:00"
+    })),
+    responses(
+        (status = 201, description = "The requested machine was reserved", body = Reservation, example = json!({
+            "reservation_id": 1,
+            "room_id": 1,
+            "machine_id": "A",
+            "username": "admin",
+            "start_time": "2023-01-01T12:00:00",
+            "end_time": "2023-01-01T13:00:00",
+            "status": "Booked",
+            "created_time": "2022-12-31T18:00:00"
+        })),
+        (status = 400, description = "The requested query was invalid or breaks the organization's reservation policy"),
+        (status = 404, description = "The requested room id was not found"),
+        (status = 409, description = "The machine is already reserved for part of the requested time"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[post("/{room_id}/reservations")]
+async fn add_reservation(
+    data: Data<AppState>,
+    tenant: Tenant,
+    path: Path<i32>,
+    Json(reservation_submission): Json<ReservationSubmission>,
+) -> impl Responder {
+    let room_id = path.into_inner();
+    let ReservationSubmission {
+        machine_id,
+        username,
+        start_time,
+        end_time,
+    } = reservation_submission;
+
+    let room_present = match room::is_room_present(&data.database, &tenant, &room_id).await {
+        Ok(result) => result,
+        Err(err) => return database_error("check room presence", err),
+    };
+
+    if !room_present {
+        return HttpResponse::NotFound().json(format!("Room id {room_id} was not found."));
+    }
+
+    let username_present = match user::is_username_present(&data.database, &tenant, &username).await
+    {
+        Ok(result) => result,
+        Err(err) => return database_error("check username presence", err),
+    };
+
+    if !username_present {
+        return HttpResponse::BadRequest().json(format!("Username {username} was not found."));
+    }
+
+    let policy = match fetch_policy(&data.database, &tenant).await {
+        Ok(policy) => policy,
+        Err(err) => return database_error("fetch reservation policy", err),
+    };
+
+    let created_time = now();
+
+    if end_time <= start_time {
+        return HttpResponse::BadRequest().json("The end time must be after the start time.");
+    }
+
+    if end_time <= created_time {
+        return HttpResponse::BadRequest().json("The reservation must not be in the past.");
+    }
+
+    if (end_time - start_time).whole_minutes() > i64::from(policy.max_duration_minutes) {
+        return HttpResponse::BadRequest().json(format!(
+            "Reservations may last at most {} minutes.",
+            policy.max_duration_minutes
+        ));
+    }
+
+    if overlaps_blackout(&policy, start_time, end_time) {
+        return HttpResponse::BadRequest()
+            .json("The reservation overlaps the hours during which reservations are not allowed.");
+    }
+
+    let mut transaction = match data.database.begin().await {
+        Ok(transaction) => transaction,
+        Err(err) => return database_error("begin reservation", err),
+    };
+
+    // Locking the user serializes their reservations, so they cannot book more than the maximum.
+    if let Err(err) = query!(
+        r#"
+        SELECT username
+        FROM public.user
+        WHERE username = $1 AND organization_id = $2
+        FOR UPDATE
+        "#,
+        &username,
+        tenant.organization_id
+    )
+    .fetch_optional(&mut transaction)
+    .await
+    {
+        return database_error("lock user", err);
+    }
+
+    // Locking the machine serializes reservations of it, so overlaps cannot slip in between.
+    let machine_present = match query!(
+        r#"
+        SELECT machine_id
+        FROM machine
+        WHERE room_id = $1 AND machine_id = $2
+        FOR UPDATE
+        "#,
+        &room_id,
+        &machine_id
+    )
+    .fetch_optional(&mut transaction)
+    .await
+    {
+        Ok(result) => result.is_some(),
+        Err(err) => return database_error("lock machine", err),
+    };
+
+    if !machine_present {
+        return HttpResponse::BadRequest().json(format!(
+            "Room id {room_id} does not contain machine id {machine_id}."
+        ));
+    }
+
+    let active_reservations = match query!(
+        r#"
+        SELECT COUNT(*) AS "count!"
+        FROM reservation
+        WHERE username = $1 AND organization_id = $2 AND status = 'booked' AND end_time > $3
+        "#,
+        &username,
+        tenant.organization_id,
+        created_time
+    )
+    .fetch_one(&mut transaction)
+    .await
+    {
+        Ok(result) => result.count,
+        Err(err) => return database_error("count active reservations", err),
+    };
+
+    if active_reservations >= i64::from(policy.max_active_reservations) {
+        return HttpResponse::BadRequest().json(format!(
+            "Username {username} already holds the maximum of {} reservations.",
+            policy.max_active_reservations
+        ));
+    }
+
+    let conflict = match query!(
+        r#"
+        SELECT id
+        FROM reservation
+        WHERE room_id = $1
+            AND machine_id = $2
+            AND status IN ('booked', 'checked_in')
+            AND start_time < $4
+            AND end_time > $3
+        LIMIT 1
+        "#,
+        &room_id,
+        &machine_id,
+        start_time,
+        end_time
+    )
+    .fetch_optional(&mut transaction)
+    .await
+    {
+        Ok(result) => result,
+        Err(err) => return database_error("check reservation conflicts", err),
+    };
+
+    if let Some(conflict) = conflict {
+        return HttpResponse::Conflict().json(format!(
+            "Machine id {machine_id} is already reserved for part of that time by reservation id {}.",
+            conflict.id
+        ));
+    }
+
+    let reservation = match query_as!(
+        Reservation,
+        r#"
+        INSERT INTO reservation (
+            room_id, machine_id, username, start_time, end_time, created_time, organization_id
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        RETURNING
+            id AS "reservation_id: i32",
+            room_id,
+            machine_id,
+            username,
+            start_time,
+            end_time,
+            status AS "status: ReservationStatus",
+            created_time
+        "#,
+        &room_id,
+        &machine_id,
+        &username,
+        start_time,
+        end_time,
+        created_time,
+        tenant.organization_id
+    )
+    .fetch_one(&mut transaction)
+    .await
+    {
+        Ok(reservation) => reservation,
+        Err(err) => return database_error("insert reservation", err),
+    };
+
+    match transaction.commit().await {
+        Ok(()) => HttpResponse::Created().json(reservation),
+        Err(err) => database_error("commit reservation", err),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/room",
+    params(Tenant),
+    responses(
+        (status = 200, description = "List of booked and checked in reservations in the requested room which have not ended, soonest first", body = Vec<Reservation>, example = json!([{
+            "reservation_id": 1,
+            "room_id": 1,
+            "machine_id": "A",
+            "username": "admin",
+            "start_time": "2023-01-01T12:00:00",
+            "end_time": "2023-01-01T13:00:00",
+            "status": "Booked",
+            "created_time": "2022-12-31T18:00:00"
+        }])),
+        (status = 404, description = "The requested room id was not found"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[get("/{room_id}/reservations")]
+async fn get_room_reservations(
+    data: Data<AppState>,
+    tenant: Tenant,
+    path: Path<i32>,
+) -> impl Responder {
+    let room_id = path.into_inner();
+
+    let room_present = match room::is_room_present(&data.database, &tenant, &room_id).await {
+        Ok(result) => result,
+        Err(err) => return database_error("check room presence", err),
+    };
+
+    if !room_present {
+        return HttpResponse::NotFound().json(format!("Room id {room_id} was not found."));
+    }
+
+    match query_as!(
+        Reservation,
+        r#"
+        SELECT
+            id AS "reservation_id: i32",
+            room_id,
+            machine_id,
+            username,
+            start_time,
+            end_time,
+            status AS "status: ReservationStatus",
+            created_time
+        FROM reservation
+        WHERE room_id = $1 AND status IN ('booked', 'checked_in') AND end_time > $2
+        ORDER BY start_time
+        "#,
+        &room_id,
+        now()
+    )
+    .fetch_all(&data.database)
+    .await
+    {
+        Ok(reservations) => HttpResponse::Ok().json(reservations),
+        Err(err) => database_error("fetch room reservations", err),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/room",
+    params(Tenant),
+    responses(
+        (status = 200, description = "The requested reservation was cancelled", body = Reservation, example = json!({
+            "reservation_id": 1,
+            "room_id": 1,
+            "machine_id": "A",
+            "username": "admin",
+            "start_time": "2023-01-01T12:00:00",
+            "end_time": "2023-01-01T13:00:00",
+            "status": "Cancelled",
+            "created_time": "2022-12-31T18:00:00"
+        })),
+        (status = 404, description = "The requested reservation was not found or is no longer booked"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[delete("/{room_id}/reservations/{reservation_id}")]
+async fn cancel_reservation(
+    data: Data<AppState>,
+    tenant: Tenant,
+    path: Path<(i32, i32)>,
+) -> impl Responder {
+    let (room_id, reservation_id) = path.into_inner();
+
+    match query_as!(
+        Reservation,
+        r#"
+        UPDATE reservation
+        SET status = 'cancelled'
+        WHERE id = $1
+            AND room_id = $2
+            AND status = 'booked'
+            AND room_id IN (SELECT id FROM room WHERE organization_id = $3)
+        RETURNING
+            id AS "reservation_id: i32",
+            room_id,
+            machine_id,
+            username,
+            start_time,
+            end_time,
+            status AS "status: ReservationStatus",
+            created_time
+        "#,
+        reservation_id,
+        room_id,
+        tenant.organization_id
+    )
+    .fetch_optional(&data.database)
+    .await
+    {
+        Ok(Some(reservation)) => HttpResponse::Ok().json(reservation),
+        Ok(None) => HttpResponse::NotFound().json(format!(
+            "Reservation id {reservation_id} was not found in room id {room_id} or is no longer booked."
+        )),
+        Err(err) => database_error("cancel reservation", err),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/admin",
+    params(Tenant),
+    responses(
+        (status = 200, description = "The organization's reservation policy, or the defaults if it has not set one", body = ReservationPolicy, example = json!({
+            "max_active_reservations": 2,
+            "max_duration_minutes": 120,
+            "claim_minutes": 10,
+            "no_show_minutes": 15,
+            "blackout_start": "23:00",
+            "blackout_end": "07:00"
+        })),
+        (status = 401, description = "No valid admin credentials were given"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[get("/reservation-policy")]
+async fn get_reservation_policy(data: Data<AppState>, tenant: Tenant) -> impl Responder {
+    match fetch_policy(&data.database, &tenant).await {
+        Ok(policy) => HttpResponse::Ok().json(policy),
+        Err(err) => database_error("fetch reservation policy", err),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/admin",
+    params(Tenant),
+    request_body(content = ReservationPolicy, content_type = "application/json", example = json!({
+        "max_active_reservations": 2,
+        "max_duration_minutes": 120,
+        "claim_minutes": 10,
+        "no_show_minutes": 15,
+        "blackout_start": "23:00",
+        "blackout_end": "07:00"
+    })),
+    responses(
+        (status = 200, description = "The organization's reservation policy was replaced", body = ReservationPolicy, example = json!({
+            "max_active_reservations": 2,
+            "max_duration_minutes": 120,
+            "claim_minutes": 10,
+            "no_show_minutes": 15,
+            "blackout_start": "23:00",
+            "blackout_end": "07:00"
+        })),
+        (status = 400, description = "The requested query was invalid"),
+        (status = 401, description = "No valid admin credentials were given"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[put("/reservation-policy")]
+async fn update_reservation_policy(
+    data: Data<AppState>,
+    tenant: Tenant,
+    Json(policy): Json<ReservationPolicy>,
+) -> impl Responder {
+    if policy.blackout_start.is_some() != policy.blackout_end.is_some() {
+        return HttpResponse::BadRequest()
+            .json("The blackout start and end must be given together.");
+    }
+
+    match query_as!(
+        ReservationPolicy,
+        r#"
+        INSERT INTO reservation_policy (
+            organization_id,
+            max_active_reservations,
+            max_duration_minutes,
+            claim_minutes,
+            no_show_minutes,
+            blackout_start,
+            blackout_end
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        ON CONFLICT (organization_id) DO UPDATE SET
+            max_active_reservations = EXCLUDED.max_active_reservations,
+            max_duration_minutes = EXCLUDED.max_duration_minutes,
+            claim_minutes = EXCLUDED.claim_minutes,
+            no_show_minutes = EXCLUDED.no_show_minutes,
+            blackout_start = EXCLUDED.blackout_start,
+            blackout_end = EXCLUDED.blackout_end
+        RETURNING
+            max_active_reservations,
+            max_duration_minutes,
+            claim_minutes,
+            no_show_minutes,
+            blackout_start,
+            blackout_end
+        "#,
+        tenant.organization_id,
+        policy.max_active_reservations,
+        policy.max_duration_minutes,
+        policy.claim_minutes,
+        policy.no_show_minutes,
+        policy.blackout_start,
+        policy.blackout_end
+    )
+    .fetch_one(&data.database)
+    .await
+    {
+        Ok(policy) => HttpResponse::Ok().json(policy),
+        Err(err) => match constraint_violation(&err) {
+            Some(Violation::Check) => HttpResponse::BadRequest().json("The maximum active reservations cannot be negative, and the minutes must be positive."),
+            _ => database_error("update reservation policy", err),
+        },
+    }
+}
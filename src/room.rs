@@ -1,5 +1,5 @@
 use actix_web::{
-    delete, get, post,
+    delete, get, post, put,
     web::{Data, Json, Path},
     HttpResponse, Responder,
 };
@@ -7,25 +7,40 @@ use serde::{Deserialize, Serialize};
 use sqlx::{query, query_as, Pool, Postgres};
 use utoipa::ToSchema;
 
-use crate::models::{AppState, Machine, MachineType, Report, ReportType, Room};
+use crate::{
+    attachment,
+    building::is_building_present,
+    error::database_error,
+    models::{AppState, Machine, MachineType, PaymentType, Report, ReportType, Room},
+    tenant::Tenant,
+};
 
 #[derive(Serialize, Deserialize, ToSchema)]
 pub struct RoomSubmission {
     name: String,
     description: Option<String>,
+    #[serde(default)]
+    building_id: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct BuildingAssignment {
+    building_id: Option<i32>,
 }
 
 pub async fn is_room_present(
     database: &Pool<Postgres>,
+    tenant: &Tenant,
     room_id: &i32,
 ) -> Result<bool, sqlx::Error> {
     match query!(
         r#"
         SELECT id
         FROM room
-        WHERE id = $1
+        WHERE id = $1 AND organization_id = $2
         "#,
-        room_id
+        room_id,
+        tenant.organization_id
     )
     .fetch_optional(database)
     .await
@@ -37,61 +52,68 @@ pub async fn is_room_present(
 
 #[utoipa::path(
     context_path = "/room",
+    params(Tenant),
     responses(
         (status = 200, description = "Lists all rooms", body = Vec<Room>, example = json!([{
             "room_id": 1,
             "name": "Room 1",
-            "description": "Room 1 in Complex A"
+            "description": "Room 1 in Complex A",
+            "building_id": 1
         }])),
         (status = 500, description = "An internal server error occurred")
     )
 )]
 #[get("/")]
-async fn get_all_rooms(data: Data<AppState>) -> impl Responder {
+async fn get_all_rooms(data: Data<AppState>, tenant: Tenant) -> impl Responder {
     match query_as!(
         Room,
         r#"
-        SELECT id as "room_id: i32", name, description
+        SELECT id as "room_id: i32", name, description, building_id
         FROM room
-        "#
+        WHERE organization_id = $1
+        "#,
+        tenant.organization_id
     )
     .fetch_all(&data.database)
     .await
     {
         Ok(rooms) => HttpResponse::Ok().json(rooms),
-        Err(err) => HttpResponse::InternalServerError().json(err.to_string()),
+        Err(err) => database_error("fetch all rooms", err),
     }
 }
 
 #[utoipa::path(
     context_path = "/room",
+    params(Tenant),
     responses(
         (status = 200, description = "The requested room", body = Room, example = json!({
             "room_id": 1,
             "name": "Room 1",
-            "description": "Room 1 in Complex A"
+            "description": "Room 1 in Complex A",
+            "building_id": 1
         })),
         (status = 404, description = "The requested room was not found"),
         (status = 500, description = "An internal server error occurred")
     )
 )]
 #[get("/{room_id}")]
-async fn get_room(data: Data<AppState>, path: Path<i32>) -> impl Responder {
+async fn get_room(data: Data<AppState>, tenant: Tenant, path: Path<i32>) -> impl Responder {
     let room_id = path.into_inner();
 
     match query_as!(
         Room,
         r#"
-        SELECT id as "room_id: i32", name, description
+        SELECT id as "room_id: i32", name, description, building_id
         FROM room
-        WHERE id = $1
+        WHERE id = $1 AND organization_id = $2
         "#,
-        room_id
+        room_id,
+        tenant.organization_id
     )
     .fetch_optional(&data.database)
     .await
     {
-        Err(err) => HttpResponse::InternalServerError().json(err.to_string()),
+        Err(err) => database_error("fetch room", err),
         Ok(room) => match room {
             Some(room) => HttpResponse::Ok().json(&room),
             None => HttpResponse::NotFound().json(format!("The room id {room_id} was not found.")),
@@ -101,109 +123,225 @@ async fn get_room(data: Data<AppState>, path: Path<i32>) -> impl Responder {
 
 #[utoipa::path(
     context_path = "/room",
+    params(Tenant),
     request_body(content = RoomSubmission, content_type = "application/json", example = json!({
         "name": "Room 1",
-        "description": "Room 1 in Complex A"
+        "description": "Room 1 in Complex A",
+        "building_id": 1
     })),
     responses(
         (status = 201, description = "The requested room was created", body = Room, example = json!({
             "room_id": 1,
             "name": "Room 1",
-            "description": "Room 1 in Complex A"
+            "description": "Room 1 in Complex A",
+            "building_id": 1
         })),
+        (status = 400, description = "The requested building does not exist"),
         (status = 500, description = "An internal server error occurred")
     )
 )]
 #[post("/")]
 async fn add_room(
     data: Data<AppState>,
+    tenant: Tenant,
     Json(room_submission): Json<RoomSubmission>,
 ) -> impl Responder {
+    if let Some(building_id) = room_submission.building_id {
+        let building_present =
+            match is_building_present(&data.database, &tenant, &building_id).await {
+                Ok(result) => result,
+                Err(err) => return database_error("check building presence", err),
+            };
+
+        if !building_present {
+            return HttpResponse::BadRequest()
+                .json(format!("Building id {building_id} was not found."));
+        }
+    }
+
     match query_as!(
         Room,
         r#"
-        INSERT INTO room (name, description)
-        VALUES ($1, $2)
+        INSERT INTO room (name, description, building_id, organization_id)
+        VALUES ($1, $2, $3, $4)
         RETURNING
             id AS "room_id: i32",
             name,
-            description
+            description,
+            building_id
         "#,
         &room_submission.name,
-        room_submission.description
+        room_submission.description,
+        room_submission.building_id,
+        tenant.organization_id
     )
     .fetch_one(&data.database)
     .await
     {
         Ok(room) => HttpResponse::Created().json(room),
-        Err(err) => HttpResponse::InternalServerError().json(err.to_string()),
+        Err(err) => database_error("insert room", err),
     }
 }
 
 #[utoipa::path(
     context_path = "/room",
+    params(Tenant),
+    request_body(
+        content = BuildingAssignment,
+        content_type = "application/json",
+        description = "JSON object containing the building the room is in, or null to unassign it",
+        example = json!({
+            "building_id": 1
+        })
+    ),
+    responses(
+        (status = 200, description = "The requested room was assigned to the building", body = Room, example = json!({
+            "room_id": 1,
+            "name": "Room 1",
+            "description": "Room 1 in Complex A",
+            "building_id": 1
+        })),
+        (status = 400, description = "The requested building does not exist"),
+        (status = 404, description = "The requested room was not found"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[put("/{room_id}/building")]
+async fn assign_room_building(
+    data: Data<AppState>,
+    tenant: Tenant,
+    path: Path<i32>,
+    Json(assignment): Json<BuildingAssignment>,
+) -> impl Responder {
+    let room_id = path.into_inner();
+
+    if let Some(building_id) = assignment.building_id {
+        let building_present =
+            match is_building_present(&data.database, &tenant, &building_id).await {
+                Ok(result) => result,
+                Err(err) => return database_error("check building presence", err),
+            };
+
+        if !building_present {
+            return HttpResponse::BadRequest()
+                .json(format!("Building id {building_id} was not found."));
+        }
+    }
+
+    match query_as!(
+        Room,
+        r#"
+        UPDATE room
+        SET building_id = $2
+        WHERE id = $1 AND organization_id = $3
+        RETURNING
+            id AS "room_id: i32",
+            name,
+            description,
+            building_id
+        "#,
+        &room_id,
+        assignment.building_id,
+        tenant.organization_id
+    )
+    .fetch_optional(&data.database)
+    .await
+    {
+        Ok(Some(room)) => HttpResponse::Ok().json(room),
+        Ok(None) => HttpResponse::NotFound().json(format!("Room id {room_id} was not found.")),
+        Err(err) => database_error("assign room building", err),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/room",
+    params(Tenant),
     responses(
         (status = 200, description = "The requested room was deleted", body = Room, example = json!({
             "room_id": 1,
             "name": "Room 1",
-            "description": "Room 1 in Complex A"
+            "description": "Room 1 in Complex A",
+            "building_id": 1
         })),
         (status = 404, description = "The requested room was not found"),
         (status = 500, description = "An internal server error occurred")
     )
 )]
 #[delete("/{room_id}")]
-async fn delete_room(data: Data<AppState>, path: Path<i32>) -> impl Responder {
+async fn delete_room(data: Data<AppState>, tenant: Tenant, path: Path<i32>) -> impl Responder {
     let room_id = path.into_inner();
 
-    let room_present = match is_room_present(&data.database, &room_id).await {
+    let room_present = match is_room_present(&data.database, &tenant, &room_id).await {
         Ok(result) => result,
-        Err(err) => return HttpResponse::InternalServerError().json(err.to_string()),
+        Err(err) => return database_error("check room presence", err),
     };
 
     if !room_present {
         return HttpResponse::NotFound().json(format!("Room id {room_id} was not found."));
     }
 
+    let blob_keys = match attachment::room_blob_keys(&data.database, &room_id).await {
+        Ok(keys) => keys,
+        Err(err) => return database_error("fetch room attachments", err),
+    };
+
     match query_as!(
         Room,
         r#"
         DELETE FROM room
-        WHERE id = $1
+        WHERE id = $1 AND organization_id = $2
         RETURNING
             id AS "room_id: i32",
             name,
-            description
+            description,
+            building_id
         "#,
-        &room_id
+        &room_id,
+        tenant.organization_id
     )
     .fetch_one(&data.database)
     .await
     {
-        Ok(room) => HttpResponse::Ok().json(room),
-        Err(err) => HttpResponse::InternalServerError().json(err.to_string()),
+        Ok(room) => {
+            attachment::remove_blobs(&data.blob_store, &blob_keys).await;
+            HttpResponse::Ok().json(room)
+        }
+        Err(err) => database_error("delete room", err),
     }
 }
 
 #[utoipa::path(
     context_path = "/room",
+    params(Tenant),
     responses(
         (status = 200, description = "List of all machines in thr requested room", body = Vec<Machine>, example = json!([{
             "room_id": 1,
             "machine_id": "A",
             "machine_type": "Dryer",
+            "manufacturer": "Speed Queen",
+            "model": "DR7",
+            "serial_number": "2203012345",
+            "install_date": "2022-03-01",
+            "warranty_expiry": "2027-03-01",
+            "capacity_kg": 9.0,
+            "payment_type": "Card",
+            "notes": "Stacked on top of washer B",
         }])),
         (status = 404, description = "The requested room id was not found"),
         (status = 500, description = "An internal server error occurred")
     )
 )]
 #[get("/{room_id}/machines")]
-async fn get_room_machines(data: Data<AppState>, path: Path<i32>) -> impl Responder {
+async fn get_room_machines(
+    data: Data<AppState>,
+    tenant: Tenant,
+    path: Path<i32>,
+) -> impl Responder {
     let room_id = path.into_inner();
 
-    let room_present = match is_room_present(&data.database, &room_id).await {
+    let room_present = match is_room_present(&data.database, &tenant, &room_id).await {
         Ok(result) => result,
-        Err(err) => return HttpResponse::InternalServerError().json(err.to_string()),
+        Err(err) => return database_error("check room presence", err),
     };
 
     if !room_present {
@@ -216,7 +354,15 @@ async fn get_room_machines(data: Data<AppState>, path: Path<i32>) -> impl Respon
         SELECT
             room_id,
             machine_id,
-            type as "machine_type: MachineType"
+            type as "machine_type: MachineType",
+            manufacturer,
+            model,
+            serial_number,
+            install_date,
+            warranty_expiry,
+            capacity_kg,
+            payment_type AS "payment_type: PaymentType",
+            notes
         FROM machine
         WHERE room_id = $1
         "#,
@@ -226,12 +372,13 @@ async fn get_room_machines(data: Data<AppState>, path: Path<i32>) -> impl Respon
     .await
     {
         Ok(machines) => HttpResponse::Ok().json(machines),
-        Err(err) => HttpResponse::InternalServerError().json(err.to_string()),
+        Err(err) => database_error("fetch room machines", err),
     }
 }
 
 #[utoipa::path(
     context_path = "/room",
+    params(Tenant),
     responses(
         (status = 200, description = "List of all unarchived reports for the requested room", body = Vec<Report>, example = json!([{
             "report_id": 1,
@@ -242,18 +389,22 @@ async fn get_room_machines(data: Data<AppState>, path: Path<i32>) -> impl Respon
             "description": "No heat",
             "time": "2023-01-01T12:00:00.000Z",
             "archived": false,
+            "automated": false,
+            "confirmation_count": 0,
+            "comment_count": 0,
+            "attachments": [],
         }])),
         (status = 404, description = "The requested room id was not found"),
         (status = 500, description = "An internal server error occurred")
     )
 )]
 #[get("/{room_id}/reports")]
-async fn get_room_reports(data: Data<AppState>, path: Path<i32>) -> impl Responder {
+async fn get_room_reports(data: Data<AppState>, tenant: Tenant, path: Path<i32>) -> impl Responder {
     let room_id = path.into_inner();
 
-    let room_present = match is_room_present(&data.database, &room_id).await {
+    let room_present = match is_room_present(&data.database, &tenant, &room_id).await {
         Ok(result) => result,
-        Err(err) => return HttpResponse::InternalServerError().json(err.to_string()),
+        Err(err) => return database_error("check room presence", err),
     };
 
     if !room_present {
@@ -271,7 +422,11 @@ async fn get_room_reports(data: Data<AppState>, path: Path<i32>) -> impl Respond
             time,
             type AS "report_type: ReportType",
             description,
-            archived
+            archived,
+            automated,
+            (SELECT COUNT(*) FROM report_confirmation WHERE report_id = report.id) AS "confirmation_count!: i64",
+            (SELECT COUNT(*) FROM report_comment WHERE report_id = report.id) AS "comment_count!: i64",
+            report_attachments(report.id) AS "attachments!: _"
         FROM report
         WHERE room_id = $1
             AND archived = false
@@ -282,12 +437,13 @@ async fn get_room_reports(data: Data<AppState>, path: Path<i32>) -> impl Respond
     .await
     {
         Ok(reports) => HttpResponse::Ok().json(reports),
-        Err(err) => HttpResponse::InternalServerError().json(err.to_string()),
+        Err(err) => database_error("fetch room reports", err),
     }
 }
 
 #[utoipa::path(
     context_path = "/room",
+    params(Tenant),
     responses(
         (status = 200, description = "List of all archived reports for the requested room", body = Vec<Report>, example = json!([{
             "report_id": 1,
@@ -298,18 +454,26 @@ async fn get_room_reports(data: Data<AppState>, path: Path<i32>) -> impl Respond
             "description": "No heat",
             "time": "2023-01-01T12:00:00.000Z",
             "archived": true,
+            "automated": false,
+            "confirmation_count": 0,
+            "comment_count": 0,
+            "attachments": [],
         }])),
         (status = 404, description = "The requested room id was not found"),
         (status = 500, description = "An internal server error occurred")
     )
 )]
 #[get("/{room_id}/reports/archived")]
-async fn get_room_archived_reports(data: Data<AppState>, path: Path<i32>) -> impl Responder {
+async fn get_room_archived_reports(
+    data: Data<AppState>,
+    tenant: Tenant,
+    path: Path<i32>,
+) -> impl Responder {
     let room_id = path.into_inner();
 
-    let room_present = match is_room_present(&data.database, &room_id).await {
+    let room_present = match is_room_present(&data.database, &tenant, &room_id).await {
         Ok(result) => result,
-        Err(err) => return HttpResponse::InternalServerError().json(err.to_string()),
+        Err(err) => return database_error("check room presence", err),
     };
 
     if !room_present {
@@ -327,7 +491,11 @@ async fn get_room_archived_reports(data: Data<AppState>, path: Path<i32>) -> imp
             time,
             type AS "report_type: ReportType",
             description,
-            archived
+            archived,
+            automated,
+            (SELECT COUNT(*) FROM report_confirmation WHERE report_id = report.id) AS "confirmation_count!: i64",
+            (SELECT COUNT(*) FROM report_comment WHERE report_id = report.id) AS "comment_count!: i64",
+            report_attachments(report.id) AS "attachments!: _"
         FROM report
         WHERE room_id = $1
             AND archived = false
@@ -338,6 +506,6 @@ async fn get_room_archived_reports(data: Data<AppState>, path: Path<i32>) -> imp
     .await
     {
         Ok(reports) => HttpResponse::Ok().json(reports),
-        Err(err) => HttpResponse::InternalServerError().json(err.to_string()),
+        Err(err) => database_error("fetch room archived reports", err),
     }
 }
@@ -0,0 +1,155 @@
+use actix_web::web::{self, ServiceConfig};
+
+use crate::{
+    admin::{self, AdminAuthentication},
+    attachment, building, comment, guest, health, machine, machine_telemetry, maintenance,
+    notification, qr, report, reservation, room, session, site, sla, user, work_order,
+};
+
+/// Registers every endpoint of the API, so that the server and the integration tests serve the
+/// same routes.
+pub fn configure(config: &mut ServiceConfig) {
+    config
+        .service(
+            web::scope("/admin")
+                .wrap(AdminAuthentication)
+                .service(admin::get_rate_limits)
+                .service(admin::get_machine_types)
+                .service(admin::add_machine_type)
+                .service(admin::update_machine_type)
+                .service(admin::get_report_types)
+                .service(admin::add_report_type)
+                .service(admin::update_report_type)
+                .service(admin::get_organizations)
+                .service(admin::add_organization)
+                .service(report::merge_reports)
+                .service(guest::get_guest_reports)
+                .service(guest::approve_guest_report)
+                .service(guest::reject_guest_report)
+                .service(reservation::get_reservation_policy)
+                .service(reservation::update_reservation_policy)
+                .service(machine_telemetry::get_telemetry_devices)
+                .service(machine_telemetry::add_telemetry_device)
+                .service(machine_telemetry::rotate_telemetry_device_key)
+                .service(machine_telemetry::delete_telemetry_device)
+                .service(sla::get_sla_policies)
+                .service(sla::add_sla_policy)
+                .service(sla::delete_sla_policy)
+                .service(maintenance::add_maintenance_plan)
+                .service(maintenance::retire_maintenance_plan)
+                .service(maintenance::complete_maintenance_task)
+                .service(work_order::add_work_order)
+                .service(work_order::update_work_order)
+                .service(work_order::close_work_order)
+                .service(work_order::log_work_order_labour)
+                .service(work_order::log_work_order_part),
+        )
+        .service(
+            web::scope("/health")
+                .service(health::live)
+                .service(health::ready),
+        )
+        .service(
+            web::scope("/machine")
+                // Telemetry batches are the largest bodies sent here, so no other is larger.
+                .app_data(web::JsonConfig::default().limit(machine_telemetry::MAX_BATCH_BYTES))
+                .service(machine::get_all_machines)
+                .service(machine::get_machine)
+                .service(machine::add_machine)
+                .service(machine::delete_machine)
+                .service(machine::update_machine_metadata)
+                .service(machine::get_machine_reports)
+                .service(machine::get_machine_archived_reports)
+                .service(qr::get_machine_qr_png)
+                .service(qr::get_machine_qr_svg)
+                .service(session::start_session)
+                .service(session::finish_session)
+                .service(machine_telemetry::submit_telemetry)
+                .service(machine_telemetry::get_machine_telemetry),
+        )
+        .service(
+            web::scope("/room")
+                .service(room::get_all_rooms)
+                .service(room::get_room)
+                .service(room::add_room)
+                .service(room::delete_room)
+                .service(room::get_room_machines)
+                .service(room::get_room_reports)
+                .service(room::get_room_archived_reports)
+                .service(room::assign_room_building)
+                .service(qr::get_room_qr_sheet)
+                .service(session::get_room_availability)
+                .service(reservation::join_waitlist)
+                .service(reservation::get_room_waitlist)
+                .service(reservation::leave_waitlist)
+                .service(reservation::add_reservation)
+                .service(reservation::get_room_reservations)
+                .service(reservation::cancel_reservation),
+        )
+        .service(
+            web::scope("/building")
+                .service(building::get_all_buildings)
+                .service(building::get_building)
+                .service(building::add_building)
+                .service(building::delete_building)
+                .service(building::get_building_rooms)
+                .service(building::get_building_reports)
+                .service(building::get_building_status),
+        )
+        .service(
+            web::scope("/site")
+                .service(site::get_all_sites)
+                .service(site::get_site)
+                .service(site::add_site)
+                .service(site::delete_site)
+                .service(site::get_site_buildings),
+        )
+        .service(
+            web::scope("/user")
+                .service(user::get_all_users)
+                .service(notification::get_vapid_public_key)
+                .service(user::get_user)
+                .service(user::add_user)
+                .service(user::delete_user)
+                .service(user::get_user_reports)
+                .service(user::get_user_archived_reports)
+                .service(notification::get_notification_preferences)
+                .service(notification::update_notification_preferences),
+        )
+        .service(
+            web::scope("/report")
+                .service(report::get_all_reports)
+                .service(report::get_all_archived_reports)
+                .service(guest::get_guest_challenge)
+                .service(guest::submit_guest_report)
+                .service(report::get_report)
+                .service(report::get_report_confirmations)
+                .service(sla::get_report_sla)
+                .service(report::submit_report)
+                .service(report::delete_report)
+                .service(report::archive_report)
+                .service(comment::get_report_comments)
+                .service(comment::add_report_comment)
+                .service(comment::edit_report_comment)
+                .service(comment::delete_report_comment)
+                .service(attachment::get_report_attachments)
+                .service(attachment::add_report_attachment)
+                .service(attachment::get_report_attachment)
+                .service(attachment::get_report_attachment_thumbnail)
+                .service(attachment::delete_report_attachment),
+        )
+        .service(
+            web::scope("/maintenance")
+                .service(maintenance::get_maintenance_plans)
+                .service(maintenance::get_upcoming_maintenance)
+                .service(maintenance::get_maintenance_tasks),
+        )
+        .service(
+            web::scope("/work-order")
+                .service(work_order::get_work_orders)
+                .service(work_order::get_work_order)
+                .service(work_order::get_work_order_labour)
+                .service(work_order::get_work_order_parts),
+        )
+        .service(web::scope("/sla").service(sla::get_sla_compliance));
+}
@@ -0,0 +1,459 @@
+use std::time::Duration;
+
+use actix_web::{
+    get, post,
+    web::{Data, Json, Path},
+    HttpResponse, Responder,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{query, query_as, Executor, Pool, Postgres};
+use time::PrimitiveDateTime;
+use utoipa::ToSchema;
+
+use crate::{
+    background::ShutdownSignal,
+    config,
+    error::database_error,
+    machine,
+    models::{now, AppState, MachineSession, MachineType, SessionEndReason},
+    report, reservation, room,
+    tenant::Tenant,
+    user,
+};
+
+/// How often sessions running past their expected end are looked for.
+const EXPIRY_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone)]
+pub struct SessionConfig {
+    /// The longest cycle a session may be started for.
+    pub max_duration: Duration,
+    /// How long after its expected end a session is ended if nobody reports it finished.
+    pub expiry_grace: Duration,
+}
+
+impl SessionConfig {
+    /// Parses the machine session configuration from the environment.
+    pub fn from_env() -> SessionConfig {
+        SessionConfig {
+            max_duration: Duration::from_secs(
+                config::env_or("MACHINE_SESSION_MAX_MINUTES", 240) * 60,
+            ),
+            expiry_grace: Duration::from_secs(
+                config::env_or("MACHINE_SESSION_EXPIRY_GRACE_MINUTES", 15) * 60,
+            ),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct SessionSubmission {
+    /// The resident running the cycle, left out by sensor integrations.
+    #[serde(default)]
+    username: Option<String>,
+    expected_duration_minutes: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub enum MachineState {
+    Available,
+    InUse,
+}
+
+/// Whether a machine is free, and if not when it is expected to be.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct MachineAvailability {
+    machine_id: String,
+    machine_type: MachineType,
+    state: MachineState,
+    session_id: Option<i32>,
+    expected_end_time: Option<PrimitiveDateTime>,
+    /// Whole minutes until the cycle is expected to end, 0 once it is overdue.
+    minutes_remaining: Option<i64>,
+}
+
+/// Ends every session which is more than `grace` past its expected end, returning how many were.
+pub async fn expire_sessions(
+    database: &Pool<Postgres>,
+    grace: Duration,
+) -> Result<u64, sqlx::Error> {
+    let now = now();
+
+    query!(
+        r#"
+        UPDATE machine_session
+        SET end_time = $1, end_reason = 'expired'
+        WHERE end_time IS NULL AND expected_end_time < $2
+        "#,
+        now,
+        now - grace
+    )
+    .execute(database)
+    .await
+    .map(|result| result.rows_affected())
+}
+
+/// Ends the session running on a machine if it is more than `grace` past its expected end, so
+/// that a forgotten cycle does not hold the machine until the expiry job next runs.
+async fn expire_machine_session<'c, E>(
+    executor: E,
+    room_id: &i32,
+    machine_id: &str,
+    grace: Duration,
+) -> Result<(), sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let now = now();
+
+    query!(
+        r#"
+        UPDATE machine_session
+        SET end_time = $1, end_reason = 'expired'
+        WHERE room_id = $2 AND machine_id = $3 AND end_time IS NULL AND expected_end_time < $4
+        "#,
+        now,
+        room_id,
+        machine_id,
+        now - grace
+    )
+    .execute(executor)
+    .await
+    .map(|_| ())
+}
+
+/// Ends overdue sessions until shutdown is requested.
+pub async fn expire_periodically(
+    database: Pool<Postgres>,
+    config: SessionConfig,
+    mut shutdown: ShutdownSignal,
+) {
+    while shutdown.sleep(EXPIRY_INTERVAL).await {
+        match expire_sessions(&database, config.expiry_grace).await {
+            Ok(0) => {}
+            Ok(expired) => log::info!("Expired {expired} machine sessions"),
+            Err(err) => log::error!("Failed to expire machine sessions: {err}"),
+        }
+    }
+}
+
+#[utoipa::path(
+    context_path = "/machine",
+    params(Tenant),
+    request_body(content = SessionSubmission, content_type = "application/json", example = json!({
+        "username": "admin",
+        "expected_duration_minutes": 45
+    })),
+    responses(
+        (status = 201, description = "A cycle was started on the requested machine", body = MachineSession, example = json!({
+            "session_id": 1,
+            "room_id": 1,
+            "machine_id": "A",
+            "username": "admin",
+            "start_time": "2023-01-01T12:00:00.000Z",
+            "expected_end_time": "2023-01-01T12:45:00.000Z",
+            "end_time": null,
+            "end_reason": null
+        })),
+        (status = 400, description = "The requested query was invalid"),
+        (status = 404, description = "The requested machine was not found"),
+        (status = 409, description = "The requested machine is already in use, or is claimed by or reserved for another user"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[post("/{room_id}/{machine_id}/session")]
+async fn start_session(
+    data: Data<AppState>,
+    tenant: Tenant,
+    path: Path<(i32, String)>,
+    Json(session_submission): Json<SessionSubmission>,
+) -> impl Responder {
+    let (room_id, machine_id) = path.into_inner();
+    let config = &data.session_config;
+
+    let duration =
+        Duration::from_secs(u64::from(session_submission.expected_duration_minutes) * 60);
+    if duration.is_zero() || duration > config.max_duration {
+        return HttpResponse::BadRequest().json(format!(
+            "The expected duration must be between 1 and {} minutes.",
+            config.max_duration.as_secs() / 60
+        ));
+    }
+
+    let machine_present =
+        match machine::is_machine_present(&data.database, &tenant, &room_id, &machine_id).await {
+            Ok(result) => result,
+            Err(err) => return database_error("check machine presence", err),
+        };
+
+    if !machine_present {
+        return HttpResponse::NotFound().json(format!(
+            "Machine id {machine_id} was not found in room id {room_id}."
+        ));
+    }
+
+    if let Some(username) = &session_submission.username {
+        let username_present =
+            match user::is_username_present(&data.database, &tenant, username).await {
+                Ok(result) => result,
+                Err(err) => return database_error("check username presence", err),
+            };
+
+        if !username_present {
+            return HttpResponse::BadRequest().json(format!("Username {username} was not found."));
+        }
+    }
+
+    let start_time = now();
+    let expected_end_time = start_time + duration;
+
+    let mut transaction = match data.database.begin().await {
+        Ok(transaction) => transaction,
+        Err(err) => return database_error("begin machine session", err),
+    };
+
+    // Locking the machine serializes sessions and reservations of it, so that a hold cannot be
+    // placed between checking for one and starting the cycle.
+    if let Err(err) = report::lock_machine(&mut transaction, &room_id, &machine_id).await {
+        return database_error("lock machine", err);
+    }
+
+    if let Err(err) =
+        expire_machine_session(&mut transaction, &room_id, &machine_id, config.expiry_grace).await
+    {
+        return database_error("expire machine session", err);
+    }
+
+    // Sensors report cycles which are already running, so only residents are held back.
+    if let Some(username) = &session_submission.username {
+        match reservation::find_conflicting_hold(
+            &mut transaction,
+            &room_id,
+            &machine_id,
+            username,
+            start_time,
+            expected_end_time,
+        )
+        .await
+        {
+            Ok(None) => {}
+            Ok(Some(conflict)) => return HttpResponse::Conflict().json(conflict),
+            Err(err) => return database_error("check machine holds", err),
+        }
+    }
+
+    let session = match query_as!(
+        MachineSession,
+        r#"
+        INSERT INTO machine_session (
+            room_id, machine_id, username, start_time, expected_end_time, organization_id
+        )
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (room_id, machine_id) WHERE end_time IS NULL DO NOTHING
+        RETURNING
+            id AS "session_id: i32",
+            room_id,
+            machine_id,
+            username,
+            start_time,
+            expected_end_time,
+            end_time,
+            end_reason AS "end_reason: SessionEndReason"
+        "#,
+        &room_id,
+        &machine_id,
+        session_submission.username,
+        start_time,
+        expected_end_time,
+        tenant.organization_id
+    )
+    .fetch_optional(&mut transaction)
+    .await
+    {
+        Ok(Some(session)) => session,
+        Ok(None) => {
+            return HttpResponse::Conflict().json(format!(
+                "Machine id {machine_id} in room id {room_id} is already in use."
+            ))
+        }
+        Err(err) => return database_error("insert machine session", err),
+    };
+
+    if let Some(username) = &session_submission.username {
+        if let Err(err) = reservation::use_holds(
+            &mut transaction,
+            &room_id,
+            &machine_id,
+            username,
+            start_time,
+            expected_end_time,
+        )
+        .await
+        {
+            return database_error("use machine holds", err);
+        }
+    }
+
+    match transaction.commit().await {
+        Ok(()) => HttpResponse::Created().json(session),
+        Err(err) => database_error("commit machine session", err),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/machine",
+    params(Tenant),
+    responses(
+        (status = 200, description = "The cycle running on the requested machine was finished", body = MachineSession, example = json!({
+            "session_id": 1,
+            "room_id": 1,
+            "machine_id": "A",
+            "username": "admin",
+            "start_time": "2023-01-01T12:00:00.000Z",
+            "expected_end_time": "2023-01-01T12:45:00.000Z",
+            "end_time": "2023-01-01T12:43:00.000Z",
+            "end_reason": "Finished"
+        })),
+        (status = 404, description = "The requested machine was not found or is not in use"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[post("/{room_id}/{machine_id}/session/finish")]
+async fn finish_session(
+    data: Data<AppState>,
+    tenant: Tenant,
+    path: Path<(i32, String)>,
+) -> impl Responder {
+    let (room_id, machine_id) = path.into_inner();
+
+    match query_as!(
+        MachineSession,
+        r#"
+        UPDATE machine_session
+        SET end_time = $4, end_reason = 'finished'
+        WHERE room_id = $1
+            AND machine_id = $2
+            AND end_time IS NULL
+            AND room_id IN (SELECT id FROM room WHERE organization_id = $3)
+        RETURNING
+            id AS "session_id: i32",
+            room_id,
+            machine_id,
+            username,
+            start_time,
+            expected_end_time,
+            end_time,
+            end_reason AS "end_reason: SessionEndReason"
+        "#,
+        &room_id,
+        &machine_id,
+        tenant.organization_id,
+        now()
+    )
+    .fetch_optional(&data.database)
+    .await
+    {
+        Ok(Some(session)) => {
+            // The machine can go to the next in line straight away.
+            if let Err(err) = reservation::dispatch(&data.database, Some(room_id)).await {
+                log::error!("Failed to dispatch the waitlist of room id {room_id}: {err}");
+            }
+
+            HttpResponse::Ok().json(session)
+        }
+        Ok(None) => HttpResponse::NotFound().json(format!(
+            "Machine id {machine_id} in room id {room_id} was not found or is not in use."
+        )),
+        Err(err) => database_error("finish machine session", err),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/room",
+    params(Tenant),
+    responses(
+        (status = 200, description = "Whether each machine in the requested room is available, and when those in use are expected to be", body = Vec<MachineAvailability>, example = json!([{
+            "machine_id": "A",
+            "machine_type": "Washer",
+            "state": "InUse",
+            "session_id": 1,
+            "expected_end_time": "2023-01-01T12:45:00.000Z",
+            "minutes_remaining": 12
+        }, {
+            "machine_id": "B",
+            "machine_type": "Dryer",
+            "state": "Available",
+            "session_id": null,
+            "expected_end_time": null,
+            "minutes_remaining": null
+        }])),
+        (status = 404, description = "The requested room id was not found"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[get("/{room_id}/availability")]
+async fn get_room_availability(
+    data: Data<AppState>,
+    tenant: Tenant,
+    path: Path<i32>,
+) -> impl Responder {
+    let room_id = path.into_inner();
+
+    let room_present = match room::is_room_present(&data.database, &tenant, &room_id).await {
+        Ok(result) => result,
+        Err(err) => return database_error("check room presence", err),
+    };
+
+    if !room_present {
+        return HttpResponse::NotFound().json(format!("Room id {room_id} was not found."));
+    }
+
+    let now = now();
+
+    // Sessions past their grace period count as ended even if the expiry job has not run yet.
+    let machines = match query!(
+        r#"
+        SELECT
+            machine.machine_id,
+            machine.type AS "machine_type: MachineType",
+            machine_session.id AS "session_id?",
+            machine_session.expected_end_time AS "expected_end_time?"
+        FROM machine
+        LEFT JOIN machine_session
+            ON machine_session.room_id = machine.room_id
+            AND machine_session.machine_id = machine.machine_id
+            AND machine_session.end_time IS NULL
+            AND machine_session.expected_end_time >= $2
+        WHERE machine.room_id = $1
+        ORDER BY machine.machine_id
+        "#,
+        &room_id,
+        now - data.session_config.expiry_grace
+    )
+    .fetch_all(&data.database)
+    .await
+    {
+        Ok(machines) => machines,
+        Err(err) => return database_error("fetch room availability", err),
+    };
+
+    let availability: Vec<MachineAvailability> = machines
+        .into_iter()
+        .map(|machine| MachineAvailability {
+            machine_id: machine.machine_id,
+            machine_type: machine.machine_type,
+            state: match machine.session_id {
+                Some(_) => MachineState::InUse,
+                None => MachineState::Available,
+            },
+            session_id: machine.session_id,
+            expected_end_time: machine.expected_end_time,
+            minutes_remaining: machine.expected_end_time.map(|expected_end_time| {
+                let remaining = (expected_end_time - now).whole_seconds().max(0);
+                (remaining + 59) / 60
+            }),
+        })
+        .collect();
+
+    HttpResponse::Ok().json(availability)
+}
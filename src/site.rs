@@ -0,0 +1,250 @@
+use actix_web::{
+    delete, get, post,
+    web::{Data, Json, Path},
+    HttpResponse, Responder,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{query, query_as, Pool, Postgres};
+use utoipa::ToSchema;
+
+use crate::{
+    error::database_error,
+    models::{AppState, Building, Site},
+    tenant::Tenant,
+};
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct SiteSubmission {
+    name: String,
+    description: Option<String>,
+}
+
+pub async fn is_site_present(
+    database: &Pool<Postgres>,
+    tenant: &Tenant,
+    site_id: &i32,
+) -> Result<bool, sqlx::Error> {
+    match query!(
+        r#"
+        SELECT id
+        FROM site
+        WHERE id = $1 AND organization_id = $2
+        "#,
+        site_id,
+        tenant.organization_id
+    )
+    .fetch_optional(database)
+    .await
+    {
+        Ok(result) => Ok(result.is_some()),
+        Err(err) => Err(err),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/site",
+    params(Tenant),
+    responses(
+        (status = 200, description = "Lists all sites", body = Vec<Site>, example = json!([{
+            "site_id": 1,
+            "name": "North Campus",
+            "description": "Residences north of the river"
+        }])),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[get("/")]
+async fn get_all_sites(data: Data<AppState>, tenant: Tenant) -> impl Responder {
+    match query_as!(
+        Site,
+        r#"
+        SELECT id as "site_id: i32", name, description
+        FROM site
+        WHERE organization_id = $1
+        "#,
+        tenant.organization_id
+    )
+    .fetch_all(&data.database)
+    .await
+    {
+        Ok(sites) => HttpResponse::Ok().json(sites),
+        Err(err) => database_error("fetch all sites", err),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/site",
+    params(Tenant),
+    responses(
+        (status = 200, description = "The requested site", body = Site, example = json!({
+            "site_id": 1,
+            "name": "North Campus",
+            "description": "Residences north of the river"
+        })),
+        (status = 404, description = "The requested site was not found"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[get("/{site_id}")]
+async fn get_site(data: Data<AppState>, tenant: Tenant, path: Path<i32>) -> impl Responder {
+    let site_id = path.into_inner();
+
+    match query_as!(
+        Site,
+        r#"
+        SELECT id as "site_id: i32", name, description
+        FROM site
+        WHERE id = $1 AND organization_id = $2
+        "#,
+        site_id,
+        tenant.organization_id
+    )
+    .fetch_optional(&data.database)
+    .await
+    {
+        Err(err) => database_error("fetch site", err),
+        Ok(site) => match site {
+            Some(site) => HttpResponse::Ok().json(&site),
+            None => HttpResponse::NotFound().json(format!("The site id {site_id} was not found.")),
+        },
+    }
+}
+
+#[utoipa::path(
+    context_path = "/site",
+    params(Tenant),
+    request_body(content = SiteSubmission, content_type = "application/json", example = json!({
+        "name": "North Campus",
+        "description": "Residences north of the river"
+    })),
+    responses(
+        (status = 201, description = "The requested site was created", body = Site, example = json!({
+            "site_id": 1,
+            "name": "North Campus",
+            "description": "Residences north of the river"
+        })),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[post("/")]
+async fn add_site(
+    data: Data<AppState>,
+    tenant: Tenant,
+    Json(site_submission): Json<SiteSubmission>,
+) -> impl Responder {
+    match query_as!(
+        Site,
+        r#"
+        INSERT INTO site (name, description, organization_id)
+        VALUES ($1, $2, $3)
+        RETURNING
+            id AS "site_id: i32",
+            name,
+            description
+        "#,
+        &site_submission.name,
+        site_submission.description,
+        tenant.organization_id
+    )
+    .fetch_one(&data.database)
+    .await
+    {
+        Ok(site) => HttpResponse::Created().json(site),
+        Err(err) => database_error("insert site", err),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/site",
+    params(Tenant),
+    responses(
+        (status = 200, description = "The requested site was deleted, its buildings are kept without a site", body = Site, example = json!({
+            "site_id": 1,
+            "name": "North Campus",
+            "description": "Residences north of the river"
+        })),
+        (status = 404, description = "The requested site was not found"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[delete("/{site_id}")]
+async fn delete_site(data: Data<AppState>, tenant: Tenant, path: Path<i32>) -> impl Responder {
+    let site_id = path.into_inner();
+
+    match query_as!(
+        Site,
+        r#"
+        DELETE FROM site
+        WHERE id = $1 AND organization_id = $2
+        RETURNING
+            id AS "site_id: i32",
+            name,
+            description
+        "#,
+        &site_id,
+        tenant.organization_id
+    )
+    .fetch_optional(&data.database)
+    .await
+    {
+        Ok(Some(site)) => HttpResponse::Ok().json(site),
+        Ok(None) => HttpResponse::NotFound().json(format!("Site id {site_id} was not found.")),
+        Err(err) => database_error("delete site", err),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/site",
+    params(Tenant),
+    responses(
+        (status = 200, description = "List of all buildings on the requested site", body = Vec<Building>, example = json!([{
+            "building_id": 1,
+            "site_id": 1,
+            "name": "Complex A",
+            "address": "1 College Road",
+            "latitude": 51.7548,
+            "longitude": -1.2544
+        }])),
+        (status = 404, description = "The requested site was not found"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[get("/{site_id}/buildings")]
+async fn get_site_buildings(
+    data: Data<AppState>,
+    tenant: Tenant,
+    path: Path<i32>,
+) -> impl Responder {
+    let site_id = path.into_inner();
+
+    let site_present = match is_site_present(&data.database, &tenant, &site_id).await {
+        Ok(result) => result,
+        Err(err) => return database_error("check site presence", err),
+    };
+
+    if !site_present {
+        return HttpResponse::NotFound().json(format!("Site id {site_id} was not found."));
+    }
+
+    match query_as!(
+        Building,
+        r#"
+        SELECT
+            id AS "building_id: i32",
+            site_id,
+            name,
+            address,
+            latitude,
+            longitude
+        FROM building
+        WHERE site_id = $1
+        "#,
+        &site_id
+    )
+    .fetch_all(&data.database)
+    .await
+    {
+        Ok(buildings) => HttpResponse::Ok().json(buildings),
+        Err(err) => database_error("fetch site buildings", err),
+    }
+}
@@ -0,0 +1,665 @@
+use std::{sync::Arc, time::Duration};
+
+use actix_web::{
+    delete, get, post,
+    web::{Data, Json, Path, Query},
+    HttpResponse, Responder,
+};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use sqlx::{query, query_as, Pool, Postgres};
+use time::{Date, PrimitiveDateTime};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    background::ShutdownSignal,
+    config,
+    error::{constraint_violation, database_error, Violation},
+    guest::is_plausible_email,
+    models::{iso_date, iso_datetime, now, AppState, ReportType, SlaPolicy, SlaTarget},
+    notification::{self, Notifier},
+    report, room,
+    tenant::Tenant,
+};
+
+/// How many months the compliance report covers by default, including the current one.
+const DEFAULT_COMPLIANCE_MONTHS: u8 = 12;
+
+#[derive(Debug, Clone)]
+pub struct SlaConfig {
+    /// How often reports are checked for missed targets.
+    pub interval: Duration,
+}
+
+impl SlaConfig {
+    /// Parses the SLA configuration from the environment.
+    pub fn from_env() -> SlaConfig {
+        SlaConfig {
+            interval: Duration::from_secs(config::env_or("SLA_CHECK_INTERVAL_MINUTES", 5) * 60),
+        }
+    }
+}
+
+/// A policy for a room, a report type, or a report type within a room.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct SlaPolicySubmission {
+    name: String,
+    #[serde(default)]
+    room_id: Option<i32>,
+    #[serde(default)]
+    report_type: Option<ReportType>,
+    #[serde(default)]
+    acknowledge_minutes: Option<i32>,
+    #[serde(default)]
+    resolve_minutes: Option<i32>,
+    #[serde(default)]
+    escalation_email: Option<String>,
+    #[serde(default)]
+    escalation_webhook_url: Option<String>,
+}
+
+/// How a report fares against the policy covering it. Times to acknowledge and resolve are
+/// `None` until the report is acknowledged or resolved.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ReportSla {
+    report_id: i32,
+    /// `None` if no policy covers the report.
+    policy_id: Option<i32>,
+    policy_name: Option<String>,
+    #[serde(with = "iso_datetime::option")]
+    acknowledge_due_time: Option<PrimitiveDateTime>,
+    #[serde(with = "iso_datetime::option")]
+    acknowledged_time: Option<PrimitiveDateTime>,
+    minutes_to_acknowledge: Option<f64>,
+    acknowledge_breached: bool,
+    #[serde(with = "iso_datetime::option")]
+    resolve_due_time: Option<PrimitiveDateTime>,
+    #[serde(with = "iso_datetime::option")]
+    resolved_time: Option<PrimitiveDateTime>,
+    minutes_to_resolve: Option<f64>,
+    resolve_breached: bool,
+}
+
+/// How the reports filed in a month against one vendor's machines fared against their policies.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct SlaCompliance {
+    /// The manufacturer of the machines, `None` for machines without one.
+    vendor: Option<String>,
+    /// The month the reports were filed in, as `YYYY-MM`.
+    month: String,
+    reports: i64,
+    /// Reports resolved without missing a target.
+    met: i64,
+    /// Reports which missed a target, whether or not they were resolved since.
+    breached: i64,
+    /// Open reports which have not missed a target yet.
+    pending: i64,
+    /// The fraction of met reports among met and breached ones, `None` if there are none.
+    compliance: Option<f64>,
+    mean_minutes_to_acknowledge: Option<f64>,
+    mean_minutes_to_resolve: Option<f64>,
+}
+
+/// The JSON body of breach escalation webhooks.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct SlaBreach {
+    target: SlaTarget,
+    report_id: i32,
+    room_id: i32,
+    machine_id: String,
+    report_type: ReportType,
+    policy_id: i32,
+    policy_name: String,
+    #[serde(with = "iso_datetime")]
+    reported_time: PrimitiveDateTime,
+    #[serde(with = "iso_datetime")]
+    due_time: PrimitiveDateTime,
+    message: String,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ComplianceQuery {
+    /// Only include machines by this manufacturer.
+    vendor: Option<String>,
+    /// The first day to include reports from, defaulting to the start of the month a year ago.
+    #[serde(default, with = "iso_date::option")]
+    since: Option<Date>,
+}
+
+/// The first day of the month `months` before the current one.
+fn months_ago(months: u8) -> Date {
+    let today = now().date();
+    let month = today.month().nth_prev(months % 12);
+    let year = today.year() - i32::from(months / 12) - i32::from(month as u8 > today.month() as u8);
+    Date::from_calendar_date(year, month, 1).unwrap_or(today)
+}
+
+/// Escalates reports which have missed a target of their policy since the last check, returning
+/// how many breaches were escalated. Each breach is marked before it is escalated, so it is
+/// escalated at most once even if delivery fails.
+pub async fn escalate_breaches(
+    database: &Pool<Postgres>,
+    notifier: &Notifier,
+) -> Result<u64, sqlx::Error> {
+    let breaches = query!(
+        r#"
+        WITH breach AS (
+            SELECT
+                'acknowledge'::sla_target AS target,
+                report_id,
+                room_id,
+                machine_id,
+                report_type,
+                time,
+                policy_id,
+                policy_name,
+                escalation_email,
+                escalation_webhook_url,
+                time + make_interval(mins => acknowledge_minutes) AS due_time
+            FROM report_sla
+            WHERE acknowledged_time IS NULL
+                AND time + make_interval(mins => acknowledge_minutes) <= $1
+            UNION ALL
+            SELECT
+                'resolve'::sla_target,
+                report_id,
+                room_id,
+                machine_id,
+                report_type,
+                time,
+                policy_id,
+                policy_name,
+                escalation_email,
+                escalation_webhook_url,
+                time + make_interval(mins => resolve_minutes)
+            FROM report_sla
+            WHERE resolved_time IS NULL
+                AND time + make_interval(mins => resolve_minutes) <= $1
+        ),
+        escalated AS (
+            INSERT INTO sla_escalation (report_id, target, time)
+            SELECT report_id, target, $1
+            FROM breach
+            ON CONFLICT DO NOTHING
+            RETURNING report_id, target
+        )
+        SELECT
+            breach.target AS "target!: SlaTarget",
+            breach.report_id AS "report_id!",
+            breach.room_id AS "room_id!",
+            breach.machine_id AS "machine_id!",
+            breach.report_type AS "report_type!: ReportType",
+            breach.time AS "time!",
+            breach.policy_id AS "policy_id!",
+            breach.policy_name AS "policy_name!",
+            breach.escalation_email,
+            breach.escalation_webhook_url,
+            breach.due_time AS "due_time!"
+        FROM breach
+        JOIN escalated ON escalated.report_id = breach.report_id AND escalated.target = breach.target
+        ORDER BY breach.due_time
+        "#,
+        now()
+    )
+    .fetch_all(database)
+    .await?;
+
+    let escalated = breaches.len() as u64;
+
+    for breach in breaches {
+        let message = format!(
+            "Report id {} of machine {} in room {} was not {} by {}, as required by the {} policy.",
+            breach.report_id,
+            breach.machine_id,
+            breach.room_id,
+            match breach.target {
+                SlaTarget::Acknowledge => "acknowledged",
+                SlaTarget::Resolve => "resolved",
+            },
+            breach.due_time,
+            breach.policy_name
+        );
+        log::warn!("{message}");
+
+        let event = SlaBreach {
+            target: breach.target,
+            report_id: breach.report_id,
+            room_id: breach.room_id,
+            machine_id: breach.machine_id,
+            report_type: breach.report_type,
+            policy_id: breach.policy_id,
+            policy_name: breach.policy_name,
+            reported_time: breach.time,
+            due_time: breach.due_time,
+            message,
+        };
+
+        if let Some(email) = &breach.escalation_email {
+            let subject = format!("Report id {} missed its SLA", event.report_id);
+            if let Err(err) = notifier
+                .send_email(email, &subject, event.message.clone())
+                .await
+            {
+                log::warn!("Failed to email an SLA breach to {email}: {err}");
+            }
+        }
+
+        if let Some(url) = &breach.escalation_webhook_url {
+            if let Err(err) = notifier.send_webhook(url, &event).await {
+                log::warn!("Failed to call the SLA escalation webhook {url}: {err}");
+            }
+        }
+    }
+
+    Ok(escalated)
+}
+
+pub async fn escalate_periodically(
+    database: Pool<Postgres>,
+    notifier: Arc<Notifier>,
+    config: SlaConfig,
+    mut shutdown: ShutdownSignal,
+) {
+    while shutdown.sleep(config.interval).await {
+        match escalate_breaches(&database, &notifier).await {
+            Ok(0) => {}
+            Ok(escalated) => log::info!("Escalated {escalated} SLA breaches"),
+            Err(err) => log::error!("Failed to escalate SLA breaches: {err}"),
+        }
+    }
+}
+
+#[utoipa::path(
+    context_path = "/admin",
+    params(Tenant),
+    responses(
+        (status = 200, description = "List of all SLA policies", body = Vec<SlaPolicy>, example = json!([{
+            "policy_id": 1,
+            "name": "Broken machines",
+            "room_id": null,
+            "report_type": "Broken",
+            "acknowledge_minutes": 240,
+            "resolve_minutes": 2880,
+            "escalation_email": "facilities@example.com",
+            "escalation_webhook_url": null,
+            "created_time": "2023-01-01T12:00:00"
+        }])),
+        (status = 401, description = "No valid admin credentials were given"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[get("/sla-policies")]
+async fn get_sla_policies(data: Data<AppState>, tenant: Tenant) -> impl Responder {
+    match query_as!(
+        SlaPolicy,
+        r#"
+        SELECT
+            id AS policy_id,
+            name,
+            room_id,
+            report_type AS "report_type: ReportType",
+            acknowledge_minutes,
+            resolve_minutes,
+            escalation_email,
+            escalation_webhook_url,
+            created_time
+        FROM sla_policy
+        WHERE organization_id = $1
+        ORDER BY id
+        "#,
+        tenant.organization_id
+    )
+    .fetch_all(&data.database)
+    .await
+    {
+        Ok(policies) => HttpResponse::Ok().json(policies),
+        Err(err) => database_error("fetch SLA policies", err),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/admin",
+    params(Tenant),
+    request_body(content = SlaPolicySubmission, content_type = "application/json", example = json!({
+        "name": "Broken machines",
+        "report_type": "Broken",
+        "acknowledge_minutes": 240,
+        "resolve_minutes": 2880,
+        "escalation_email": "facilities@example.com"
+    })),
+    responses(
+        (status = 201, description = "The policy was added, covering reports filed from now on", body = SlaPolicy, example = json!({
+            "policy_id": 1,
+            "name": "Broken machines",
+            "room_id": null,
+            "report_type": "Broken",
+            "acknowledge_minutes": 240,
+            "resolve_minutes": 2880,
+            "escalation_email": "facilities@example.com",
+            "escalation_webhook_url": null,
+            "created_time": "2023-01-01T12:00:00"
+        })),
+        (status = 400, description = "The requested query was invalid"),
+        (status = 401, description = "No valid admin credentials were given"),
+        (status = 409, description = "A policy for the same room and report type already exists"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[post("/sla-policies")]
+async fn add_sla_policy(
+    data: Data<AppState>,
+    tenant: Tenant,
+    Json(submission): Json<SlaPolicySubmission>,
+) -> impl Responder {
+    if submission.room_id.is_none() && submission.report_type.is_none() {
+        return HttpResponse::BadRequest()
+            .json("A policy must cover a room id, a report type, or both.");
+    }
+
+    if submission.acknowledge_minutes.is_none() && submission.resolve_minutes.is_none() {
+        return HttpResponse::BadRequest()
+            .json("A policy must set acknowledge minutes, resolve minutes, or both.");
+    }
+
+    if let Some(email) = &submission.escalation_email {
+        if !is_plausible_email(email) {
+            return HttpResponse::BadRequest().json(format!("{email} is not an email address."));
+        }
+    }
+
+    if let Some(url) = &submission.escalation_webhook_url {
+        match Url::parse(url) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => {
+                if let Err(err) = notification::check_delivery_url(&url).await {
+                    return HttpResponse::BadRequest()
+                        .json(format!("The escalation webhook URL cannot be used: {err}."));
+                }
+            }
+            _ => return HttpResponse::BadRequest().json(format!("{url} is not an HTTP URL.")),
+        }
+    }
+
+    if let Some(room_id) = submission.room_id {
+        match room::is_room_present(&data.database, &tenant, &room_id).await {
+            Ok(true) => {}
+            Ok(false) => {
+                return HttpResponse::BadRequest().json(format!("Room id {room_id} was not found."))
+            }
+            Err(err) => return database_error("check room presence", err),
+        }
+    }
+
+    match query_as!(
+        SlaPolicy,
+        r#"
+        INSERT INTO sla_policy (
+            organization_id, name, room_id, report_type, acknowledge_minutes, resolve_minutes,
+            escalation_email, escalation_webhook_url, created_time
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        ON CONFLICT DO NOTHING
+        RETURNING
+            id AS policy_id,
+            name,
+            room_id,
+            report_type AS "report_type: ReportType",
+            acknowledge_minutes,
+            resolve_minutes,
+            escalation_email,
+            escalation_webhook_url,
+            created_time
+        "#,
+        tenant.organization_id,
+        submission.name,
+        submission.room_id,
+        submission.report_type as Option<ReportType>,
+        submission.acknowledge_minutes,
+        submission.resolve_minutes,
+        submission.escalation_email,
+        submission.escalation_webhook_url,
+        now()
+    )
+    .fetch_optional(&data.database)
+    .await
+    {
+        Ok(Some(policy)) => HttpResponse::Created().json(policy),
+        Ok(None) => HttpResponse::Conflict()
+            .json("A policy for the same room id and report type already exists."),
+        Err(err) => match constraint_violation(&err) {
+            Some(Violation::Check) => HttpResponse::BadRequest()
+                .json("The acknowledge and resolve minutes must be positive."),
+            Some(Violation::ForeignKey) => HttpResponse::BadRequest()
+                .json("The report type does not exist, or the room was removed."),
+            _ => database_error("insert SLA policy", err),
+        },
+    }
+}
+
+#[utoipa::path(
+    context_path = "/admin",
+    params(Tenant),
+    responses(
+        (status = 200, description = "The requested policy was deleted", body = SlaPolicy, example = json!({
+            "policy_id": 1,
+            "name": "Broken machines",
+            "room_id": null,
+            "report_type": "Broken",
+            "acknowledge_minutes": 240,
+            "resolve_minutes": 2880,
+            "escalation_email": "facilities@example.com",
+            "escalation_webhook_url": null,
+            "created_time": "2023-01-01T12:00:00"
+        })),
+        (status = 401, description = "No valid admin credentials were given"),
+        (status = 404, description = "The requested policy was not found"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[delete("/sla-policies/{policy_id}")]
+async fn delete_sla_policy(
+    data: Data<AppState>,
+    tenant: Tenant,
+    path: Path<i32>,
+) -> impl Responder {
+    let policy_id = path.into_inner();
+
+    match query_as!(
+        SlaPolicy,
+        r#"
+        DELETE FROM sla_policy
+        WHERE id = $1 AND organization_id = $2
+        RETURNING
+            id AS policy_id,
+            name,
+            room_id,
+            report_type AS "report_type: ReportType",
+            acknowledge_minutes,
+            resolve_minutes,
+            escalation_email,
+            escalation_webhook_url,
+            created_time
+        "#,
+        policy_id,
+        tenant.organization_id
+    )
+    .fetch_optional(&data.database)
+    .await
+    {
+        Ok(Some(policy)) => HttpResponse::Ok().json(policy),
+        Ok(None) => {
+            HttpResponse::NotFound().json(format!("SLA policy id {policy_id} was not found."))
+        }
+        Err(err) => database_error("delete SLA policy", err),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/sla",
+    params(Tenant, ComplianceQuery),
+    responses(
+        (status = 200, description = "Compliance of the reports covered by a policy, per vendor and month, oldest month first", body = Vec<SlaCompliance>, example = json!([{
+            "vendor": "Speed Queen",
+            "month": "2023-01",
+            "reports": 12,
+            "met": 9,
+            "breached": 2,
+            "pending": 1,
+            "compliance": 0.8181818181818182,
+            "mean_minutes_to_acknowledge": 95.5,
+            "mean_minutes_to_resolve": 1630.0
+        }])),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[get("/compliance")]
+async fn get_sla_compliance(
+    data: Data<AppState>,
+    tenant: Tenant,
+    Query(compliance_query): Query<ComplianceQuery>,
+) -> impl Responder {
+    let since = compliance_query
+        .since
+        .unwrap_or_else(|| months_ago(DEFAULT_COMPLIANCE_MONTHS - 1));
+
+    let months = match query!(
+        r#"
+        WITH outcome AS (
+            SELECT
+                report_sla.*,
+                COALESCE(
+                    COALESCE(acknowledged_time, $2) > time + make_interval(mins => acknowledge_minutes),
+                    false
+                ) AS acknowledge_breached,
+                COALESCE(
+                    COALESCE(resolved_time, $2) > time + make_interval(mins => resolve_minutes),
+                    false
+                ) AS resolve_breached
+            FROM report_sla
+            WHERE organization_id = $1 AND policy_id IS NOT NULL AND time >= $3
+        )
+        SELECT
+            machine.manufacturer AS vendor,
+            to_char(date_trunc('month', outcome.time), 'YYYY-MM') AS "month!",
+            COUNT(*) AS "reports!",
+            COUNT(*) FILTER (
+                WHERE outcome.resolved_time IS NOT NULL
+                    AND NOT outcome.acknowledge_breached
+                    AND NOT outcome.resolve_breached
+            ) AS "met!",
+            COUNT(*) FILTER (
+                WHERE outcome.acknowledge_breached OR outcome.resolve_breached
+            ) AS "breached!",
+            AVG(EXTRACT(EPOCH FROM outcome.acknowledged_time - outcome.time) / 60)::FLOAT8
+                AS mean_minutes_to_acknowledge,
+            AVG(EXTRACT(EPOCH FROM outcome.resolved_time - outcome.time) / 60)::FLOAT8
+                AS mean_minutes_to_resolve
+        FROM outcome
+        JOIN machine ON machine.room_id = outcome.room_id AND machine.machine_id = outcome.machine_id
+        WHERE ($4::VARCHAR IS NULL OR machine.manufacturer = $4)
+        GROUP BY machine.manufacturer, date_trunc('month', outcome.time)
+        ORDER BY date_trunc('month', outcome.time), machine.manufacturer NULLS LAST
+        "#,
+        tenant.organization_id,
+        now(),
+        PrimitiveDateTime::new(since, time::Time::MIDNIGHT),
+        compliance_query.vendor
+    )
+    .fetch_all(&data.database)
+    .await
+    {
+        Ok(months) => months,
+        Err(err) => return database_error("fetch SLA compliance", err),
+    };
+
+    let compliance: Vec<SlaCompliance> = months
+        .into_iter()
+        .map(|month| SlaCompliance {
+            vendor: month.vendor,
+            month: month.month,
+            reports: month.reports,
+            met: month.met,
+            breached: month.breached,
+            pending: month.reports - month.met - month.breached,
+            compliance: match month.met + month.breached {
+                0 => None,
+                decided => Some(month.met as f64 / decided as f64),
+            },
+            mean_minutes_to_acknowledge: month.mean_minutes_to_acknowledge,
+            mean_minutes_to_resolve: month.mean_minutes_to_resolve,
+        })
+        .collect();
+
+    HttpResponse::Ok().json(compliance)
+}
+
+#[utoipa::path(
+    context_path = "/report",
+    params(Tenant),
+    responses(
+        (status = 200, description = "How the requested report fares against the policy covering it", body = ReportSla, example = json!({
+            "report_id": 1,
+            "policy_id": 1,
+            "policy_name": "Broken machines",
+            "acknowledge_due_time": "2023-01-01T16:00:00",
+            "acknowledged_time": "2023-01-01T13:30:00",
+            "minutes_to_acknowledge": 90.0,
+            "acknowledge_breached": false,
+            "resolve_due_time": "2023-01-03T12:00:00",
+            "resolved_time": null,
+            "minutes_to_resolve": null,
+            "resolve_breached": false
+        })),
+        (status = 404, description = "The requested report was not found, or was archived before archive times were recorded"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[get("/{report_id}/sla")]
+async fn get_report_sla(data: Data<AppState>, tenant: Tenant, path: Path<i32>) -> impl Responder {
+    let report_id = path.into_inner();
+
+    match report::is_report_present(&data.database, &tenant, &report_id).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return HttpResponse::NotFound().json(format!("Report id {report_id} was not found."))
+        }
+        Err(err) => return database_error("check report presence", err),
+    }
+
+    match query_as!(
+        ReportSla,
+        r#"
+        SELECT
+            report_id AS "report_id!",
+            policy_id,
+            policy_name,
+            time + make_interval(mins => acknowledge_minutes) AS acknowledge_due_time,
+            acknowledged_time,
+            (EXTRACT(EPOCH FROM acknowledged_time - time) / 60)::FLOAT8 AS minutes_to_acknowledge,
+            COALESCE(
+                COALESCE(acknowledged_time, $2) > time + make_interval(mins => acknowledge_minutes),
+                false
+            ) AS "acknowledge_breached!",
+            time + make_interval(mins => resolve_minutes) AS resolve_due_time,
+            resolved_time,
+            (EXTRACT(EPOCH FROM resolved_time - time) / 60)::FLOAT8 AS minutes_to_resolve,
+            COALESCE(
+                COALESCE(resolved_time, $2) > time + make_interval(mins => resolve_minutes),
+                false
+            ) AS "resolve_breached!"
+        FROM report_sla
+        WHERE report_id = $1
+        "#,
+        report_id,
+        now()
+    )
+    .fetch_optional(&data.database)
+    .await
+    {
+        Ok(Some(report_sla)) => HttpResponse::Ok().json(report_sla),
+        Ok(None) => HttpResponse::NotFound().json(format!(
+            "Report id {report_id} was archived before archive times were recorded."
+        )),
+        Err(err) => database_error("fetch report SLA", err),
+    }
+}
@@ -0,0 +1,224 @@
+use std::{
+    env,
+    future::{ready, Ready},
+    rc::Rc,
+    time::{Duration, SystemTime},
+};
+
+use actix_web::{
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http::header::HeaderMap,
+    Error,
+};
+use futures_util::future::LocalBoxFuture;
+use log::Record;
+use opentelemetry::{
+    context::FutureExt,
+    global,
+    propagation::Extractor,
+    trace::{SpanKind, Status, TraceContextExt, Tracer},
+    Context, KeyValue,
+};
+use opentelemetry_sdk::{propagation::TraceContextPropagator, trace::SdkTracerProvider, Resource};
+
+use crate::request_id;
+
+/// The name of the tracer used for every span created by this application.
+const TRACER_NAME: &str = "laundry-api";
+
+/// Initialize OpenTelemetry tracing, exporting spans over OTLP/HTTP.
+///
+/// Tracing is only enabled when the OTEL_EXPORTER_OTLP_ENDPOINT [environment variable](std::env::var)
+/// is set, in which case the returned provider must be [shut down](SdkTracerProvider::shutdown)
+/// before exiting to flush any pending spans.
+pub fn initialize(app_name: &'static str) -> Option<SdkTracerProvider> {
+    if env::var("OTEL_EXPORTER_OTLP_ENDPOINT").is_err() {
+        return None;
+    }
+
+    let exporter = match opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .build()
+    {
+        Ok(exporter) => exporter,
+        Err(err) => {
+            eprintln!("WARNING: Failed to create the OTLP exporter, tracing is disabled: {err}");
+            return None;
+        }
+    };
+
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(Resource::builder().with_service_name(app_name).build())
+        .build();
+
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    global::set_tracer_provider(provider.clone());
+
+    Some(provider)
+}
+
+/// Records a statement logged by sqlx as a client span, parented to the span of the current request.
+///
+/// sqlx 0.6 has no tracing hooks of its own, but it logs every statement along with its duration
+/// once it completes, so the span is reconstructed from that record.
+pub fn record_query(record: &Record) {
+    let parent = Context::current();
+    if !parent.has_active_span() {
+        return;
+    }
+
+    let message = record.args().to_string();
+    let (summary, statement) = match message.split_once("\n\n") {
+        Some((summary, statement)) => (summary, statement.trim()),
+        None => (message.as_str(), ""),
+    };
+
+    let end = SystemTime::now();
+    let start = parse_elapsed(summary)
+        .and_then(|elapsed| end.checked_sub(elapsed))
+        .unwrap_or(end);
+
+    let tracer = global::tracer(TRACER_NAME);
+    let span = tracer
+        .span_builder("sqlx.query")
+        .with_kind(SpanKind::Client)
+        .with_start_time(start)
+        .with_attributes([
+            KeyValue::new("db.system", "postgresql"),
+            KeyValue::new("db.statement", statement.to_string()),
+            KeyValue::new("db.summary", summary.to_string()),
+        ])
+        .start_with_context(&tracer, &parent);
+
+    parent.with_span(span).span().end_with_timestamp(end);
+}
+
+/// Parses the `elapsed: 1.234ms` suffix of a sqlx statement log summary.
+fn parse_elapsed(summary: &str) -> Option<Duration> {
+    let elapsed = summary.rsplit_once("elapsed: ")?.1;
+    let split = elapsed
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(elapsed.len());
+    let (value, unit) = elapsed.split_at(split);
+    let value: f64 = value.parse().ok()?;
+
+    let seconds = match unit.trim() {
+        "s" => value,
+        "ms" => value / 1_000.0,
+        "µs" => value / 1_000_000.0,
+        "ns" => value / 1_000_000_000.0,
+        _ => return None,
+    };
+
+    Some(Duration::from_secs_f64(seconds))
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|key| key.as_str()).collect()
+    }
+}
+
+/// Middleware which wraps every request in an OpenTelemetry server span.
+///
+/// Incoming W3C `traceparent` headers are honoured, and the span is named after the matched route.
+pub struct Tracing;
+
+impl<S, B> Transform<S, ServiceRequest> for Tracing
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = TracingMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(TracingMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct TracingMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for TracingMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, request: ServiceRequest) -> Self::Future {
+        let parent = global::get_text_map_propagator(|propagator| {
+            propagator.extract(&HeaderExtractor(request.headers()))
+        });
+
+        let method = request.method().to_string();
+        let mut attributes = vec![
+            KeyValue::new("http.request.method", method.clone()),
+            KeyValue::new("url.path", request.path().to_string()),
+        ];
+        if let Some(request_id) = request_id::current() {
+            attributes.push(KeyValue::new("request.id", request_id.as_str().to_string()));
+        }
+
+        let tracer = global::tracer(TRACER_NAME);
+        let span = tracer
+            .span_builder(format!("{method} {}", request.path()))
+            .with_kind(SpanKind::Server)
+            .with_attributes(attributes)
+            .start_with_context(&tracer, &parent);
+        let context = parent.with_span(span);
+
+        let service = Rc::clone(&self.service);
+
+        Box::pin(
+            async move {
+                let context = Context::current();
+                let span = context.span();
+                let result = service.call(request).await;
+
+                match &result {
+                    Ok(response) => {
+                        if let Some(route) = response.request().match_pattern() {
+                            span.update_name(format!("{method} {route}"));
+                            span.set_attribute(KeyValue::new("http.route", route));
+                        }
+
+                        let status = response.status();
+                        span.set_attribute(KeyValue::new(
+                            "http.response.status_code",
+                            i64::from(status.as_u16()),
+                        ));
+                        if status.is_server_error() {
+                            span.set_status(Status::error(status.to_string()));
+                        }
+                    }
+                    Err(err) => span.set_status(Status::error(err.to_string())),
+                }
+
+                span.end();
+                result
+            }
+            .with_context(context),
+        )
+    }
+}
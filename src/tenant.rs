@@ -0,0 +1,118 @@
+use std::env;
+
+use actix_web::{
+    dev::Payload, error::InternalError, http::header::HeaderName, web::Data, Error, FromRequest,
+    HttpRequest, HttpResponse,
+};
+use futures_util::future::LocalBoxFuture;
+use sqlx::query;
+use utoipa::{
+    openapi::{
+        path::{Parameter, ParameterBuilder, ParameterIn},
+        ObjectBuilder, Required, SchemaType,
+    },
+    IntoParams,
+};
+
+use crate::{error::database_error, models::AppState};
+
+/// The header naming the organization a request acts on.
+pub const ORGANIZATION_HEADER: HeaderName = HeaderName::from_static("x-organization");
+
+/// The organization existing data was moved into when organizations were introduced.
+const DEFAULT_ORGANIZATION: &str = "default";
+
+#[derive(Clone)]
+pub struct TenantConfig {
+    /// The organization used when a request does not name one.
+    default_organization: String,
+}
+
+impl TenantConfig {
+    /// Reads the default organization from DEFAULT_ORGANIZATION, falling back to `default`, so
+    /// that clients from before organizations keep working.
+    pub fn from_env() -> TenantConfig {
+        TenantConfig {
+            default_organization: env::var("DEFAULT_ORGANIZATION")
+                .ok()
+                .filter(|slug| !slug.is_empty())
+                .unwrap_or_else(|| DEFAULT_ORGANIZATION.to_string()),
+        }
+    }
+}
+
+/// The organization a request is scoped to, resolved from the `X-Organization` header.
+///
+/// Every room, building, site and user belongs to one organization, and machines and reports
+/// belong to the organization of their room. Handlers take a [Tenant] and filter every query
+/// by it, so that records of other organizations behave as if they did not exist.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tenant {
+    pub organization_id: i32,
+}
+
+impl FromRequest for Tenant {
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(request: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let data = request.app_data::<Data<AppState>>().cloned();
+        let header = request
+            .headers()
+            .get(ORGANIZATION_HEADER)
+            .map(|value| value.to_str().map(str::to_string));
+
+        Box::pin(async move {
+            let data = data.expect("the application state is registered");
+
+            let slug = match header {
+                Some(Ok(slug)) => slug,
+                Some(Err(_)) => {
+                    return Err(rejection(
+                        HttpResponse::BadRequest()
+                            .json(format!("The {ORGANIZATION_HEADER} header is not valid.")),
+                    ))
+                }
+                None => data.tenant_config.default_organization.clone(),
+            };
+
+            match query!(
+                r#"
+                SELECT id
+                FROM organization
+                WHERE slug = $1
+                "#,
+                &slug
+            )
+            .fetch_optional(&data.database)
+            .await
+            {
+                Ok(Some(organization)) => Ok(Tenant {
+                    organization_id: organization.id,
+                }),
+                Ok(None) => Err(rejection(
+                    HttpResponse::NotFound().json(format!("Organization {slug} was not found.")),
+                )),
+                Err(err) => Err(rejection(database_error("resolve organization", err))),
+            }
+        })
+    }
+}
+
+fn rejection(response: HttpResponse) -> Error {
+    InternalError::from_response("organization rejected", response).into()
+}
+
+impl IntoParams for Tenant {
+    fn into_params(_: impl Fn() -> Option<ParameterIn>) -> Vec<Parameter> {
+        vec![ParameterBuilder::new()
+            .name("X-Organization")
+            .parameter_in(ParameterIn::Header)
+            .required(Required::False)
+            .description(Some(
+                "Slug of the organization to act on, the configured default organization if left out",
+            ))
+            .schema(Some(ObjectBuilder::new().schema_type(SchemaType::String)))
+            .build()]
+    }
+}
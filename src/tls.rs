@@ -0,0 +1,313 @@
+use std::{
+    any::Any,
+    fmt::Write,
+    fs::{self, File},
+    io::{self, BufReader},
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime},
+};
+
+use actix_tls::accept::rustls_0_22::TlsStream;
+use actix_web::{
+    dev::Extensions, http::header, rt::net::TcpStream, web::Data, HttpRequest, HttpResponse,
+    Responder,
+};
+use ring::digest;
+use rustls::{
+    crypto::ring::sign::any_supported_type,
+    pki_types::CertificateDer,
+    server::{ClientHello, ResolvesServerCert, WebPkiClientVerifier},
+    sign::CertifiedKey,
+    RootCertStore, ServerConfig,
+};
+
+use crate::{background::ShutdownSignal, config};
+
+/// How the server treats certificates presented by clients.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientAuth {
+    /// Clients must present a certificate signed by the configured CA.
+    Required,
+    /// Clients may present a certificate, which is verified if present.
+    Optional,
+}
+
+/// TLS settings parsed from the environment.
+#[derive(Debug, Clone)]
+pub struct TlsConfig {
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+    pub client_ca_path: Option<PathBuf>,
+    pub client_auth: ClientAuth,
+    pub https_port: u16,
+    /// The host plain HTTP requests are redirected to, since the Host header of a request cannot
+    /// be trusted to name this server.
+    pub public_host: Option<String>,
+    pub redirect_http: bool,
+    pub reload_interval: Duration,
+}
+
+impl TlsConfig {
+    /// Parses the TLS configuration from the environment.
+    ///
+    /// TLS is only enabled when both TLS_CERT_PATH and TLS_KEY_PATH are set.
+    ///
+    /// # Errors
+    /// Returns an error naming the value when TLS_CLIENT_AUTH is neither `required` nor `optional`,
+    /// rather than guessing which of the two was meant, and when TLS_REDIRECT_HTTP is enabled
+    /// without a TLS_PUBLIC_HOST to redirect to.
+    pub fn from_env() -> Result<Option<TlsConfig>, String> {
+        let (Ok(cert_path), Ok(key_path)) = (
+            std::env::var("TLS_CERT_PATH"),
+            std::env::var("TLS_KEY_PATH"),
+        ) else {
+            return Ok(None);
+        };
+
+        let client_auth = match std::env::var("TLS_CLIENT_AUTH")
+            .unwrap_or_default()
+            .to_lowercase()
+            .as_str()
+        {
+            "" | "required" => ClientAuth::Required,
+            "optional" => ClientAuth::Optional,
+            other => {
+                return Err(format!(
+                    "TLS_CLIENT_AUTH={other} is not valid, use required or optional"
+                ))
+            }
+        };
+
+        let public_host = std::env::var("TLS_PUBLIC_HOST")
+            .ok()
+            .map(|host| host.trim().to_string())
+            .filter(|host| !host.is_empty());
+        if let Some(host) = &public_host {
+            if host.contains(['/', '?', '#', '@', ' ']) {
+                return Err(format!(
+                    "TLS_PUBLIC_HOST={host} is not valid, use a host name such as laundry.example"
+                ));
+            }
+        }
+
+        let redirect_http = config::env_or("TLS_REDIRECT_HTTP", public_host.is_some());
+        if redirect_http && public_host.is_none() {
+            return Err(
+                "TLS_REDIRECT_HTTP requires TLS_PUBLIC_HOST to name the host to redirect to"
+                    .to_string(),
+            );
+        }
+
+        Ok(Some(TlsConfig {
+            cert_path: cert_path.into(),
+            key_path: key_path.into(),
+            client_ca_path: std::env::var("TLS_CLIENT_CA_PATH").ok().map(PathBuf::from),
+            client_auth,
+            https_port: config::env_or("HTTPS_PORT", 8443),
+            public_host,
+            redirect_http,
+            reload_interval: Duration::from_secs(config::env_or("TLS_RELOAD_INTERVAL_SECS", 30)),
+        }))
+    }
+
+    /// Builds the rustls server configuration, using `resolver` to provide the server certificate.
+    pub fn server_config(&self, resolver: Arc<ReloadingCertResolver>) -> io::Result<ServerConfig> {
+        let builder = ServerConfig::builder();
+
+        let builder = match &self.client_ca_path {
+            None => builder.with_no_client_auth(),
+            Some(client_ca_path) => {
+                let mut roots = RootCertStore::empty();
+                for cert in load_certs(client_ca_path)? {
+                    roots.add(cert).map_err(invalid_data)?;
+                }
+
+                let verifier = WebPkiClientVerifier::builder(Arc::new(roots));
+                let verifier = match self.client_auth {
+                    ClientAuth::Required => verifier,
+                    ClientAuth::Optional => verifier.allow_unauthenticated(),
+                };
+
+                builder.with_client_cert_verifier(verifier.build().map_err(invalid_data)?)
+            }
+        };
+
+        let mut server_config = builder.with_cert_resolver(resolver);
+        server_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+        Ok(server_config)
+    }
+}
+
+fn invalid_data<E: std::error::Error + Send + Sync + 'static>(err: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+fn load_certs(path: &Path) -> io::Result<Vec<CertificateDer<'static>>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let certs = rustls_pemfile::certs(&mut reader).collect::<Result<Vec<_>, _>>()?;
+
+    match certs.is_empty() {
+        true => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("no certificates found in {}", path.display()),
+        )),
+        false => Ok(certs),
+    }
+}
+
+fn load_certified_key(cert_path: &Path, key_path: &Path) -> io::Result<CertifiedKey> {
+    let certs = load_certs(cert_path)?;
+
+    let mut reader = BufReader::new(File::open(key_path)?);
+    let key = rustls_pemfile::private_key(&mut reader)?.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("no private key found in {}", key_path.display()),
+        )
+    })?;
+    let key = any_supported_type(&key).map_err(invalid_data)?;
+
+    Ok(CertifiedKey::new(certs, key))
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Serves the server certificate, reloading it from disk whenever the files change.
+#[derive(Debug)]
+pub struct ReloadingCertResolver {
+    cert_path: PathBuf,
+    key_path: PathBuf,
+    current: RwLock<Arc<CertifiedKey>>,
+    loaded_at: RwLock<(Option<SystemTime>, Option<SystemTime>)>,
+}
+
+impl ReloadingCertResolver {
+    /// Loads the certificate chain and private key, failing if either cannot be read.
+    pub fn new(cert_path: &Path, key_path: &Path) -> io::Result<ReloadingCertResolver> {
+        let certified_key = load_certified_key(cert_path, key_path)?;
+
+        Ok(ReloadingCertResolver {
+            cert_path: cert_path.to_path_buf(),
+            key_path: key_path.to_path_buf(),
+            current: RwLock::new(Arc::new(certified_key)),
+            loaded_at: RwLock::new((modified(cert_path), modified(key_path))),
+        })
+    }
+
+    /// Reloads the certificate if either file has changed since it was last loaded.
+    ///
+    /// A certificate which fails to load is logged and the previous certificate stays in use.
+    pub fn reload_if_changed(&self) {
+        let latest = (modified(&self.cert_path), modified(&self.key_path));
+
+        let changed = match self.loaded_at.read() {
+            Ok(loaded_at) => *loaded_at != latest,
+            Err(_) => true,
+        };
+        if !changed {
+            return;
+        }
+
+        match load_certified_key(&self.cert_path, &self.key_path) {
+            Ok(certified_key) => {
+                if let Ok(mut current) = self.current.write() {
+                    *current = Arc::new(certified_key);
+                }
+                if let Ok(mut loaded_at) = self.loaded_at.write() {
+                    *loaded_at = latest;
+                }
+                log::info!("Reloaded TLS certificate from {}", self.cert_path.display());
+            }
+            Err(err) => log::error!(
+                "Failed to reload TLS certificate from {}, keeping the previous certificate: {err}",
+                self.cert_path.display()
+            ),
+        }
+    }
+
+    /// Background job which periodically checks the certificate files for changes.
+    pub async fn watch(self: Arc<Self>, interval: Duration, mut shutdown: ShutdownSignal) {
+        while shutdown.sleep(interval).await {
+            self.reload_if_changed();
+        }
+    }
+}
+
+impl ResolvesServerCert for ReloadingCertResolver {
+    fn resolve(&self, _client_hello: ClientHello) -> Option<Arc<CertifiedKey>> {
+        self.current.read().ok().map(|current| Arc::clone(&current))
+    }
+}
+
+/// The certificate a client authenticated the TLS connection with, stored in the connection data
+/// by [store_client_certificate]. Certificates with an allowed fingerprint authenticate requests
+/// to the admin endpoints, see [AdminConfig](crate::admin::AdminConfig), and those registered to
+/// a telemetry device authenticate the readings it submits.
+#[derive(Debug, Clone)]
+pub struct ClientCertificate(CertificateDer<'static>);
+
+impl ClientCertificate {
+    /// The DER encoded certificate.
+    pub fn der(&self) -> &[u8] {
+        self.0.as_ref()
+    }
+
+    /// The lowercase hex encoded SHA-256 fingerprint of the certificate.
+    pub fn fingerprint(&self) -> String {
+        digest::digest(&digest::SHA256, self.der())
+            .as_ref()
+            .iter()
+            .fold(String::new(), |mut fingerprint, byte| {
+                let _ = write!(fingerprint, "{byte:02x}");
+                fingerprint
+            })
+    }
+}
+
+/// Connection callback for [HttpServer::on_connect](actix_web::HttpServer::on_connect),
+/// storing the verified client certificate of TLS connections so requests can be authenticated by it.
+pub fn store_client_certificate(connection: &dyn Any, extensions: &mut Extensions) {
+    let Some(stream) = connection.downcast_ref::<TlsStream<TcpStream>>() else {
+        return;
+    };
+
+    let (_, session) = stream.get_ref();
+    if let Some(cert) = session.peer_certificates().and_then(|certs| certs.first()) {
+        extensions.insert(ClientCertificate(cert.clone().into_owned()));
+    }
+}
+
+/// Where requests are redirected to by [redirect_to_https].
+#[derive(Clone)]
+pub struct HttpsRedirect {
+    /// The configured public host, never taken from the request.
+    pub host: String,
+    pub port: u16,
+}
+
+/// Redirects every plain HTTP request to the same path on the public host over HTTPS.
+pub async fn redirect_to_https(
+    request: HttpRequest,
+    redirect: Data<HttpsRedirect>,
+) -> impl Responder {
+    let host = &redirect.host;
+    let path = request
+        .uri()
+        .path_and_query()
+        .map_or("/", |path_and_query| path_and_query.as_str());
+
+    let location = match redirect.port {
+        443 => format!("https://{host}{path}"),
+        port => format!("https://{host}:{port}{path}"),
+    };
+
+    HttpResponse::PermanentRedirect()
+        .insert_header((header::LOCATION, location))
+        .finish()
+}
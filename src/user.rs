@@ -7,25 +7,34 @@ use serde::{Deserialize, Serialize};
 use sqlx::{query, query_as, Pool, Postgres};
 use utoipa::ToSchema;
 
-use crate::models::{AppState, Report, ReportType, User};
+use crate::{
+    attachment,
+    error::{constraint_violation, database_error, Violation},
+    models::{AppState, Report, ReportType, User},
+    tenant::Tenant,
+};
 
 #[derive(Serialize, Deserialize, ToSchema)]
 pub struct UserSubmission {
     username: String,
     admin: bool,
+    #[serde(default)]
+    technician: bool,
 }
 
-async fn is_username_present(
+pub async fn is_username_present(
     database: &Pool<Postgres>,
+    tenant: &Tenant,
     username: &String,
 ) -> Result<bool, sqlx::Error> {
     match query!(
         r#"
         SELECT username
         FROM public.user
-        WHERE username = $1
+        WHERE username = $1 AND organization_id = $2
         "#,
-        username
+        username,
+        tenant.organization_id
     )
     .fetch_optional(database)
     .await
@@ -35,55 +44,81 @@ async fn is_username_present(
     }
 }
 
+/// Whether the user may carry out maintenance, being a technician or an admin, or `None` if the
+/// user does not exist.
+pub async fn is_technician(
+    database: &Pool<Postgres>,
+    tenant: &Tenant,
+    username: &String,
+) -> Result<Option<bool>, sqlx::Error> {
+    query!(
+        r#"
+        SELECT technician OR admin AS "technician!"
+        FROM public.user
+        WHERE username = $1 AND organization_id = $2
+        "#,
+        username,
+        tenant.organization_id
+    )
+    .fetch_optional(database)
+    .await
+    .map(|user| user.map(|user| user.technician))
+}
+
 #[utoipa::path(
     context_path = "/user",
+    params(Tenant),
     responses(
-        (status = 200, description = "Lists all users", body = Vec<User>, example = json!([{"username": "admin", "admin": true}])),
+        (status = 200, description = "Lists all users", body = Vec<User>, example = json!([{"username": "admin", "admin": true, "technician": false}])),
         (status = 500, description = "An internal server error occurred")
     )
 )]
 #[get("/")]
-async fn get_all_users(data: Data<AppState>) -> impl Responder {
+async fn get_all_users(data: Data<AppState>, tenant: Tenant) -> impl Responder {
     match query_as!(
         User,
         r#"
-        SELECT username, admin
+        SELECT username, admin, technician
         FROM public.user
-        "#
+        WHERE organization_id = $1
+        "#,
+        tenant.organization_id
     )
     .fetch_all(&data.database)
     .await
     {
         Ok(users) => HttpResponse::Ok().json(users),
-        Err(err) => HttpResponse::InternalServerError().json(err.to_string()),
+        Err(err) => database_error("fetch all users", err),
     }
 }
 
 #[utoipa::path(
     context_path = "/user",
+    params(Tenant),
     responses(
-        (status = 200, description = "The requested user", body=User, example = json!({"username": "admin", "admin": true})),
+        (status = 200, description = "The requested user", body=User, example = json!({"username": "admin", "admin": true, "technician": false})),
         (status = 404, description = "The requested user was not found"),
         (status = 500, description = "An internal server error occurred")
     )
 )]
 #[get("/{username}")]
-async fn get_user(data: Data<AppState>, path: Path<String>) -> impl Responder {
+async fn get_user(data: Data<AppState>, tenant: Tenant, path: Path<String>) -> impl Responder {
     let username = path.into_inner();
 
     match query_as!(
         User,
         r#"
-        SELECT username, admin
+        SELECT username, admin, technician
         FROM public.user
-        WHERE username = $1
+        WHERE username = $1 AND organization_id = $2
         "#,
-        username
+        username,
+        tenant.organization_id
     )
     .fetch_optional(&data.database)
     .await
     {
-        Err(err) => HttpResponse::InternalServerError().json(err.to_string()),
+        Err(err) => database_error("fetch user", err),
         Ok(user) => match user {
             Some(user) => HttpResponse::Ok().json(&user),
             None => HttpResponse::NotFound().json(format!("The user {username} was not found.")),
@@ -93,14 +128,15 @@ async fn get_user(data: Data<AppState>, path: Path<String>) -> impl Responder {
 
 #[utoipa::path(
     context_path = "/user",
+    params(Tenant),
     request_body(
         content = UserSubmission,
         content_type = "application/json",
-        description = "JSON object containing username and admin status",
-        example = json!({"username": "admin", "admin": true})
+        description = "JSON object containing username, admin status and whether the user is a technician",
+        example = json!({"username": "admin", "admin": true, "technician": false})
     ),
     responses(
-        (status = 201, description = "The user was added", body = User, example = json!({"username": "admin", "admin": true})),
+        (status = 201, description = "The user was added", body = User, example = json!({"username": "admin", "admin": true, "technician": false})),
         (status = 409, description = "The requested username is already in use"),
         (status = 500, description = "An internal server error occurred")
     )
@@ -108,12 +144,13 @@ async fn get_user(data: Data<AppState>, path: Path<String>) -> impl Responder {
 #[post("/")]
 async fn add_user(
     data: Data<AppState>,
+    tenant: Tenant,
     Json(user_submission): Json<UserSubmission>,
 ) -> impl Responder {
     let username_present =
-        match is_username_present(&data.database, &user_submission.username).await {
+        match is_username_present(&data.database, &tenant, &user_submission.username).await {
             Ok(result) => result,
-            Err(err) => return HttpResponse::InternalServerError().json(err.to_string()),
+            Err(err) => return database_error("check username presence", err),
         };
 
     if username_present {
@@ -124,61 +161,78 @@ async fn add_user(
     match query_as!(
         User,
         r#"
-        INSERT INTO public.user (username, admin)
-        VALUES ($1, $2)
-        RETURNING username, admin
+        INSERT INTO public.user (username, admin, technician, organization_id)
+        VALUES ($1, $2, $3, $4)
+        RETURNING username, admin, technician
         "#,
         &user_submission.username,
-        &user_submission.admin
+        &user_submission.admin,
+        &user_submission.technician,
+        tenant.organization_id
     )
     .fetch_one(&data.database)
     .await
     {
         Ok(user) => HttpResponse::Created().json(user),
-        Err(err) => HttpResponse::InternalServerError().json(err.to_string()),
+        Err(err) => match constraint_violation(&err) {
+            Some(Violation::Unique) => HttpResponse::Conflict()
+                .json(format!("{} is already taken", &user_submission.username)),
+            _ => database_error("insert user", err),
+        },
     }
 }
 
 #[utoipa::path(
     context_path = "/user",
+    params(Tenant),
     responses(
-        (status = 200, description = "The requested user was deleted", body = User, example = json!({"username": "admin", "admin": true})),
+        (status = 200, description = "The requested user was deleted", body = User, example = json!({"username": "admin", "admin": true, "technician": false})),
         (status = 404, description = "The requested user was not found"),
         (status = 500, description = "An internal server error occurred")
     )
 )]
 #[delete("/{username}")]
-async fn delete_user(data: Data<AppState>, path: Path<String>) -> impl Responder {
+async fn delete_user(data: Data<AppState>, tenant: Tenant, path: Path<String>) -> impl Responder {
     let username = path.into_inner();
 
-    let username_present = match is_username_present(&data.database, &username).await {
+    let username_present = match is_username_present(&data.database, &tenant, &username).await {
         Ok(result) => result,
-        Err(err) => return HttpResponse::InternalServerError().json(err.to_string()),
+        Err(err) => return database_error("check username presence", err),
     };
 
     if !username_present {
         return HttpResponse::NotFound().json(format!("The user {username} was not found."));
     }
 
+    let blob_keys = match attachment::user_blob_keys(&data.database, &tenant, &username).await {
+        Ok(keys) => keys,
+        Err(err) => return database_error("fetch user attachments", err),
+    };
+
     match query_as!(
         User,
         r#"
         DELETE FROM public.user
-        WHERE username = $1
-        RETURNING username, admin
+        WHERE username = $1 AND organization_id = $2
+        RETURNING username, admin, technician
         "#,
-        &username
+        &username,
+        tenant.organization_id
     )
     .fetch_one(&data.database)
     .await
     {
-        Ok(user) => HttpResponse::Ok().json(user),
-        Err(err) => HttpResponse::InternalServerError().json(err.to_string()),
+        Ok(user) => {
+            attachment::remove_blobs(&data.blob_store, &blob_keys).await;
+            HttpResponse::Ok().json(user)
+        }
+        Err(err) => database_error("delete user", err),
     }
 }
 
 #[utoipa::path(
     context_path = "/user",
+    params(Tenant),
     responses(
         (status = 200, description = "List of all unarchived reports made by the requested user", body = Vec<Report>, example = json!([{
             "report_id": 1,
@@ -189,18 +243,26 @@ async fn delete_user(data: Data<AppState>, path: Path<String>) -> impl Responder
             "description": "No heat",
             "time": "2023-01-01T12:00:00.000Z",
             "archived": false,
+            "automated": false,
+            "confirmation_count": 0,
+            "comment_count": 0,
+            "attachments": [],
         }])),
         (status = 404, description = "The requested user was not found"),
         (status = 500, description = "An internal server error occurred")
     )
 )]
 #[get("/{username}/reports")]
-async fn get_user_reports(data: Data<AppState>, path: Path<String>) -> impl Responder {
+async fn get_user_reports(
+    data: Data<AppState>,
+    tenant: Tenant,
+    path: Path<String>,
+) -> impl Responder {
     let username = path.into_inner();
 
-    let username_present = match is_username_present(&data.database, &username).await {
+    let username_present = match is_username_present(&data.database, &tenant, &username).await {
         Ok(result) => result,
-        Err(err) => return HttpResponse::InternalServerError().json(err.to_string()),
+        Err(err) => return database_error("check username presence", err),
     };
 
     if !username_present {
@@ -218,23 +280,29 @@ async fn get_user_reports(data: Data<AppState>, path: Path<String>) -> impl Resp
             time,
             type as "report_type: ReportType",
             description,
-            archived
+            archived,
+            automated,
+            (SELECT COUNT(*) FROM report_confirmation WHERE report_id = report.id) AS "confirmation_count!: i64",
+            (SELECT COUNT(*) FROM report_comment WHERE report_id = report.id) AS "comment_count!: i64",
+            report_attachments(report.id) AS "attachments!: _"
         FROM report
-        WHERE reporter_username = $1
+        WHERE reporter_username = $1 AND organization_id = $2
             AND archived = false
         "#,
-        &username
+        &username,
+        tenant.organization_id
     )
     .fetch_all(&data.database)
     .await
     {
         Ok(reports) => HttpResponse::Ok().json(reports),
-        Err(err) => HttpResponse::InternalServerError().json(err.to_string()),
+        Err(err) => database_error("fetch user reports", err),
     }
 }
 
 #[utoipa::path(
     context_path = "/user",
+    params(Tenant),
     responses(
         (status = 200, description = "List of all archived reports made by the requested user", body = Vec<Report>, example = json!([{
             "report_id": 1,
@@ -245,18 +313,26 @@ async fn get_user_reports(data: Data<AppState>, path: Path<String>) -> impl Resp
             "description": "No heat",
             "time": "2023-01-01T12:00:00.000Z",
             "archived": true,
+            "automated": false,
+            "confirmation_count": 0,
+            "comment_count": 0,
+            "attachments": [],
         }])),
         (status = 404, description = "The requested user was not found"),
         (status = 500, description = "An internal server error occurred")
     )
 )]
 #[get("/{username}/reports/archived")]
-async fn get_user_archived_reports(data: Data<AppState>, path: Path<String>) -> impl Responder {
+async fn get_user_archived_reports(
+    data: Data<AppState>,
+    tenant: Tenant,
+    path: Path<String>,
+) -> impl Responder {
     let username = path.into_inner();
 
-    let username_present = match is_username_present(&data.database, &username).await {
+    let username_present = match is_username_present(&data.database, &tenant, &username).await {
         Ok(result) => result,
-        Err(err) => return HttpResponse::InternalServerError().json(err.to_string()),
+        Err(err) => return database_error("check username presence", err),
     };
 
     if !username_present {
@@ -274,17 +350,22 @@ async fn get_user_archived_reports(data: Data<AppState>, path: Path<String>) ->
             time,
             type as "report_type: ReportType",
             description,
-            archived
+            archived,
+            automated,
+            (SELECT COUNT(*) FROM report_confirmation WHERE report_id = report.id) AS "confirmation_count!: i64",
+            (SELECT COUNT(*) FROM report_comment WHERE report_id = report.id) AS "comment_count!: i64",
+            report_attachments(report.id) AS "attachments!: _"
         FROM report
-        WHERE reporter_username = $1
+        WHERE reporter_username = $1 AND organization_id = $2
             AND archived = true
         "#,
-        &username
+        &username,
+        tenant.organization_id
     )
     .fetch_all(&data.database)
     .await
     {
         Ok(reports) => HttpResponse::Ok().json(reports),
-        Err(err) => HttpResponse::InternalServerError().json(err.to_string()),
+        Err(err) => database_error("fetch user archived reports", err),
     }
 }
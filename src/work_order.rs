@@ -0,0 +1,939 @@
+use actix_web::{
+    get, post, put,
+    web::{Data, Json, Path, Query},
+    HttpResponse, Responder,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{query, query_as, Executor, Postgres};
+use time::Date;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    error::{constraint_violation, database_error, Violation},
+    models::{
+        iso_date, now, AppState, WorkOrder, WorkOrderLabour, WorkOrderPart, WorkOrderPriority,
+        WorkOrderStatus,
+    },
+    report,
+    tenant::Tenant,
+    user,
+};
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct WorkOrderSubmission {
+    title: String,
+    description: Option<String>,
+    /// The open reports the work order covers, at least one.
+    report_ids: Vec<i32>,
+    #[serde(default = "default_priority")]
+    priority: WorkOrderPriority,
+    #[serde(default, with = "iso_date::option")]
+    due_date: Option<Date>,
+    #[serde(default)]
+    assignee_username: Option<String>,
+}
+
+/// The editable details of a work order, which are replaced as a whole when edited.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct WorkOrderUpdate {
+    title: String,
+    description: Option<String>,
+    /// Any status but `Closed`, as work orders are closed through their own endpoint.
+    status: WorkOrderStatus,
+    priority: WorkOrderPriority,
+    #[serde(default, with = "iso_date::option")]
+    due_date: Option<Date>,
+    #[serde(default)]
+    assignee_username: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct WorkOrderClosure {
+    resolution: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ClosedWorkOrder {
+    work_order: WorkOrder,
+    archived_report_ids: Vec<i32>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct LabourSubmission {
+    technician_username: String,
+    minutes: i32,
+    description: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct PartSubmission {
+    name: String,
+    #[serde(default)]
+    part_number: Option<String>,
+    quantity: i32,
+    #[serde(default)]
+    unit_cost_cents: Option<i32>,
+}
+
+/// Filters applied when listing work orders.
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct WorkOrderQuery {
+    status: Option<WorkOrderStatus>,
+    priority: Option<WorkOrderPriority>,
+    assignee_username: Option<String>,
+}
+
+fn default_priority() -> WorkOrderPriority {
+    WorkOrderPriority::Normal
+}
+
+async fn find_work_order<'c, E>(
+    executor: E,
+    tenant: &Tenant,
+    work_order_id: &i32,
+) -> Result<Option<WorkOrder>, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    query_as!(
+        WorkOrder,
+        r#"
+        SELECT
+            id AS work_order_id,
+            title,
+            description,
+            status AS "status: WorkOrderStatus",
+            priority AS "priority: WorkOrderPriority",
+            due_date,
+            COALESCE(status <> 'closed' AND due_date < $3, false) AS "overdue!",
+            assignee_username,
+            ARRAY(
+                SELECT report_id FROM work_order_report WHERE work_order_id = work_order.id ORDER BY report_id
+            ) AS "report_ids!",
+            (SELECT COALESCE(SUM(minutes), 0) FROM work_order_labour WHERE work_order_id = work_order.id) AS "labour_minutes!",
+            (SELECT COALESCE(SUM(quantity * unit_cost_cents::BIGINT), 0) FROM work_order_part WHERE work_order_id = work_order.id)::BIGINT AS "parts_cost_cents!",
+            created_time,
+            closed_time,
+            resolution
+        FROM work_order
+        WHERE id = $1 AND organization_id = $2
+        "#,
+        work_order_id,
+        tenant.organization_id,
+        now().date()
+    )
+    .fetch_optional(executor)
+    .await
+}
+
+async fn find_work_order_status<'c, E>(
+    executor: E,
+    tenant: &Tenant,
+    work_order_id: &i32,
+) -> Result<Option<WorkOrderStatus>, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    Ok(query!(
+        r#"
+        SELECT status AS "status: WorkOrderStatus"
+        FROM work_order
+        WHERE id = $1 AND organization_id = $2
+        "#,
+        work_order_id,
+        tenant.organization_id
+    )
+    .fetch_optional(executor)
+    .await?
+    .map(|work_order| work_order.status))
+}
+
+/// The response for a work order which could not be changed, because it is missing or closed.
+fn unchangeable_work_order(work_order_id: i32, status: Option<WorkOrderStatus>) -> HttpResponse {
+    match status {
+        None => {
+            HttpResponse::NotFound().json(format!("Work order id {work_order_id} was not found."))
+        }
+        Some(_) => HttpResponse::Conflict()
+            .json(format!("Work order id {work_order_id} was already closed.")),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/work-order",
+    params(Tenant, WorkOrderQuery),
+    responses(
+        (status = 200, description = "List of work orders matching the filters, unclosed and most urgent first", body = Vec<WorkOrder>, example = json!([{
+            "work_order_id": 1,
+            "title": "Replace drum bearing",
+            "description": "Loud grinding during spin",
+            "status": "InProgress",
+            "priority": "High",
+            "due_date": "2023-01-05",
+            "overdue": false,
+            "assignee_username": "admin",
+            "report_ids": [1, 3],
+            "labour_minutes": 90,
+            "parts_cost_cents": 4599,
+            "created_time": "2023-01-01T12:00:00",
+            "closed_time": null,
+            "resolution": null
+        }])),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[get("/")]
+async fn get_work_orders(
+    data: Data<AppState>,
+    tenant: Tenant,
+    Query(work_order_query): Query<WorkOrderQuery>,
+) -> impl Responder {
+    match query_as!(
+        WorkOrder,
+        r#"
+        SELECT
+            id AS work_order_id,
+            title,
+            description,
+            status AS "status: WorkOrderStatus",
+            priority AS "priority: WorkOrderPriority",
+            due_date,
+            COALESCE(status <> 'closed' AND due_date < $1, false) AS "overdue!",
+            assignee_username,
+            ARRAY(
+                SELECT report_id FROM work_order_report WHERE work_order_id = work_order.id ORDER BY report_id
+            ) AS "report_ids!",
+            (SELECT COALESCE(SUM(minutes), 0) FROM work_order_labour WHERE work_order_id = work_order.id) AS "labour_minutes!",
+            (SELECT COALESCE(SUM(quantity * unit_cost_cents::BIGINT), 0) FROM work_order_part WHERE work_order_id = work_order.id)::BIGINT AS "parts_cost_cents!",
+            created_time,
+            closed_time,
+            resolution
+        FROM work_order
+        WHERE organization_id = $2
+            AND ($3::work_order_status IS NULL OR status = $3)
+            AND ($4::work_order_priority IS NULL OR priority = $4)
+            AND ($5::VARCHAR IS NULL OR assignee_username = $5)
+        ORDER BY status = 'closed', priority DESC, due_date NULLS LAST, id
+        "#,
+        now().date(),
+        tenant.organization_id,
+        work_order_query.status as Option<WorkOrderStatus>,
+        work_order_query.priority as Option<WorkOrderPriority>,
+        work_order_query.assignee_username
+    )
+    .fetch_all(&data.database)
+    .await
+    {
+        Ok(work_orders) => HttpResponse::Ok().json(work_orders),
+        Err(err) => database_error("fetch work orders", err),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/work-order",
+    params(Tenant),
+    responses(
+        (status = 200, description = "The requested work order", body = WorkOrder, example = json!({
+            "work_order_id": 1,
+            "title": "Replace drum bearing",
+            "description": "Loud grinding during spin",
+            "status": "InProgress",
+            "priority": "High",
+            "due_date": "2023-01-05",
+            "overdue": false,
+            "assignee_username": "admin",
+            "report_ids": [1, 3],
+            "labour_minutes": 90,
+            "parts_cost_cents": 4599,
+            "created_time": "2023-01-01T12:00:00",
+            "closed_time": null,
+            "resolution": null
+        })),
+        (status = 404, description = "The requested work order was not found"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[get("/{work_order_id}")]
+async fn get_work_order(data: Data<AppState>, tenant: Tenant, path: Path<i32>) -> impl Responder {
+    let work_order_id = path.into_inner();
+
+    match find_work_order(&data.database, &tenant, &work_order_id).await {
+        Ok(Some(work_order)) => HttpResponse::Ok().json(work_order),
+        Ok(None) => {
+            HttpResponse::NotFound().json(format!("Work order id {work_order_id} was not found."))
+        }
+        Err(err) => database_error("fetch work order", err),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/admin",
+    params(Tenant),
+    request_body(content = WorkOrderSubmission, content_type = "application/json", example = json!({
+        "title": "Replace drum bearing",
+        "description": "Loud grinding during spin",
+        "report_ids": [1, 3],
+        "priority": "High",
+        "due_date": "2023-01-05",
+        "assignee_username": "admin"
+    })),
+    responses(
+        (status = 201, description = "The work order was created", body = WorkOrder, example = json!({
+            "work_order_id": 1,
+            "title": "Replace drum bearing",
+            "description": "Loud grinding during spin",
+            "status": "Open",
+            "priority": "High",
+            "due_date": "2023-01-05",
+            "overdue": false,
+            "assignee_username": "admin",
+            "report_ids": [1, 3],
+            "labour_minutes": 0,
+            "parts_cost_cents": 0,
+            "created_time": "2023-01-01T12:00:00",
+            "closed_time": null,
+            "resolution": null
+        })),
+        (status = 400, description = "The requested query was invalid or the assignee is neither a technician nor an admin"),
+        (status = 401, description = "No valid admin credentials were given"),
+        (status = 409, description = "A report is already covered by another unclosed work order"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[post("/work-orders")]
+async fn add_work_order(
+    data: Data<AppState>,
+    tenant: Tenant,
+    Json(mut submission): Json<WorkOrderSubmission>,
+) -> impl Responder {
+    submission.report_ids.sort_unstable();
+    submission.report_ids.dedup();
+
+    if submission.report_ids.is_empty() {
+        return HttpResponse::BadRequest().json("A work order must cover at least one report.");
+    }
+
+    if let Some(assignee_username) = &submission.assignee_username {
+        match user::is_technician(&data.database, &tenant, assignee_username).await {
+            Ok(Some(true)) => {}
+            Ok(Some(false)) => {
+                return HttpResponse::BadRequest().json(format!(
+                    "The user {assignee_username} is neither a technician nor an admin."
+                ))
+            }
+            Ok(None) => {
+                return HttpResponse::BadRequest()
+                    .json(format!("The user {assignee_username} was not found."))
+            }
+            Err(err) => return database_error("check technician", err),
+        }
+    }
+
+    let mut transaction = match data.database.begin().await {
+        Ok(transaction) => transaction,
+        Err(err) => return database_error("begin work order creation", err),
+    };
+
+    let reports = match query!(
+        r#"
+        SELECT
+            id,
+            archived,
+            (
+                SELECT work_order.id
+                FROM work_order_report
+                JOIN work_order ON work_order.id = work_order_report.work_order_id
+                WHERE work_order_report.report_id = report.id AND work_order.status <> 'closed'
+                LIMIT 1
+            ) AS open_work_order_id
+        FROM report
+        WHERE id = ANY($1) AND room_id IN (SELECT id FROM room WHERE organization_id = $2)
+        ORDER BY id
+        FOR UPDATE
+        "#,
+        &submission.report_ids,
+        tenant.organization_id
+    )
+    .fetch_all(&mut transaction)
+    .await
+    {
+        Ok(reports) => reports,
+        Err(err) => return database_error("fetch work order reports", err),
+    };
+
+    for report_id in &submission.report_ids {
+        let Some(report) = reports.iter().find(|report| report.id == *report_id) else {
+            return HttpResponse::BadRequest()
+                .json(format!("Report id {report_id} was not found."));
+        };
+
+        if report.archived {
+            return HttpResponse::BadRequest()
+                .json(format!("Report id {report_id} is already archived."));
+        }
+
+        if let Some(work_order_id) = report.open_work_order_id {
+            return HttpResponse::Conflict().json(format!(
+                "Report id {report_id} is already covered by work order id {work_order_id}."
+            ));
+        }
+    }
+
+    let work_order_id = match query!(
+        r#"
+        INSERT INTO work_order (
+            organization_id, title, description, priority, due_date, assignee_username, created_time
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        RETURNING id
+        "#,
+        tenant.organization_id,
+        submission.title,
+        submission.description,
+        submission.priority as WorkOrderPriority,
+        submission.due_date,
+        submission.assignee_username,
+        now()
+    )
+    .fetch_one(&mut transaction)
+    .await
+    {
+        Ok(work_order) => work_order.id,
+        Err(err) => return database_error("insert work order", err),
+    };
+
+    if let Err(err) = query!(
+        r#"
+        INSERT INTO work_order_report (work_order_id, report_id)
+        SELECT $1, UNNEST($2::INTEGER[])
+        "#,
+        work_order_id,
+        &submission.report_ids
+    )
+    .execute(&mut transaction)
+    .await
+    {
+        return database_error("link work order reports", err);
+    }
+
+    let work_order = match find_work_order(&mut transaction, &tenant, &work_order_id).await {
+        Ok(Some(work_order)) => work_order,
+        Ok(None) => return HttpResponse::InternalServerError().finish(),
+        Err(err) => return database_error("fetch work order", err),
+    };
+
+    match transaction.commit().await {
+        Ok(()) => HttpResponse::Created().json(work_order),
+        Err(err) => database_error("commit work order creation", err),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/admin",
+    params(Tenant),
+    request_body(content = WorkOrderUpdate, content_type = "application/json", example = json!({
+        "title": "Replace drum bearing",
+        "description": "Loud grinding during spin, bearing ordered",
+        "status": "OnHold",
+        "priority": "High",
+        "due_date": "2023-01-09",
+        "assignee_username": "admin"
+    })),
+    responses(
+        (status = 200, description = "The details of the requested work order were replaced", body = WorkOrder, example = json!({
+            "work_order_id": 1,
+            "title": "Replace drum bearing",
+            "description": "Loud grinding during spin, bearing ordered",
+            "status": "OnHold",
+            "priority": "High",
+            "due_date": "2023-01-09",
+            "overdue": false,
+            "assignee_username": "admin",
+            "report_ids": [1, 3],
+            "labour_minutes": 90,
+            "parts_cost_cents": 0,
+            "created_time": "2023-01-01T12:00:00",
+            "closed_time": null,
+            "resolution": null
+        })),
+        (status = 400, description = "The requested query was invalid or the assignee is neither a technician nor an admin"),
+        (status = 401, description = "No valid admin credentials were given"),
+        (status = 404, description = "The requested work order was not found"),
+        (status = 409, description = "The work order was already closed"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[put("/work-orders/{work_order_id}")]
+async fn update_work_order(
+    data: Data<AppState>,
+    tenant: Tenant,
+    path: Path<i32>,
+    Json(update): Json<WorkOrderUpdate>,
+) -> impl Responder {
+    let work_order_id = path.into_inner();
+
+    if update.status == WorkOrderStatus::Closed {
+        return HttpResponse::BadRequest().json(format!(
+            "Work orders are closed with /work-order/{work_order_id}/close."
+        ));
+    }
+
+    if let Some(assignee_username) = &update.assignee_username {
+        match user::is_technician(&data.database, &tenant, assignee_username).await {
+            Ok(Some(true)) => {}
+            Ok(Some(false)) => {
+                return HttpResponse::BadRequest().json(format!(
+                    "The user {assignee_username} is neither a technician nor an admin."
+                ))
+            }
+            Ok(None) => {
+                return HttpResponse::BadRequest()
+                    .json(format!("The user {assignee_username} was not found."))
+            }
+            Err(err) => return database_error("check technician", err),
+        }
+    }
+
+    match query!(
+        r#"
+        UPDATE work_order
+        SET title = $3,
+            description = $4,
+            status = $5,
+            priority = $6,
+            due_date = $7,
+            assignee_username = $8
+        WHERE id = $1 AND organization_id = $2 AND status <> 'closed'
+        RETURNING id
+        "#,
+        work_order_id,
+        tenant.organization_id,
+        update.title,
+        update.description,
+        update.status as WorkOrderStatus,
+        update.priority as WorkOrderPriority,
+        update.due_date,
+        update.assignee_username
+    )
+    .fetch_optional(&data.database)
+    .await
+    {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            return match find_work_order_status(&data.database, &tenant, &work_order_id).await {
+                Ok(status) => unchangeable_work_order(work_order_id, status),
+                Err(err) => database_error("fetch work order status", err),
+            }
+        }
+        Err(err) => return database_error("update work order", err),
+    }
+
+    match find_work_order(&data.database, &tenant, &work_order_id).await {
+        Ok(Some(work_order)) => HttpResponse::Ok().json(work_order),
+        Ok(None) => {
+            HttpResponse::NotFound().json(format!("Work order id {work_order_id} was not found."))
+        }
+        Err(err) => database_error("fetch work order", err),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/admin",
+    params(Tenant),
+    request_body(content = WorkOrderClosure, content_type = "application/json", example = json!({
+        "resolution": "Replaced the drum bearing"
+    })),
+    responses(
+        (status = 200, description = "The work order was closed and its unarchived reports archived", body = ClosedWorkOrder, example = json!({
+            "work_order": {
+                "work_order_id": 1,
+                "title": "Replace drum bearing",
+                "description": "Loud grinding during spin",
+                "status": "Closed",
+                "priority": "High",
+                "due_date": "2023-01-05",
+                "overdue": false,
+                "assignee_username": "admin",
+                "report_ids": [1, 3],
+                "labour_minutes": 90,
+                "parts_cost_cents": 4599,
+                "created_time": "2023-01-01T12:00:00",
+                "closed_time": "2023-01-04T16:00:00",
+                "resolution": "Replaced the drum bearing"
+            },
+            "archived_report_ids": [1, 3]
+        })),
+        (status = 401, description = "No valid admin credentials were given"),
+        (status = 404, description = "The requested work order was not found"),
+        (status = 409, description = "The work order was already closed"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[post("/work-orders/{work_order_id}/close")]
+async fn close_work_order(
+    data: Data<AppState>,
+    tenant: Tenant,
+    path: Path<i32>,
+    Json(closure): Json<WorkOrderClosure>,
+) -> impl Responder {
+    let work_order_id = path.into_inner();
+
+    let mut transaction = match data.database.begin().await {
+        Ok(transaction) => transaction,
+        Err(err) => return database_error("begin work order closure", err),
+    };
+
+    match query!(
+        r#"
+        UPDATE work_order
+        SET status = 'closed', closed_time = $3, resolution = $4
+        WHERE id = $1 AND organization_id = $2 AND status <> 'closed'
+        RETURNING id
+        "#,
+        work_order_id,
+        tenant.organization_id,
+        now(),
+        closure.resolution
+    )
+    .fetch_optional(&mut transaction)
+    .await
+    {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            return match find_work_order_status(&mut transaction, &tenant, &work_order_id).await {
+                Ok(status) => unchangeable_work_order(work_order_id, status),
+                Err(err) => database_error("fetch work order status", err),
+            }
+        }
+        Err(err) => return database_error("close work order", err),
+    }
+
+    let report_ids = match query!(
+        r#"
+        SELECT report.id
+        FROM work_order_report
+        JOIN report ON report.id = work_order_report.report_id
+        WHERE work_order_report.work_order_id = $1 AND report.archived = false
+        ORDER BY report.id
+        "#,
+        work_order_id
+    )
+    .fetch_all(&mut transaction)
+    .await
+    {
+        Ok(reports) => reports.into_iter().map(|report| report.id),
+        Err(err) => return database_error("fetch work order reports", err),
+    };
+
+    let mut archived_report_ids = Vec::new();
+
+    for report_id in report_ids {
+        match report::archive(&mut transaction, &tenant, &report_id).await {
+            Ok(Some(report)) => archived_report_ids.push(report.report_id),
+            Ok(None) => {}
+            Err(err) => return database_error("archive report", err),
+        }
+    }
+
+    let work_order = match find_work_order(&mut transaction, &tenant, &work_order_id).await {
+        Ok(Some(work_order)) => work_order,
+        Ok(None) => return HttpResponse::InternalServerError().finish(),
+        Err(err) => return database_error("fetch work order", err),
+    };
+
+    match transaction.commit().await {
+        Ok(()) => HttpResponse::Ok().json(ClosedWorkOrder {
+            work_order,
+            archived_report_ids,
+        }),
+        Err(err) => database_error("commit work order closure", err),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/work-order",
+    params(Tenant),
+    responses(
+        (status = 200, description = "List of labour logged against the work order, oldest first", body = Vec<WorkOrderLabour>, example = json!([{
+            "labour_id": 1,
+            "work_order_id": 1,
+            "technician_username": "admin",
+            "minutes": 90,
+            "description": "Removed the drum",
+            "logged_time": "2023-01-02T15:00:00"
+        }])),
+        (status = 404, description = "The requested work order was not found"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[get("/{work_order_id}/labour")]
+async fn get_work_order_labour(
+    data: Data<AppState>,
+    tenant: Tenant,
+    path: Path<i32>,
+) -> impl Responder {
+    let work_order_id = path.into_inner();
+
+    match find_work_order_status(&data.database, &tenant, &work_order_id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            return HttpResponse::NotFound()
+                .json(format!("Work order id {work_order_id} was not found."))
+        }
+        Err(err) => return database_error("fetch work order status", err),
+    }
+
+    match query_as!(
+        WorkOrderLabour,
+        r#"
+        SELECT
+            id AS labour_id,
+            work_order_id,
+            technician_username,
+            minutes,
+            description,
+            logged_time
+        FROM work_order_labour
+        WHERE work_order_id = $1
+        ORDER BY logged_time, id
+        "#,
+        work_order_id
+    )
+    .fetch_all(&data.database)
+    .await
+    {
+        Ok(labour) => HttpResponse::Ok().json(labour),
+        Err(err) => database_error("fetch work order labour", err),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/admin",
+    params(Tenant),
+    request_body(content = LabourSubmission, content_type = "application/json", example = json!({
+        "technician_username": "admin",
+        "minutes": 90,
+        "description": "Removed the drum"
+    })),
+    responses(
+        (status = 201, description = "The labour was logged", body = WorkOrderLabour, example = json!({
+            "labour_id": 1,
+            "work_order_id": 1,
+            "technician_username": "admin",
+            "minutes": 90,
+            "description": "Removed the drum",
+            "logged_time": "2023-01-02T15:00:00"
+        })),
+        (status = 400, description = "The requested query was invalid or the user who did the work is neither a technician nor an admin"),
+        (status = 401, description = "No valid admin credentials were given"),
+        (status = 404, description = "The requested work order was not found"),
+        (status = 409, description = "The work order was already closed"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[post("/work-orders/{work_order_id}/labour")]
+async fn log_work_order_labour(
+    data: Data<AppState>,
+    tenant: Tenant,
+    path: Path<i32>,
+    Json(submission): Json<LabourSubmission>,
+) -> impl Responder {
+    let work_order_id = path.into_inner();
+
+    match user::is_technician(&data.database, &tenant, &submission.technician_username).await {
+        Ok(Some(true)) => {}
+        Ok(Some(false)) => {
+            return HttpResponse::BadRequest().json(format!(
+                "The user {} is neither a technician nor an admin.",
+                submission.technician_username
+            ))
+        }
+        Ok(None) => {
+            return HttpResponse::BadRequest().json(format!(
+                "The user {} was not found.",
+                submission.technician_username
+            ))
+        }
+        Err(err) => return database_error("check technician", err),
+    }
+
+    match find_work_order_status(&data.database, &tenant, &work_order_id).await {
+        Ok(status @ (Some(WorkOrderStatus::Closed) | None)) => {
+            return unchangeable_work_order(work_order_id, status)
+        }
+        Ok(Some(_)) => {}
+        Err(err) => return database_error("fetch work order status", err),
+    }
+
+    match query_as!(
+        WorkOrderLabour,
+        r#"
+        INSERT INTO work_order_labour (
+            work_order_id, technician_username, minutes, description, logged_time, organization_id
+        )
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING
+            id AS labour_id,
+            work_order_id,
+            technician_username,
+            minutes,
+            description,
+            logged_time
+        "#,
+        work_order_id,
+        submission.technician_username,
+        submission.minutes,
+        submission.description,
+        now(),
+        tenant.organization_id
+    )
+    .fetch_one(&data.database)
+    .await
+    {
+        Ok(labour) => HttpResponse::Created().json(labour),
+        Err(err) => match constraint_violation(&err) {
+            Some(Violation::Check) => {
+                HttpResponse::BadRequest().json("The minutes must be positive.")
+            }
+            Some(Violation::ForeignKey) => HttpResponse::NotFound()
+                .json(format!("Work order id {work_order_id} was not found.")),
+            _ => database_error("insert work order labour", err),
+        },
+    }
+}
+
+#[utoipa::path(
+    context_path = "/work-order",
+    params(Tenant),
+    responses(
+        (status = 200, description = "List of parts logged against the work order, oldest first", body = Vec<WorkOrderPart>, example = json!([{
+            "part_id": 1,
+            "work_order_id": 1,
+            "name": "Drum bearing",
+            "part_number": "SQ-201440",
+            "quantity": 1,
+            "unit_cost_cents": 4599,
+            "logged_time": "2023-01-02T15:00:00"
+        }])),
+        (status = 404, description = "The requested work order was not found"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[get("/{work_order_id}/parts")]
+async fn get_work_order_parts(
+    data: Data<AppState>,
+    tenant: Tenant,
+    path: Path<i32>,
+) -> impl Responder {
+    let work_order_id = path.into_inner();
+
+    match find_work_order_status(&data.database, &tenant, &work_order_id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            return HttpResponse::NotFound()
+                .json(format!("Work order id {work_order_id} was not found."))
+        }
+        Err(err) => return database_error("fetch work order status", err),
+    }
+
+    match query_as!(
+        WorkOrderPart,
+        r#"
+        SELECT
+            id AS part_id,
+            work_order_id,
+            name,
+            part_number,
+            quantity,
+            unit_cost_cents,
+            logged_time
+        FROM work_order_part
+        WHERE work_order_id = $1
+        ORDER BY logged_time, id
+        "#,
+        work_order_id
+    )
+    .fetch_all(&data.database)
+    .await
+    {
+        Ok(parts) => HttpResponse::Ok().json(parts),
+        Err(err) => database_error("fetch work order parts", err),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/admin",
+    params(Tenant),
+    request_body(content = PartSubmission, content_type = "application/json", example = json!({
+        "name": "Drum bearing",
+        "part_number": "SQ-201440",
+        "quantity": 1,
+        "unit_cost_cents": 4599
+    })),
+    responses(
+        (status = 201, description = "The part was logged", body = WorkOrderPart, example = json!({
+            "part_id": 1,
+            "work_order_id": 1,
+            "name": "Drum bearing",
+            "part_number": "SQ-201440",
+            "quantity": 1,
+            "unit_cost_cents": 4599,
+            "logged_time": "2023-01-02T15:00:00"
+        })),
+        (status = 400, description = "The requested query was invalid"),
+        (status = 401, description = "No valid admin credentials were given"),
+        (status = 404, description = "The requested work order was not found"),
+        (status = 409, description = "The work order was already closed"),
+        (status = 500, description = "An internal server error occurred")
+    )
+)]
+#[post("/work-orders/{work_order_id}/parts")]
+async fn log_work_order_part(
+    data: Data<AppState>,
+    tenant: Tenant,
+    path: Path<i32>,
+    Json(submission): Json<PartSubmission>,
+) -> impl Responder {
+    let work_order_id = path.into_inner();
+
+    match find_work_order_status(&data.database, &tenant, &work_order_id).await {
+        Ok(status @ (Some(WorkOrderStatus::Closed) | None)) => {
+            return unchangeable_work_order(work_order_id, status)
+        }
+        Ok(Some(_)) => {}
+        Err(err) => return database_error("fetch work order status", err),
+    }
+
+    match query_as!(
+        WorkOrderPart,
+        r#"
+        INSERT INTO work_order_part (work_order_id, name, part_number, quantity, unit_cost_cents, logged_time)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING
+            id AS part_id,
+            work_order_id,
+            name,
+            part_number,
+            quantity,
+            unit_cost_cents,
+            logged_time
+        "#,
+        work_order_id,
+        submission.name,
+        submission.part_number,
+        submission.quantity,
+        submission.unit_cost_cents,
+        now()
+    )
+    .fetch_one(&data.database)
+    .await
+    {
+        Ok(part) => HttpResponse::Created().json(part),
+        Err(err) => match constraint_violation(&err) {
+            Some(Violation::Check) => HttpResponse::BadRequest().json("The quantity must be positive, and the unit cost cannot be negative."),
+            Some(Violation::ForeignKey) => HttpResponse::NotFound().json(format!("Work order id {work_order_id} was not found.")),
+            _ => database_error("insert work order part", err),
+        },
+    }
+}
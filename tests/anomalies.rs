@@ -0,0 +1,152 @@
+mod common;
+
+use actix_web::{http::StatusCode, test::TestRequest};
+use common::{as_organization, call};
+use laundry_api::{anomaly::AnomalyConfig, report::ReportConfig};
+use serde_json::json;
+use sqlx::{Pool, Postgres};
+
+/// Records finished cycles of a machine, the first ending `hours_ago` hours ago and the rest an
+/// hour apart before it, each lasting the given minutes.
+async fn add_cycles(
+    database: &Pool<Postgres>,
+    room_id: i64,
+    machine_id: &str,
+    hours_ago: i32,
+    minutes: &[i32],
+) {
+    sqlx::query(
+        r#"
+        INSERT INTO machine_session (
+            organization_id, room_id, machine_id, start_time, expected_end_time, end_time, end_reason
+        )
+        SELECT
+            (SELECT organization_id FROM room WHERE id = $1),
+            $1,
+            $2,
+            end_time - cycle.minutes * INTERVAL '1 minute',
+            end_time,
+            end_time,
+            'finished'
+        FROM UNNEST($4::INTEGER[]) WITH ORDINALITY AS cycle (minutes, position),
+            LATERAL (
+                SELECT (now() AT TIME ZONE 'UTC')
+                    - ($3 + cycle.position - 1) * INTERVAL '1 hour' AS end_time
+            ) AS cycle_end
+        "#,
+    )
+    .bind(room_id as i32)
+    .bind(machine_id)
+    .bind(hours_ago)
+    .bind(minutes)
+    .execute(database)
+    .await
+    .expect("the cycles are recorded");
+}
+
+/// Machines whose recent cycles ran much longer than usual get a single automated Caution report.
+#[actix_web::test]
+async fn unusual_cycle_durations_are_reported_once() {
+    let state = common::app_state().await;
+    let database = state.database.clone();
+    let app = common::init_app(state).await;
+
+    let slug = common::add_organization(&app).await;
+    let (_, room) = call(
+        &app,
+        as_organization(
+            TestRequest::post()
+                .uri("/room/")
+                .set_json(json!({ "name": "Basement", "description": null })),
+            &slug,
+        ),
+    )
+    .await;
+    let room_id = room["room_id"].as_i64().unwrap();
+
+    let usual = [40, 45, 50, 42, 48, 44, 46, 41, 49, 43, 47, 45];
+    for machine_id in ["W1", "W2"] {
+        let (status, _) = call(
+            &app,
+            as_organization(
+                TestRequest::post().uri("/machine/").set_json(json!({
+                    "room_id": room_id,
+                    "machine_id": machine_id,
+                    "machine_type": "Washer"
+                })),
+                &slug,
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+
+        add_cycles(&database, room_id, machine_id, 48, &usual).await;
+    }
+
+    add_cycles(&database, room_id, "W1", 1, &[45, 44, 46]).await;
+    add_cycles(&database, room_id, "W2", 1, &[120, 125, 118]).await;
+
+    // A machine is reported once per recent window, however often the job runs.
+    for _ in 0..2 {
+        laundry_api::anomaly::detect_anomalies(
+            &database,
+            &ReportConfig::from_env(),
+            &AnomalyConfig::from_env(),
+        )
+        .await
+        .expect("anomalies are detected");
+    }
+
+    let (_, reports) = call(
+        &app,
+        as_organization(TestRequest::get().uri("/report/"), &slug),
+    )
+    .await;
+    let reports = reports.as_array().unwrap();
+    assert_eq!(reports.len(), 1, "{reports:?}");
+    assert_eq!(reports[0]["machine_id"], "W2");
+    assert_eq!(reports[0]["report_type"], "Caution");
+    assert_eq!(reports[0]["reporter_username"], json!(null));
+    assert_eq!(reports[0]["automated"], true);
+
+    common::remove_organization(&database, &slug).await;
+}
+
+/// A single unusually long cycle is not an anomaly, but several in a row are.
+#[actix_web::test]
+async fn only_unusual_recent_medians_are_reported() {
+    let state = common::app_state().await;
+    let database = state.database.clone();
+    let app = common::init_app(state).await;
+
+    let slug = common::add_organization(&app).await;
+    let room_id = common::add_room(&app, &slug, &["W1", "W2"]).await;
+
+    let usual = [40, 45, 50, 42, 48, 44, 46, 41, 49, 43, 47, 45];
+    for machine_id in ["W1", "W2"] {
+        add_cycles(&database, room_id, machine_id, 48, &usual).await;
+    }
+
+    add_cycles(&database, room_id, "W1", 1, &[200, 45, 44]).await;
+    add_cycles(&database, room_id, "W2", 1, &[120, 125, 118]).await;
+
+    laundry_api::anomaly::detect_anomalies(
+        &database,
+        &ReportConfig::from_env(),
+        &AnomalyConfig::from_env(),
+    )
+    .await
+    .expect("anomalies are detected");
+
+    let (_, reports) = call(
+        &app,
+        as_organization(TestRequest::get().uri("/report/"), &slug),
+    )
+    .await;
+    let reports = reports.as_array().unwrap();
+    assert_eq!(reports.len(), 1, "{reports:?}");
+    assert_eq!(reports[0]["machine_id"], "W2");
+    assert_eq!(reports[0]["automated"], true);
+
+    common::remove_organization(&database, &slug).await;
+}
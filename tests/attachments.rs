@@ -0,0 +1,374 @@
+mod common;
+
+use std::{
+    env, fs,
+    io::Cursor,
+    path::{Path, PathBuf},
+    process,
+};
+
+use actix_http::Request;
+use actix_web::{
+    body::MessageBody,
+    dev::{Service, ServiceResponse},
+    http::StatusCode,
+    test::TestRequest,
+    Error,
+};
+use common::{as_organization, call, unique};
+use image::{ImageFormat, RgbImage};
+use serde_json::{json, Value};
+
+const BOUNDARY: &str = "attachment-test-boundary";
+
+/// Stores the blobs of every test in this binary below one directory, since tests share the
+/// process environment.
+fn use_blob_root() -> PathBuf {
+    let root = env::temp_dir().join(format!("laundry-blobs-{}", process::id()));
+    env::set_var("BLOB_STORE_BACKEND", "local");
+    env::set_var("BLOB_STORE_PATH", &root);
+    root
+}
+
+/// A PNG of the given size.
+fn png(width: u32, height: u32) -> Vec<u8> {
+    let mut png = Cursor::new(Vec::new());
+    RgbImage::new(width, height)
+        .write_to(&mut png, ImageFormat::Png)
+        .unwrap();
+    png.into_inner()
+}
+
+/// A multipart upload of `file` by `uploader_username` of the organization `slug`, declared as
+/// `content_type`.
+fn upload(slug: &str, uploader_username: &str, content_type: &str, file: &[u8]) -> TestRequest {
+    let mut body = format!(
+        "--{BOUNDARY}\r\n\
+         Content-Disposition: form-data; name=\"uploader_username\"\r\n\r\n\
+         {uploader_username}\r\n\
+         --{BOUNDARY}\r\n\
+         Content-Disposition: form-data; name=\"file\"; filename=\"display.png\"\r\n\
+         Content-Type: {content_type}\r\n\r\n"
+    )
+    .into_bytes();
+    body.extend_from_slice(file);
+    body.extend_from_slice(format!("\r\n--{BOUNDARY}--\r\n").as_bytes());
+
+    as_organization(
+        TestRequest::post()
+            .insert_header((
+                "Content-Type",
+                format!("multipart/form-data; boundary={BOUNDARY}"),
+            ))
+            .set_payload(body),
+        slug,
+    )
+}
+
+/// How many blobs are stored for the attachments of a report.
+fn blob_count(root: &Path, report_id: i64) -> usize {
+    fs::read_dir(root.join("reports").join(report_id.to_string()))
+        .map(|entries| entries.count())
+        .unwrap_or(0)
+}
+
+/// Files a report about a machine and attaches an image uploaded by `uploader_username`,
+/// returning the report id.
+async fn report_with_attachment<S, B>(
+    app: &S,
+    slug: &str,
+    room_id: i64,
+    machine_id: &str,
+    reporter_username: &str,
+    uploader_username: &str,
+) -> i64
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = Error>,
+    B: MessageBody,
+{
+    let (status, report) = call(
+        app,
+        as_organization(
+            TestRequest::post().uri("/report/").set_json(json!({
+                "room_id": room_id,
+                "machine_id": machine_id,
+                "reporter_username": reporter_username,
+                "report_type": "Broken",
+                "description": null
+            })),
+            slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    let report_id = report["report_id"].as_i64().unwrap();
+
+    let (status, _) = call(
+        app,
+        upload(slug, uploader_username, "image/png", &png(4, 4))
+            .uri(&format!("/report/{report_id}/attachments")),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    report_id
+}
+
+/// An image attached to a report can be listed, downloaded along with its thumbnail and deleted.
+#[actix_web::test]
+async fn attachments_are_stored_with_a_thumbnail() {
+    use_blob_root();
+    let state = common::app_state().await;
+    let database = state.database.clone();
+    let app = common::init_app(state).await;
+
+    let slug = common::add_organization(&app).await;
+
+    let machine_id = unique("W");
+    let room_id = common::add_room(&app, &slug, &[&machine_id]).await;
+    let username = common::add_user(&app, &slug).await;
+
+    let (status, report) = call(
+        &app,
+        as_organization(
+            TestRequest::post().uri("/report/").set_json(json!({
+                "room_id": room_id,
+                "machine_id": machine_id,
+                "reporter_username": username,
+                "report_type": "Broken",
+                "description": null
+            })),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    let attachments = format!("/report/{}/attachments", report["report_id"]);
+
+    let image = png(640, 480);
+    let (status, attachment) = call(
+        &app,
+        upload(&slug, &username, "image/png", &image).uri(&attachments),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(attachment["uploader_username"], username.as_str());
+    assert_eq!(attachment["file_name"], "display.png");
+    assert_eq!(attachment["content_type"], "image/png");
+    assert_eq!(attachment["size_bytes"], image.len());
+    assert_eq!(attachment["width"], 640);
+    assert_eq!(attachment["height"], 480);
+    let attachment_url = format!("{attachments}/{}", attachment["attachment_id"]);
+
+    let (status, listed) = call(
+        &app,
+        as_organization(TestRequest::get().uri(&attachments), &slug),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(listed, json!([attachment]));
+
+    let response = actix_web::test::call_service(
+        &app,
+        as_organization(TestRequest::get().uri(&attachment_url), &slug).to_request(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers().get("Content-Type").unwrap(), "image/png");
+    assert_eq!(actix_web::test::read_body(response).await, image);
+
+    let response = actix_web::test::call_service(
+        &app,
+        as_organization(
+            TestRequest::get().uri(&format!("{attachment_url}/thumbnail")),
+            &slug,
+        )
+        .to_request(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers().get("Content-Type").unwrap(),
+        "image/jpeg"
+    );
+    let thumbnail = image::load_from_memory_with_format(
+        &actix_web::test::read_body(response).await,
+        ImageFormat::Jpeg,
+    )
+    .expect("the thumbnail is a JPEG");
+    assert_eq!((thumbnail.width(), thumbnail.height()), (256, 192));
+
+    let (status, deleted) = call(
+        &app,
+        as_organization(TestRequest::delete().uri(&attachment_url), &slug),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(deleted, attachment);
+
+    for uri in [
+        attachment_url.clone(),
+        format!("{attachment_url}/thumbnail"),
+    ] {
+        let (status, _) = call(&app, as_organization(TestRequest::get().uri(&uri), &slug)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND, "{uri}");
+    }
+    let (status, _) = call(
+        &app,
+        as_organization(TestRequest::delete().uri(&attachment_url), &slug),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, listed) = call(
+        &app,
+        as_organization(TestRequest::get().uri(&attachments), &slug),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(listed, json!([]));
+
+    common::remove_organization(&database, &slug).await;
+}
+
+/// Uploads which are too large, not images or missing a field are rejected.
+#[actix_web::test]
+async fn invalid_uploads_are_rejected() {
+    use_blob_root();
+    let mut state = common::app_state().await;
+    state.attachment_config.max_bytes = 4096;
+    let database = state.database.clone();
+    let app = common::init_app(state).await;
+
+    let slug = common::add_organization(&app).await;
+
+    let machine_id = unique("W");
+    let room_id = common::add_room(&app, &slug, &[&machine_id]).await;
+    let username = common::add_user(&app, &slug).await;
+
+    let (_, report) = call(
+        &app,
+        as_organization(
+            TestRequest::post().uri("/report/").set_json(json!({
+                "room_id": room_id,
+                "machine_id": machine_id,
+                "reporter_username": username,
+                "report_type": "Broken",
+                "description": null
+            })),
+            &slug,
+        ),
+    )
+    .await;
+    let attachments = format!("/report/{}/attachments", report["report_id"]);
+
+    let image = png(8, 8);
+    for (request, expected) in [
+        (
+            upload(&slug, &username, "image/png", &vec![0; 8192]),
+            StatusCode::PAYLOAD_TOO_LARGE,
+        ),
+        (
+            upload(&slug, &username, "text/plain", b"not an image"),
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+        ),
+        // The contents must match the declared type.
+        (
+            upload(&slug, &username, "image/jpeg", &image),
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+        ),
+        (
+            upload(&slug, &username, "image/png", b"\x89PNG\r\n\x1a\ntruncated"),
+            StatusCode::BAD_REQUEST,
+        ),
+        // The uploader must be a known user.
+        (
+            upload(&slug, &unique("user"), "image/png", &image),
+            StatusCode::BAD_REQUEST,
+        ),
+    ] {
+        let (status, _) = call(&app, request.uri(&attachments)).await;
+        assert_eq!(status, expected);
+    }
+
+    let (status, _) = call(
+        &app,
+        upload(&slug, &username, "image/png", &image).uri("/report/0/attachments"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, listed) = call(
+        &app,
+        as_organization(TestRequest::get().uri(&attachments), &slug),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(listed, Value::Array(Vec::new()));
+
+    common::remove_organization(&database, &slug).await;
+}
+
+/// Blobs of reports removed along with their machine, room or user are removed too.
+#[actix_web::test]
+async fn cascading_deletes_remove_attachment_blobs() {
+    let root = use_blob_root();
+    let state = common::app_state().await;
+    let database = state.database.clone();
+    let app = common::init_app(state).await;
+
+    let slug = common::add_organization(&app).await;
+
+    let basement = common::add_room(&app, &slug, &["W1", "W2"]).await;
+    let attic = common::add_room(&app, &slug, &["W3", "W4"]).await;
+    let leaving = common::add_user(&app, &slug).await;
+    let staying = common::add_user(&app, &slug).await;
+
+    let machine_report =
+        report_with_attachment(&app, &slug, basement, "W1", &staying, &staying).await;
+    let user_report = report_with_attachment(&app, &slug, basement, "W2", &staying, &leaving).await;
+    let room_report = report_with_attachment(&app, &slug, attic, "W3", &staying, &staying).await;
+    let other_room_report =
+        report_with_attachment(&app, &slug, attic, "W4", &staying, &staying).await;
+
+    for report_id in [machine_report, user_report, room_report, other_room_report] {
+        assert_eq!(blob_count(&root, report_id), 2, "report id {report_id}");
+    }
+
+    let (status, _) = call(
+        &app,
+        as_organization(
+            TestRequest::delete().uri(&format!("/machine/{basement}/W1")),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(blob_count(&root, machine_report), 0);
+    assert_eq!(blob_count(&root, user_report), 2);
+    assert_eq!(blob_count(&root, room_report), 2);
+
+    // The attachment uploaded by the user goes with them, though the report stays.
+    let (status, _) = call(
+        &app,
+        as_organization(
+            TestRequest::delete().uri(&format!("/user/{leaving}")),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(blob_count(&root, user_report), 0);
+    assert_eq!(blob_count(&root, room_report), 2);
+
+    let (status, _) = call(
+        &app,
+        as_organization(TestRequest::delete().uri(&format!("/room/{attic}")), &slug),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(blob_count(&root, room_report), 0);
+    assert_eq!(blob_count(&root, other_room_report), 0);
+
+    common::remove_organization(&database, &slug).await;
+}
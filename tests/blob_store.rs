@@ -0,0 +1,128 @@
+#![cfg(feature = "s3")]
+
+use std::{
+    collections::HashMap,
+    env, io,
+    net::TcpListener,
+    sync::{Arc, Mutex},
+};
+
+use actix_web::{
+    http::header,
+    web::{self, Bytes, Data, Path},
+    App, HttpRequest, HttpResponse, HttpServer,
+};
+use laundry_api::blob_store::BlobStore;
+
+/// The objects held by [serve_bucket], by bucket and key, with their content type.
+type Objects = Arc<Mutex<HashMap<(String, String), (Bytes, String)>>>;
+
+/// Answers the path-style object requests the S3 backend makes, standing in for S3 or MinIO.
+async fn object(
+    request: HttpRequest,
+    path: Path<(String, String)>,
+    objects: Data<Objects>,
+    body: Bytes,
+) -> HttpResponse {
+    let key = path.into_inner();
+    let mut objects = objects.lock().unwrap();
+
+    match request.method().as_str() {
+        "PUT" => {
+            let content_type = request
+                .headers()
+                .get(header::CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or_default()
+                .to_string();
+            objects.insert(key, (body, content_type));
+            HttpResponse::Ok()
+                .insert_header((header::ETAG, "\"stand-in\""))
+                .finish()
+        }
+        "GET" => match objects.get(&key) {
+            Some((bytes, content_type)) => HttpResponse::Ok()
+                .insert_header((header::ETAG, "\"stand-in\""))
+                .insert_header((header::LAST_MODIFIED, "Mon, 19 Oct 2026 12:00:00 GMT"))
+                .insert_header((header::CONTENT_TYPE, content_type.as_str()))
+                .body(bytes.clone()),
+            None => HttpResponse::NotFound().finish(),
+        },
+        "DELETE" => {
+            objects.remove(&key);
+            HttpResponse::NoContent().finish()
+        }
+        _ => HttpResponse::MethodNotAllowed().finish(),
+    }
+}
+
+/// Starts the stand-in bucket server, returning its objects and the endpoint it listens on.
+fn serve_bucket() -> (Objects, String) {
+    let objects = Objects::default();
+
+    let listener = TcpListener::bind(("127.0.0.1", 0)).expect("a port is free");
+    let port = listener.local_addr().expect("the port is known").port();
+    let server_objects = Arc::clone(&objects);
+    let server = HttpServer::new(move || {
+        App::new()
+            .app_data(Data::new(Arc::clone(&server_objects)))
+            .route("/{bucket}/{key:.*}", web::route().to(object))
+    })
+    .workers(1)
+    .listen(listener)
+    .expect("the server listens")
+    .run();
+    actix_web::rt::spawn(server);
+
+    (objects, format!("http://127.0.0.1:{port}"))
+}
+
+/// Blobs are stored in, read from and removed from the configured bucket, with their content type.
+#[actix_web::test]
+async fn blobs_are_stored_in_the_s3_bucket() {
+    let (objects, endpoint) = serve_bucket();
+
+    env::set_var("BLOB_STORE_BACKEND", "s3");
+    env::set_var("S3_BUCKET", "attachments");
+    env::set_var("S3_ENDPOINT", &endpoint);
+    env::set_var("S3_ACCESS_KEY_ID", "laundry");
+    env::set_var("S3_SECRET_ACCESS_KEY", "laundry-secret");
+    let store = BlobStore::from_env().expect("the S3 backend is configured");
+
+    let key = "reports/1/photo.png";
+    store
+        .put(key, Bytes::from_static(b"not really a png"), "image/png")
+        .await
+        .expect("the blob is stored");
+
+    let bucket_key = ("attachments".to_string(), key.to_string());
+    assert_eq!(
+        objects.lock().unwrap().get(&bucket_key),
+        Some(&(
+            Bytes::from_static(b"not really a png"),
+            "image/png".to_string()
+        ))
+    );
+
+    let bytes = store.get(key).await.expect("the blob is read");
+    assert_eq!(bytes, Bytes::from_static(b"not really a png"));
+
+    store.delete(key).await.expect("the blob is removed");
+    assert!(objects.lock().unwrap().is_empty());
+
+    let err = store.get(key).await.expect_err("the blob was removed");
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+    // Removing a blob which is already gone is not an error.
+    store.delete(key).await.expect("the removal is idempotent");
+
+    for name in [
+        "BLOB_STORE_BACKEND",
+        "S3_BUCKET",
+        "S3_ENDPOINT",
+        "S3_ACCESS_KEY_ID",
+        "S3_SECRET_ACCESS_KEY",
+    ] {
+        env::remove_var(name);
+    }
+}
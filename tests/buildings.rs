@@ -0,0 +1,183 @@
+mod common;
+
+use actix_web::{http::StatusCode, test::TestRequest};
+use common::{as_organization, call};
+use serde_json::{json, Value};
+
+/// Buildings sum up the rooms, machines and open reports in them, leaving out rooms elsewhere,
+/// and sites list their buildings.
+#[actix_web::test]
+async fn buildings_roll_up_their_rooms() {
+    let state = common::app_state().await;
+    let database = state.database.clone();
+    let app = common::init_app(state).await;
+
+    let slug = common::add_organization(&app).await;
+
+    let (status, site) = call(
+        &app,
+        as_organization(
+            TestRequest::post()
+                .uri("/site/")
+                .set_json(json!({ "name": "North campus", "description": null })),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    let site_id = site["site_id"].as_i64().unwrap();
+
+    let building = |site_id: i64, latitude: f64| {
+        as_organization(
+            TestRequest::post().uri("/building/").set_json(json!({
+                "site_id": site_id,
+                "name": "Complex A",
+                "address": "1 College Road",
+                "latitude": latitude,
+                "longitude": -1.2544
+            })),
+            &slug,
+        )
+    };
+
+    let (status, _) = call(&app, building(site_id, 91.0)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = call(&app, building(site_id + 1_000_000, 51.7548)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, building) = call(&app, building(site_id, 51.7548)).await;
+    assert_eq!(status, StatusCode::CREATED);
+    let building_id = building["building_id"].as_i64().unwrap();
+
+    // Two rooms in the building, and one outside of it.
+    let mut room_ids = Vec::new();
+    for (building_id, machine_ids) in [
+        (Some(building_id), ["W1", "W2"].as_slice()),
+        (Some(building_id), ["D1"].as_slice()),
+        (None, ["X1"].as_slice()),
+    ] {
+        let (status, room) = call(
+            &app,
+            as_organization(
+                TestRequest::post().uri("/room/").set_json(json!({
+                    "name": "Laundry",
+                    "description": null,
+                    "building_id": building_id
+                })),
+                &slug,
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        let room_id = room["room_id"].as_i64().unwrap();
+
+        for machine_id in machine_ids {
+            let (status, _) = call(
+                &app,
+                as_organization(
+                    TestRequest::post().uri("/machine/").set_json(json!({
+                        "room_id": room_id,
+                        "machine_id": machine_id,
+                        "machine_type": "Washer"
+                    })),
+                    &slug,
+                ),
+            )
+            .await;
+            assert_eq!(status, StatusCode::CREATED);
+        }
+        room_ids.push(room_id);
+    }
+
+    let username = common::add_user(&app, &slug).await;
+
+    for (room_id, machine_id, report_type) in [
+        (room_ids[0], "W1", "Broken"),
+        (room_ids[0], "W1", "Caution"),
+        (room_ids[1], "D1", "Broken"),
+        (room_ids[2], "X1", "Broken"),
+    ] {
+        let (status, _) = call(
+            &app,
+            as_organization(
+                TestRequest::post().uri("/report/").set_json(json!({
+                    "room_id": room_id,
+                    "machine_id": machine_id,
+                    "reporter_username": username,
+                    "report_type": report_type,
+                    "description": null
+                })),
+                &slug,
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+    }
+
+    let get = |uri: String| {
+        let request = as_organization(TestRequest::get().uri(&uri), &slug);
+        let app = &app;
+        async move {
+            let (status, body) = call(app, request).await;
+            assert_eq!(status, StatusCode::OK, "{uri}");
+            body
+        }
+    };
+
+    let building_status = get(format!("/building/{building_id}/status")).await;
+    assert_eq!(building_status["room_count"], 2);
+    assert_eq!(building_status["machine_count"], 3);
+    assert_eq!(building_status["open_report_count"], 3);
+    assert_eq!(building_status["machines_with_open_reports"], 2);
+    assert_eq!(
+        building_status["open_reports_by_type"],
+        json!([
+            { "report_type": "Broken", "count": 2 },
+            { "report_type": "Caution", "count": 1 }
+        ])
+    );
+
+    let reports = get(format!("/building/{building_id}/reports")).await;
+    let mut machine_ids: Vec<&str> = reports
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|report| report["machine_id"].as_str().unwrap())
+        .collect();
+    machine_ids.sort();
+    assert_eq!(machine_ids, ["D1", "W1", "W1"]);
+
+    let rooms = get(format!("/building/{building_id}/rooms")).await;
+    assert_eq!(rooms.as_array().unwrap().len(), 2);
+
+    let buildings = get(format!("/site/{site_id}/buildings")).await;
+    assert_eq!(buildings.as_array().unwrap().len(), 1);
+    assert_eq!(buildings[0]["building_id"], json!(building_id));
+
+    // Deleting the building keeps its rooms, which are left without a building.
+    let (status, _) = call(
+        &app,
+        as_organization(
+            TestRequest::delete().uri(&format!("/building/{building_id}")),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let room = get(format!("/room/{}", room_ids[0])).await;
+    assert_eq!(room["building_id"], Value::Null);
+
+    let (status, _) = call(
+        &app,
+        as_organization(
+            TestRequest::delete().uri(&format!("/site/{site_id}")),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    common::remove_organization(&database, &slug).await;
+}
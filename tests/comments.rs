@@ -0,0 +1,122 @@
+mod common;
+
+use actix_web::{http::StatusCode, test::TestRequest};
+use common::{as_organization, call, unique};
+use serde_json::{json, Value};
+
+/// Comments are added by known users, counted on their report, and only edited by their author.
+#[actix_web::test]
+async fn comments_are_only_edited_by_their_author() {
+    let state = common::app_state().await;
+    let database = state.database.clone();
+    let app = common::init_app(state).await;
+
+    let slug = common::add_organization(&app).await;
+
+    let room_id = common::add_room(&app, &slug, &["W1"]).await;
+    let usernames = [
+        common::add_user(&app, &slug).await,
+        common::add_user(&app, &slug).await,
+    ];
+
+    let (status, report) = call(
+        &app,
+        as_organization(
+            TestRequest::post().uri("/report/").set_json(json!({
+                "room_id": room_id,
+                "machine_id": "W1",
+                "reporter_username": usernames[0],
+                "report_type": "Broken",
+                "description": null
+            })),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    let report_id = report["report_id"].as_i64().unwrap();
+    let comments_uri = format!("/report/{report_id}/comments");
+
+    let add_comment = |author_username: &str, body: &str| {
+        as_organization(
+            TestRequest::post()
+                .uri(&comments_uri)
+                .set_json(json!({ "author_username": author_username, "body": body })),
+            &slug,
+        )
+    };
+
+    let (status, _) = call(&app, add_comment(&usernames[0], "  ")).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = call(&app, add_comment(&unique("nobody"), "Which cycle?")).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, comment) = call(&app, add_comment(&usernames[0], "Which cycle?")).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(comment["edited_time"], Value::Null);
+    let comment_uri = format!("{comments_uri}/{}", comment["comment_id"]);
+
+    let comment_count = || {
+        let request = as_organization(
+            TestRequest::get().uri(&format!("/report/{report_id}")),
+            &slug,
+        );
+        let app = &app;
+        async move { call(app, request).await.1["comment_count"].clone() }
+    };
+    assert_eq!(comment_count().await, 1);
+
+    let edit_comment = |author_username: &str| {
+        as_organization(
+            TestRequest::patch().uri(&comment_uri).set_json(json!({
+                "author_username": author_username,
+                "body": "Which cycle was it?"
+            })),
+            &slug,
+        )
+    };
+
+    let (status, _) = call(&app, edit_comment(&usernames[1])).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, comment) = call(&app, edit_comment(&usernames[0])).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(comment["body"], "Which cycle was it?");
+    assert_ne!(comment["edited_time"], Value::Null);
+
+    // The comment is only found on its own report.
+    let (status, _) = call(
+        &app,
+        as_organization(
+            TestRequest::patch()
+                .uri(&format!(
+                    "/report/{}/comments/{}",
+                    report_id + 1,
+                    comment["comment_id"]
+                ))
+                .set_json(json!({ "author_username": usernames[0], "body": "Moved" })),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _) = call(
+        &app,
+        as_organization(TestRequest::delete().uri(&comment_uri), &slug),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, comments) = call(
+        &app,
+        as_organization(TestRequest::get().uri(&comments_uri), &slug),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(comments, json!([]));
+    assert_eq!(comment_count().await, 0);
+
+    common::remove_organization(&database, &slug).await;
+}
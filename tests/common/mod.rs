@@ -0,0 +1,207 @@
+//! Helpers shared by the integration tests, which run against the database at DATABASE_URL.
+
+// Every test binary uses a different subset of these helpers.
+#![allow(dead_code)]
+
+use std::{env, sync::Arc};
+
+use actix_http::Request;
+use actix_web::{
+    body::MessageBody,
+    dev::{Service, ServiceResponse},
+    http::StatusCode,
+    test::{self, TestRequest},
+    web::Data,
+    App, Error,
+};
+use laundry_api::{
+    admin::AdminConfig,
+    attachment::AttachmentConfig,
+    blob_store::BlobStore,
+    database,
+    guest::GuestReportConfig,
+    machine_telemetry::TelemetryConfig,
+    maintenance::MaintenanceConfig,
+    models::AppState,
+    notification::Notifier,
+    qr::QrConfig,
+    rate_limit::RateLimiter,
+    report::ReportConfig,
+    routes,
+    session::SessionConfig,
+    tenant::{TenantConfig, ORGANIZATION_HEADER},
+};
+use serde_json::{json, Value};
+use sqlx::{postgres::PgPoolOptions, Pool, Postgres};
+use uuid::Uuid;
+
+/// The key the admin endpoints accept in the tests.
+pub const ADMIN_KEY: &str = "integration-test-admin-key";
+
+/// Connects to the test database and applies the migrations.
+pub async fn database() -> Pool<Postgres> {
+    let database_url =
+        env::var("DATABASE_URL").expect("DATABASE_URL names the database to test against");
+    let database = PgPoolOptions::new()
+        .max_connections(database::MAX_CONNECTIONS)
+        .connect(&database_url)
+        .await
+        .expect("the test database is reachable");
+
+    database::MIGRATOR
+        .run(&database)
+        .await
+        .expect("the migrations apply");
+
+    database
+}
+
+/// Builds the application state the server would, configured from the environment.
+pub async fn app_state() -> AppState {
+    env::set_var("ADMIN_API_KEYS", ADMIN_KEY);
+    env::remove_var("DEFAULT_ORGANIZATION");
+
+    let database = database().await;
+    AppState {
+        rate_limiter: Arc::new(RateLimiter::from_env(&database)),
+        report_config: ReportConfig::from_env(),
+        blob_store: Arc::new(BlobStore::from_env().expect("the blob store is configured")),
+        attachment_config: AttachmentConfig::from_env(),
+        tenant_config: TenantConfig::from_env(),
+        qr_config: QrConfig::from_env(),
+        guest_report_config: GuestReportConfig::from_env(),
+        session_config: SessionConfig::from_env(),
+        notifier: Arc::new(Notifier::from_env().expect("notifications are configured")),
+        telemetry_config: TelemetryConfig::from_env(),
+        maintenance_config: MaintenanceConfig::from_env(),
+        admin_config: AdminConfig::from_env(),
+        database,
+    }
+}
+
+/// Initializes the API with every route the server registers.
+pub async fn init_app(
+    state: AppState,
+) -> impl Service<Request, Response = ServiceResponse<impl MessageBody>, Error = Error> {
+    test::init_service(
+        App::new()
+            .app_data(Data::new(state))
+            .configure(routes::configure),
+    )
+    .await
+}
+
+/// Sends `request` and returns the response status along with its JSON body,
+/// or `Value::Null` if the body is not JSON.
+pub async fn call<S, B>(app: &S, request: TestRequest) -> (StatusCode, Value)
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = Error>,
+    B: MessageBody,
+{
+    let response = test::call_service(app, request.to_request()).await;
+    let status = response.status();
+    let body = test::read_body(response).await;
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+/// Scopes `request` to the organization `slug`.
+pub fn as_organization(request: TestRequest, slug: &str) -> TestRequest {
+    request.insert_header((ORGANIZATION_HEADER, slug))
+}
+
+/// Authenticates `request` for the admin endpoints.
+pub fn as_admin(request: TestRequest) -> TestRequest {
+    request.insert_header(("Authorization", format!("Bearer {ADMIN_KEY}")))
+}
+
+/// A name which does not clash with those of earlier test runs.
+pub fn unique(prefix: &str) -> String {
+    format!("{prefix}-{}", &Uuid::new_v4().simple().to_string()[..12])
+}
+
+/// Adds an organization through the admin endpoints and returns its slug.
+pub async fn add_organization<S, B>(app: &S) -> String
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = Error>,
+    B: MessageBody,
+{
+    let slug = unique("test");
+    let (status, _) = call(
+        app,
+        as_admin(TestRequest::post().uri("/admin/organizations"))
+            .set_json(json!({ "slug": slug, "name": slug })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "adding organization {slug}");
+    slug
+}
+
+/// Removes an organization added by a test, along with everything belonging to it.
+pub async fn remove_organization(database: &Pool<Postgres>, slug: &str) {
+    sqlx::query("DELETE FROM organization WHERE slug = $1")
+        .bind(slug)
+        .execute(database)
+        .await
+        .expect("the organization is removed");
+}
+
+/// Adds a room with the given washers to the organization `slug` and returns its id.
+pub async fn add_room<S, B, M>(app: &S, slug: &str, machine_ids: &[M]) -> i64
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = Error>,
+    B: MessageBody,
+    M: AsRef<str>,
+{
+    let (status, room) = call(
+        app,
+        as_organization(
+            TestRequest::post()
+                .uri("/room/")
+                .set_json(json!({ "name": unique("room"), "description": null })),
+            slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    let room_id = room["room_id"].as_i64().unwrap();
+
+    for machine_id in machine_ids {
+        let machine_id = machine_id.as_ref();
+        let (status, _) = call(
+            app,
+            as_organization(
+                TestRequest::post().uri("/machine/").set_json(json!({
+                    "room_id": room_id,
+                    "machine_id": machine_id,
+                    "machine_type": "Washer"
+                })),
+                slug,
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED, "adding machine {machine_id}");
+    }
+
+    room_id
+}
+
+/// Adds a user who is not an admin to the organization `slug` and returns the username.
+pub async fn add_user<S, B>(app: &S, slug: &str) -> String
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = Error>,
+    B: MessageBody,
+{
+    let username = unique("user");
+    let (status, _) = call(
+        app,
+        as_organization(
+            TestRequest::post()
+                .uri("/user/")
+                .set_json(json!({ "username": username, "admin": false })),
+            slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "adding user {username}");
+    username
+}
@@ -0,0 +1,64 @@
+mod common;
+
+use actix_web::{http::StatusCode, test::TestRequest};
+use common::{as_organization, call};
+use serde_json::json;
+
+/// Writes rejected by a constraint are explained without passing on the database's message.
+#[actix_web::test]
+async fn constraint_violations_are_explained_without_database_details() {
+    let state = common::app_state().await;
+    let database = state.database.clone();
+    let app = common::init_app(state).await;
+
+    let slug = common::add_organization(&app).await;
+
+    let (status, body) = call(
+        &app,
+        as_organization(
+            TestRequest::post().uri("/building/").set_json(json!({
+                "site_id": null,
+                "name": "Complex A",
+                "address": null,
+                "latitude": 100.0,
+                "longitude": 0.0
+            })),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(
+        body,
+        "The latitude must be between -90 and 90, and the longitude between -180 and 180."
+    );
+
+    let (_, room) = call(
+        &app,
+        as_organization(
+            TestRequest::post()
+                .uri("/room/")
+                .set_json(json!({ "name": "Basement", "description": null })),
+            &slug,
+        ),
+    )
+    .await;
+
+    let (status, body) = call(
+        &app,
+        as_organization(
+            TestRequest::post().uri("/machine/").set_json(json!({
+                "room_id": room["room_id"],
+                "machine_id": "W1",
+                "machine_type": "Washer",
+                "capacity_kg": -1.0
+            })),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body, "The capacity must be positive.");
+
+    common::remove_organization(&database, &slug).await;
+}
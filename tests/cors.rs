@@ -0,0 +1,124 @@
+use actix_web::{
+    http::{
+        header::{
+            ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_REQUEST_HEADERS,
+            ACCESS_CONTROL_REQUEST_METHOD, ORIGIN,
+        },
+        Method, StatusCode,
+    },
+    test::{self, TestRequest},
+    web, App, HttpResponse,
+};
+use laundry_api::cors::CorsConfig;
+
+fn cors_config(allowed_origins: &[&str]) -> CorsConfig {
+    CorsConfig {
+        allowed_origins: allowed_origins
+            .iter()
+            .map(|origin| origin.to_string())
+            .collect(),
+        allowed_methods: vec![Method::GET, Method::POST],
+        allowed_headers: vec!["content-type".parse().unwrap()],
+        allow_credentials: false,
+        max_age: 3600,
+    }
+}
+
+/// Sends a preflight from `origin` to an app guarded by `cors_config`, returning the status and
+/// the allowed origin answered.
+async fn preflight(cors_config: CorsConfig, origin: &str) -> (StatusCode, Option<String>) {
+    let app = test::init_service(
+        App::new()
+            .wrap(cors_config.middleware())
+            .route("/room/", web::post().to(HttpResponse::Ok)),
+    )
+    .await;
+
+    let request = TestRequest::default()
+        .method(Method::OPTIONS)
+        .uri("/room/")
+        .insert_header((ORIGIN, origin))
+        .insert_header((ACCESS_CONTROL_REQUEST_METHOD, "POST"))
+        .insert_header((ACCESS_CONTROL_REQUEST_HEADERS, "content-type"))
+        .to_request();
+
+    let response = test::call_service(&app, request).await;
+    let allowed_origin = response
+        .headers()
+        .get(ACCESS_CONTROL_ALLOW_ORIGIN)
+        .map(|value| value.to_str().unwrap().to_string());
+    (response.status(), allowed_origin)
+}
+
+#[actix_web::test]
+async fn preflight_from_allowed_origin_is_accepted() {
+    let cors_config = cors_config(&["https://laundry.example.com", "http://localhost:3000"]);
+    assert_eq!(cors_config.validate(), Ok(()));
+
+    let (status, allowed_origin) = preflight(cors_config, "http://localhost:3000").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(allowed_origin.as_deref(), Some("http://localhost:3000"));
+}
+
+#[actix_web::test]
+async fn preflight_from_other_origin_is_denied() {
+    let cors_config = cors_config(&["https://laundry.example.com"]);
+
+    let (status, allowed_origin) = preflight(cors_config, "https://evil.example.com").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(allowed_origin, None);
+}
+
+#[actix_web::test]
+async fn preflight_is_denied_without_allowed_origins() {
+    let cors_config = cors_config(&[]);
+    assert_eq!(cors_config.validate(), Ok(()));
+
+    let (status, allowed_origin) = preflight(cors_config, "https://laundry.example.com").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(allowed_origin, None);
+}
+
+#[actix_web::test]
+async fn wildcard_allows_any_origin() {
+    let cors_config = cors_config(&["*"]);
+    assert_eq!(cors_config.validate(), Ok(()));
+
+    let (status, allowed_origin) = preflight(cors_config, "https://anywhere.example.com").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(allowed_origin.is_some());
+}
+
+#[test]
+fn wildcard_cannot_be_mixed_with_origins() {
+    assert!(cors_config(&["*", "https://laundry.example.com"])
+        .validate()
+        .is_err());
+}
+
+#[test]
+fn wildcard_cannot_be_combined_with_credentials() {
+    let mut wildcard = cors_config(&["*"]);
+    wildcard.allow_credentials = true;
+    assert!(wildcard.validate().is_err());
+
+    let mut listed = cors_config(&["https://laundry.example.com"]);
+    listed.allow_credentials = true;
+    assert_eq!(listed.validate(), Ok(()));
+}
+
+#[test]
+fn invalid_origins_are_rejected() {
+    for origin in [
+        "laundry.example.com",
+        "https://laundry.example.com/",
+        "https://laundry.example.com/app",
+        "https://laundry example.com",
+        "https://",
+    ] {
+        assert!(
+            cors_config(&[origin]).validate().is_err(),
+            "{origin} was accepted"
+        );
+    }
+}
@@ -0,0 +1,369 @@
+mod common;
+
+use std::env;
+
+use actix_web::{http::StatusCode, test::TestRequest};
+use common::{as_admin, as_organization, call, unique};
+use ring::digest;
+use serde_json::{json, Value};
+
+/// Finds a nonce for which the hash of `{challenge}:{nonce}` starts with `difficulty` zero bits.
+fn solve(challenge: &str, difficulty: u32) -> String {
+    (0u64..)
+        .map(|nonce| nonce.to_string())
+        .find(|nonce| {
+            let hash = digest::digest(&digest::SHA256, format!("{challenge}:{nonce}").as_bytes());
+            let bits = u128::from_be_bytes(hash.as_ref()[..16].try_into().unwrap());
+            bits.leading_zeros() >= difficulty
+        })
+        .unwrap()
+}
+
+/// Guest reports need a solved challenge, wait for a moderator and become reports once approved.
+#[actix_web::test]
+async fn guest_reports_are_moderated() {
+    env::set_var("GUEST_REPORT_POW_DIFFICULTY", "8");
+    let state = common::app_state().await;
+    let database = state.database.clone();
+    let app = common::init_app(state).await;
+
+    let slug = common::add_organization(&app).await;
+    let (_, room) = call(
+        &app,
+        as_organization(
+            TestRequest::post()
+                .uri("/room/")
+                .set_json(json!({ "name": "Basement", "description": null })),
+            &slug,
+        ),
+    )
+    .await;
+    let room_id = room["room_id"].as_i64().unwrap();
+
+    for machine_id in ["W1", "W2"] {
+        let (status, _) = call(
+            &app,
+            as_organization(
+                TestRequest::post().uri("/machine/").set_json(json!({
+                    "room_id": room_id,
+                    "machine_id": machine_id,
+                    "machine_type": "Washer"
+                })),
+                &slug,
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+    }
+
+    let submit = |submission: Value, peer: &str| {
+        as_organization(
+            TestRequest::post()
+                .uri("/report/guest")
+                .peer_addr(peer.parse().unwrap())
+                .set_json(submission),
+            &slug,
+        )
+    };
+    let submission = |machine_id: &str, proof_of_work: Value| {
+        json!({
+            "room_id": room_id,
+            "machine_id": machine_id,
+            "report_type": "Broken",
+            "description": "No heat",
+            "contact_email": "guest@example.com",
+            "proof_of_work": proof_of_work
+        })
+    };
+    let solved = || async {
+        let (status, challenge) =
+            call(&app, TestRequest::get().uri("/report/guest/challenge")).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let difficulty = challenge["difficulty"].as_u64().unwrap() as u32;
+        let challenge = challenge["challenge"].as_str().unwrap();
+        json!({ "challenge": challenge, "nonce": solve(challenge, difficulty) })
+    };
+
+    for (proof_of_work, peer) in [
+        (Value::Null, "198.51.100.1:4000"),
+        (
+            json!({ "challenge": "0.00.00", "nonce": "0" }),
+            "198.51.100.1:4000",
+        ),
+    ] {
+        let (status, _) = call(&app, submit(submission("W1", proof_of_work), peer)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    // Unknown machines are rejected even with a solved challenge.
+    let (status, _) = call(
+        &app,
+        submit(submission("W9", solved().await), "198.51.100.1:4000"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // Submissions filling in the honeypot field look accepted, but are dropped.
+    let mut honeypot = submission("W1", solved().await);
+    honeypot["website"] = json!("https://spam.example");
+    let (status, _) = call(&app, submit(honeypot, "198.51.100.1:4000")).await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+
+    for (machine_id, peer) in [("W1", "198.51.100.2:4000"), ("W2", "198.51.100.3:4000")] {
+        let (status, _) = call(&app, submit(submission(machine_id, solved().await), peer)).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+    }
+
+    let (status, guest_reports) = call(
+        &app,
+        as_organization(
+            as_admin(TestRequest::get().uri("/admin/guest-reports")),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let guest_reports = guest_reports.as_array().unwrap();
+    assert_eq!(guest_reports.len(), 2);
+    assert_eq!(guest_reports[0]["status"], "Pending");
+    assert_eq!(guest_reports[0]["client_ip"], "198.51.100.2");
+    assert_eq!(guest_reports[0]["contact_email"], "guest@example.com");
+
+    let moderate = |machine_id: &str, action: &str| {
+        let guest_report = guest_reports
+            .iter()
+            .find(|guest_report| guest_report["machine_id"] == machine_id)
+            .unwrap();
+        as_organization(
+            as_admin(TestRequest::post().uri(&format!(
+                "/admin/guest-reports/{}/{action}",
+                guest_report["guest_report_id"]
+            ))),
+            &slug,
+        )
+    };
+
+    let (status, report) = call(&app, moderate("W1", "approve")).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(report["machine_id"], "W1");
+    assert_eq!(report["reporter_username"], Value::Null);
+    assert_eq!(report["description"], "No heat");
+
+    let (status, rejected) = call(&app, moderate("W2", "reject")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(rejected["status"], "Rejected");
+    assert_eq!(rejected["report_id"], Value::Null);
+
+    // Moderated reports cannot be moderated again.
+    for (machine_id, action) in [("W1", "reject"), ("W2", "approve")] {
+        let (status, _) = call(&app, moderate(machine_id, action)).await;
+        assert_eq!(status, StatusCode::CONFLICT, "{action} {machine_id}");
+    }
+
+    let (_, pending) = call(
+        &app,
+        as_organization(
+            as_admin(TestRequest::get().uri("/admin/guest-reports")),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(pending, json!([]));
+
+    let (_, approved) = call(
+        &app,
+        as_organization(
+            as_admin(TestRequest::get().uri("/admin/guest-reports?status=Approved")),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(approved[0]["report_id"], report["report_id"]);
+
+    let (_, reports) = call(
+        &app,
+        as_organization(TestRequest::get().uri("/report/"), &slug),
+    )
+    .await;
+    assert_eq!(reports.as_array().unwrap().len(), 1);
+
+    common::remove_organization(&database, &slug).await;
+}
+
+/// A solved challenge is spent by the first submission using it, and approving a guest report
+/// about an already reported machine links it to the open report.
+#[actix_web::test]
+async fn solved_challenges_are_accepted_once_and_approvals_are_deduplicated() {
+    env::set_var("GUEST_REPORT_POW_DIFFICULTY", "8");
+    let state = common::app_state().await;
+    let database = state.database.clone();
+    let app = common::init_app(state).await;
+
+    let slug = common::add_organization(&app).await;
+    let (_, room) = call(
+        &app,
+        as_organization(
+            TestRequest::post()
+                .uri("/room/")
+                .set_json(json!({ "name": "Basement", "description": null })),
+            &slug,
+        ),
+    )
+    .await;
+    let room_id = room["room_id"].as_i64().unwrap();
+
+    for machine_id in ["W1", "W2"] {
+        let (status, _) = call(
+            &app,
+            as_organization(
+                TestRequest::post().uri("/machine/").set_json(json!({
+                    "room_id": room_id,
+                    "machine_id": machine_id,
+                    "machine_type": "Washer"
+                })),
+                &slug,
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+    }
+
+    let username = unique("user");
+    let (status, _) = call(
+        &app,
+        as_organization(
+            TestRequest::post()
+                .uri("/user/")
+                .set_json(json!({ "username": username, "admin": false })),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let (status, report) = call(
+        &app,
+        as_organization(
+            TestRequest::post().uri("/report/").set_json(json!({
+                "room_id": room_id,
+                "machine_id": "W1",
+                "reporter_username": username,
+                "report_type": "Broken",
+                "description": null
+            })),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let submit = |machine_id: &str, proof_of_work: &Value, peer: &str| {
+        as_organization(
+            TestRequest::post()
+                .uri("/report/guest")
+                .peer_addr(peer.parse().unwrap())
+                .set_json(json!({
+                    "room_id": room_id,
+                    "machine_id": machine_id,
+                    "report_type": "Broken",
+                    "description": "No heat",
+                    "proof_of_work": proof_of_work
+                })),
+            &slug,
+        )
+    };
+
+    let (status, _) = call(
+        &app,
+        submit(
+            "W1",
+            &json!({ "challenge": "0.00.00", "nonce": "0" }),
+            "198.51.100.1:4000",
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let mut proofs = Vec::new();
+    for _ in 0..2 {
+        let (status, challenge) =
+            call(&app, TestRequest::get().uri("/report/guest/challenge")).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let difficulty = challenge["difficulty"].as_u64().unwrap() as u32;
+        let challenge = challenge["challenge"].as_str().unwrap();
+        proofs.push(json!({ "challenge": challenge, "nonce": solve(challenge, difficulty) }));
+    }
+
+    let (status, _) = call(&app, submit("W1", &proofs[0], "198.51.100.2:4000")).await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+
+    // A solved challenge cannot be replayed, even from another client.
+    let (status, _) = call(&app, submit("W2", &proofs[0], "198.51.100.3:4000")).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = call(&app, submit("W2", &proofs[1], "198.51.100.3:4000")).await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+
+    let (_, guest_reports) = call(
+        &app,
+        as_organization(
+            as_admin(TestRequest::get().uri("/admin/guest-reports")),
+            &slug,
+        ),
+    )
+    .await;
+    let guest_reports = guest_reports.as_array().unwrap();
+    assert_eq!(guest_reports.len(), 2);
+
+    let approve = |machine_id: &str| {
+        let guest_report = guest_reports
+            .iter()
+            .find(|guest_report| guest_report["machine_id"] == machine_id)
+            .unwrap();
+        as_organization(
+            as_admin(TestRequest::post().uri(&format!(
+                "/admin/guest-reports/{}/approve",
+                guest_report["guest_report_id"]
+            ))),
+            &slug,
+        )
+    };
+
+    // The machine is already reported, so the approval points at the open report.
+    let (status, approved) = call(&app, approve("W1")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(approved["report_id"], report["report_id"]);
+
+    let (status, approved) = call(&app, approve("W2")).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_ne!(approved["report_id"], report["report_id"]);
+    assert_eq!(approved["reporter_username"], Value::Null);
+    assert_eq!(approved["automated"], false);
+
+    let (_, reports) = call(
+        &app,
+        as_organization(TestRequest::get().uri("/report/"), &slug),
+    )
+    .await;
+    assert_eq!(reports.as_array().unwrap().len(), 2);
+
+    let (_, guest_reports) = call(
+        &app,
+        as_organization(
+            as_admin(TestRequest::get().uri("/admin/guest-reports?status=Approved")),
+            &slug,
+        ),
+    )
+    .await;
+    let linked = guest_reports
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|guest_report| guest_report["machine_id"] == "W1")
+        .unwrap();
+    assert_eq!(linked["report_id"], report["report_id"]);
+
+    common::remove_organization(&database, &slug).await;
+}
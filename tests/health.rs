@@ -0,0 +1,36 @@
+mod common;
+
+use actix_web::{http::StatusCode, test::TestRequest};
+use common::call;
+use laundry_api::database;
+use serde_json::json;
+
+#[actix_web::test]
+async fn live_answers_without_the_database() {
+    let state = common::app_state().await;
+    let app = common::init_app(state).await;
+
+    let (status, body) = call(&app, TestRequest::get().uri("/health/live")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, json!("Alive"));
+}
+
+#[actix_web::test]
+async fn ready_reports_the_applied_migration_version() {
+    let state = common::app_state().await;
+    let app = common::init_app(state).await;
+
+    let (status, body) = call(&app, TestRequest::get().uri("/health/ready")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["ready"], json!(true));
+    assert_eq!(body["database"]["reachable"], json!(true));
+    assert_eq!(
+        body["migrations"]["applied_version"],
+        json!(database::latest_migration_version())
+    );
+    assert_eq!(body["migrations"]["up_to_date"], json!(true));
+    assert_eq!(
+        body["pool"]["max_connections"],
+        json!(database::MAX_CONNECTIONS)
+    );
+}
@@ -0,0 +1,68 @@
+mod common;
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use laundry_api::{background::BackgroundJobs, database};
+use sqlx::postgres::PgPoolOptions;
+
+/// Waiting for a database which never comes up gives up after the configured attempts, backing
+/// off in between, while a running database is used straight away.
+#[actix_web::test]
+async fn startup_waits_for_the_database_with_backoff() {
+    let unreachable = PgPoolOptions::new()
+        .acquire_timeout(Duration::from_millis(200))
+        .connect_lazy("postgres://postgres@127.0.0.1:1/laundry")
+        .expect("the URL is valid");
+
+    let start = Instant::now();
+    let result = database::wait_for_database(
+        &unreachable,
+        3,
+        Duration::from_millis(50),
+        Duration::from_millis(80),
+    )
+    .await;
+    assert!(result.is_err());
+    // Two waits between three attempts, the second capped by the maximum backoff.
+    assert!(start.elapsed() >= Duration::from_millis(130));
+
+    let database = common::database().await;
+    let start = Instant::now();
+    database::wait_for_database(
+        &database,
+        3,
+        Duration::from_secs(10),
+        Duration::from_secs(10),
+    )
+    .await
+    .expect("the database is available");
+    assert!(start.elapsed() < Duration::from_secs(10));
+}
+
+/// Shutting down lets jobs watching the signal finish their work, and aborts those which do not
+/// finish in time.
+#[actix_web::test]
+async fn shutdown_stops_background_jobs() {
+    let finished = Arc::new(AtomicBool::new(false));
+    let mut background_jobs = BackgroundJobs::new();
+
+    let job_finished = Arc::clone(&finished);
+    background_jobs.spawn("watching", move |mut shutdown| async move {
+        while shutdown.sleep(Duration::from_secs(3600)).await {}
+        job_finished.store(true, Ordering::SeqCst);
+    });
+    background_jobs.spawn("stuck", |_| async {
+        tokio::time::sleep(Duration::from_secs(3600)).await;
+    });
+
+    let start = Instant::now();
+    background_jobs.shutdown(Duration::from_millis(200)).await;
+    assert!(finished.load(Ordering::SeqCst));
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
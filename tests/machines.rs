@@ -0,0 +1,122 @@
+mod common;
+
+use actix_web::{http::StatusCode, test::TestRequest};
+use common::{as_organization, call};
+use serde_json::{json, Value};
+
+/// Machines are listed by their details, and the details are replaced as a whole when edited.
+#[actix_web::test]
+async fn machines_are_filtered_by_their_details() {
+    let state = common::app_state().await;
+    let database = state.database.clone();
+    let app = common::init_app(state).await;
+
+    let slug = common::add_organization(&app).await;
+
+    let room_id = common::add_room::<_, _, &str>(&app, &slug, &[]).await;
+
+    for machine in [
+        json!({
+            "room_id": room_id,
+            "machine_id": "W1",
+            "machine_type": "Washer",
+            "manufacturer": "Speed Queen",
+            "model": "TR7",
+            "install_date": "2022-03-01",
+            "capacity_kg": 8.0,
+            "payment_type": "Card",
+            "notes": "By the door"
+        }),
+        json!({
+            "room_id": room_id,
+            "machine_id": "D1",
+            "machine_type": "Dryer",
+            "manufacturer": "Electrolux",
+            "model": "TD6",
+            "payment_type": "Coin"
+        }),
+        json!({ "room_id": room_id, "machine_id": "W2", "machine_type": "Washer" }),
+    ] {
+        let (status, _) = call(
+            &app,
+            as_organization(
+                TestRequest::post().uri("/machine/").set_json(machine),
+                &slug,
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+    }
+
+    let machine_ids = |query: &str| {
+        let request = as_organization(TestRequest::get().uri(&format!("/machine/?{query}")), &slug);
+        let app = &app;
+        async move {
+            let (status, machines) = call(app, request).await;
+            assert_eq!(status, StatusCode::OK);
+            machines
+                .as_array()
+                .unwrap()
+                .iter()
+                .filter(|machine| machine["room_id"] == room_id)
+                .map(|machine| machine["machine_id"].as_str().unwrap().to_string())
+                .collect::<Vec<_>>()
+        }
+    };
+
+    assert_eq!(machine_ids("").await, ["D1", "W1", "W2"]);
+    assert_eq!(machine_ids("manufacturer=speed%20queen").await, ["W1"]);
+    assert_eq!(machine_ids("model=td6").await, ["D1"]);
+    assert_eq!(machine_ids("payment_type=Coin").await, ["D1"]);
+    assert_eq!(machine_ids("machine_type=Washer").await, ["W1", "W2"]);
+    assert_eq!(
+        machine_ids("machine_type=Washer&payment_type=Coin").await,
+        Vec::<String>::new()
+    );
+
+    let (status, machine) = call(
+        &app,
+        as_organization(
+            TestRequest::get().uri(&format!("/machine/{room_id}/W1")),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(machine["install_date"], "2022-03-01");
+    assert_eq!(machine["capacity_kg"], 8.0);
+    assert_eq!(machine["notes"], "By the door");
+
+    let metadata_uri = format!("/machine/{room_id}/W1/metadata");
+    let (status, _) = call(
+        &app,
+        as_organization(
+            TestRequest::put()
+                .uri(&metadata_uri)
+                .set_json(json!({ "capacity_kg": -1.0 })),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, machine) = call(
+        &app,
+        as_organization(
+            TestRequest::put().uri(&metadata_uri).set_json(json!({
+                "manufacturer": "Electrolux",
+                "warranty_expiry": "2027-03-01"
+            })),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(machine["warranty_expiry"], "2027-03-01");
+    assert_eq!(machine["install_date"], Value::Null);
+    assert_eq!(machine["notes"], Value::Null);
+
+    assert_eq!(machine_ids("manufacturer=Electrolux").await, ["D1", "W1"]);
+
+    common::remove_organization(&database, &slug).await;
+}
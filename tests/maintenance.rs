@@ -0,0 +1,201 @@
+mod common;
+
+use actix_web::{http::StatusCode, test::TestRequest};
+use common::{as_admin, as_organization, call, unique};
+use serde_json::{json, Value};
+use time::{Duration, OffsetDateTime};
+
+/// Admins manage plans, which schedule their tasks from today on without a backlog for a start
+/// date in the past, and record the tasks technicians complete, which may archive the machine's
+/// open reports.
+#[actix_web::test]
+async fn plans_schedule_tasks_which_technicians_complete() {
+    let state = common::app_state().await;
+    let database = state.database.clone();
+    let app = common::init_app(state).await;
+
+    let slug = common::add_organization(&app).await;
+    let (_, room) = call(
+        &app,
+        as_organization(
+            TestRequest::post()
+                .uri("/room/")
+                .set_json(json!({ "name": "Basement", "description": null })),
+            &slug,
+        ),
+    )
+    .await;
+    let room_id = room["room_id"].as_i64().unwrap();
+
+    let (status, _) = call(
+        &app,
+        as_organization(
+            TestRequest::post().uri("/machine/").set_json(json!({
+                "room_id": room_id,
+                "machine_id": "W1",
+                "machine_type": "Washer"
+            })),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let resident = unique("resident");
+    let technician = unique("technician");
+    for (username, is_technician) in [(&resident, false), (&technician, true)] {
+        let (status, _) = call(
+            &app,
+            as_organization(
+                TestRequest::post().uri("/user/").set_json(json!({
+                    "username": username,
+                    "admin": false,
+                    "technician": is_technician
+                })),
+                &slug,
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+    }
+
+    let (status, report) = call(
+        &app,
+        as_organization(
+            TestRequest::post().uri("/report/").set_json(json!({
+                "room_id": room_id,
+                "machine_id": "W1",
+                "reporter_username": resident,
+                "report_type": "Caution",
+                "description": "Squeaks"
+            })),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let today = OffsetDateTime::now_utc().date();
+    let plan = |targets: Value| {
+        let mut plan = json!({
+            "name": "Lint trap",
+            "description": null,
+            "recurrence_interval": 1,
+            "recurrence_unit": "Week",
+            "start_date": (today - Duration::days(17)).to_string()
+        });
+        plan.as_object_mut()
+            .unwrap()
+            .extend(targets.as_object().unwrap().clone());
+        as_organization(
+            TestRequest::post()
+                .uri("/admin/maintenance-plans")
+                .set_json(plan),
+            &slug,
+        )
+    };
+
+    let (status, _) = call(
+        &app,
+        plan(json!({ "room_id": room_id, "machine_id": "W1" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    for targets in [
+        json!({}),
+        json!({ "machine_type": "Washer", "room_id": room_id, "machine_id": "W1" }),
+        json!({ "room_id": room_id, "machine_id": "W9" }),
+    ] {
+        let (status, _) = call(&app, as_admin(plan(targets.clone()))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{targets}");
+    }
+
+    let (status, plan) = call(
+        &app,
+        as_admin(plan(json!({ "room_id": room_id, "machine_id": "W1" }))),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(plan["active"], true);
+    let plan_uri = format!("/admin/maintenance-plans/{}", plan["plan_id"]);
+
+    let (status, tasks) = call(
+        &app,
+        as_organization(TestRequest::get().uri("/maintenance/upcoming"), &slug),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let due_dates: Vec<Value> = tasks
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|task| task["due_date"].clone())
+        .collect();
+    let expected: Vec<Value> = [4, 11, 18, 25]
+        .into_iter()
+        .map(|days| json!((today + Duration::days(days)).to_string()))
+        .collect();
+    assert_eq!(due_dates, expected);
+
+    let complete_uri = format!("/admin/maintenance-tasks/{}/complete", tasks[0]["task_id"]);
+    let completion = |username: &String| {
+        as_organization(
+            TestRequest::post().uri(&complete_uri).set_json(json!({
+                "technician_username": username,
+                "notes": "Cleaned",
+                "archive_reports": true
+            })),
+            &slug,
+        )
+    };
+
+    // Naming a technician does not stand in for admin credentials.
+    let (status, _) = call(&app, completion(&technician)).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    for username in [unique("technician"), resident] {
+        let (status, _) = call(&app, as_admin(completion(&username))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{username}");
+    }
+
+    let (status, body) = call(&app, as_admin(completion(&technician))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["task"]["status"], "Completed");
+    assert_eq!(body["task"]["technician_username"], json!(technician));
+    assert_eq!(body["archived_report_ids"], json!([report["report_id"]]));
+
+    let (status, _) = call(&app, as_admin(completion(&technician))).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    // Retiring the plan removes its scheduled tasks and keeps the completed one.
+    let (status, _) = call(
+        &app,
+        as_organization(TestRequest::delete().uri(&plan_uri), &slug),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, retired) = call(
+        &app,
+        as_organization(as_admin(TestRequest::delete().uri(&plan_uri)), &slug),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(retired["active"], false);
+
+    let (_, tasks) = call(
+        &app,
+        as_organization(TestRequest::get().uri("/maintenance/tasks"), &slug),
+    )
+    .await;
+    let statuses: Vec<&Value> = tasks
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|task| &task["status"])
+        .collect();
+    assert_eq!(statuses, [&json!("Completed")]);
+
+    common::remove_organization(&database, &slug).await;
+}
@@ -0,0 +1,202 @@
+#![cfg(feature = "mqtt")]
+
+mod common;
+
+use actix_web::{http::StatusCode, test::TestRequest};
+use common::{as_admin, as_organization, call, unique};
+use laundry_api::mqtt::{self, MqttConfig};
+use rumqttc::{Publish, QoS};
+use serde_json::{json, Value};
+
+/// The device id is the topic level matched by the first `+` of the first filter the topic
+/// matches, or the whole topic if that filter has none.
+#[test]
+fn topics_identify_their_device() {
+    let config = MqttConfig {
+        host: "localhost".to_string(),
+        port: 1883,
+        client_id: "laundry-api-test".to_string(),
+        credentials: None,
+        topics: vec!["laundry/+/power".to_string(), "tele/+/#".to_string()],
+    };
+
+    assert_eq!(config.device_id("laundry/plug-1/power"), Some("plug-1"));
+    assert_eq!(config.device_id("tele/plug-2/SENSOR"), Some("plug-2"));
+    assert_eq!(config.device_id("tele/plug-2/a/b"), Some("plug-2"));
+    assert_eq!(config.device_id("laundry/plug-1/energy"), None);
+    assert_eq!(config.device_id("laundry/plug-1/power/extra"), None);
+    assert_eq!(config.device_id("laundry/power"), None);
+
+    let config = MqttConfig {
+        topics: vec!["plugs/#".to_string()],
+        ..config
+    };
+    assert_eq!(config.device_id("plugs/plug-3"), Some("plugs/plug-3"));
+}
+
+/// Readings are taken from each supported payload format, and refused unless finite.
+#[test]
+fn only_finite_readings_are_parsed() {
+    assert_eq!(mqtt::parse_payload(b" 850 "), Some((850.0, None)));
+    assert_eq!(
+        mqtt::parse_payload(br#"{"device_id": "plug-1", "power_watts": 12.5}"#),
+        Some((12.5, Some("plug-1".to_string())))
+    );
+    assert_eq!(mqtt::parse_payload(br#"{"power": 3}"#), Some((3.0, None)));
+    assert_eq!(
+        mqtt::parse_payload(br#"{"ENERGY": {"Power": 1800}}"#),
+        Some((1800.0, None))
+    );
+
+    for payload in [
+        "NaN",
+        "inf",
+        "-inf",
+        "1e39",
+        r#"{"power": 1e300}"#,
+        r#"{"power_watts": "850"}"#,
+        r#"{"voltage": 230}"#,
+        "on",
+    ] {
+        assert_eq!(
+            mqtt::parse_payload(payload.as_bytes()),
+            None,
+            "{payload} was parsed"
+        );
+    }
+}
+
+/// Readings are recorded for the device the topic names, whatever the payload claims.
+#[actix_web::test]
+async fn readings_are_recorded_for_the_device_of_the_topic() {
+    let state = common::app_state().await;
+    let database = state.database.clone();
+    let telemetry_config = state.telemetry_config.clone();
+    let report_config = state.report_config.clone();
+    let app = common::init_app(state).await;
+
+    let slug = common::add_organization(&app).await;
+    let (_, room) = call(
+        &app,
+        as_organization(
+            TestRequest::post()
+                .uri("/room/")
+                .set_json(json!({ "name": "Basement", "description": null })),
+            &slug,
+        ),
+    )
+    .await;
+    let room_id = room["room_id"].as_i64().unwrap();
+
+    let mut device_ids = Vec::new();
+    for machine_id in ["W1", "W2"] {
+        let (status, _) = call(
+            &app,
+            as_organization(
+                TestRequest::post().uri("/machine/").set_json(json!({
+                    "room_id": room_id,
+                    "machine_id": machine_id,
+                    "machine_type": "Washer"
+                })),
+                &slug,
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+
+        let device_id = unique("plug");
+        let (status, _) = call(
+            &app,
+            as_organization(
+                as_admin(TestRequest::post().uri("/admin/telemetry-devices")).set_json(json!({
+                    "device_id": device_id,
+                    "room_id": room_id,
+                    "machine_id": machine_id
+                })),
+                &slug,
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        device_ids.push(device_id);
+    }
+
+    let config = MqttConfig {
+        host: "localhost".to_string(),
+        port: 1883,
+        client_id: "laundry-api-test".to_string(),
+        credentials: None,
+        topics: vec!["laundry/+/power".to_string()],
+    };
+    let (app, config, database, telemetry_config, report_config) =
+        (&app, &config, &database, &telemetry_config, &report_config);
+    let publish = |device_id: &str, payload: &str| {
+        let topic = format!("laundry/{device_id}/power");
+        let publish = Publish::new(topic, QoS::AtLeastOnce, payload);
+        async move {
+            mqtt::handle_publish(publish, config, database, telemetry_config, report_config).await
+        }
+    };
+    let telemetry = |machine_id: &str| {
+        let request = as_organization(
+            TestRequest::get().uri(&format!("/machine/{room_id}/{machine_id}/telemetry")),
+            &slug,
+        );
+        async move { call(app, request).await.1 }
+    };
+    let machine_state = |machine_id: &str| {
+        let telemetry = telemetry(machine_id);
+        async move { telemetry.await["state"].clone() }
+    };
+    let readings = |machine_id: &str| {
+        let telemetry = telemetry(machine_id);
+        async move {
+            telemetry.await["samples"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|sample| sample["sample_count"].as_i64().unwrap())
+                .sum::<i64>()
+        }
+    };
+    let reports = || {
+        let request = as_organization(TestRequest::get().uri("/report/"), &slug);
+        async move { call(app, request).await.1.as_array().unwrap().clone() }
+    };
+
+    publish(&device_ids[0], "850").await;
+    assert_eq!(machine_state("W1").await, "Running");
+    assert_eq!(machine_state("W2").await, Value::Null);
+
+    // The topic identifies the device, and a payload claiming to be another device is ignored.
+    let spoofed = json!({ "device_id": device_ids[1], "power_watts": 5000 }).to_string();
+    publish(&device_ids[0], &spoofed).await;
+    assert_eq!(machine_state("W1").await, "Running");
+    assert_eq!(machine_state("W2").await, Value::Null);
+
+    let named = json!({ "device_id": device_ids[1], "power_watts": 0 }).to_string();
+    publish(&device_ids[1], &named).await;
+    assert_eq!(machine_state("W2").await, "Idle");
+    assert_eq!(readings("W2").await, 1);
+
+    for payload in ["NaN", "inf", "-inf", r#"{"power": 1e300}"#] {
+        publish(&device_ids[1], payload).await;
+        assert_eq!(readings("W2").await, 1, "{payload} was recorded");
+    }
+    publish("unknown-device", "5000").await;
+    assert!(reports().await.is_empty());
+
+    // A reading above the fault threshold files a single automated Broken report.
+    let fault = json!({ "ENERGY": { "Power": telemetry_config.fault_watts + 100.0 } }).to_string();
+    publish(&device_ids[0], &fault).await;
+    publish(&device_ids[0], &fault).await;
+    assert_eq!(machine_state("W1").await, "Fault");
+
+    let reports = reports().await;
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0]["machine_id"], "W1");
+    assert_eq!(reports[0]["report_type"], "Broken");
+    assert_eq!(reports[0]["automated"], true);
+
+    common::remove_organization(database, &slug).await;
+}
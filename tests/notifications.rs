@@ -0,0 +1,401 @@
+mod common;
+
+use std::net::IpAddr;
+
+use actix_web::{http::StatusCode, test::TestRequest};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use common::{as_admin, as_organization, call, unique};
+use laundry_api::notification::{self, Notifier};
+use serde_json::{json, Value};
+use time::PrimitiveDateTime;
+use tokio::net::TcpListener;
+
+/// Preferences are checked before they are saved, and read back as they were saved.
+#[actix_web::test]
+async fn preferences_are_validated_and_saved() {
+    let state = common::app_state().await;
+    let database = state.database.clone();
+    let app = common::init_app(state).await;
+
+    let (status, key) = call(
+        &app,
+        TestRequest::get().uri("/user/notifications/vapid-public-key"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let key = URL_SAFE_NO_PAD
+        .decode(key["public_key"].as_str().unwrap())
+        .unwrap();
+    assert_eq!(key.len(), 65);
+
+    let slug = common::add_organization(&app).await;
+    let username = unique("user");
+    let (status, _) = call(
+        &app,
+        as_organization(
+            TestRequest::post()
+                .uri("/user/")
+                .set_json(json!({ "username": username, "admin": false })),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let uri = format!("/user/{username}/notifications");
+    let (status, preferences) =
+        call(&app, as_organization(TestRequest::get().uri(&uri), &slug)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        preferences,
+        json!({
+            "email": null,
+            "webhook_url": null,
+            "push_subscription": null,
+            "reminder_minutes": null
+        })
+    );
+
+    let subscription = |endpoint: &str, p256dh_length: usize| {
+        json!({
+            "endpoint": endpoint,
+            "keys": {
+                "p256dh": URL_SAFE_NO_PAD.encode(vec![4; p256dh_length]),
+                "auth": URL_SAFE_NO_PAD.encode([7; 16])
+            }
+        })
+    };
+
+    for preferences in [
+        json!({ "email": "not an address" }),
+        json!({ "webhook_url": "ftp://example.com/hook" }),
+        json!({ "push_subscription": subscription("http://push.example.com/1", 65) }),
+        json!({ "push_subscription": subscription("https://93.184.216.34/push/1", 33) }),
+        json!({ "reminder_minutes": 0 }),
+    ] {
+        let (status, _) = call(
+            &app,
+            as_organization(TestRequest::put().uri(&uri).set_json(&preferences), &slug),
+        )
+        .await;
+        assert_eq!(
+            status,
+            StatusCode::BAD_REQUEST,
+            "{preferences} was accepted"
+        );
+    }
+
+    // Hosts are resolved when they are saved, so public addresses stand in for names.
+    let preferences = json!({
+        "email": "user@example.com",
+        "webhook_url": "https://93.184.216.34/hook",
+        "push_subscription": subscription("https://93.184.216.34/push/1", 65),
+        "reminder_minutes": 10
+    });
+    let (status, saved) = call(
+        &app,
+        as_organization(TestRequest::put().uri(&uri).set_json(&preferences), &slug),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(saved, preferences);
+
+    let (_, fetched) = call(&app, as_organization(TestRequest::get().uri(&uri), &slug)).await;
+    assert_eq!(fetched, preferences);
+
+    // Channels left out are cleared.
+    let (status, _) = call(
+        &app,
+        as_organization(
+            TestRequest::put()
+                .uri(&uri)
+                .set_json(json!({ "email": "user@example.com" })),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, fetched) = call(&app, as_organization(TestRequest::get().uri(&uri), &slug)).await;
+    assert_eq!(fetched["push_subscription"], Value::Null);
+    assert_eq!(fetched["reminder_minutes"], Value::Null);
+
+    let (status, _) = call(
+        &app,
+        as_organization(
+            TestRequest::put()
+                .uri(&format!("/user/{}/notifications", unique("user")))
+                .set_json(json!({})),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    common::remove_organization(&database, &slug).await;
+}
+
+/// Users are told once that their cycle is done, then reminded at their interval until the
+/// session is finished.
+#[actix_web::test]
+async fn done_sessions_are_notified_once_then_reminded() {
+    let state = common::app_state().await;
+    let database = state.database.clone();
+    let app = common::init_app(state).await;
+    let notifier = Notifier::from_env().expect("notifications are configured");
+
+    let slug = common::add_organization(&app).await;
+    let (_, room) = call(
+        &app,
+        as_organization(
+            TestRequest::post()
+                .uri("/room/")
+                .set_json(json!({ "name": "Basement", "description": null })),
+            &slug,
+        ),
+    )
+    .await;
+    let room_id = room["room_id"].as_i64().unwrap();
+
+    let (status, _) = call(
+        &app,
+        as_organization(
+            TestRequest::post().uri("/machine/").set_json(json!({
+                "room_id": room_id,
+                "machine_id": "W1",
+                "machine_type": "Washer"
+            })),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let username = unique("user");
+    let (status, _) = call(
+        &app,
+        as_organization(
+            TestRequest::post()
+                .uri("/user/")
+                .set_json(json!({ "username": username, "admin": false })),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let (status, _) = call(
+        &app,
+        as_organization(
+            TestRequest::put()
+                .uri(&format!("/user/{username}/notifications"))
+                .set_json(json!({ "reminder_minutes": 5 })),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, session) = call(
+        &app,
+        as_organization(
+            TestRequest::post()
+                .uri(&format!("/machine/{room_id}/W1/session"))
+                .set_json(json!({ "username": username, "expected_duration_minutes": 45 })),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    let session_id = session["session_id"].as_i64().unwrap() as i32;
+
+    let notified = || async {
+        sqlx::query_as::<_, (Option<PrimitiveDateTime>, Option<PrimitiveDateTime>)>(
+            "SELECT done_notified_time, last_reminder_time FROM machine_session WHERE id = $1",
+        )
+        .bind(session_id)
+        .fetch_one(&database)
+        .await
+        .expect("the session is fetched")
+    };
+
+    // The cycle is not done yet.
+    notifier
+        .notify_due_sessions(&database)
+        .await
+        .expect("the notifications are sent");
+    assert_eq!(notified().await, (None, None));
+
+    sqlx::query(
+        r#"
+        UPDATE machine_session
+        SET start_time = start_time - INTERVAL '1 hour',
+            expected_end_time = expected_end_time - INTERVAL '1 hour'
+        WHERE id = $1
+        "#,
+    )
+    .bind(session_id)
+    .execute(&database)
+    .await
+    .expect("the session is moved");
+
+    notifier
+        .notify_due_sessions(&database)
+        .await
+        .expect("the notifications are sent");
+    let (done_notified_time, last_reminder_time) = notified().await;
+    assert!(done_notified_time.is_some());
+    assert_eq!(last_reminder_time, None);
+
+    // Neither the notification nor a reminder is repeated before the interval passes.
+    notifier
+        .notify_due_sessions(&database)
+        .await
+        .expect("the notifications are sent");
+    assert_eq!(notified().await, (done_notified_time, None));
+
+    sqlx::query(
+        r#"
+        UPDATE machine_session
+        SET done_notified_time = done_notified_time - INTERVAL '6 minutes'
+        WHERE id = $1
+        "#,
+    )
+    .bind(session_id)
+    .execute(&database)
+    .await
+    .expect("the session is moved");
+
+    notifier
+        .notify_due_sessions(&database)
+        .await
+        .expect("the notifications are sent");
+    let (_, last_reminder_time) = notified().await;
+    assert!(last_reminder_time.is_some());
+
+    common::remove_organization(&database, &slug).await;
+}
+
+/// Loopback, private, link-local and shared addresses, and IPv6 addresses mapping to them, are
+/// never delivered to.
+#[test]
+fn only_public_addresses_are_delivered_to() {
+    for address in [
+        "127.0.0.1",
+        "10.1.2.3",
+        "172.16.0.1",
+        "192.168.1.1",
+        "169.254.169.254",
+        "100.64.0.1",
+        "0.0.0.0",
+        "::1",
+        "fe80::1",
+        "fd00::1",
+        "fc00::1",
+        "fec0::1",
+        "::ffff:127.0.0.1",
+        "::ffff:10.0.0.1",
+        "::127.0.0.1",
+        "64:ff9b::7f00:1",
+        "64:ff9b:1::a00:1",
+        "2002:7f00:1::1",
+        "2001:0:4136:e378:8000:63bf:3fff:fdd2",
+        "2001:db8::1",
+        "3fff::1",
+        "100::1",
+    ] {
+        let address = address.parse::<IpAddr>().unwrap();
+        assert!(
+            !notification::is_public_address(address),
+            "{address} was public"
+        );
+    }
+
+    for address in [
+        "93.184.216.34",
+        "::ffff:93.184.216.34",
+        "2606:2800:220:1:248:1893:25c9:1946",
+        "2001:4860:4860::8888",
+    ] {
+        let address = address.parse::<IpAddr>().unwrap();
+        assert!(
+            notification::is_public_address(address),
+            "{address} was not public"
+        );
+    }
+}
+
+/// Webhooks are not delivered to internal services, even when the URL was never validated.
+#[actix_web::test]
+async fn webhooks_are_not_sent_to_internal_addresses() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let notifier = Notifier::from_env().expect("notifications are configured");
+
+    for url in [
+        format!("http://127.0.0.1:{port}/hook"),
+        format!("http://localhost:{port}/hook"),
+        format!("http://[::ffff:127.0.0.1]:{port}/hook"),
+    ] {
+        let result = notifier.send_webhook(&url, &json!({})).await;
+        assert!(result.is_err(), "{url} was called");
+    }
+}
+
+/// Webhook URLs naming internal addresses are rejected when they are saved.
+#[actix_web::test]
+async fn internal_webhook_urls_are_rejected() {
+    let state = common::app_state().await;
+    let database = state.database.clone();
+    let app = common::init_app(state).await;
+
+    let slug = common::add_organization(&app).await;
+    let username = unique("user");
+    let (status, _) = call(
+        &app,
+        as_organization(
+            TestRequest::post()
+                .uri("/user/")
+                .set_json(json!({ "username": username, "admin": false })),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    for url in [
+        "http://127.0.0.1/hook",
+        "http://localhost:8080/hook",
+        "http://169.254.169.254/latest/meta-data/",
+        "http://[fd00::1]/hook",
+    ] {
+        let (status, _) = call(
+            &app,
+            as_organization(
+                TestRequest::put()
+                    .uri(&format!("/user/{username}/notifications"))
+                    .set_json(json!({ "webhook_url": url })),
+                &slug,
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{url} was accepted");
+
+        let (status, _) = call(
+            &app,
+            as_organization(
+                as_admin(TestRequest::post().uri("/admin/sla-policies")).set_json(json!({
+                    "name": "Escalation",
+                    "resolve_minutes": 60,
+                    "report_type": "Broken",
+                    "escalation_webhook_url": url
+                })),
+                &slug,
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{url} was accepted");
+    }
+
+    common::remove_organization(&database, &slug).await;
+}
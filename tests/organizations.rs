@@ -0,0 +1,766 @@
+//! Checks that every organization scoped endpoint keeps the records of one organization out of
+//! reach of another.
+
+mod common;
+
+use actix_http::Request;
+use actix_web::{
+    body::MessageBody,
+    dev::{Service, ServiceResponse},
+    http::StatusCode,
+    test::TestRequest,
+    Error,
+};
+use common::{as_admin, as_organization, call, unique};
+use serde_json::{json, Value};
+use time::{Duration, OffsetDateTime};
+
+/// One of everything an organization can own.
+struct Fixture {
+    slug: String,
+    site_id: i64,
+    building_id: i64,
+    room_id: i64,
+    machine_id: String,
+    vendor: String,
+    username: String,
+    report_id: i64,
+    comment_id: i64,
+    plan_id: i64,
+    work_order_id: i64,
+    policy_id: i64,
+    waitlist_entry_id: i64,
+    reservation_id: i64,
+    device_id: String,
+    guest_report_id: i64,
+}
+
+async fn add<S, B>(app: &S, slug: &str, request: TestRequest) -> Value
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = Error>,
+    B: MessageBody,
+{
+    let request = as_organization(request, slug).to_request();
+    let route = format!("{} {}", request.method(), request.path());
+    let response = actix_web::test::call_service(app, request).await;
+    let status = response.status();
+    let body = actix_web::test::read_body(response).await;
+    assert!(status.is_success(), "{route} answered {status}: {body:?}");
+    serde_json::from_slice(&body).expect("the response is JSON")
+}
+
+impl Fixture {
+    async fn create<S, B>(app: &S) -> Fixture
+    where
+        S: Service<Request, Response = ServiceResponse<B>, Error = Error>,
+        B: MessageBody,
+    {
+        let slug = common::add_organization(app).await;
+        let machine_id = "W1".to_string();
+        let vendor = unique("vendor");
+        let username = unique("user");
+        let device_id = unique("device");
+
+        let site = add(
+            app,
+            &slug,
+            TestRequest::post()
+                .uri("/site/")
+                .set_json(json!({ "name": "Campus", "description": null })),
+        )
+        .await;
+        let site_id = site["site_id"].as_i64().unwrap();
+
+        let building = add(
+            app,
+            &slug,
+            TestRequest::post()
+                .uri("/building/")
+                .set_json(json!({ "site_id": site_id, "name": "Hall" })),
+        )
+        .await;
+        let building_id = building["building_id"].as_i64().unwrap();
+
+        let room = add(
+            app,
+            &slug,
+            TestRequest::post().uri("/room/").set_json(json!({
+                "name": "Basement",
+                "description": null,
+                "building_id": building_id
+            })),
+        )
+        .await;
+        let room_id = room["room_id"].as_i64().unwrap();
+
+        add(
+            app,
+            &slug,
+            TestRequest::post().uri("/machine/").set_json(json!({
+                "room_id": room_id,
+                "machine_id": machine_id,
+                "machine_type": "Washer",
+                "manufacturer": vendor
+            })),
+        )
+        .await;
+
+        add(
+            app,
+            &slug,
+            TestRequest::post()
+                .uri("/user/")
+                .set_json(json!({ "username": username, "admin": false })),
+        )
+        .await;
+
+        // Reports are only measured against policies which already applied when they were filed.
+        let policy = add(
+            app,
+            &slug,
+            as_admin(TestRequest::post().uri("/admin/sla-policies")).set_json(json!({
+                "name": "Basement",
+                "room_id": room_id,
+                "resolve_minutes": 1440
+            })),
+        )
+        .await;
+        let policy_id = policy["policy_id"].as_i64().unwrap();
+
+        let report = add(
+            app,
+            &slug,
+            TestRequest::post().uri("/report/").set_json(json!({
+                "room_id": room_id,
+                "machine_id": machine_id,
+                "reporter_username": username,
+                "report_type": "Broken",
+                "description": "Does not drain"
+            })),
+        )
+        .await;
+        let report_id = report["report_id"].as_i64().unwrap();
+
+        let comment = add(
+            app,
+            &slug,
+            TestRequest::post()
+                .uri(&format!("/report/{report_id}/comments"))
+                .set_json(json!({ "author_username": username, "body": "Still broken" })),
+        )
+        .await;
+        let comment_id = comment["comment_id"].as_i64().unwrap();
+
+        let today = OffsetDateTime::now_utc().date();
+        let plan = add(
+            app,
+            &slug,
+            as_admin(TestRequest::post().uri("/admin/maintenance-plans")).set_json(json!({
+                "name": "Lint trap",
+                "description": null,
+                "room_id": room_id,
+                "machine_id": machine_id,
+                "recurrence_interval": 1,
+                "recurrence_unit": "Week",
+                "start_date": today.to_string()
+            })),
+        )
+        .await;
+        let plan_id = plan["plan_id"].as_i64().unwrap();
+
+        let work_order = add(
+            app,
+            &slug,
+            as_admin(TestRequest::post().uri("/admin/work-orders")).set_json(json!({
+                "title": "Replace pump",
+                "description": null,
+                "report_ids": [report_id]
+            })),
+        )
+        .await;
+        let work_order_id = work_order["work_order_id"].as_i64().unwrap();
+
+        let entry = add(
+            app,
+            &slug,
+            TestRequest::post()
+                .uri(&format!("/room/{room_id}/waitlist"))
+                .set_json(json!({ "username": username, "machine_type": "Washer" })),
+        )
+        .await;
+        let waitlist_entry_id = entry["entry_id"].as_i64().unwrap();
+
+        let tomorrow = today + Duration::days(1);
+        let reservation = add(
+            app,
+            &slug,
+            TestRequest::post()
+                .uri(&format!("/room/{room_id}/reservations"))
+                .set_json(json!({
+                    "machine_id": machine_id,
+                    "username": username,
+                    "start_time": format!("{tomorrow}T12:00:00"),
+                    "end_time": format!("{tomorrow}T13:00:00")
+                })),
+        )
+        .await;
+        let reservation_id = reservation["reservation_id"].as_i64().unwrap();
+
+        add(
+            app,
+            &slug,
+            as_admin(TestRequest::post().uri("/admin/telemetry-devices")).set_json(json!({
+                "device_id": device_id,
+                "room_id": room_id,
+                "machine_id": machine_id
+            })),
+        )
+        .await;
+
+        add(
+            app,
+            &slug,
+            TestRequest::post()
+                .uri("/report/guest")
+                .peer_addr(
+                    format!("192.0.2.{}:4000", room_id % 250 + 1)
+                        .parse()
+                        .unwrap(),
+                )
+                .set_json(json!({
+                    "room_id": room_id,
+                    "machine_id": machine_id,
+                    "report_type": "Caution",
+                    "description": "Smells of smoke"
+                })),
+        )
+        .await;
+        let guest_reports = add(
+            app,
+            &slug,
+            as_admin(TestRequest::get().uri("/admin/guest-reports")),
+        )
+        .await;
+        let guest_report_id = guest_reports[0]["guest_report_id"].as_i64().unwrap();
+
+        Fixture {
+            slug,
+            site_id,
+            building_id,
+            room_id,
+            machine_id,
+            vendor,
+            username,
+            report_id,
+            comment_id,
+            plan_id,
+            work_order_id,
+            policy_id,
+            waitlist_entry_id,
+            reservation_id,
+            device_id,
+            guest_report_id,
+        }
+    }
+}
+
+/// Collects `field` of every object in the JSON array `list`.
+fn field(list: &Value, field: &str) -> Vec<Value> {
+    list.as_array()
+        .unwrap_or_else(|| panic!("expected a list, got {list}"))
+        .iter()
+        .map(|item| item[field].clone())
+        .collect()
+}
+
+#[actix_web::test]
+async fn organizations_only_see_their_own_records() {
+    let state = common::app_state().await;
+    let database = state.database.clone();
+    let app = common::init_app(state).await;
+
+    let ours = Fixture::create(&app).await;
+    let theirs = Fixture::create(&app).await;
+
+    let room_id = theirs.room_id;
+    let machine_id = &theirs.machine_id;
+    let username = &theirs.username;
+    let report_id = theirs.report_id;
+
+    // Every request touching another organization's record answers as it would for a record which
+    // does not exist, with either `404 Not Found` or `400 Bad Request` for unknown references.
+    let unreachable = [
+        TestRequest::get().uri(&format!("/site/{}", theirs.site_id)),
+        TestRequest::get().uri(&format!("/site/{}/buildings", theirs.site_id)),
+        TestRequest::delete().uri(&format!("/site/{}", theirs.site_id)),
+        TestRequest::get().uri(&format!("/building/{}", theirs.building_id)),
+        TestRequest::get().uri(&format!("/building/{}/rooms", theirs.building_id)),
+        TestRequest::get().uri(&format!("/building/{}/reports", theirs.building_id)),
+        TestRequest::get().uri(&format!("/building/{}/status", theirs.building_id)),
+        TestRequest::delete().uri(&format!("/building/{}", theirs.building_id)),
+        TestRequest::get().uri(&format!("/room/{room_id}")),
+        TestRequest::get().uri(&format!("/room/{room_id}/machines")),
+        TestRequest::get().uri(&format!("/room/{room_id}/reports")),
+        TestRequest::get().uri(&format!("/room/{room_id}/reports/archived")),
+        TestRequest::get().uri(&format!("/room/{room_id}/availability")),
+        TestRequest::get().uri(&format!("/room/{room_id}/waitlist")),
+        TestRequest::get().uri(&format!("/room/{room_id}/reservations")),
+        TestRequest::get().uri(&format!("/room/{room_id}/qr-sheet.svg")),
+        TestRequest::put()
+            .uri(&format!("/room/{room_id}/building"))
+            .set_json(json!({ "building_id": null })),
+        TestRequest::delete().uri(&format!(
+            "/room/{room_id}/waitlist/{}",
+            theirs.waitlist_entry_id
+        )),
+        TestRequest::delete().uri(&format!(
+            "/room/{room_id}/reservations/{}",
+            theirs.reservation_id
+        )),
+        TestRequest::delete().uri(&format!("/room/{room_id}")),
+        TestRequest::get().uri(&format!("/machine/{room_id}/{machine_id}")),
+        TestRequest::get().uri(&format!("/machine/{room_id}/{machine_id}/reports")),
+        TestRequest::get().uri(&format!("/machine/{room_id}/{machine_id}/reports/archived")),
+        TestRequest::get().uri(&format!("/machine/{room_id}/{machine_id}/telemetry")),
+        TestRequest::get().uri(&format!("/machine/{room_id}/{machine_id}/qr.png")),
+        TestRequest::put()
+            .uri(&format!("/machine/{room_id}/{machine_id}/metadata"))
+            .set_json(json!({})),
+        TestRequest::post()
+            .uri(&format!("/machine/{room_id}/{machine_id}/session"))
+            .set_json(json!({ "username": null, "expected_duration_minutes": 30 })),
+        TestRequest::delete().uri(&format!("/machine/{room_id}/{machine_id}")),
+        TestRequest::get().uri(&format!("/user/{username}")),
+        TestRequest::get().uri(&format!("/user/{username}/reports")),
+        TestRequest::get().uri(&format!("/user/{username}/reports/archived")),
+        TestRequest::get().uri(&format!("/user/{username}/notifications")),
+        TestRequest::delete().uri(&format!("/user/{username}")),
+        TestRequest::get().uri(&format!("/report/{report_id}")),
+        TestRequest::get().uri(&format!("/report/{report_id}/confirmations")),
+        TestRequest::get().uri(&format!("/report/{report_id}/comments")),
+        TestRequest::get().uri(&format!("/report/{report_id}/attachments")),
+        TestRequest::get().uri(&format!("/report/{report_id}/sla")),
+        TestRequest::post()
+            .uri(&format!("/report/{report_id}/comments"))
+            .set_json(json!({ "author_username": ours.username, "body": "Hello" })),
+        TestRequest::delete().uri(&format!(
+            "/report/{report_id}/comments/{}",
+            theirs.comment_id
+        )),
+        TestRequest::post()
+            .uri("/report/archive")
+            .set_json(json!({ "report_id": report_id })),
+        as_admin(TestRequest::post().uri("/admin/reports/merge")).set_json(json!({
+            "source_report_id": report_id,
+            "target_report_id": ours.report_id
+        })),
+        TestRequest::delete().uri(&format!("/report/{report_id}")),
+        as_admin(
+            TestRequest::delete().uri(&format!("/admin/maintenance-plans/{}", theirs.plan_id)),
+        ),
+        TestRequest::get().uri(&format!("/work-order/{}", theirs.work_order_id)),
+        TestRequest::get().uri(&format!("/work-order/{}/labour", theirs.work_order_id)),
+        TestRequest::get().uri(&format!("/work-order/{}/parts", theirs.work_order_id)),
+        as_admin(TestRequest::post().uri(&format!(
+            "/admin/work-orders/{}/close",
+            theirs.work_order_id
+        )))
+        .set_json(json!({ "resolution": null })),
+        as_admin(TestRequest::delete().uri(&format!("/admin/sla-policies/{}", theirs.policy_id))),
+        as_admin(TestRequest::post().uri(&format!(
+            "/admin/guest-reports/{}/approve",
+            theirs.guest_report_id
+        ))),
+        as_admin(TestRequest::post().uri(&format!(
+            "/admin/guest-reports/{}/reject",
+            theirs.guest_report_id
+        ))),
+        as_admin(TestRequest::post().uri(&format!(
+            "/admin/telemetry-devices/{}/key",
+            theirs.device_id
+        ))),
+        as_admin(
+            TestRequest::delete().uri(&format!("/admin/telemetry-devices/{}", theirs.device_id)),
+        ),
+        // Records of another organization cannot be referred to from new records either.
+        TestRequest::post().uri("/machine/").set_json(json!({
+            "room_id": room_id,
+            "machine_id": "W2",
+            "machine_type": "Washer"
+        })),
+        TestRequest::post().uri("/building/").set_json(json!({
+            "site_id": theirs.site_id,
+            "name": "Annex"
+        })),
+        TestRequest::post().uri("/room/").set_json(json!({
+            "name": "Annex",
+            "description": null,
+            "building_id": theirs.building_id
+        })),
+        TestRequest::post().uri("/report/").set_json(json!({
+            "room_id": room_id,
+            "machine_id": machine_id,
+            "reporter_username": ours.username,
+            "report_type": "Broken",
+            "description": null
+        })),
+        TestRequest::post().uri("/report/").set_json(json!({
+            "room_id": ours.room_id,
+            "machine_id": ours.machine_id,
+            "reporter_username": username,
+            "report_type": "Broken",
+            "description": null
+        })),
+        as_admin(TestRequest::post().uri("/admin/maintenance-plans")).set_json(json!({
+            "name": "Lint trap",
+            "description": null,
+            "room_id": room_id,
+            "machine_id": machine_id,
+            "recurrence_interval": 1,
+            "recurrence_unit": "Week",
+            "start_date": "2026-01-01"
+        })),
+        as_admin(TestRequest::post().uri("/admin/work-orders")).set_json(json!({
+            "title": "Replace pump",
+            "description": null,
+            "report_ids": [report_id]
+        })),
+        as_admin(TestRequest::post().uri("/admin/sla-policies")).set_json(json!({
+            "name": "Basement",
+            "room_id": room_id,
+            "resolve_minutes": 60
+        })),
+        as_admin(TestRequest::post().uri("/admin/telemetry-devices")).set_json(json!({
+            "device_id": unique("device"),
+            "room_id": room_id,
+            "machine_id": machine_id
+        })),
+    ];
+
+    for request in unreachable {
+        let request = as_organization(request, &ours.slug).to_request();
+        let route = format!("{} {}", request.method(), request.path());
+        let response = actix_web::test::call_service(&app, request).await;
+        assert!(
+            matches!(
+                response.status(),
+                StatusCode::BAD_REQUEST | StatusCode::NOT_FOUND
+            ),
+            "{route} answered {}",
+            response.status()
+        );
+    }
+
+    // None of the changes attempted above went through.
+    let untouched = [
+        format!("/site/{}", theirs.site_id),
+        format!("/building/{}", theirs.building_id),
+        format!("/room/{room_id}"),
+        format!("/machine/{room_id}/{machine_id}"),
+        format!("/user/{username}"),
+        format!("/report/{report_id}"),
+        format!("/work-order/{}", theirs.work_order_id),
+    ];
+
+    for uri in untouched {
+        let (status, _) = call(
+            &app,
+            as_organization(TestRequest::get().uri(&uri), &theirs.slug),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "GET {uri}");
+    }
+
+    let (_, body) = call(
+        &app,
+        as_organization(
+            TestRequest::get().uri(&format!("/report/{report_id}/comments")),
+            &theirs.slug,
+        ),
+    )
+    .await;
+    assert_eq!(field(&body, "comment_id"), [json!(theirs.comment_id)]);
+
+    let (_, body) = call(
+        &app,
+        as_organization(
+            TestRequest::get().uri(&format!("/room/{room_id}/reservations")),
+            &theirs.slug,
+        ),
+    )
+    .await;
+    assert_eq!(field(&body, "status"), [json!("Booked")]);
+
+    // Listings only contain the organization's own records, including those filtered by room.
+    let listings = [
+        (
+            "/site/",
+            "site_id",
+            json!(ours.site_id),
+            json!(theirs.site_id),
+        ),
+        (
+            "/building/",
+            "building_id",
+            json!(ours.building_id),
+            json!(theirs.building_id),
+        ),
+        (
+            "/room/",
+            "room_id",
+            json!(ours.room_id),
+            json!(theirs.room_id),
+        ),
+        (
+            "/machine/",
+            "room_id",
+            json!(ours.room_id),
+            json!(theirs.room_id),
+        ),
+        (
+            "/user/",
+            "username",
+            json!(ours.username),
+            json!(theirs.username),
+        ),
+        (
+            "/report/",
+            "report_id",
+            json!(ours.report_id),
+            json!(theirs.report_id),
+        ),
+        (
+            "/maintenance/plans",
+            "plan_id",
+            json!(ours.plan_id),
+            json!(theirs.plan_id),
+        ),
+        (
+            "/maintenance/upcoming",
+            "plan_id",
+            json!(ours.plan_id),
+            json!(theirs.plan_id),
+        ),
+        (
+            "/maintenance/tasks",
+            "plan_id",
+            json!(ours.plan_id),
+            json!(theirs.plan_id),
+        ),
+        (
+            "/work-order/",
+            "work_order_id",
+            json!(ours.work_order_id),
+            json!(theirs.work_order_id),
+        ),
+        (
+            "/admin/sla-policies",
+            "policy_id",
+            json!(ours.policy_id),
+            json!(theirs.policy_id),
+        ),
+        (
+            "/sla/compliance",
+            "vendor",
+            json!(ours.vendor),
+            json!(theirs.vendor),
+        ),
+        (
+            "/admin/telemetry-devices",
+            "device_id",
+            json!(ours.device_id),
+            json!(theirs.device_id),
+        ),
+        (
+            "/admin/guest-reports",
+            "guest_report_id",
+            json!(ours.guest_report_id),
+            json!(theirs.guest_report_id),
+        ),
+    ];
+
+    for (uri, key, own, foreign) in listings {
+        let request = as_organization(as_admin(TestRequest::get().uri(uri)), &ours.slug);
+        let (status, body) = call(&app, request).await;
+        assert_eq!(status, StatusCode::OK, "GET {uri}");
+
+        let values = field(&body, key);
+        assert!(values.contains(&own), "GET {uri} is missing {own}");
+        assert!(!values.contains(&foreign), "GET {uri} leaks {foreign}");
+    }
+
+    // Archiving moves the report out of reach of the other organization's archive too.
+    let (status, _) = call(
+        &app,
+        as_organization(
+            TestRequest::post()
+                .uri("/report/archive")
+                .set_json(json!({ "report_id": theirs.report_id })),
+            &theirs.slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = call(
+        &app,
+        as_organization(TestRequest::get().uri("/report/archived"), &ours.slug),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(!field(&body, "report_id").contains(&json!(theirs.report_id)));
+
+    // The reservation policy of one organization does not apply to another.
+    let (status, _) = call(
+        &app,
+        as_organization(
+            as_admin(TestRequest::put().uri("/admin/reservation-policy")).set_json(json!({
+                "max_active_reservations": 1,
+                "max_duration_minutes": 45,
+                "claim_minutes": 10,
+                "no_show_minutes": 10,
+                "blackout_start": null,
+                "blackout_end": null
+            })),
+            &theirs.slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = call(
+        &app,
+        as_organization(
+            as_admin(TestRequest::get().uri("/admin/reservation-policy")),
+            &ours.slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_ne!(body["max_duration_minutes"], json!(45));
+
+    // Usernames are only unique within an organization, so taking one reveals nothing about others.
+    let (status, _) = call(
+        &app,
+        as_organization(
+            TestRequest::post()
+                .uri("/user/")
+                .set_json(json!({ "username": username, "admin": true })),
+            &ours.slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let (status, _) = call(
+        &app,
+        as_organization(
+            TestRequest::post()
+                .uri("/user/")
+                .set_json(json!({ "username": username, "admin": true })),
+            &ours.slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let (status, body) = call(
+        &app,
+        as_organization(
+            TestRequest::get().uri(&format!("/user/{username}")),
+            &theirs.slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["admin"], json!(false));
+
+    let (_, body) = call(
+        &app,
+        as_organization(
+            TestRequest::get().uri(&format!("/user/{username}/reports")),
+            &ours.slug,
+        ),
+    )
+    .await;
+    assert_eq!(body, json!([]));
+
+    common::remove_organization(&database, &ours.slug).await;
+    common::remove_organization(&database, &theirs.slug).await;
+}
+
+/// Requests without an organization act on the default one, and requests naming an unknown one
+/// are refused.
+#[actix_web::test]
+async fn requests_act_on_the_default_or_a_known_organization() {
+    let state = common::app_state().await;
+    let app = common::init_app(state).await;
+
+    let name = unique("Basement");
+    let (status, room) = call(
+        &app,
+        TestRequest::post()
+            .uri("/room/")
+            .set_json(json!({ "name": name, "description": null })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let (status, rooms) = call(
+        &app,
+        as_organization(TestRequest::get().uri("/room/"), "default"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(rooms.as_array().unwrap().contains(&room), "{rooms}");
+
+    let (status, _) = call(
+        &app,
+        TestRequest::delete().uri(&format!("/room/{}", room["room_id"])),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = call(
+        &app,
+        as_organization(TestRequest::get().uri("/room/"), &unique("missing")),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn admin_endpoints_require_a_key() {
+    let state = common::app_state().await;
+    let app = common::init_app(state).await;
+
+    for uri in [
+        "/admin/organizations",
+        "/admin/rate-limits",
+        "/admin/machine-types",
+    ] {
+        let (status, _) = call(&app, TestRequest::get().uri(uri)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED, "GET {uri}");
+
+        let (status, _) = call(
+            &app,
+            TestRequest::get()
+                .uri(uri)
+                .insert_header(("Authorization", "Bearer not-the-key")),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED, "GET {uri}");
+
+        let (status, _) = call(&app, as_admin(TestRequest::get().uri(uri))).await;
+        assert_eq!(status, StatusCode::OK, "GET {uri}");
+    }
+
+    let (status, _) = call(
+        &app,
+        TestRequest::post()
+            .uri("/admin/organizations")
+            .set_json(json!({ "slug": unique("test"), "name": "Intruder" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
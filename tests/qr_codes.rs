@@ -0,0 +1,114 @@
+mod common;
+
+use actix_web::{
+    http::{header::CONTENT_TYPE, StatusCode},
+    test::{self, TestRequest},
+};
+use common::{as_organization, call};
+use laundry_api::qr::QrConfig;
+use qrcode::{Color, EcLevel, QrCode};
+use serde_json::json;
+
+/// Light modules around every code.
+const QUIET_ZONE: u32 = 4;
+
+/// Machine codes encode the configured URL for the machine, as PNG and SVG, and a room's sheet
+/// labels every machine in it.
+#[actix_web::test]
+async fn machine_codes_encode_their_report_url() {
+    let mut state = common::app_state().await;
+    state.qr_config = QrConfig {
+        url_template: "https://laundry.example.com/report?room={room_id}&machine={machine_id}"
+            .to_string(),
+        module_pixels: 3,
+    };
+    let database = state.database.clone();
+    let app = common::init_app(state).await;
+
+    let slug = common::add_organization(&app).await;
+    let (_, room) = call(
+        &app,
+        as_organization(
+            TestRequest::post()
+                .uri("/room/")
+                .set_json(json!({ "name": "Smith & Sons", "description": null })),
+            &slug,
+        ),
+    )
+    .await;
+    let room_id = room["room_id"].as_i64().unwrap();
+
+    for machine_id in ["W1", "Dryer 2"] {
+        let (status, _) = call(
+            &app,
+            as_organization(
+                TestRequest::post().uri("/machine/").set_json(json!({
+                    "room_id": room_id,
+                    "machine_id": machine_id,
+                    "machine_type": "Washer"
+                })),
+                &slug,
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+    }
+
+    let fetch = |uri: String| {
+        let request = as_organization(TestRequest::get().uri(&uri), &slug).to_request();
+        let app = &app;
+        async move {
+            let response = test::call_service(app, request).await;
+            let status = response.status();
+            let content_type = response
+                .headers()
+                .get(CONTENT_TYPE)
+                .map(|value| value.to_str().unwrap().to_string());
+            (status, content_type, test::read_body(response).await)
+        }
+    };
+
+    // The PNG shows the code of the URL with the machine id percent encoded, module by module.
+    let (status, content_type, png) = fetch(format!("/machine/{room_id}/Dryer%202/qr.png")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(content_type.as_deref(), Some("image/png"));
+
+    let url = format!("https://laundry.example.com/report?room={room_id}&machine=Dryer%202");
+    let expected = QrCode::with_error_correction_level(url, EcLevel::M).unwrap();
+    let image = image::load_from_memory(&png).unwrap().to_luma8();
+    let width = expected.width() as u32;
+    assert_eq!(image.width(), (width + 2 * QUIET_ZONE) * 3);
+    assert_eq!(image.height(), image.width());
+
+    for (index, color) in expected.to_colors().into_iter().enumerate() {
+        let column = index as u32 % width + QUIET_ZONE;
+        let row = index as u32 / width + QUIET_ZONE;
+        let dark = image.get_pixel(column * 3 + 1, row * 3 + 1).0 == [0];
+        assert_eq!(dark, color == Color::Dark, "module {column}, {row}");
+    }
+    assert_eq!(image.get_pixel(0, 0).0, [255]);
+
+    let (status, content_type, svg) = fetch(format!("/machine/{room_id}/W1/qr.svg")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(content_type.as_deref(), Some("image/svg+xml"));
+    let svg = String::from_utf8(svg.to_vec()).unwrap();
+    assert!(svg.starts_with("<svg"), "{svg}");
+    assert!(svg.contains(r#"<path d="M"#), "{svg}");
+
+    let (status, _, _) = fetch(format!("/machine/{room_id}/W9/qr.png")).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, content_type, sheet) = fetch(format!("/room/{room_id}/qr-sheet.svg")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(content_type.as_deref(), Some("image/svg+xml"));
+    let sheet = String::from_utf8(sheet.to_vec()).unwrap();
+    assert_eq!(sheet.matches("<svg x=").count(), 2);
+    assert!(sheet.contains(">Dryer 2</text>"), "{sheet}");
+    assert!(sheet.contains(">W1</text>"), "{sheet}");
+    assert_eq!(sheet.matches(">Smith &amp; Sons</text>").count(), 2);
+
+    let (status, _, _) = fetch(format!("/room/{}/qr-sheet.svg", room_id + 1_000_000)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    common::remove_organization(&database, &slug).await;
+}
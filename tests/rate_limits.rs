@@ -0,0 +1,281 @@
+mod common;
+
+use std::sync::Arc;
+
+use actix_web::{
+    http::{header, StatusCode},
+    test::{self, TestRequest},
+    web, App, HttpResponse,
+};
+use common::{as_admin, as_organization, call};
+use laundry_api::rate_limit::{Limit, RateLimitPerIp, RateLimiter};
+use serde_json::json;
+
+/// A report submission about a machine of `room_id` in the organization `slug`, made by
+/// `username` from `peer`.
+fn report(
+    slug: &str,
+    room_id: i64,
+    machine_id: &str,
+    report_type: &str,
+    username: &str,
+    peer: &str,
+) -> TestRequest {
+    as_organization(
+        TestRequest::post()
+            .uri("/report/")
+            .peer_addr(peer.parse().unwrap())
+            .set_json(json!({
+                "room_id": room_id,
+                "machine_id": machine_id,
+                "reporter_username": username,
+                "report_type": report_type,
+                "description": null
+            })),
+        slug,
+    )
+}
+
+/// New reports are limited per reporter and client, and only once they are valid.
+#[actix_web::test]
+async fn only_valid_reports_use_up_tokens() {
+    let state = common::app_state().await;
+    let database = state.database.clone();
+    let report_per_user = state.rate_limiter.config.report_per_user.burst as usize;
+    let report_per_ip = state.rate_limiter.config.report_per_ip.burst as usize;
+    let app = common::init_app(state).await;
+
+    let slug = common::add_organization(&app).await;
+    let machine_ids = (0..=report_per_user)
+        .map(|index| format!("W{index}"))
+        .collect::<Vec<_>>();
+    let room_id = common::add_room(&app, &slug, &machine_ids).await;
+    let usernames = [
+        common::add_user(&app, &slug).await,
+        common::add_user(&app, &slug).await,
+    ];
+    let peer = "198.51.100.1:4000";
+
+    // Rejected submissions leave the client's bucket untouched.
+    for _ in 0..=report_per_ip {
+        let request = report(&slug, room_id, "X9", "Broken", &usernames[0], peer);
+        let (status, _) = call(&app, request).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    for (index, machine_id) in machine_ids.iter().enumerate() {
+        let request = report(&slug, room_id, machine_id, "Broken", &usernames[0], peer);
+        let (status, _) = call(&app, request).await;
+        match index < report_per_user {
+            true => assert_eq!(status, StatusCode::CREATED, "report {index}"),
+            false => assert_eq!(status, StatusCode::TOO_MANY_REQUESTS),
+        }
+    }
+
+    // Switching to another client does not give the reporter a fresh limit.
+    let last_machine_id = machine_ids.last().unwrap();
+    let request = report(
+        &slug,
+        room_id,
+        last_machine_id,
+        "Broken",
+        &usernames[0],
+        "198.51.100.2:4000",
+    );
+    let (status, _) = call(&app, request).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+
+    let request = report(
+        &slug,
+        room_id,
+        last_machine_id,
+        "Broken",
+        &usernames[1],
+        peer,
+    );
+    let (status, _) = call(&app, request).await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    common::remove_organization(&database, &slug).await;
+}
+
+/// However many residents confirm a broken machine, others can still report it.
+#[actix_web::test]
+async fn confirmations_do_not_keep_others_from_reporting() {
+    let state = common::app_state().await;
+    let database = state.database.clone();
+    let report_per_user = state.rate_limiter.config.report_per_user.burst as usize;
+    let app = common::init_app(state).await;
+
+    let slug = common::add_organization(&app).await;
+    let room_id = common::add_room(&app, &slug, &["W1"]).await;
+
+    for index in 0..report_per_user + 2 {
+        let username = common::add_user(&app, &slug).await;
+        let peer = format!("198.51.100.{}:4000", index + 1);
+        let request = report(&slug, room_id, "W1", "Broken", &username, &peer);
+        let (status, _) = call(&app, request).await;
+        match index {
+            0 => assert_eq!(status, StatusCode::CREATED),
+            _ => assert_eq!(status, StatusCode::OK, "confirmation {index}"),
+        }
+    }
+
+    let username = common::add_user(&app, &slug).await;
+    let request = report(
+        &slug,
+        room_id,
+        "W1",
+        "Caution",
+        &username,
+        "198.51.100.200:4000",
+    );
+    let (status, _) = call(&app, request).await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    common::remove_organization(&database, &slug).await;
+}
+
+#[actix_web::test]
+async fn reports_are_limited_per_client() {
+    let state = common::app_state().await;
+    let database = state.database.clone();
+    let report_per_user = state.rate_limiter.config.report_per_user.burst as usize;
+    let report_per_ip = state.rate_limiter.config.report_per_ip.burst as usize;
+    let app = common::init_app(state).await;
+
+    let slug = common::add_organization(&app).await;
+
+    let machine_ids = (0..=report_per_ip)
+        .map(|index| format!("W{index}"))
+        .collect::<Vec<_>>();
+    let room_id = common::add_room(&app, &slug, &machine_ids).await;
+    // Enough reporters that none of them runs into their own limit first.
+    let mut usernames = Vec::new();
+    for _ in 0..=report_per_ip / report_per_user {
+        usernames.push(common::add_user(&app, &slug).await);
+    }
+    let username = |index: usize| &usernames[index % usernames.len()];
+    let peer = "198.51.100.1:4000";
+
+    for (index, machine_id) in machine_ids[..report_per_ip].iter().enumerate() {
+        let request = report(&slug, room_id, machine_id, "Broken", username(index), peer);
+        let (status, _) = call(&app, request).await;
+        assert_eq!(status, StatusCode::CREATED, "report {index}");
+    }
+
+    let last_machine_id = machine_ids.last().unwrap();
+    let last_username = username(report_per_ip);
+    let response = test::call_service(
+        &app,
+        report(
+            &slug,
+            room_id,
+            last_machine_id,
+            "Broken",
+            last_username,
+            peer,
+        )
+        .to_request(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(response.headers().contains_key(header::RETRY_AFTER));
+
+    let request = report(
+        &slug,
+        room_id,
+        last_machine_id,
+        "Broken",
+        last_username,
+        "198.51.100.2:4000",
+    );
+    let (status, _) = call(&app, request).await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    // The limited client shows up for admins.
+    let (status, throttled) =
+        call(&app, as_admin(TestRequest::get().uri("/admin/rate-limits"))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(
+        throttled
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|client| client["key"] == "report-ip:198.51.100.1"),
+        "{throttled}"
+    );
+
+    common::remove_organization(&database, &slug).await;
+}
+
+/// Every request but the health checks counts towards the limit of its client.
+#[actix_web::test]
+async fn requests_are_limited_per_client() {
+    let database = common::database().await;
+    let mut rate_limiter = RateLimiter::from_env(&database);
+    rate_limiter.config.per_ip = Limit::per_minute(2);
+
+    let app = test::init_service(
+        App::new()
+            .wrap(RateLimitPerIp(Arc::new(rate_limiter)))
+            .route("/room/", web::get().to(HttpResponse::Ok))
+            .route("/health/live", web::get().to(HttpResponse::Ok)),
+    )
+    .await;
+
+    let request = |uri: &str, peer: &str| {
+        TestRequest::get()
+            .uri(uri)
+            .peer_addr(peer.parse().unwrap())
+            .to_request()
+    };
+
+    for _ in 0..2 {
+        let response = test::call_service(&app, request("/room/", "198.51.100.1:4000")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+    let response = test::call_service(&app, request("/room/", "198.51.100.1:4000")).await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    let response = test::call_service(&app, request("/health/live", "198.51.100.1:4000")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = test::call_service(&app, request("/room/", "198.51.100.2:4000")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+/// Behind a trusted proxy, clients are told apart by the address the proxy appended, so
+/// forging earlier X-Forwarded-For entries does not get a client a fresh limit.
+#[actix_web::test]
+async fn forged_forwarded_addresses_do_not_reset_the_limit() {
+    let database = common::database().await;
+    let mut rate_limiter = RateLimiter::from_env(&database);
+    rate_limiter.config.per_ip = Limit::per_minute(2);
+    rate_limiter.config.trusted_proxies = 1;
+
+    let app = test::init_service(
+        App::new()
+            .wrap(RateLimitPerIp(Arc::new(rate_limiter)))
+            .route("/room/", web::get().to(HttpResponse::Ok)),
+    )
+    .await;
+
+    let request = |forwarded_for: &str| {
+        TestRequest::get()
+            .uri("/room/")
+            .peer_addr("10.0.0.1:4000".parse().unwrap())
+            .insert_header(("X-Forwarded-For", forwarded_for))
+            .to_request()
+    };
+
+    for forged in ["203.0.113.1", "203.0.113.2"] {
+        let forwarded_for = format!("{forged}, 198.51.100.1");
+        let response = test::call_service(&app, request(&forwarded_for)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+    let response = test::call_service(&app, request("203.0.113.3, 198.51.100.1")).await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    let response = test::call_service(&app, request("198.51.100.2")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
@@ -0,0 +1,246 @@
+mod common;
+
+use actix_web::{
+    http::{header, StatusCode},
+    test::{self, TestRequest},
+};
+use common::{as_admin, as_organization, call};
+use futures_util::future;
+use laundry_api::{
+    models::ReportType,
+    report::{self, DuplicateMode, ReportConfig},
+};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+/// A report submission about `machine_id` of `room_id` in the organization `slug`, made by
+/// `username`.
+fn report(
+    slug: &str,
+    room_id: i64,
+    machine_id: &str,
+    username: &str,
+    report_type: &str,
+) -> TestRequest {
+    as_organization(
+        TestRequest::post().uri("/report/").set_json(json!({
+            "room_id": room_id,
+            "machine_id": machine_id,
+            "reporter_username": username,
+            "report_type": report_type,
+            "description": null
+        })),
+        slug,
+    )
+}
+
+/// Further submissions about an open report are recorded as confirmations of it.
+#[actix_web::test]
+async fn duplicate_submissions_confirm_the_open_report() {
+    let state = common::app_state().await;
+    let database = state.database.clone();
+    let app = common::init_app(state).await;
+
+    let slug = common::add_organization(&app).await;
+
+    let room_id = common::add_room(&app, &slug, &["W1"]).await;
+    let usernames = [
+        common::add_user(&app, &slug).await,
+        common::add_user(&app, &slug).await,
+    ];
+
+    let (status, filed) = call(&app, report(&slug, room_id, "W1", &usernames[0], "Broken")).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(filed["confirmation_count"], json!(0));
+
+    let (status, confirmed) =
+        call(&app, report(&slug, room_id, "W1", &usernames[1], "Broken")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(confirmed["report_id"], filed["report_id"]);
+    assert_eq!(confirmed["confirmation_count"], json!(1));
+
+    // Neither the reporter nor a repeated confirmation counts again.
+    for username in &usernames {
+        let (status, confirmed) =
+            call(&app, report(&slug, room_id, "W1", username, "Broken")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(confirmed["report_id"], filed["report_id"]);
+        assert_eq!(confirmed["confirmation_count"], json!(1));
+    }
+
+    // Another kind of problem is a report of its own.
+    let (status, other) = call(&app, report(&slug, room_id, "W1", &usernames[1], "Caution")).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_ne!(other["report_id"], filed["report_id"]);
+
+    let uri = format!("/report/{}/confirmations", filed["report_id"]);
+    let (status, confirmations) =
+        call(&app, as_organization(TestRequest::get().uri(&uri), &slug)).await;
+    assert_eq!(status, StatusCode::OK);
+    let confirmations = confirmations.as_array().unwrap();
+    assert_eq!(confirmations.len(), 1);
+    assert_eq!(confirmations[0]["reporter_username"], json!(usernames[1]));
+
+    common::remove_organization(&database, &slug).await;
+}
+
+/// Duplicates can be rejected instead, pointing at the open report.
+#[actix_web::test]
+async fn duplicate_submissions_can_be_rejected() {
+    let mut state = common::app_state().await;
+    state.report_config.duplicate_mode = DuplicateMode::Reject;
+    let database = state.database.clone();
+    let app = common::init_app(state).await;
+
+    let slug = common::add_organization(&app).await;
+
+    let room_id = common::add_room(&app, &slug, &["W1"]).await;
+    let usernames = [
+        common::add_user(&app, &slug).await,
+        common::add_user(&app, &slug).await,
+    ];
+
+    let (status, filed) = call(&app, report(&slug, room_id, "W1", &usernames[0], "Broken")).await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let response = test::call_service(
+        &app,
+        report(&slug, room_id, "W1", &usernames[1], "Broken").to_request(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    assert_eq!(
+        response.headers().get(header::LOCATION).unwrap(),
+        &format!("/report/{}", filed["report_id"])
+    );
+
+    common::remove_organization(&database, &slug).await;
+}
+
+/// Admins can merge a report into another, which moves the reporter and confirmations of the
+/// source report to the target report.
+#[actix_web::test]
+async fn merged_reports_become_confirmations() {
+    let mut state = common::app_state().await;
+    // Every submission files a report of its own, so there is a duplicate to merge.
+    state.report_config.duplicate_window = Duration::ZERO;
+    let database = state.database.clone();
+    let app = common::init_app(state).await;
+
+    let slug = common::add_organization(&app).await;
+
+    let room_id = common::add_room(&app, &slug, &["W1", "W2"]).await;
+    let usernames = [
+        common::add_user(&app, &slug).await,
+        common::add_user(&app, &slug).await,
+    ];
+
+    let (_, target) = call(&app, report(&slug, room_id, "W1", &usernames[0], "Broken")).await;
+    let (_, source) = call(&app, report(&slug, room_id, "W1", &usernames[1], "Broken")).await;
+    let (_, other) = call(&app, report(&slug, room_id, "W1", &usernames[1], "Caution")).await;
+    let (_, elsewhere) = call(&app, report(&slug, room_id, "W2", &usernames[1], "Broken")).await;
+
+    let merge = |source: &Value, target: &Value| {
+        as_organization(
+            TestRequest::post()
+                .uri("/admin/reports/merge")
+                .set_json(json!({
+                    "source_report_id": source["report_id"],
+                    "target_report_id": target["report_id"]
+                })),
+            &slug,
+        )
+    };
+
+    let (status, _) = call(&app, merge(&source, &target)).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, _) = call(&app, as_admin(merge(&target, &target))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = call(&app, as_admin(merge(&elsewhere, &target))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = call(&app, as_admin(merge(&other, &target))).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let (status, merged) = call(&app, as_admin(merge(&source, &target))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(merged["report_id"], target["report_id"]);
+    assert_eq!(merged["confirmation_count"], json!(1));
+
+    let uri = format!("/report/{}", source["report_id"]);
+    let (status, _) = call(&app, as_organization(TestRequest::get().uri(&uri), &slug)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _) = call(&app, as_organization(TestRequest::delete().uri(&uri), &slug)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    common::remove_organization(&database, &slug).await;
+}
+
+/// Concurrent submissions about one machine file a single report, whichever way they interleave.
+#[actix_web::test]
+async fn concurrent_submissions_file_one_report() {
+    let state = common::app_state().await;
+    let database = state.database.clone();
+    let app = common::init_app(state).await;
+
+    let slug = common::add_organization(&app).await;
+    let room_id = common::add_room(&app, &slug, &["W1", "W2"]).await;
+    let mut usernames = Vec::new();
+    for _ in 0..6 {
+        usernames.push(common::add_user(&app, &slug).await);
+    }
+
+    let submissions = usernames
+        .iter()
+        .map(|username| call(&app, report(&slug, room_id, "W1", username, "Broken")));
+    let responses = future::join_all(submissions).await;
+
+    let created = responses
+        .iter()
+        .filter(|(status, _)| *status == StatusCode::CREATED)
+        .count();
+    assert_eq!(created, 1, "{responses:?}");
+
+    let report_ids = responses
+        .iter()
+        .map(|(_, report)| report["report_id"].clone())
+        .collect::<Vec<_>>();
+    assert!(report_ids.windows(2).all(|pair| pair[0] == pair[1]));
+
+    let (_, confirmations) = call(
+        &app,
+        as_organization(
+            TestRequest::get().uri(&format!("/report/{}/confirmations", report_ids[0])),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(confirmations.as_array().unwrap().len(), usernames.len() - 1);
+
+    // Automated reports, such as those filed by anomaly detection, are deduplicated the same way.
+    let report_config = ReportConfig::from_env();
+    let report_type = ReportType("Caution".to_string());
+    let room_id = room_id as i32;
+    let filings = (0..4).map(|_| {
+        report::submit_automated_report(
+            &database,
+            &report_config,
+            &room_id,
+            "W2",
+            &report_type,
+            None,
+        )
+    });
+    let filed = future::join_all(filings)
+        .await
+        .into_iter()
+        .map(|result| result.expect("the report is filed"))
+        .filter(Option::is_some)
+        .count();
+    assert_eq!(filed, 1);
+
+    common::remove_organization(&database, &slug).await;
+}
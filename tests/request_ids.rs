@@ -0,0 +1,79 @@
+use actix_web::{
+    http::StatusCode,
+    test::{self, TestRequest},
+    web, App, HttpResponse,
+};
+use laundry_api::{
+    error,
+    request_id::{self, RequestIdentifier, REQUEST_ID_HEADER},
+};
+use uuid::Uuid;
+
+/// Sends a request with the given `X-Request-Id` to `path` of an app which identifies requests,
+/// returning the status, the id answered and the body.
+async fn send(path: &str, request_id: Option<&str>) -> (StatusCode, String, String) {
+    let app = test::init_service(
+        App::new()
+            .wrap(RequestIdentifier)
+            .route(
+                "/ok",
+                web::get().to(|| async {
+                    HttpResponse::Ok().body(request_id::current().unwrap().as_str().to_string())
+                }),
+            )
+            .route(
+                "/error",
+                web::get().to(|| async {
+                    error::database_error("fail on purpose", sqlx::Error::RowNotFound)
+                }),
+            ),
+    )
+    .await;
+
+    let request = TestRequest::get().uri(path);
+    let request = match request_id {
+        Some(request_id) => request.insert_header((REQUEST_ID_HEADER, request_id)),
+        None => request,
+    };
+
+    let response = test::call_service(&app, request.to_request()).await;
+    let status = response.status();
+    let answered = response
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .map(|value| value.to_str().unwrap().to_string())
+        .expect("the request id is answered");
+    let body = test::read_body(response).await;
+    (status, answered, String::from_utf8(body.to_vec()).unwrap())
+}
+
+/// A reasonable request id supplied by the client is used for the request and echoed back.
+#[actix_web::test]
+async fn supplied_request_ids_are_kept() {
+    let (status, answered, body) = send("/ok", Some("client-1234")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(answered, "client-1234");
+    assert_eq!(body, "client-1234");
+}
+
+/// Requests without a usable id are given a random one.
+#[actix_web::test]
+async fn missing_or_unreasonable_request_ids_are_generated() {
+    let too_long = "a".repeat(129);
+    for request_id in [None, Some(""), Some("has spaces"), Some(too_long.as_str())] {
+        let (status, answered, body) = send("/ok", request_id).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(Uuid::parse_str(&answered).is_ok(), "{answered}");
+        assert_eq!(body, answered);
+    }
+}
+
+/// Internal server errors name the request id, so that a reported failure can be found in the
+/// server logs.
+#[actix_web::test]
+async fn error_bodies_contain_the_request_id() {
+    let (status, answered, body) = send("/error", Some("client-5678")).await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(answered, "client-5678");
+    assert!(body.contains("(request id client-5678)"), "{body}");
+}
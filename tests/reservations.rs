@@ -0,0 +1,629 @@
+mod common;
+
+use actix_web::{http::StatusCode, test::TestRequest};
+use common::{as_admin, as_organization, call, unique};
+use futures_util::future;
+use serde_json::{json, Value};
+use time::{Duration, OffsetDateTime};
+
+/// Reservations of a machine cannot overlap, and are held to the organization's policy.
+#[actix_web::test]
+async fn reservations_follow_the_policy() {
+    let state = common::app_state().await;
+    let database = state.database.clone();
+    let app = common::init_app(state).await;
+
+    let slug = common::add_organization(&app).await;
+    let (_, room) = call(
+        &app,
+        as_organization(
+            TestRequest::post()
+                .uri("/room/")
+                .set_json(json!({ "name": "Basement", "description": null })),
+            &slug,
+        ),
+    )
+    .await;
+    let room_id = room["room_id"].as_i64().unwrap();
+
+    for machine_id in ["W1", "W2"] {
+        let (status, _) = call(
+            &app,
+            as_organization(
+                TestRequest::post().uri("/machine/").set_json(json!({
+                    "room_id": room_id,
+                    "machine_id": machine_id,
+                    "machine_type": "Washer"
+                })),
+                &slug,
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+    }
+
+    let mut usernames = Vec::new();
+    for _ in 0..2 {
+        let username = unique("user");
+        let (status, _) = call(
+            &app,
+            as_organization(
+                TestRequest::post()
+                    .uri("/user/")
+                    .set_json(json!({ "username": username, "admin": false })),
+                &slug,
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        usernames.push(username);
+    }
+
+    let today = OffsetDateTime::now_utc().date();
+    let tomorrow = today + Duration::days(1);
+    let reserve = |machine_id: &str, username: &String, start: String, end: String| {
+        as_organization(
+            TestRequest::post()
+                .uri(&format!("/room/{room_id}/reservations"))
+                .set_json(json!({
+                    "machine_id": machine_id,
+                    "username": username,
+                    "start_time": start,
+                    "end_time": end
+                })),
+            &slug,
+        )
+    };
+
+    let (status, reservation) = call(
+        &app,
+        reserve(
+            "W1",
+            &usernames[0],
+            format!("{tomorrow}T12:00:00"),
+            format!("{tomorrow}T13:00:00"),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(reservation["status"], "Booked");
+    assert_eq!(reservation["start_time"], format!("{tomorrow}T12:00:00"));
+
+    let (status, _) = call(
+        &app,
+        reserve(
+            "W1",
+            &usernames[1],
+            format!("{tomorrow}T12:30:00"),
+            format!("{tomorrow}T13:30:00"),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    // Back to back reservations do not overlap.
+    let (status, _) = call(
+        &app,
+        reserve(
+            "W1",
+            &usernames[1],
+            format!("{tomorrow}T13:00:00"),
+            format!("{tomorrow}T14:00:00"),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    for (machine_id, start, end, expected) in [
+        // The end must follow the start.
+        (
+            "W2",
+            format!("{tomorrow}T12:00:00"),
+            format!("{tomorrow}T11:00:00"),
+            StatusCode::BAD_REQUEST,
+        ),
+        // Reservations may not be in the past.
+        (
+            "W2",
+            format!("{today}T00:00:00"),
+            format!("{today}T00:01:00"),
+            StatusCode::BAD_REQUEST,
+        ),
+        // Nor longer than 120 minutes by default.
+        (
+            "W2",
+            format!("{tomorrow}T12:00:00"),
+            format!("{tomorrow}T15:00:00"),
+            StatusCode::BAD_REQUEST,
+        ),
+        (
+            "W9",
+            format!("{tomorrow}T12:00:00"),
+            format!("{tomorrow}T13:00:00"),
+            StatusCode::BAD_REQUEST,
+        ),
+    ] {
+        let (status, _) = call(&app, reserve(machine_id, &usernames[0], start, end)).await;
+        assert_eq!(status, expected);
+    }
+
+    // Two reservations may be held at once by default.
+    let (status, _) = call(
+        &app,
+        reserve(
+            "W2",
+            &usernames[0],
+            format!("{tomorrow}T15:00:00"),
+            format!("{tomorrow}T16:00:00"),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let (status, _) = call(
+        &app,
+        reserve(
+            "W2",
+            &usernames[0],
+            format!("{tomorrow}T17:00:00"),
+            format!("{tomorrow}T18:00:00"),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, policy) = call(
+        &app,
+        as_organization(
+            as_admin(TestRequest::put().uri("/admin/reservation-policy")).set_json(json!({
+                "max_active_reservations": 3,
+                "max_duration_minutes": 45,
+                "claim_minutes": 10,
+                "no_show_minutes": 10,
+                "blackout_start": "23:00",
+                "blackout_end": "07:00"
+            })),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(policy["blackout_start"], "23:00");
+
+    let (_, fetched) = call(
+        &app,
+        as_organization(
+            as_admin(TestRequest::get().uri("/admin/reservation-policy")),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(fetched, policy);
+
+    for (start, end) in [
+        (
+            format!("{tomorrow}T17:00:00"),
+            format!("{tomorrow}T18:00:00"),
+        ),
+        // The blackout runs past midnight.
+        (
+            format!("{tomorrow}T06:30:00"),
+            format!("{tomorrow}T07:00:00"),
+        ),
+    ] {
+        let (status, _) = call(&app, reserve("W2", &usernames[0], start, end)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    let (status, _) = call(
+        &app,
+        reserve(
+            "W2",
+            &usernames[0],
+            format!("{tomorrow}T17:00:00"),
+            format!("{tomorrow}T17:45:00"),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let (status, _) = call(
+        &app,
+        as_organization(
+            as_admin(TestRequest::put().uri("/admin/reservation-policy")).set_json(json!({
+                "max_active_reservations": 3,
+                "max_duration_minutes": 45,
+                "claim_minutes": 10,
+                "no_show_minutes": 10,
+                "blackout_start": "23:00",
+                "blackout_end": null
+            })),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let cancel = || {
+        as_organization(
+            TestRequest::delete().uri(&format!(
+                "/room/{room_id}/reservations/{}",
+                reservation["reservation_id"]
+            )),
+            &slug,
+        )
+    };
+
+    let (status, cancelled) = call(&app, cancel()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(cancelled["status"], "Cancelled");
+
+    let (status, _) = call(&app, cancel()).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, reservations) = call(
+        &app,
+        as_organization(
+            TestRequest::get().uri(&format!("/room/{room_id}/reservations")),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(!reservations
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(
+            |listed| listed["reservation_id"] == reservation["reservation_id"]
+                && listed["status"] == "Booked"
+        ));
+
+    common::remove_organization(&database, &slug).await;
+}
+
+/// Free machines are offered to the longest waiting user, and held for them.
+#[actix_web::test]
+async fn waitlists_are_offered_free_machines() {
+    let state = common::app_state().await;
+    let database = state.database.clone();
+    let app = common::init_app(state).await;
+
+    let slug = common::add_organization(&app).await;
+    let (_, room) = call(
+        &app,
+        as_organization(
+            TestRequest::post()
+                .uri("/room/")
+                .set_json(json!({ "name": "Basement", "description": null })),
+            &slug,
+        ),
+    )
+    .await;
+    let room_id = room["room_id"].as_i64().unwrap();
+
+    for machine_id in ["W1", "W2"] {
+        let (status, _) = call(
+            &app,
+            as_organization(
+                TestRequest::post().uri("/machine/").set_json(json!({
+                    "room_id": room_id,
+                    "machine_id": machine_id,
+                    "machine_type": "Washer"
+                })),
+                &slug,
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+    }
+
+    let mut usernames = Vec::new();
+    for _ in 0..3 {
+        let username = unique("user");
+        let (status, _) = call(
+            &app,
+            as_organization(
+                TestRequest::post()
+                    .uri("/user/")
+                    .set_json(json!({ "username": username, "admin": false })),
+                &slug,
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        usernames.push(username);
+    }
+
+    let start_session = |machine_id: &str, username: &String| {
+        as_organization(
+            TestRequest::post()
+                .uri(&format!("/machine/{room_id}/{machine_id}/session"))
+                .set_json(json!({ "username": username, "expected_duration_minutes": 45 })),
+            &slug,
+        )
+    };
+    let join = |username: &String, machine_type: &str| {
+        as_organization(
+            TestRequest::post()
+                .uri(&format!("/room/{room_id}/waitlist"))
+                .set_json(json!({ "username": username, "machine_type": machine_type })),
+            &slug,
+        )
+    };
+
+    let (status, _) = call(&app, start_session("W1", &usernames[2])).await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let (status, first) = call(&app, join(&usernames[0], "Washer")).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(first["status"], "Claimed");
+    assert_eq!(first["machine_id"], "W2");
+
+    let (status, second) = call(&app, join(&usernames[1], "Washer")).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(second["status"], "Waiting");
+    assert_eq!(second["machine_id"], Value::Null);
+
+    let (status, _) = call(&app, join(&usernames[1], "Washer")).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let (status, _) = call(&app, join(&usernames[1], "Dryer")).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // The claimed machine is held for the user it was offered to.
+    let (status, _) = call(&app, start_session("W2", &usernames[1])).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let (status, left) = call(
+        &app,
+        as_organization(
+            TestRequest::delete().uri(&format!("/room/{room_id}/waitlist/{}", first["entry_id"])),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(left["status"], "Cancelled");
+
+    // The released machine goes to the next in line.
+    let (status, waitlist) = call(
+        &app,
+        as_organization(
+            TestRequest::get().uri(&format!("/room/{room_id}/waitlist")),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let entry = waitlist
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|entry| entry["entry_id"] == second["entry_id"])
+        .unwrap();
+    assert_eq!(entry["status"], "Claimed");
+    assert_eq!(entry["machine_id"], "W2");
+
+    let (status, _) = call(&app, start_session("W2", &usernames[1])).await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let (_, waitlist) = call(
+        &app,
+        as_organization(
+            TestRequest::get().uri(&format!("/room/{room_id}/waitlist")),
+            &slug,
+        ),
+    )
+    .await;
+    assert!(!waitlist
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|entry| entry["entry_id"] == second["entry_id"] && entry["status"] == "Claimed"));
+
+    common::remove_organization(&database, &slug).await;
+}
+
+/// Machines with an open report at least as severe as Broken, or shown to be faulty by their
+/// telemetry, are not offered to the waitlist, while less severe reports do not hold a machine
+/// back.
+#[actix_web::test]
+async fn waitlists_are_not_offered_broken_or_faulty_machines() {
+    let state = common::app_state().await;
+    let database = state.database.clone();
+    let app = common::init_app(state).await;
+
+    let slug = common::add_organization(&app).await;
+    let (_, room) = call(
+        &app,
+        as_organization(
+            TestRequest::post()
+                .uri("/room/")
+                .set_json(json!({ "name": "Basement", "description": null })),
+            &slug,
+        ),
+    )
+    .await;
+    let room_id = room["room_id"].as_i64().unwrap();
+
+    for machine_id in ["W1", "W2", "W3", "W4"] {
+        let (status, _) = call(
+            &app,
+            as_organization(
+                TestRequest::post().uri("/machine/").set_json(json!({
+                    "room_id": room_id,
+                    "machine_id": machine_id,
+                    "machine_type": "Washer"
+                })),
+                &slug,
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+    }
+
+    let mut usernames = Vec::new();
+    for _ in 0..2 {
+        let username = unique("user");
+        let (status, _) = call(
+            &app,
+            as_organization(
+                TestRequest::post()
+                    .uri("/user/")
+                    .set_json(json!({ "username": username, "admin": false })),
+                &slug,
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        usernames.push(username);
+    }
+
+    // A category admins added which is more severe than Broken.
+    let flooding = unique("Flooding");
+    let (status, _) = call(
+        &app,
+        as_admin(TestRequest::post().uri("/admin/report-types")).set_json(json!({
+            "name": flooding,
+            "display_name": "Flooding",
+            "severity": 3,
+            "icon": null,
+            "active": true
+        })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    for (machine_id, report_type) in [("W1", "Broken"), ("W3", &flooding), ("W4", "Caution")] {
+        let (status, _) = call(
+            &app,
+            as_organization(
+                TestRequest::post().uri("/report/").set_json(json!({
+                    "room_id": room_id,
+                    "machine_id": machine_id,
+                    "reporter_username": usernames[0],
+                    "report_type": report_type,
+                    "description": null
+                })),
+                &slug,
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+    }
+
+    sqlx::query(
+        r#"
+        INSERT INTO machine_operating_state (room_id, machine_id, state, since, last_reading_time)
+        VALUES ($1, 'W2', 'fault', now() AT TIME ZONE 'UTC', now() AT TIME ZONE 'UTC')
+        "#,
+    )
+    .bind(room_id as i32)
+    .execute(&database)
+    .await
+    .expect("the fault is recorded");
+
+    let mut entries = Vec::new();
+    for username in &usernames {
+        let (status, entry) = call(
+            &app,
+            as_organization(
+                TestRequest::post()
+                    .uri(&format!("/room/{room_id}/waitlist"))
+                    .set_json(json!({ "username": username, "machine_type": "Washer" })),
+                &slug,
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        entries.push(entry);
+    }
+
+    assert_eq!(entries[0]["status"], "Claimed");
+    assert_eq!(entries[0]["machine_id"], "W4");
+    assert_eq!(entries[1]["status"], "Waiting", "{:?}", entries[1]);
+
+    common::remove_organization(&database, &slug).await;
+}
+
+/// Concurrent bookings by one user cannot exceed the number of reservations they may hold.
+#[actix_web::test]
+async fn concurrent_reservations_respect_the_maximum() {
+    let state = common::app_state().await;
+    let database = state.database.clone();
+    let app = common::init_app(state).await;
+
+    let slug = common::add_organization(&app).await;
+    let (_, room) = call(
+        &app,
+        as_organization(
+            TestRequest::post()
+                .uri("/room/")
+                .set_json(json!({ "name": "Basement", "description": null })),
+            &slug,
+        ),
+    )
+    .await;
+    let room_id = room["room_id"].as_i64().unwrap();
+
+    let machine_ids = ["W1", "W2", "W3", "W4", "W5", "W6"];
+    for machine_id in machine_ids {
+        let (status, _) = call(
+            &app,
+            as_organization(
+                TestRequest::post().uri("/machine/").set_json(json!({
+                    "room_id": room_id,
+                    "machine_id": machine_id,
+                    "machine_type": "Washer"
+                })),
+                &slug,
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+    }
+
+    let username = unique("user");
+    let (status, _) = call(
+        &app,
+        as_organization(
+            TestRequest::post()
+                .uri("/user/")
+                .set_json(json!({ "username": username, "admin": false })),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let tomorrow = OffsetDateTime::now_utc().date() + Duration::days(1);
+    let bookings = machine_ids.iter().map(|machine_id| {
+        call(
+            &app,
+            as_organization(
+                TestRequest::post()
+                    .uri(&format!("/room/{room_id}/reservations"))
+                    .set_json(json!({
+                        "machine_id": machine_id,
+                        "username": username,
+                        "start_time": format!("{tomorrow}T12:00:00"),
+                        "end_time": format!("{tomorrow}T13:00:00")
+                    })),
+                &slug,
+            ),
+        )
+    });
+    let responses = future::join_all(bookings).await;
+
+    let created = responses
+        .iter()
+        .filter(|(status, _)| *status == StatusCode::CREATED)
+        .count();
+    assert_eq!(created, 2, "{responses:?}");
+    assert!(responses
+        .iter()
+        .all(|(status, _)| matches!(*status, StatusCode::CREATED | StatusCode::BAD_REQUEST)));
+
+    common::remove_organization(&database, &slug).await;
+}
@@ -0,0 +1,428 @@
+mod common;
+
+use actix_web::{http::StatusCode, test::TestRequest};
+use common::{as_organization, call, unique};
+use futures_util::future;
+use laundry_api::session;
+use serde_json::{json, Value};
+use std::time::Duration as StdDuration;
+use time::{Duration, OffsetDateTime};
+
+/// A started machine shows as in use until its session is finished, and cannot be started again
+/// meanwhile.
+#[actix_web::test]
+async fn sessions_mark_machines_in_use_until_finished() {
+    let state = common::app_state().await;
+    let database = state.database.clone();
+    let app = common::init_app(state).await;
+
+    let slug = common::add_organization(&app).await;
+    let (_, room) = call(
+        &app,
+        as_organization(
+            TestRequest::post()
+                .uri("/room/")
+                .set_json(json!({ "name": "Basement", "description": null })),
+            &slug,
+        ),
+    )
+    .await;
+    let room_id = room["room_id"].as_i64().unwrap();
+
+    for (machine_id, machine_type) in [("W1", "Washer"), ("D1", "Dryer")] {
+        let (status, _) = call(
+            &app,
+            as_organization(
+                TestRequest::post().uri("/machine/").set_json(json!({
+                    "room_id": room_id,
+                    "machine_id": machine_id,
+                    "machine_type": machine_type
+                })),
+                &slug,
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+    }
+
+    let username = unique("user");
+    let (status, _) = call(
+        &app,
+        as_organization(
+            TestRequest::post()
+                .uri("/user/")
+                .set_json(json!({ "username": username, "admin": false })),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let session_uri = format!("/machine/{room_id}/W1/session");
+    let start_session = |username: Value, expected_duration_minutes: u32| {
+        as_organization(
+            TestRequest::post().uri(&session_uri).set_json(json!({
+                "username": username,
+                "expected_duration_minutes": expected_duration_minutes
+            })),
+            &slug,
+        )
+    };
+
+    for (username, expected_duration_minutes, expected) in [
+        (json!(username), 0, StatusCode::BAD_REQUEST),
+        (json!(username), 24 * 60, StatusCode::BAD_REQUEST),
+        (json!(unique("user")), 45, StatusCode::BAD_REQUEST),
+    ] {
+        let (status, _) = call(&app, start_session(username, expected_duration_minutes)).await;
+        assert_eq!(status, expected);
+    }
+
+    let (status, _) = call(
+        &app,
+        as_organization(
+            TestRequest::post()
+                .uri(&format!("/machine/{room_id}/W9/session"))
+                .set_json(json!({ "username": null, "expected_duration_minutes": 45 })),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, session) = call(&app, start_session(json!(username), 45)).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(session["username"], json!(username));
+    assert_eq!(session["end_time"], Value::Null);
+
+    let (status, _) = call(&app, start_session(Value::Null, 45)).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let availability = || {
+        let request = as_organization(
+            TestRequest::get().uri(&format!("/room/{room_id}/availability")),
+            &slug,
+        );
+        let app = &app;
+        async move {
+            let (status, availability) = call(app, request).await;
+            assert_eq!(status, StatusCode::OK);
+            let mut machines = availability.as_array().unwrap().clone();
+            machines.sort_by_key(|machine| machine["machine_id"].to_string());
+            machines
+        }
+    };
+
+    let machines = availability().await;
+    assert_eq!(machines[0]["machine_id"], "D1");
+    assert_eq!(machines[0]["state"], "Available");
+    assert_eq!(machines[0]["session_id"], Value::Null);
+    assert_eq!(machines[1]["machine_id"], "W1");
+    assert_eq!(machines[1]["state"], "InUse");
+    assert_eq!(machines[1]["session_id"], session["session_id"]);
+    let minutes_remaining = machines[1]["minutes_remaining"].as_i64().unwrap();
+    assert!(
+        (44..=45).contains(&minutes_remaining),
+        "{minutes_remaining}"
+    );
+
+    let finish = || {
+        as_organization(
+            TestRequest::post().uri(&format!("{session_uri}/finish")),
+            &slug,
+        )
+    };
+
+    let (status, finished) = call(&app, finish()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(finished["session_id"], session["session_id"]);
+    assert_eq!(finished["end_reason"], "Finished");
+
+    let (status, _) = call(&app, finish()).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let machines = availability().await;
+    assert_eq!(machines[1]["state"], "Available");
+
+    let (status, _) = call(&app, start_session(Value::Null, 45)).await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let (status, _) = call(
+        &app,
+        as_organization(
+            TestRequest::get().uri(&format!("/room/{}/availability", room_id + 1_000_000)),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    common::remove_organization(&database, &slug).await;
+}
+
+/// A reserved machine is only started by the user who booked it, which checks them in, and a
+/// machine in use cannot be started again until its session is finished.
+#[actix_web::test]
+async fn reserved_machines_are_started_by_their_holder_only() {
+    let state = common::app_state().await;
+    let database = state.database.clone();
+    let app = common::init_app(state).await;
+
+    let slug = common::add_organization(&app).await;
+    let room_id = common::add_room(&app, &slug, &["W1"]).await;
+    let usernames = [
+        common::add_user(&app, &slug).await,
+        common::add_user(&app, &slug).await,
+    ];
+
+    // A reservation which began a few minutes ago.
+    let start = OffsetDateTime::now_utc() - Duration::minutes(5);
+    let format_time = |time: OffsetDateTime| {
+        format!(
+            "{}T{:02}:{:02}:{:02}",
+            time.date(),
+            time.hour(),
+            time.minute(),
+            time.second()
+        )
+    };
+    let (status, reservation) = call(
+        &app,
+        as_organization(
+            TestRequest::post()
+                .uri(&format!("/room/{room_id}/reservations"))
+                .set_json(json!({
+                    "machine_id": "W1",
+                    "username": usernames[0],
+                    "start_time": format_time(start),
+                    "end_time": format_time(start + Duration::hours(1))
+                })),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let session_uri = format!("/machine/{room_id}/W1/session");
+    let start_session = |username: &String| {
+        as_organization(
+            TestRequest::post().uri(&session_uri).set_json(json!({
+                "username": username,
+                "expected_duration_minutes": 45
+            })),
+            &slug,
+        )
+    };
+
+    let (status, _) = call(&app, start_session(&usernames[1])).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let (status, session) = call(&app, start_session(&usernames[0])).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(session["username"], json!(usernames[0]));
+
+    let (_, reservations) = call(
+        &app,
+        as_organization(
+            TestRequest::get().uri(&format!("/room/{room_id}/reservations")),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(
+        reservations[0]["reservation_id"],
+        reservation["reservation_id"]
+    );
+    assert_eq!(reservations[0]["status"], "CheckedIn");
+
+    let (status, _) = call(&app, start_session(&usernames[0])).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    common::remove_organization(&database, &slug).await;
+}
+
+/// Concurrent starts of one machine result in a single session.
+#[actix_web::test]
+async fn concurrent_sessions_start_once() {
+    let state = common::app_state().await;
+    let database = state.database.clone();
+    let app = common::init_app(state).await;
+
+    let slug = common::add_organization(&app).await;
+    let room_id = common::add_room(&app, &slug, &["W1"]).await;
+    let mut usernames = Vec::new();
+    for _ in 0..4 {
+        usernames.push(common::add_user(&app, &slug).await);
+    }
+
+    let starts = usernames.iter().map(|username| {
+        call(
+            &app,
+            as_organization(
+                TestRequest::post()
+                    .uri(&format!("/machine/{room_id}/W1/session"))
+                    .set_json(json!({
+                        "username": username,
+                        "expected_duration_minutes": 45
+                    })),
+                &slug,
+            ),
+        )
+    });
+    let responses = future::join_all(starts).await;
+
+    let created = responses
+        .iter()
+        .filter(|(status, _)| *status == StatusCode::CREATED)
+        .count();
+    assert_eq!(created, 1, "{responses:?}");
+    assert!(responses
+        .iter()
+        .all(|(status, _)| matches!(*status, StatusCode::CREATED | StatusCode::CONFLICT)));
+
+    common::remove_organization(&database, &slug).await;
+}
+
+/// Sessions running past their expected end by the grace period are ended by the expiry job.
+#[actix_web::test]
+async fn overdue_sessions_are_expired() {
+    let state = common::app_state().await;
+    let database = state.database.clone();
+    let app = common::init_app(state).await;
+
+    let slug = common::add_organization(&app).await;
+    let (_, room) = call(
+        &app,
+        as_organization(
+            TestRequest::post()
+                .uri("/room/")
+                .set_json(json!({ "name": "Basement", "description": null })),
+            &slug,
+        ),
+    )
+    .await;
+    let room_id = room["room_id"].as_i64().unwrap();
+
+    let (status, _) = call(
+        &app,
+        as_organization(
+            TestRequest::post().uri("/machine/").set_json(json!({
+                "room_id": room_id,
+                "machine_id": "W1",
+                "machine_type": "Washer"
+            })),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let (status, session) = call(
+        &app,
+        as_organization(
+            TestRequest::post()
+                .uri(&format!("/machine/{room_id}/W1/session"))
+                .set_json(json!({ "username": null, "expected_duration_minutes": 45 })),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    let session_id = session["session_id"].as_i64().unwrap();
+
+    // Move the session two hours into the past, well past the grace period.
+    sqlx::query(
+        r#"
+        UPDATE machine_session
+        SET start_time = start_time - INTERVAL '2 hours',
+            expected_end_time = expected_end_time - INTERVAL '2 hours'
+        WHERE id = $1
+        "#,
+    )
+    .bind(session_id as i32)
+    .execute(&database)
+    .await
+    .expect("the session is moved");
+
+    session::expire_sessions(&database, StdDuration::from_secs(15 * 60))
+        .await
+        .expect("the sessions are expired");
+
+    let end_reason: Option<String> =
+        sqlx::query_scalar("SELECT end_reason::VARCHAR FROM machine_session WHERE id = $1")
+            .bind(session_id as i32)
+            .fetch_one(&database)
+            .await
+            .expect("the session is fetched");
+    assert_eq!(end_reason.as_deref(), Some("expired"));
+
+    common::remove_organization(&database, &slug).await;
+}
+
+/// A session past its grace period no longer holds the machine, even before the expiry job runs.
+#[actix_web::test]
+async fn overdue_sessions_do_not_block_new_ones() {
+    let state = common::app_state().await;
+    let database = state.database.clone();
+    let app = common::init_app(state).await;
+
+    let slug = common::add_organization(&app).await;
+    let room_id = common::add_room(&app, &slug, &["W1"]).await;
+
+    let start = || {
+        as_organization(
+            TestRequest::post()
+                .uri(&format!("/machine/{room_id}/W1/session"))
+                .set_json(json!({ "username": null, "expected_duration_minutes": 45 })),
+            &slug,
+        )
+    };
+
+    let (status, session) = call(&app, start()).await;
+    assert_eq!(status, StatusCode::CREATED);
+    let session_id = session["session_id"].as_i64().unwrap();
+
+    // Within the grace period the machine is still in use.
+    sqlx::query(
+        r#"
+        UPDATE machine_session
+        SET start_time = start_time - INTERVAL '50 minutes',
+            expected_end_time = expected_end_time - INTERVAL '50 minutes'
+        WHERE id = $1
+        "#,
+    )
+    .bind(session_id as i32)
+    .execute(&database)
+    .await
+    .expect("the session is moved");
+
+    let (status, _) = call(&app, start()).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    sqlx::query(
+        r#"
+        UPDATE machine_session
+        SET start_time = start_time - INTERVAL '2 hours',
+            expected_end_time = expected_end_time - INTERVAL '2 hours'
+        WHERE id = $1
+        "#,
+    )
+    .bind(session_id as i32)
+    .execute(&database)
+    .await
+    .expect("the session is moved");
+
+    let (status, started) = call(&app, start()).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_ne!(started["session_id"], session["session_id"]);
+
+    let end_reason: Option<String> =
+        sqlx::query_scalar("SELECT end_reason::VARCHAR FROM machine_session WHERE id = $1")
+            .bind(session_id as i32)
+            .fetch_one(&database)
+            .await
+            .expect("the session is fetched");
+    assert_eq!(end_reason.as_deref(), Some("expired"));
+
+    common::remove_organization(&database, &slug).await;
+}
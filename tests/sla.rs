@@ -0,0 +1,221 @@
+mod common;
+
+use actix_web::{http::StatusCode, test::TestRequest};
+use common::{as_admin, as_organization, call, unique};
+use laundry_api::{notification::Notifier, sla};
+use serde_json::json;
+
+/// Policies are managed by admins and need a scope and a target, and one policy at most covers a
+/// room and report type.
+#[actix_web::test]
+async fn policies_are_validated_and_removed() {
+    let state = common::app_state().await;
+    let database = state.database.clone();
+    let app = common::init_app(state).await;
+
+    let slug = common::add_organization(&app).await;
+    let add = |policy: serde_json::Value| {
+        as_organization(
+            as_admin(TestRequest::post().uri("/admin/sla-policies")).set_json(policy),
+            &slug,
+        )
+    };
+
+    for policy in [
+        json!({ "name": "Everything", "resolve_minutes": 60 }),
+        json!({ "name": "No target", "report_type": "Broken" }),
+        json!({ "name": "Email", "report_type": "Broken", "resolve_minutes": 60, "escalation_email": "nobody" }),
+        json!({ "name": "Webhook", "report_type": "Broken", "resolve_minutes": 60, "escalation_webhook_url": "ftp://example.com/hook" }),
+        json!({ "name": "Room", "room_id": 0, "resolve_minutes": 60 }),
+    ] {
+        let (status, _) = call(&app, add(policy.clone())).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{policy}");
+    }
+
+    let policy =
+        json!({ "name": "Broken machines", "report_type": "Broken", "resolve_minutes": 60 });
+    let (status, _) = call(
+        &app,
+        as_organization(
+            TestRequest::post()
+                .uri("/admin/sla-policies")
+                .set_json(&policy),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, added) = call(&app, add(policy.clone())).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(added["acknowledge_minutes"], json!(null));
+
+    let (status, _) = call(&app, add(policy)).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let (status, _) = call(
+        &app,
+        as_organization(TestRequest::get().uri("/admin/sla-policies"), &slug),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, policies) = call(
+        &app,
+        as_organization(
+            as_admin(TestRequest::get().uri("/admin/sla-policies")),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(policies, json!([added]));
+
+    let uri = format!("/admin/sla-policies/{}", added["policy_id"]);
+    let remove = || as_organization(TestRequest::delete().uri(&uri), &slug);
+
+    let (status, _) = call(&app, remove()).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, removed) = call(&app, as_admin(remove())).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(removed, added);
+
+    let (status, _) = call(&app, as_admin(remove())).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _) = call(
+        &app,
+        as_organization(TestRequest::get().uri("/sla/compliance"), &slug),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    common::remove_organization(&database, &slug).await;
+}
+
+/// A report left unacknowledged past its policy's target is flagged and escalated exactly once,
+/// while the target it has time left for is not.
+#[actix_web::test]
+async fn missed_targets_are_escalated_once() {
+    let state = common::app_state().await;
+    let database = state.database.clone();
+    let app = common::init_app(state).await;
+
+    let slug = common::add_organization(&app).await;
+    let (_, room) = call(
+        &app,
+        as_organization(
+            TestRequest::post()
+                .uri("/room/")
+                .set_json(json!({ "name": "Basement", "description": null })),
+            &slug,
+        ),
+    )
+    .await;
+    let room_id = room["room_id"].as_i64().unwrap();
+
+    let (status, _) = call(
+        &app,
+        as_organization(
+            TestRequest::post().uri("/machine/").set_json(json!({
+                "room_id": room_id,
+                "machine_id": "W1",
+                "machine_type": "Washer"
+            })),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let username = unique("user");
+    let (status, _) = call(
+        &app,
+        as_organization(
+            TestRequest::post()
+                .uri("/user/")
+                .set_json(json!({ "username": username, "admin": false })),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let (status, policy) = call(
+        &app,
+        as_organization(
+            as_admin(TestRequest::post().uri("/admin/sla-policies")).set_json(json!({
+                "name": "Basement",
+                "room_id": room_id,
+                "acknowledge_minutes": 60,
+                "resolve_minutes": 180
+            })),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    let policy_id = policy["policy_id"].as_i64().unwrap();
+
+    let (status, report) = call(
+        &app,
+        as_organization(
+            TestRequest::post().uri("/report/").set_json(json!({
+                "room_id": room_id,
+                "machine_id": "W1",
+                "reporter_username": username,
+                "report_type": "Broken",
+                "description": null
+            })),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    let report_id = report["report_id"].as_i64().unwrap();
+
+    // Move the policy and the report two hours into the past, past the acknowledge target.
+    sqlx::query(
+        "UPDATE sla_policy SET created_time = created_time - INTERVAL '3 hours' WHERE id = $1",
+    )
+    .bind(policy_id as i32)
+    .execute(&database)
+    .await
+    .expect("the policy is moved");
+    sqlx::query("UPDATE report SET time = time - INTERVAL '2 hours' WHERE id = $1")
+        .bind(report_id as i32)
+        .execute(&database)
+        .await
+        .expect("the report is moved");
+
+    let (status, body) = call(
+        &app,
+        as_organization(
+            TestRequest::get().uri(&format!("/report/{report_id}/sla")),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["policy_id"], json!(policy_id));
+    assert_eq!(body["acknowledge_breached"], json!(true));
+    assert_eq!(body["resolve_breached"], json!(false));
+
+    let notifier = Notifier::from_env().expect("notifications are configured");
+    for _ in 0..2 {
+        sla::escalate_breaches(&database, &notifier)
+            .await
+            .expect("breaches are escalated");
+
+        let targets: Vec<String> =
+            sqlx::query_scalar("SELECT target::VARCHAR FROM sla_escalation WHERE report_id = $1")
+                .bind(report_id as i32)
+                .fetch_all(&database)
+                .await
+                .expect("the escalations are listed");
+        assert_eq!(targets, ["acknowledge"]);
+    }
+
+    common::remove_organization(&database, &slug).await;
+}
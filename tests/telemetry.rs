@@ -0,0 +1,508 @@
+mod common;
+
+use std::time::Duration as StdDuration;
+
+use actix_web::{http::StatusCode, test::TestRequest};
+use common::{as_admin, as_organization, call, unique};
+use laundry_api::{
+    machine_telemetry::{self, TelemetryConfig, TelemetryReading},
+    models::OperatingState,
+};
+use serde_json::{json, Value};
+use time::{Duration, OffsetDateTime, PrimitiveDateTime};
+use uuid::Uuid;
+
+/// Admins map devices to the machines of their organization.
+#[actix_web::test]
+async fn devices_are_added_and_removed_by_admins() {
+    let state = common::app_state().await;
+    let database = state.database.clone();
+    let app = common::init_app(state).await;
+
+    let slug = common::add_organization(&app).await;
+    let (_, room) = call(
+        &app,
+        as_organization(
+            TestRequest::post()
+                .uri("/room/")
+                .set_json(json!({ "name": "Basement", "description": null })),
+            &slug,
+        ),
+    )
+    .await;
+    let room_id = room["room_id"].as_i64().unwrap();
+
+    let (status, _) = call(
+        &app,
+        as_organization(
+            TestRequest::post().uri("/machine/").set_json(json!({
+                "room_id": room_id,
+                "machine_id": "W1",
+                "machine_type": "Washer"
+            })),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let device_id = unique("plug");
+    let add = |device_id: &str, machine_id: &str| {
+        as_organization(
+            as_admin(TestRequest::post().uri("/admin/telemetry-devices")).set_json(json!({
+                "device_id": device_id,
+                "room_id": room_id,
+                "machine_id": machine_id
+            })),
+            &slug,
+        )
+    };
+
+    let (status, credentials) = call(&app, add(&device_id, "W1")).await;
+    assert_eq!(status, StatusCode::CREATED);
+    let device = credentials["device"].clone();
+    assert_eq!(device["device_id"], device_id.as_str());
+    assert_eq!(device["machine_id"], "W1");
+
+    for (device_id, machine_id, expected) in [
+        (device_id.as_str(), "W1", StatusCode::CONFLICT),
+        (" ", "W1", StatusCode::BAD_REQUEST),
+        ("plug", "W9", StatusCode::BAD_REQUEST),
+    ] {
+        let (status, _) = call(&app, add(device_id, machine_id)).await;
+        assert_eq!(status, expected, "{device_id} {machine_id}");
+    }
+
+    let (status, devices) = call(
+        &app,
+        as_organization(
+            as_admin(TestRequest::get().uri("/admin/telemetry-devices")),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(devices, json!([device]));
+
+    // Other organizations neither see nor remove the device.
+    let other = common::add_organization(&app).await;
+    let (_, devices) = call(
+        &app,
+        as_organization(
+            as_admin(TestRequest::get().uri("/admin/telemetry-devices")),
+            &other,
+        ),
+    )
+    .await;
+    assert_eq!(devices, json!([]));
+
+    let rotate = |slug: &str| {
+        as_organization(
+            as_admin(TestRequest::post().uri(&format!("/admin/telemetry-devices/{device_id}/key"))),
+            slug,
+        )
+    };
+
+    let (status, rotated) = call(&app, rotate(&slug)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(rotated["device"], device);
+    assert_ne!(rotated["key"], credentials["key"]);
+
+    let remove = |slug: &str| {
+        as_organization(
+            as_admin(TestRequest::delete().uri(&format!("/admin/telemetry-devices/{device_id}"))),
+            slug,
+        )
+    };
+
+    for request in [rotate(&other), remove(&other)] {
+        let (status, _) = call(&app, request).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    let (status, removed) = call(&app, remove(&slug)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(removed, device);
+
+    let (status, _) = call(&app, remove(&slug)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    common::remove_organization(&database, &other).await;
+    common::remove_organization(&database, &slug).await;
+}
+
+/// Devices authenticate with their key for their own machine only, and readings taken within one
+/// sample interval are stored as a single sample.
+#[actix_web::test]
+async fn device_readings_are_authenticated_and_downsampled() {
+    let state = common::app_state().await;
+    let database = state.database.clone();
+    let app = common::init_app(state).await;
+
+    let slug = common::add_organization(&app).await;
+    let (_, room) = call(
+        &app,
+        as_organization(
+            TestRequest::post()
+                .uri("/room/")
+                .set_json(json!({ "name": "Basement", "description": null })),
+            &slug,
+        ),
+    )
+    .await;
+    let room_id = room["room_id"].as_i64().unwrap();
+
+    for machine_id in ["W1", "W2"] {
+        let (status, _) = call(
+            &app,
+            as_organization(
+                TestRequest::post().uri("/machine/").set_json(json!({
+                    "room_id": room_id,
+                    "machine_id": machine_id,
+                    "machine_type": "Washer"
+                })),
+                &slug,
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+    }
+
+    let (status, credentials) = call(
+        &app,
+        as_organization(
+            as_admin(TestRequest::post().uri("/admin/telemetry-devices")).set_json(json!({
+                "device_id": unique("plug"),
+                "room_id": room_id,
+                "machine_id": "W1"
+            })),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    let key = credentials["key"].as_str().unwrap();
+
+    // Three readings within one minute, a few minutes ago.
+    let start = OffsetDateTime::now_utc() - Duration::minutes(5);
+    let start = start
+        .replace_second(0)
+        .unwrap()
+        .replace_nanosecond(0)
+        .unwrap();
+    let readings: Vec<Value> = [(5, 1800.0), (25, 1900.0), (45, 2000.0)]
+        .into_iter()
+        .map(|(seconds, power_watts)| {
+            let time = start + Duration::seconds(seconds);
+            json!({
+                "time": format!(
+                    "{}T{:02}:{:02}:{:02}",
+                    time.date(),
+                    time.hour(),
+                    time.minute(),
+                    time.second()
+                ),
+                "power_watts": power_watts
+            })
+        })
+        .collect();
+
+    let submission = |machine_id: &str, authorization: Option<&str>| {
+        let request = TestRequest::post()
+            .uri(&format!("/machine/{room_id}/{machine_id}/telemetry"))
+            .set_json(json!({ "readings": readings }));
+        let request = match authorization {
+            Some(authorization) => request.insert_header(("Authorization", authorization)),
+            None => request,
+        };
+        as_organization(request, &slug)
+    };
+
+    let (status, _) = call(&app, submission("W1", None)).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, _) = call(&app, submission("W1", Some("Bearer not-the-key"))).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let bearer = format!("Bearer {key}");
+    let (status, _) = call(&app, submission("W2", Some(&bearer))).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, receipt) = call(&app, submission("W1", Some(&bearer))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(receipt["accepted"], 3);
+    assert_eq!(receipt["state"], "Running");
+
+    let (status, telemetry) = call(
+        &app,
+        as_organization(
+            TestRequest::get().uri(&format!("/machine/{room_id}/W1/telemetry")),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let samples = telemetry["samples"].as_array().unwrap();
+    assert_eq!(samples.len(), 1, "{samples:?}");
+    assert_eq!(samples[0]["sample_count"], 3);
+    assert_eq!(samples[0]["power_watts"], 1900.0);
+    assert_eq!(samples[0]["peak_power_watts"], 2000.0);
+
+    for readings in [json!([]), json!([{ "time": null }])] {
+        let (status, _) = call(
+            &app,
+            as_organization(
+                TestRequest::post()
+                    .uri(&format!("/machine/{room_id}/W1/telemetry"))
+                    .insert_header(("Authorization", bearer.as_str()))
+                    .set_json(json!({ "readings": readings })),
+                &slug,
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{readings}");
+    }
+
+    common::remove_organization(&database, &slug).await;
+}
+
+/// A full batch of readings with every field set fits the body size limit, and larger bodies are
+/// refused before they are parsed.
+#[actix_web::test]
+async fn batches_are_limited_in_size() {
+    let state = common::app_state().await;
+    let database = state.database.clone();
+    let app = common::init_app(state).await;
+
+    let slug = common::add_organization(&app).await;
+    let room_id = common::add_room(&app, &slug, &["W1"]).await;
+
+    let (status, credentials) = call(
+        &app,
+        as_organization(
+            as_admin(TestRequest::post().uri("/admin/telemetry-devices")).set_json(json!({
+                "device_id": unique("plug"),
+                "room_id": room_id,
+                "machine_id": "W1"
+            })),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    let bearer = format!("Bearer {}", credentials["key"].as_str().unwrap());
+
+    let time = OffsetDateTime::now_utc() - Duration::minutes(5);
+    let time = format!(
+        "{}T{:02}:{:02}:{:02}",
+        time.date(),
+        time.hour(),
+        time.minute(),
+        time.second()
+    );
+    let reading = json!({
+        "time": time,
+        "power_watts": -1.1754944e-38,
+        "vibration": -1.1754944e-38,
+        "door_open": false,
+        "temperature_celsius": -1.1754944e-38
+    });
+
+    let submit = |readings: Vec<Value>| {
+        as_organization(
+            TestRequest::post()
+                .uri(&format!("/machine/{room_id}/W1/telemetry"))
+                .insert_header(("Authorization", bearer.as_str()))
+                .set_json(json!({ "readings": readings })),
+            &slug,
+        )
+    };
+
+    let (status, receipt) = call(&app, submit(vec![reading.clone(); 1000])).await;
+    assert_eq!(status, StatusCode::OK, "{receipt}");
+    assert_eq!(receipt["accepted"], 1000);
+
+    let (status, _) = call(&app, submit(vec![reading.clone(); 1001])).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = call(&app, submit(vec![reading; 2000])).await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+
+    common::remove_organization(&database, &slug).await;
+}
+
+/// The operating state follows the power drawn, ignores late readings, and becomes a fault
+/// which files a single Broken report when the machine draws too much or runs for too long.
+#[actix_web::test]
+async fn readings_drive_the_operating_state() {
+    let state = common::app_state().await;
+    let database = state.database.clone();
+    let report_config = state.report_config.clone();
+    let app = common::init_app(state).await;
+
+    let slug = common::add_organization(&app).await;
+    let (_, room) = call(
+        &app,
+        as_organization(
+            TestRequest::post()
+                .uri("/room/")
+                .set_json(json!({ "name": "Basement", "description": null })),
+            &slug,
+        ),
+    )
+    .await;
+    let room_id = room["room_id"].as_i64().unwrap();
+
+    let mut devices = Vec::new();
+    for machine_id in ["W1", "W2"] {
+        let (status, _) = call(
+            &app,
+            as_organization(
+                TestRequest::post().uri("/machine/").set_json(json!({
+                    "room_id": room_id,
+                    "machine_id": machine_id,
+                    "machine_type": "Washer"
+                })),
+                &slug,
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+
+        let device_id = unique("plug");
+        let (status, _) = call(
+            &app,
+            as_organization(
+                as_admin(TestRequest::post().uri("/admin/telemetry-devices")).set_json(json!({
+                    "device_id": device_id,
+                    "room_id": room_id,
+                    "machine_id": machine_id
+                })),
+                &slug,
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+
+        let device = machine_telemetry::find_device(&database, &device_id)
+            .await
+            .expect("the device is fetched")
+            .expect("the device exists");
+        devices.push(device);
+    }
+
+    let telemetry_config = TelemetryConfig {
+        running_watts: 10.0,
+        fault_watts: 3500.0,
+        running_vibration: 0.05,
+        fault_celsius: 95.0,
+        max_run: StdDuration::from_secs(30 * 60),
+        sample_interval: StdDuration::from_secs(60),
+    };
+    let now = OffsetDateTime::now_utc();
+    let start = PrimitiveDateTime::new(now.date(), now.time()) - Duration::hours(2);
+    let record = |device: usize, minutes: i64, power_watts: f32| {
+        let reading = TelemetryReading {
+            time: Some(start + Duration::minutes(minutes)),
+            power_watts: Some(power_watts),
+            ..TelemetryReading::default()
+        };
+        let (database, telemetry_config, report_config, devices) =
+            (&database, &telemetry_config, &report_config, &devices);
+        async move {
+            machine_telemetry::record_readings(
+                database,
+                telemetry_config,
+                report_config,
+                &devices[device],
+                vec![reading],
+            )
+            .await
+            .expect("the reading is recorded")
+        }
+    };
+
+    assert_eq!(record(0, 0, 2.0).await, Some(OperatingState::Idle));
+    assert_eq!(record(0, 5, 850.0).await, Some(OperatingState::Running));
+
+    // A reading delivered after a later one does not move the state backwards.
+    assert_eq!(record(0, 3, 2.0).await, Some(OperatingState::Running));
+
+    assert_eq!(record(0, 30, 900.0).await, Some(OperatingState::Running));
+    assert_eq!(record(0, 36, 900.0).await, Some(OperatingState::Fault));
+
+    // The fault only clears once the machine goes idle.
+    assert_eq!(record(0, 40, 900.0).await, Some(OperatingState::Fault));
+    assert_eq!(record(0, 45, 0.0).await, Some(OperatingState::Idle));
+    assert_eq!(record(0, 50, 900.0).await, Some(OperatingState::Running));
+
+    assert_eq!(record(1, 0, 5000.0).await, Some(OperatingState::Fault));
+
+    let (_, reports) = call(
+        &app,
+        as_organization(TestRequest::get().uri("/report/"), &slug),
+    )
+    .await;
+    let mut reported = reports
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|report| {
+            assert_eq!(report["report_type"], "Broken");
+            assert_eq!(report["reporter_username"], json!(null));
+            report["machine_id"].as_str().unwrap().to_string()
+        })
+        .collect::<Vec<_>>();
+    reported.sort();
+    assert_eq!(reported, ["W1", "W2"]);
+
+    common::remove_organization(&database, &slug).await;
+}
+
+/// Devices may be registered with the fingerprint of the client certificate they connect with.
+#[actix_web::test]
+async fn devices_are_registered_with_certificate_fingerprints() {
+    let state = common::app_state().await;
+    let database = state.database.clone();
+    let app = common::init_app(state).await;
+
+    let slug = common::add_organization(&app).await;
+    let room_id = common::add_room(&app, &slug, &["W1"]).await;
+
+    // A fingerprint in the colon separated uppercase form tools print.
+    let fingerprint = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+    let printed = fingerprint
+        .to_uppercase()
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| String::from_utf8_lossy(pair).into_owned())
+        .collect::<Vec<_>>()
+        .join(":");
+
+    let register = |certificate_fingerprint: &str| {
+        as_organization(
+            as_admin(TestRequest::post().uri("/admin/telemetry-devices")).set_json(json!({
+                "device_id": unique("plug"),
+                "room_id": room_id,
+                "machine_id": "W1",
+                "certificate_fingerprint": certificate_fingerprint
+            })),
+            &slug,
+        )
+    };
+
+    let (status, _) = call(&app, register("not-a-fingerprint")).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, credentials) = call(&app, register(&printed)).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(
+        credentials["device"]["certificate_fingerprint"],
+        json!(fingerprint)
+    );
+
+    let (status, _) = call(&app, register(&printed)).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    common::remove_organization(&database, &slug).await;
+}
@@ -0,0 +1,335 @@
+use std::{
+    env, fs,
+    io::{self, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use actix_web::{
+    http::{header, StatusCode},
+    test::{self, TestRequest},
+    web::{self, Data},
+    App, HttpRequest, HttpServer,
+};
+use laundry_api::tls::{
+    self, ClientAuth, ClientCertificate, HttpsRedirect, ReloadingCertResolver, TlsConfig,
+};
+use ring::digest;
+use rustls::{
+    pki_types::{CertificateDer, ServerName},
+    ClientConfig, ClientConnection, RootCertStore, StreamOwned,
+};
+use uuid::Uuid;
+
+/// The directory holding the certificates the tests use, all issued by `ca.pem`.
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/tls")
+        .join(name)
+}
+
+fn certificates(path: &Path) -> Vec<CertificateDer<'static>> {
+    let mut reader = BufReader::new(fs::File::open(path).expect("the fixture exists"));
+    rustls_pemfile::certs(&mut reader)
+        .collect::<Result<_, _>>()
+        .expect("the fixture holds certificates")
+}
+
+fn fingerprint(certificate: &[u8]) -> String {
+    digest::digest(&digest::SHA256, certificate)
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Copies the certificate `name` and its key into a fresh directory, as the server would find them.
+fn install_certificate(directory: &Path, name: &str) -> (PathBuf, PathBuf) {
+    let cert_path = directory.join("server.pem");
+    let key_path = directory.join("server-key.pem");
+    fs::copy(fixture(&format!("{name}.pem")), &cert_path).expect("the certificate is copied");
+    fs::copy(fixture(&format!("{name}-key.pem")), &key_path).expect("the key is copied");
+    (cert_path, key_path)
+}
+
+/// Connects to `port` over TLS, presenting the test client certificate if `with_client_certificate`
+/// is set, and returns the server certificate along with the body answered to `GET /whoami`.
+fn whoami(port: u16, with_client_certificate: bool) -> io::Result<(Vec<u8>, String)> {
+    let mut roots = RootCertStore::empty();
+    for certificate in certificates(&fixture("ca.pem")) {
+        roots.add(certificate).expect("the CA is valid");
+    }
+
+    let builder = ClientConfig::builder().with_root_certificates(roots);
+    let config = match with_client_certificate {
+        true => {
+            let mut reader = BufReader::new(fs::File::open(fixture("client-key.pem"))?);
+            let key = rustls_pemfile::private_key(&mut reader)?.expect("the fixture holds a key");
+            builder
+                .with_client_auth_cert(certificates(&fixture("client.pem")), key)
+                .expect("the client certificate is valid")
+        }
+        false => builder.with_no_client_auth(),
+    };
+
+    let connection = ClientConnection::new(
+        Arc::new(config),
+        ServerName::try_from("localhost").expect("the name is valid"),
+    )
+    .map_err(io::Error::other)?;
+    let socket = TcpStream::connect(("127.0.0.1", port))?;
+    let mut stream = StreamOwned::new(connection, socket);
+
+    stream.write_all(b"GET /whoami HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")?;
+
+    let mut response = Vec::new();
+    match stream.read_to_end(&mut response) {
+        Ok(_) => {}
+        // The connection may be closed without a close_notify once the response has been sent.
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof && !response.is_empty() => {}
+        Err(err) => return Err(err),
+    }
+
+    let server_certificate = stream
+        .conn
+        .peer_certificates()
+        .and_then(|certificates| certificates.first())
+        .map(|certificate| certificate.as_ref().to_vec())
+        .expect("the server presented a certificate");
+    let response = String::from_utf8_lossy(&response);
+    let body = response
+        .split_once("\r\n\r\n")
+        .map(|(_, body)| body.to_string())
+        .unwrap_or_default();
+
+    Ok((server_certificate, body))
+}
+
+/// Every TLS variable is read in one test, since tests share the process environment.
+#[test]
+fn tls_is_configured_from_the_environment() {
+    for name in [
+        "TLS_CERT_PATH",
+        "TLS_KEY_PATH",
+        "TLS_CLIENT_CA_PATH",
+        "TLS_CLIENT_AUTH",
+        "HTTPS_PORT",
+        "TLS_PUBLIC_HOST",
+        "TLS_REDIRECT_HTTP",
+        "TLS_RELOAD_INTERVAL_SECS",
+    ] {
+        env::remove_var(name);
+    }
+
+    env::set_var("TLS_CERT_PATH", "/etc/laundry/cert.pem");
+    assert_eq!(
+        TlsConfig::from_env().map(|config| config.is_none()),
+        Ok(true)
+    );
+
+    env::set_var("TLS_KEY_PATH", "/etc/laundry/key.pem");
+    let config = TlsConfig::from_env()
+        .expect("the TLS configuration is valid")
+        .expect("TLS is enabled");
+    assert_eq!(config.cert_path, PathBuf::from("/etc/laundry/cert.pem"));
+    assert_eq!(config.key_path, PathBuf::from("/etc/laundry/key.pem"));
+    assert_eq!(config.client_ca_path, None);
+    assert_eq!(config.client_auth, ClientAuth::Required);
+    assert_eq!(config.https_port, 8443);
+    assert_eq!(config.public_host, None);
+    assert!(!config.redirect_http);
+
+    // Redirects go to the configured host, so they cannot be asked for without one.
+    env::set_var("TLS_REDIRECT_HTTP", "true");
+    assert!(TlsConfig::from_env().is_err());
+    env::set_var("TLS_PUBLIC_HOST", "laundry.example/evil");
+    assert!(TlsConfig::from_env().is_err());
+
+    env::remove_var("TLS_REDIRECT_HTTP");
+    env::set_var("TLS_PUBLIC_HOST", "laundry.example");
+    let config = TlsConfig::from_env()
+        .expect("the TLS configuration is valid")
+        .expect("TLS is enabled");
+    assert_eq!(config.public_host.as_deref(), Some("laundry.example"));
+    assert!(config.redirect_http);
+    assert_eq!(config.reload_interval, Duration::from_secs(30));
+
+    env::set_var("TLS_CLIENT_CA_PATH", "/etc/laundry/ca.pem");
+    env::set_var("TLS_CLIENT_AUTH", "Optional");
+    env::set_var("HTTPS_PORT", "443");
+    env::set_var("TLS_REDIRECT_HTTP", "false");
+    env::set_var("TLS_RELOAD_INTERVAL_SECS", "5");
+    let config = TlsConfig::from_env()
+        .expect("the TLS configuration is valid")
+        .expect("TLS is enabled");
+    assert_eq!(
+        config.client_ca_path,
+        Some(PathBuf::from("/etc/laundry/ca.pem"))
+    );
+    assert_eq!(config.client_auth, ClientAuth::Optional);
+    assert_eq!(config.https_port, 443);
+    assert!(!config.redirect_http);
+    assert_eq!(config.reload_interval, Duration::from_secs(5));
+
+    env::set_var("TLS_CLIENT_AUTH", "sometimes");
+    assert!(TlsConfig::from_env().is_err());
+
+    for name in [
+        "TLS_CERT_PATH",
+        "TLS_KEY_PATH",
+        "TLS_CLIENT_CA_PATH",
+        "TLS_CLIENT_AUTH",
+        "HTTPS_PORT",
+        "TLS_PUBLIC_HOST",
+        "TLS_REDIRECT_HTTP",
+        "TLS_RELOAD_INTERVAL_SECS",
+    ] {
+        env::remove_var(name);
+    }
+}
+
+/// The server certificate is replaced once its files change, and verified client certificates
+/// are available to handlers.
+#[actix_web::test]
+async fn certificates_are_reloaded_and_client_certificates_are_stored() {
+    let directory = env::temp_dir().join(format!("laundry-tls-{}", Uuid::new_v4().simple()));
+    fs::create_dir_all(&directory).expect("the directory is created");
+    let (cert_path, key_path) = install_certificate(&directory, "server");
+
+    let config = TlsConfig {
+        cert_path: cert_path.clone(),
+        key_path: key_path.clone(),
+        client_ca_path: Some(fixture("ca.pem")),
+        client_auth: ClientAuth::Optional,
+        https_port: 0,
+        public_host: None,
+        redirect_http: false,
+        reload_interval: Duration::from_secs(30),
+    };
+    let resolver =
+        Arc::new(ReloadingCertResolver::new(&cert_path, &key_path).expect("the certificate loads"));
+    let server_config = config
+        .server_config(Arc::clone(&resolver))
+        .expect("the server configuration is valid");
+
+    let listener = TcpListener::bind(("127.0.0.1", 0)).expect("a port is free");
+    let port = listener.local_addr().expect("the port is known").port();
+    let server = HttpServer::new(|| {
+        App::new().route(
+            "/whoami",
+            web::get().to(|request: HttpRequest| async move {
+                match request.conn_data::<ClientCertificate>() {
+                    Some(certificate) => certificate.fingerprint(),
+                    None => "anonymous".to_string(),
+                }
+            }),
+        )
+    })
+    .workers(1)
+    .on_connect(tls::store_client_certificate)
+    .listen_rustls_0_22(listener, server_config)
+    .expect("the server listens")
+    .run();
+    let handle = server.handle();
+    actix_web::rt::spawn(server);
+
+    let (server_certificate, body) = web::block(move || whoami(port, true))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        server_certificate,
+        certificates(&fixture("server.pem"))[0].as_ref()
+    );
+    assert_eq!(
+        body,
+        fingerprint(certificates(&fixture("client.pem"))[0].as_ref())
+    );
+
+    let (_, body) = web::block(move || whoami(port, false))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(body, "anonymous");
+
+    // Nothing changed, so the same certificate stays in use.
+    resolver.reload_if_changed();
+    let (server_certificate, _) = web::block(move || whoami(port, false))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        server_certificate,
+        certificates(&fixture("server.pem"))[0].as_ref()
+    );
+
+    install_certificate(&directory, "server-renewed");
+    let later = SystemTime::now() + Duration::from_secs(60);
+    for path in [&cert_path, &key_path] {
+        fs::File::options()
+            .write(true)
+            .open(path)
+            .and_then(|file| file.set_modified(later))
+            .expect("the modification time is set");
+    }
+    resolver.reload_if_changed();
+
+    let (server_certificate, _) = web::block(move || whoami(port, false))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        server_certificate,
+        certificates(&fixture("server-renewed.pem"))[0].as_ref()
+    );
+
+    // A certificate which fails to load leaves the previous one in use.
+    fs::write(&cert_path, "not a certificate").expect("the certificate is overwritten");
+    resolver.reload_if_changed();
+    let (server_certificate, _) = web::block(move || whoami(port, false))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        server_certificate,
+        certificates(&fixture("server-renewed.pem"))[0].as_ref()
+    );
+
+    handle.stop(false).await;
+    let _ = fs::remove_dir_all(&directory);
+}
+
+/// Plain HTTP requests are redirected to the same path on the public host over HTTPS, whatever
+/// host the request names.
+#[actix_web::test]
+async fn plain_requests_are_redirected_to_https() {
+    for (port, location) in [
+        (8443, "https://laundry.example:8443/room/1?details=true"),
+        (443, "https://laundry.example/room/1?details=true"),
+    ] {
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(HttpsRedirect {
+                    host: "laundry.example".to_string(),
+                    port,
+                }))
+                .default_service(web::to(tls::redirect_to_https)),
+        )
+        .await;
+
+        for request in [
+            TestRequest::get().insert_header((header::HOST, "laundry.example:8080")),
+            TestRequest::get().insert_header((header::HOST, "evil.example")),
+            TestRequest::get()
+                .insert_header((header::HOST, "laundry.example"))
+                .insert_header(("X-Forwarded-Host", "evil.example")),
+        ] {
+            let request = request.uri("/room/1?details=true").to_request();
+            let response = test::call_service(&app, request).await;
+            assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+            assert_eq!(response.headers().get(header::LOCATION).unwrap(), location);
+        }
+    }
+}
@@ -0,0 +1,368 @@
+mod common;
+
+use actix_web::{http::StatusCode, test::TestRequest};
+use common::{as_admin, as_organization, call, unique};
+use serde_json::{json, Value};
+
+/// The type named `name` in a listing of machine or report types.
+fn listed(types: &Value, name: &str) -> Option<Value> {
+    types
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|definition| definition["name"] == name)
+        .cloned()
+}
+
+/// Admins add machine and report types, and deactivated types cannot be used for new machines
+/// or reports while existing ones keep them.
+#[actix_web::test]
+async fn types_are_added_and_deactivated_by_admins() {
+    let state = common::app_state().await;
+    let database = state.database.clone();
+    let app = common::init_app(state).await;
+
+    let slug = common::add_organization(&app).await;
+
+    let machine_type = unique("WasherDryer");
+    let definition = json!({
+        "name": machine_type,
+        "display_name": "Combo washer-dryer",
+        "icon": "washer-dryer",
+        "active": true
+    });
+    let (status, added) = call(
+        &app,
+        as_admin(TestRequest::post().uri("/admin/machine-types")).set_json(&definition),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(added, definition);
+
+    let (status, _) = call(
+        &app,
+        as_admin(TestRequest::post().uri("/admin/machine-types")).set_json(&definition),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let (status, _) = call(
+        &app,
+        as_admin(TestRequest::post().uri("/admin/machine-types")).set_json(json!({
+            "name": " ",
+            "display_name": "Blank",
+            "icon": null,
+            "active": true
+        })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let report_type = unique("PaymentFailure");
+    let (status, _) = call(
+        &app,
+        as_admin(TestRequest::post().uri("/admin/report-types")).set_json(json!({
+            "name": report_type,
+            "display_name": "Payment failure",
+            "severity": 1,
+            "icon": "credit-card",
+            "active": true
+        })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let room_id = common::add_room::<_, _, &str>(&app, &slug, &[]).await;
+    let username = common::add_user(&app, &slug).await;
+
+    let (status, machine) = call(
+        &app,
+        as_organization(
+            TestRequest::post().uri("/machine/").set_json(json!({
+                "room_id": room_id,
+                "machine_id": "C1",
+                "machine_type": machine_type
+            })),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(machine["machine_type"], machine_type.as_str());
+
+    let (status, report) = call(
+        &app,
+        as_organization(
+            TestRequest::post().uri("/report/").set_json(json!({
+                "room_id": room_id,
+                "machine_id": "C1",
+                "reporter_username": username,
+                "report_type": report_type,
+                "description": null
+            })),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(report["report_type"], report_type.as_str());
+
+    let (status, updated) = call(
+        &app,
+        as_admin(TestRequest::put().uri(&format!("/admin/machine-types/{machine_type}"))).set_json(
+            json!({
+                "display_name": "Combo washer-dryer",
+                "icon": "washer-dryer",
+                "active": false
+            }),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(updated["active"], false);
+
+    let (status, updated) = call(
+        &app,
+        as_admin(TestRequest::put().uri(&format!("/admin/report-types/{report_type}"))).set_json(
+            json!({
+                "display_name": "Payment failure",
+                "severity": 2,
+                "icon": "credit-card",
+                "active": false
+            }),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(updated["severity"], 2);
+    assert_eq!(updated["active"], false);
+
+    // Inactive types are still listed for admins.
+    let (status, machine_types) = call(
+        &app,
+        as_admin(TestRequest::get().uri("/admin/machine-types")),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        listed(&machine_types, &machine_type).unwrap()["active"],
+        false
+    );
+    let (status, report_types) = call(
+        &app,
+        as_admin(TestRequest::get().uri("/admin/report-types")),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        listed(&report_types, &report_type).unwrap()["active"],
+        false
+    );
+
+    let (status, _) = call(
+        &app,
+        as_organization(
+            TestRequest::post().uri("/machine/").set_json(json!({
+                "room_id": room_id,
+                "machine_id": "C2",
+                "machine_type": machine_type
+            })),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = call(
+        &app,
+        as_organization(
+            TestRequest::post().uri("/report/").set_json(json!({
+                "room_id": room_id,
+                "machine_id": "C1",
+                "reporter_username": username,
+                "report_type": report_type,
+                "description": null
+            })),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // Types which were never added are rejected the same way.
+    let (status, _) = call(
+        &app,
+        as_organization(
+            TestRequest::post().uri("/machine/").set_json(json!({
+                "room_id": room_id,
+                "machine_id": "C2",
+                "machine_type": unique("Mangle")
+            })),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, machine) = call(
+        &app,
+        as_organization(
+            TestRequest::get().uri(&format!("/machine/{room_id}/C1")),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(machine["machine_type"], machine_type.as_str());
+
+    for uri in [
+        format!("/admin/machine-types/{}", unique("Mangle")),
+        format!("/admin/report-types/{}", unique("Flooded")),
+    ] {
+        let (status, _) = call(
+            &app,
+            as_admin(TestRequest::put().uri(&uri)).set_json(json!({
+                "display_name": "Missing",
+                "severity": 0,
+                "icon": null,
+                "active": true
+            })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND, "{uri}");
+    }
+
+    common::remove_organization(&database, &slug).await;
+}
+
+/// The types which used to be enums are seeded, and machines and reports using them are
+/// serialized with the same names as before.
+#[actix_web::test]
+async fn former_enum_values_keep_their_json_names() {
+    let state = common::app_state().await;
+    let database = state.database.clone();
+    let app = common::init_app(state).await;
+
+    let slug = common::add_organization(&app).await;
+
+    let (_, machine_types) = call(
+        &app,
+        as_admin(TestRequest::get().uri("/admin/machine-types")),
+    )
+    .await;
+    for name in ["Washer", "Dryer"] {
+        assert_eq!(
+            listed(&machine_types, name).unwrap()["active"],
+            true,
+            "{name}"
+        );
+    }
+
+    let (_, report_types) = call(
+        &app,
+        as_admin(TestRequest::get().uri("/admin/report-types")),
+    )
+    .await;
+    for (name, severity) in [("Operational", 0), ("Caution", 1), ("Broken", 2)] {
+        assert_eq!(
+            listed(&report_types, name).unwrap()["severity"],
+            severity,
+            "{name}"
+        );
+    }
+
+    let room_id = common::add_room(&app, &slug, &["W1"]).await;
+    let username = common::add_user(&app, &slug).await;
+
+    let (status, _) = call(
+        &app,
+        as_organization(
+            TestRequest::post().uri("/machine/").set_json(json!({
+                "room_id": room_id,
+                "machine_id": "D1",
+                "machine_type": "Dryer"
+            })),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let (status, machines) = call(
+        &app,
+        as_organization(
+            TestRequest::get().uri(&format!("/room/{room_id}/machines")),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let mut machine_types = machines
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|machine| {
+            (
+                machine["machine_id"].clone(),
+                machine["machine_type"].clone(),
+            )
+        })
+        .collect::<Vec<_>>();
+    machine_types.sort_by_key(|(machine_id, _)| machine_id.to_string());
+    assert_eq!(
+        machine_types,
+        [
+            (json!("D1"), json!("Dryer")),
+            (json!("W1"), json!("Washer"))
+        ]
+    );
+
+    for report_type in ["Operational", "Caution", "Broken"] {
+        let (status, report) = call(
+            &app,
+            as_organization(
+                TestRequest::post().uri("/report/").set_json(json!({
+                    "room_id": room_id,
+                    "machine_id": "W1",
+                    "reporter_username": username,
+                    "report_type": report_type,
+                    "description": null
+                })),
+                &slug,
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED, "{report_type}");
+        assert_eq!(report["report_type"], report_type);
+
+        let (status, fetched) = call(
+            &app,
+            as_organization(
+                TestRequest::get().uri(&format!("/report/{}", report["report_id"])),
+                &slug,
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(fetched["report_type"], report_type);
+    }
+
+    // Type names are case sensitive, like the enum variants were.
+    let (status, _) = call(
+        &app,
+        as_organization(
+            TestRequest::post().uri("/report/").set_json(json!({
+                "room_id": room_id,
+                "machine_id": "W1",
+                "reporter_username": username,
+                "report_type": "broken",
+                "description": null
+            })),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    common::remove_organization(&database, &slug).await;
+}
@@ -0,0 +1,224 @@
+mod common;
+
+use actix_web::{http::StatusCode, test::TestRequest};
+use common::{as_admin, as_organization, call, unique};
+use serde_json::json;
+
+/// Admins manage work orders, which cover open reports not covered elsewhere and are carried out by
+/// technicians, and closing one archives those reports.
+#[actix_web::test]
+async fn admins_close_work_orders_archiving_their_reports() {
+    let state = common::app_state().await;
+    let database = state.database.clone();
+    let app = common::init_app(state).await;
+
+    let slug = common::add_organization(&app).await;
+    let (_, room) = call(
+        &app,
+        as_organization(
+            TestRequest::post()
+                .uri("/room/")
+                .set_json(json!({ "name": "Basement", "description": null })),
+            &slug,
+        ),
+    )
+    .await;
+    let room_id = room["room_id"].as_i64().unwrap();
+
+    let (status, _) = call(
+        &app,
+        as_organization(
+            TestRequest::post().uri("/machine/").set_json(json!({
+                "room_id": room_id,
+                "machine_id": "W1",
+                "machine_type": "Washer"
+            })),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let resident = unique("resident");
+    let technician = unique("technician");
+    for (username, is_technician) in [(&resident, false), (&technician, true)] {
+        let (status, _) = call(
+            &app,
+            as_organization(
+                TestRequest::post().uri("/user/").set_json(json!({
+                    "username": username,
+                    "admin": false,
+                    "technician": is_technician
+                })),
+                &slug,
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+    }
+
+    let (status, report) = call(
+        &app,
+        as_organization(
+            TestRequest::post().uri("/report/").set_json(json!({
+                "room_id": room_id,
+                "machine_id": "W1",
+                "reporter_username": resident,
+                "report_type": "Broken",
+                "description": "Does not drain"
+            })),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    let report_id = report["report_id"].as_i64().unwrap();
+
+    let add = |report_ids: serde_json::Value, assignee_username: &str| {
+        as_organization(
+            TestRequest::post()
+                .uri("/admin/work-orders")
+                .set_json(json!({
+                    "title": "Replace pump",
+                    "description": null,
+                    "report_ids": report_ids,
+                    "assignee_username": assignee_username
+                })),
+            &slug,
+        )
+    };
+
+    let (status, _) = call(&app, add(json!([report_id]), &technician)).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    for (report_ids, assignee_username) in [
+        (json!([]), technician.clone()),
+        (json!([report_id + 1_000_000]), technician.clone()),
+        (json!([report_id]), unique("user")),
+        (json!([report_id]), resident.clone()),
+    ] {
+        let (status, _) = call(&app, as_admin(add(report_ids.clone(), &assignee_username))).await;
+        assert_eq!(
+            status,
+            StatusCode::BAD_REQUEST,
+            "{report_ids} {assignee_username}"
+        );
+    }
+
+    let (status, work_order) = call(&app, as_admin(add(json!([report_id]), &technician))).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(work_order["status"], "Open");
+    assert_eq!(work_order["priority"], "Normal");
+    assert_eq!(work_order["report_ids"], json!([report_id]));
+
+    // A report is covered by one open work order at a time.
+    let (status, _) = call(&app, as_admin(add(json!([report_id]), &technician))).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let uri = format!("/admin/work-orders/{}", work_order["work_order_id"]);
+    let update = |status: &str, assignee_username: &str| {
+        as_organization(
+            TestRequest::put().uri(&uri).set_json(json!({
+                "title": "Replace pump",
+                "description": "Ordered",
+                "status": status,
+                "priority": "High",
+                "assignee_username": assignee_username
+            })),
+            &slug,
+        )
+    };
+
+    let (status, _) = call(&app, update("OnHold", &technician)).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    for (status, assignee_username) in [("Closed", &technician), ("OnHold", &resident)] {
+        let (answered, _) = call(&app, as_admin(update(status, assignee_username))).await;
+        assert_eq!(
+            answered,
+            StatusCode::BAD_REQUEST,
+            "{status} {assignee_username}"
+        );
+    }
+
+    let (status, updated) = call(&app, as_admin(update("OnHold", &technician))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(updated["status"], "OnHold");
+    assert_eq!(updated["priority"], "High");
+
+    let labour = |technician_username: &str| {
+        as_organization(
+            TestRequest::post()
+                .uri(&format!("{uri}/labour"))
+                .set_json(json!({
+                    "technician_username": technician_username,
+                    "minutes": 45,
+                    "description": null
+                })),
+            &slug,
+        )
+    };
+    let part = || {
+        as_organization(
+            TestRequest::post()
+                .uri(&format!("{uri}/parts"))
+                .set_json(json!({ "name": "Drain pump", "quantity": 2, "unit_cost_cents": 1750 })),
+            &slug,
+        )
+    };
+
+    let (status, _) = call(&app, as_admin(labour(&resident))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    for (authorize, expected) in [
+        (false, StatusCode::UNAUTHORIZED),
+        (true, StatusCode::CREATED),
+    ] {
+        let (labour, part) = match authorize {
+            true => (as_admin(labour(&technician)), as_admin(part())),
+            false => (labour(&technician), part()),
+        };
+        let (status, _) = call(&app, labour).await;
+        assert_eq!(status, expected);
+        let (status, _) = call(&app, part).await;
+        assert_eq!(status, expected);
+    }
+
+    let closure = || {
+        as_organization(
+            TestRequest::post()
+                .uri(&format!("{uri}/close"))
+                .set_json(json!({ "resolution": "Pump replaced" })),
+            &slug,
+        )
+    };
+
+    let (status, _) = call(&app, closure()).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, body) = call(&app, as_admin(closure())).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["work_order"]["status"], "Closed");
+    assert_eq!(body["work_order"]["labour_minutes"], 45);
+    assert_eq!(body["work_order"]["parts_cost_cents"], 3500);
+    assert_eq!(body["archived_report_ids"], json!([report_id]));
+
+    let (_, report) = call(
+        &app,
+        as_organization(
+            TestRequest::get().uri(&format!("/report/{report_id}")),
+            &slug,
+        ),
+    )
+    .await;
+    assert_eq!(report["archived"], true);
+
+    // Closed work orders can no longer be changed.
+    let (status, _) = call(&app, as_admin(closure())).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let (status, _) = call(&app, as_admin(update("Open", &technician))).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    common::remove_organization(&database, &slug).await;
+}